@@ -0,0 +1,19 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="tiles" tilewidth="16" tileheight="16" tilecount="4" columns="4">
+ <image source="../texture/tiles.png" width="64" height="16"/>
+ <tile id="0" type="grass">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="1" type="dirt">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="2" type="platform">
+  <properties>
+   <property name="one_way" type="bool" value="true"/>
+  </properties>
+ </tile>
+</tileset>
@@ -0,0 +1,8 @@
+#version 150
+
+in vec4 Color;
+
+void main()
+{
+    gl_FragColor = Color;
+}
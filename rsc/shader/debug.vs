@@ -0,0 +1,17 @@
+#version 150
+#extension GL_ARB_explicit_attrib_location : require
+
+layout(location = 0) in vec3 iPosition;
+layout(location = 1) in vec4 iColor;
+
+uniform mat4 uModel;
+uniform mat4 uView;
+uniform mat4 uProjection;
+
+out vec4 Color;
+
+void main()
+{
+    Color = iColor;
+    gl_Position = uProjection * uView * uModel * vec4(iPosition, 1.0);
+}
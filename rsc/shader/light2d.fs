@@ -0,0 +1,29 @@
+#version 150
+
+in vec2 WorldPosition;
+
+uniform vec2 uLightPosition;
+uniform vec3 uLightColor; // 強さを掛けた色
+uniform float uLightRadius;
+uniform float uFalloff;
+uniform float uLightHeight;
+
+uniform bool uUseNormals;
+uniform sampler2D uNormals; // 法線バッファー (アルファが0のところは法線マップがない)
+uniform vec2 uTargetSize;
+
+void main()
+{
+    vec2 toLight = uLightPosition - WorldPosition;
+    float distance = length(toLight) / uLightRadius;
+    float attenuation = pow(clamp(1.0 - distance, 0.0, 1.0), uFalloff);
+
+    float diffuse = 1.0;
+    if (uUseNormals) {
+        vec4 normal = texture(uNormals, gl_FragCoord.xy / uTargetSize);
+        vec3 n = normalize(normal.xyz * 2.0 - 1.0);
+        vec3 l = normalize(vec3(toLight, uLightHeight));
+        diffuse = mix(1.0, max(dot(n, l), 0.0), normal.a);
+    }
+    gl_FragColor = vec4(uLightColor * attenuation * diffuse, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 150
+#extension GL_ARB_explicit_attrib_location : require
+
+layout(location = 0) in vec2 iPosition;
+
+uniform mat4 uModel;
+uniform mat4 uView;
+uniform mat4 uProjection;
+
+out vec2 WorldPosition;
+
+void main()
+{
+    vec4 world = uModel * vec4(iPosition, 0.0, 1.0);
+    WorldPosition = world.xy;
+    gl_Position = uProjection * uView * world;
+}
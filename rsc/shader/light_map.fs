@@ -0,0 +1,10 @@
+#version 150
+
+in vec2 TexCoord;
+
+uniform sampler2D uLightMap;
+
+void main()
+{
+    gl_FragColor = vec4(texture(uLightMap, TexCoord).rgb, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 150
+#extension GL_ARB_explicit_attrib_location : require
+
+// 画面全体を覆う四角形 (クリップ座標をそのまま使う)
+layout(location = 0) in vec2 iPosition;
+
+out vec2 TexCoord;
+
+void main()
+{
+    TexCoord = iPosition * 0.5 + 0.5;
+    gl_Position = vec4(iPosition, 0.0, 1.0);
+}
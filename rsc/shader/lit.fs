@@ -0,0 +1,99 @@
+#version 150
+
+// src/lighting.rs の MAX_*_LIGHTS と合わせる
+#define MAX_DIRECTIONAL_LIGHTS 4
+#define MAX_POINT_LIGHTS 8
+#define MAX_SPOT_LIGHTS 4
+
+struct DirectionalLight {
+    vec3 direction; // 光が進む向き
+    vec3 color;     // 強さを掛けた色
+};
+
+struct PointLight {
+    vec3 position;
+    vec3 color;
+    float range;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+    vec3 color;
+    float range;
+    float innerCos;
+    float outerCos;
+};
+
+in vec3 FragPosition;
+in vec3 FragNormal;
+in vec2 TexCoord;
+
+uniform vec3 uAmbientLight;
+uniform vec3 uCameraPosition;
+uniform bool uBlinnPhong;
+uniform DirectionalLight uDirectionalLights[MAX_DIRECTIONAL_LIGHTS];
+uniform int uDirectionalLightCount;
+uniform PointLight uPointLights[MAX_POINT_LIGHTS];
+uniform int uPointLightCount;
+uniform SpotLight uSpotLights[MAX_SPOT_LIGHTS];
+uniform int uSpotLightCount;
+
+// マテリアル
+uniform vec4 uColor;
+uniform vec3 uSpecularColor;
+uniform float uShininess;
+
+// 光の向き (面から光源へ) と明るさから、拡散反射と鏡面反射を求める
+vec3 shade(vec3 lightDirection, vec3 radiance, vec3 normal, vec3 viewDirection)
+{
+    float diffuse = max(dot(normal, lightDirection), 0.0);
+    float specular = 0.0;
+    if (diffuse > 0.0) {
+        if (uBlinnPhong) {
+            vec3 halfway = normalize(lightDirection + viewDirection);
+            specular = pow(max(dot(normal, halfway), 0.0), uShininess);
+        } else {
+            vec3 reflected = reflect(-lightDirection, normal);
+            specular = pow(max(dot(viewDirection, reflected), 0.0), uShininess);
+        }
+    }
+    return radiance * (diffuse * uColor.rgb + specular * uSpecularColor);
+}
+
+// range で0になるように、なめらかに暗くする
+float attenuation(float distance, float range)
+{
+    float t = clamp(1.0 - (distance * distance) / (range * range), 0.0, 1.0);
+    return t * t;
+}
+
+void main()
+{
+    vec3 normal = normalize(FragNormal);
+    if (!gl_FrontFacing) {
+        normal = -normal; // カリングを切ったときの裏面
+    }
+    vec3 viewDirection = normalize(uCameraPosition - FragPosition);
+
+    vec3 color = uAmbientLight * uColor.rgb;
+    for (int i = 0; i < uDirectionalLightCount; i++) {
+        color += shade(-uDirectionalLights[i].direction, uDirectionalLights[i].color, normal, viewDirection);
+    }
+    for (int i = 0; i < uPointLightCount; i++) {
+        vec3 toLight = uPointLights[i].position - FragPosition;
+        float distance = length(toLight);
+        vec3 radiance = uPointLights[i].color * attenuation(distance, uPointLights[i].range);
+        color += shade(toLight / max(distance, 0.0001), radiance, normal, viewDirection);
+    }
+    for (int i = 0; i < uSpotLightCount; i++) {
+        vec3 toLight = uSpotLights[i].position - FragPosition;
+        float distance = length(toLight);
+        vec3 lightDirection = toLight / max(distance, 0.0001);
+        float theta = dot(-lightDirection, uSpotLights[i].direction);
+        float cone = smoothstep(uSpotLights[i].outerCos, uSpotLights[i].innerCos, theta);
+        vec3 radiance = uSpotLights[i].color * attenuation(distance, uSpotLights[i].range) * cone;
+        color += shade(lightDirection, radiance, normal, viewDirection);
+    }
+    gl_FragColor = vec4(color, uColor.a);
+}
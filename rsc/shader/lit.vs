@@ -0,0 +1,23 @@
+#version 150
+#extension GL_ARB_explicit_attrib_location : require
+
+layout(location = 0) in vec3 iPosition;
+layout(location = 1) in vec3 iNormal;
+layout(location = 2) in vec2 iTexCoord;
+
+uniform mat4 uModel;
+uniform mat4 uView;
+uniform mat4 uProjection;
+
+out vec3 FragPosition;
+out vec3 FragNormal;
+out vec2 TexCoord;
+
+void main()
+{
+    FragPosition = vec3(uModel * vec4(iPosition, 1.0));
+    // 拡大縮小が軸ごとに違っても法線が面に垂直なままになるように、逆行列の転置を使う
+    FragNormal = mat3(transpose(inverse(uModel))) * iNormal;
+    TexCoord = iTexCoord;
+    gl_Position = uProjection * uView * vec4(FragPosition, 1.0);
+}
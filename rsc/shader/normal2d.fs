@@ -0,0 +1,27 @@
+#version 150
+
+in vec2 TexCoord;
+
+uniform mat4 uModel;
+uniform bool uHasNormalMap;
+uniform sampler2D uNormalMap;
+
+void main()
+{
+    if (!uHasNormalMap) {
+        // 手前のスプライトで、奥の法線マップを隠す
+        gl_FragColor = vec4(0.5, 0.5, 1.0, 0.0);
+        return;
+    }
+    vec4 color = texture(uNormalMap, TexCoord);
+    if (color.a < 0.5) {
+        discard;
+    }
+    vec3 normal = color.xyz * 2.0 - 1.0;
+    // スプライトの回転と反転に合わせて、画面に沿った向きを回す
+    vec2 planar = mat2(uModel) * normal.xy;
+    if (length(planar) > 0.0) {
+        planar = normalize(planar) * length(normal.xy);
+    }
+    gl_FragColor = vec4(normalize(vec3(planar, normal.z)) * 0.5 + 0.5, 1.0);
+}
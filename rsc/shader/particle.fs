@@ -0,0 +1,18 @@
+#version 150
+
+in vec2 TexCoord;
+in vec4 Color;
+
+uniform sampler2D uTexture;
+uniform bool uHasTexture;
+
+void main()
+{
+    if (uHasTexture) {
+        gl_FragColor = texture(uTexture, TexCoord) * Color;
+    } else {
+        // テクスチャがなければ、縁をぼかした丸を描く
+        float distance = length(TexCoord * 2.0 - 1.0);
+        gl_FragColor = vec4(Color.rgb, Color.a * (1.0 - smoothstep(0.5, 1.0, distance)));
+    }
+}
@@ -0,0 +1,28 @@
+#version 150
+#extension GL_ARB_explicit_attrib_location : require
+
+// 四角形の角 (-0.5 ~ 0.5)
+layout(location = 0) in vec2 iCorner;
+// 粒子ごと (インスタンスごと) の属性
+layout(location = 1) in vec4 iParticle; // 位置xy、大きさ、回転 (ラジアン)
+layout(location = 2) in vec4 iTexRect;  // UVの範囲 (左上xy、右下xy)
+layout(location = 3) in vec4 iColor;
+
+uniform mat4 uModel;
+uniform mat4 uView;
+uniform mat4 uProjection;
+
+out vec2 TexCoord;
+out vec4 Color;
+
+void main()
+{
+    float s = sin(iParticle.w);
+    float c = cos(iParticle.w);
+    vec2 offset = mat2(c, s, -s, c) * (iCorner * iParticle.z);
+    // 画像の座標系では y が下向きなので、下の角が v = 1
+    vec2 t = iCorner + 0.5;
+    TexCoord = mix(iTexRect.xy, iTexRect.zw, vec2(t.x, 1.0 - t.y));
+    Color = iColor;
+    gl_Position = uProjection * uView * uModel * vec4(iParticle.xy + offset, 0.0, 1.0);
+}
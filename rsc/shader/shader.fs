@@ -2,7 +2,9 @@
 
 in vec3 FragPosition;
 
+uniform vec4 uColor;
+
 void main()
 {
-    gl_FragColor = vec4(0.0, 0.0, 1.0, 1.0);
+    gl_FragColor = uColor;
 }
\ No newline at end of file
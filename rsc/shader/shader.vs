@@ -1,6 +1,7 @@
 #version 150
+#extension GL_ARB_explicit_attrib_location : require
 
-in vec3 iPosition;
+layout(location = 0) in vec3 iPosition;
 
 uniform mat4 uModel;
 uniform mat4 uView;
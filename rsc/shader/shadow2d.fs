@@ -0,0 +1,9 @@
+#version 150
+
+in vec2 WorldPosition;
+
+// 影はステンシルにだけ描く (色は書き込まない)
+void main()
+{
+    gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+}
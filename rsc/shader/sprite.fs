@@ -0,0 +1,11 @@
+#version 150
+
+in vec2 TexCoord;
+
+uniform sampler2D uTexture;
+uniform vec4 uColor;
+
+void main()
+{
+    gl_FragColor = texture(uTexture, TexCoord) * uColor;
+}
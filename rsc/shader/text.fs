@@ -0,0 +1,11 @@
+#version 150
+
+in vec2 TexCoord;
+in vec4 Color;
+
+uniform sampler2D uTexture;
+
+void main()
+{
+    gl_FragColor = Color * texture(uTexture, TexCoord);
+}
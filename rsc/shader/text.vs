@@ -0,0 +1,25 @@
+#version 150
+#extension GL_ARB_explicit_attrib_location : require
+
+layout(location = 0) in vec2 iPosition;
+layout(location = 1) in vec2 iTexCoord;
+layout(location = 2) in vec4 iColor;
+
+uniform mat4 uModel;
+uniform mat4 uView;
+uniform mat4 uProjection;
+uniform bool uScreenSpace; // true: uModel だけでウィンドウ上の位置に変換する
+
+out vec2 TexCoord;
+out vec4 Color;
+
+void main()
+{
+    TexCoord = iTexCoord;
+    Color = iColor;
+    if (uScreenSpace) {
+        gl_Position = uModel * vec4(iPosition, 0.0, 1.0);
+    } else {
+        gl_Position = uProjection * uView * uModel * vec4(iPosition, 0.0, 1.0);
+    }
+}
@@ -0,0 +1,12 @@
+#version 150
+
+in vec2 TexCoord;
+in float Opacity;
+
+uniform sampler2D uTexture;
+
+void main()
+{
+    vec4 color = texture(uTexture, TexCoord);
+    gl_FragColor = vec4(color.rgb, color.a * Opacity);
+}
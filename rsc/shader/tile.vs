@@ -0,0 +1,20 @@
+#version 150
+#extension GL_ARB_explicit_attrib_location : require
+
+layout(location = 0) in vec2 iPosition;
+layout(location = 1) in vec2 iTexCoord;
+layout(location = 2) in float iOpacity;
+
+uniform mat4 uModel;
+uniform mat4 uView;
+uniform mat4 uProjection;
+
+out vec2 TexCoord;
+out float Opacity;
+
+void main()
+{
+    TexCoord = iTexCoord;
+    Opacity = iOpacity;
+    gl_Position = uProjection * uView * uModel * vec4(iPosition, 0.0, 1.0);
+}
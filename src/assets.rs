@@ -0,0 +1,18 @@
+mod archive;
+mod config;
+mod group;
+mod handle;
+mod loader;
+mod manager;
+mod watcher;
+
+#[allow(unused_imports)]
+pub use archive::{Archive, ArchiveEntry, AssetReader};
+#[allow(unused_imports)]
+pub use config::Config;
+#[allow(unused_imports)]
+pub use group::LoadGroup;
+#[allow(unused_imports)]
+pub use handle::{Handle, LoadState};
+#[allow(unused_imports)]
+pub use manager::{Asset, Assets, AsyncAsset};
@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+// アーカイブファイルの形式 (数値はすべてリトルエンディアン)
+// ヘッダー: "RPAK", バージョン (u32), 目次の位置 (u64)
+// 中身: ファイルのデータを順に並べる
+// 目次: ファイルの数 (u32), ファイルごとに
+//       名前の長さ (u16), 名前 (UTF-8), 位置 (u64), 格納したサイズ (u64), 元のサイズ (u64), 圧縮方式 (u8)
+const MAGIC: &[u8; 4] = b"RPAK";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 16;
+
+// アーカイブの拡張子
+pub const ARCHIVE_EXTENSION: &str = "pak";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Stored,  // そのまま
+    Deflate, // zlibで圧縮
+}
+
+impl Method {
+    fn from_u8(value: u8) -> Option<Method> {
+        match value {
+            0 => Some(Method::Stored),
+            1 => Some(Method::Deflate),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Method::Stored => 0,
+            Method::Deflate => 1,
+        }
+    }
+}
+
+// 目次の1項目
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub name: String, // ルートからの相対パス (区切り文字は '/')
+    pub offset: u64,
+    pub stored_size: u64,
+    pub size: u64,
+    pub method: Method,
+}
+
+// rsc ディレクトリをまとめた1つのファイル (配布用)
+// 読み込むたびにファイルを開き直すので、バックグラウンドのスレッドからも読める
+pub struct Archive {
+    path: PathBuf,
+    entries: HashMap<String, ArchiveEntry>,
+}
+
+#[allow(dead_code)]
+impl Archive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Archive, String> {
+        let path = path.as_ref();
+        let error = |e: io::Error| format!("failed to read archive: {}: {}", path.display(), e);
+        let mut file = BufReader::new(
+            File::open(path)
+                .map_err(|e| format!("failed to open file: {}: {}", path.display(), e))?,
+        );
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic).map_err(error)?;
+        if &magic != MAGIC {
+            return Err(format!("{}: not an archive", path.display()));
+        }
+        let version = read_u32(&mut file).map_err(error)?;
+        if version != VERSION {
+            return Err(format!(
+                "{}: unsupported archive version {}",
+                path.display(),
+                version
+            ));
+        }
+        let toc_offset = read_u64(&mut file).map_err(error)?;
+        file.seek(SeekFrom::Start(toc_offset)).map_err(error)?;
+        let count = read_u32(&mut file).map_err(error)?;
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let entry = read_entry(&mut file)
+                .map_err(error)?
+                .ok_or_else(|| format!("{}: broken table of contents", path.display()))?;
+            entries.insert(entry.name.clone(), entry);
+        }
+        Ok(Archive {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&ArchiveEntry> {
+        self.entries.get(name)
+    }
+
+    // 名前の順に並べた目次
+    pub fn entries(&self) -> Vec<&ArchiveEntry> {
+        let mut entries: Vec<_> = self.entries.values().collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        self.open_entry(name)?
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("failed to read file: {}: {}", name, e))?;
+        Ok(bytes)
+    }
+
+    // 圧縮していないファイルは必要なところだけ読む (BGMのように少しずつ読むもの向け)
+    // 圧縮したファイルはすべて展開してから返す
+    pub fn open_entry(&self, name: &str) -> Result<AssetReader, String> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or_else(|| format!("file not found in archive: {}", name))?;
+        let error = |e: io::Error| format!("failed to read file: {}: {}", name, e);
+        let mut file = File::open(&self.path)
+            .map_err(|e| format!("failed to open file: {}: {}", self.path.display(), e))?;
+        file.seek(SeekFrom::Start(entry.offset)).map_err(error)?;
+        match entry.method {
+            Method::Stored => Ok(AssetReader::Slice {
+                file: BufReader::new(file),
+                start: entry.offset,
+                size: entry.size,
+                position: 0,
+            }),
+            Method::Deflate => {
+                let mut bytes = Vec::with_capacity(entry.size as usize);
+                ZlibDecoder::new(file.take(entry.stored_size))
+                    .read_to_end(&mut bytes)
+                    .map_err(error)?;
+                Ok(AssetReader::Memory(Cursor::new(bytes)))
+            }
+        }
+    }
+}
+
+// アーカイブを書き出す (pack ツールで使う)
+pub struct ArchiveWriter<W: Write + Seek> {
+    writer: W,
+    entries: Vec<ArchiveEntry>,
+    compression: Option<Compression>, // None なら圧縮しない
+}
+
+#[allow(dead_code)]
+impl<W: Write + Seek> ArchiveWriter<W> {
+    pub fn new(mut writer: W, compression: Option<Compression>) -> io::Result<ArchiveWriter<W>> {
+        // 目次の位置は最後に書き込む
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&0u64.to_le_bytes())?;
+        Ok(ArchiveWriter {
+            writer,
+            entries: Vec::new(),
+            compression,
+        })
+    }
+
+    // 圧縮しても小さくならないファイル (PNGやOGGなど) はそのまま格納する
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<&ArchiveEntry> {
+        if name.len() > u16::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "name too long"));
+        }
+        let compressed = match self.compression {
+            Some(level) => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                Some(encoder.finish()?).filter(|compressed| compressed.len() < data.len())
+            }
+            None => None,
+        };
+        let (method, stored) = match &compressed {
+            Some(compressed) => (Method::Deflate, compressed.as_slice()),
+            None => (Method::Stored, data),
+        };
+        let offset = self.writer.stream_position()?;
+        self.writer.write_all(stored)?;
+        self.entries.push(ArchiveEntry {
+            name: name.to_string(),
+            offset,
+            stored_size: stored.len() as u64,
+            size: data.len() as u64,
+            method,
+        });
+        Ok(self.entries.last().unwrap())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let toc_offset = self.writer.stream_position()?;
+        self.writer
+            .write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for entry in &self.entries {
+            self.writer
+                .write_all(&(entry.name.len() as u16).to_le_bytes())?;
+            self.writer.write_all(entry.name.as_bytes())?;
+            self.writer.write_all(&entry.offset.to_le_bytes())?;
+            self.writer.write_all(&entry.stored_size.to_le_bytes())?;
+            self.writer.write_all(&entry.size.to_le_bytes())?;
+            self.writer.write_all(&[entry.method.to_u8()])?;
+        }
+        self.writer.seek(SeekFrom::Start(HEADER_SIZE - 8))?;
+        self.writer.write_all(&toc_offset.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// ばらばらのファイルとアーカイブの中のファイルを同じように読むためのもの
+// スレッド間で受け渡せる
+pub enum AssetReader {
+    File(BufReader<File>),
+    // アーカイブの中の圧縮していないファイル
+    Slice {
+        file: BufReader<File>,
+        start: u64,
+        size: u64,
+        position: u64,
+    },
+    // 展開済みのファイル
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Read for AssetReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            AssetReader::File(file) => file.read(buf),
+            AssetReader::Slice {
+                file,
+                size,
+                position,
+                ..
+            } => {
+                let remaining = size.saturating_sub(*position);
+                let len = (buf.len() as u64).min(remaining) as usize;
+                let read = file.read(&mut buf[..len])?;
+                *position += read as u64;
+                Ok(read)
+            }
+            AssetReader::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for AssetReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            AssetReader::File(file) => file.seek(pos),
+            AssetReader::Slice {
+                file,
+                start,
+                size,
+                position,
+            } => {
+                let target = match pos {
+                    SeekFrom::Start(offset) => offset as i64,
+                    SeekFrom::Current(offset) => *position as i64 + offset,
+                    SeekFrom::End(offset) => *size as i64 + offset,
+                };
+                if target < 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "seek before start of file",
+                    ));
+                }
+                file.seek(SeekFrom::Start(*start + target as u64))?;
+                *position = target as u64;
+                Ok(*position)
+            }
+            AssetReader::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// 名前か圧縮方式が壊れていれば None
+fn read_entry<R: Read>(reader: &mut R) -> io::Result<Option<ArchiveEntry>> {
+    let len = read_u16(reader)? as usize;
+    let mut name = vec![0; len];
+    reader.read_exact(&mut name)?;
+    let offset = read_u64(reader)?;
+    let stored_size = read_u64(reader)?;
+    let size = read_u64(reader)?;
+    let mut method = [0; 1];
+    reader.read_exact(&mut method)?;
+    let (name, method) = match (String::from_utf8(name), Method::from_u8(method[0])) {
+        (Ok(name), Some(method)) => (name, method),
+        _ => return Ok(None),
+    };
+    Ok(Some(ArchiveEntry {
+        name,
+        offset,
+        stored_size,
+        size,
+        method,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // テストごとに別の一時ファイルに書き出す (終わったら消す)
+    struct TempArchive(PathBuf);
+
+    impl TempArchive {
+        fn new(name: &str, files: &[(&str, &[u8])]) -> TempArchive {
+            let path = std::env::temp_dir().join(format!(
+                "{}-{}.{}",
+                name,
+                std::process::id(),
+                ARCHIVE_EXTENSION
+            ));
+            let file = File::create(&path).unwrap();
+            let mut writer = ArchiveWriter::new(file, Some(Compression::default())).unwrap();
+            for (name, data) in files {
+                writer.add(name, data).unwrap();
+            }
+            writer.finish().unwrap();
+            TempArchive(path)
+        }
+    }
+
+    impl Drop for TempArchive {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    // 圧縮すると小さくなるデータと、ならないデータ
+    fn text() -> Vec<u8> {
+        "tile ".repeat(200).into_bytes()
+    }
+
+    fn noise() -> Vec<u8> {
+        let mut x = 0x1234_5678u32;
+        (0..300)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let (text, noise) = (text(), noise());
+        let temp = TempArchive::new(
+            "archive-round-trip",
+            &[
+                ("map/level.tmx", &text),
+                ("music/field.ogg", &noise),
+                ("empty", &[]),
+            ],
+        );
+        let archive = Archive::open(&temp.0).unwrap();
+        assert_eq!(archive.len(), 3);
+        let names: Vec<_> = archive.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["empty", "map/level.tmx", "music/field.ogg"]);
+
+        let entry = archive.entry("map/level.tmx").unwrap();
+        assert_eq!(entry.method, Method::Deflate);
+        assert!(entry.stored_size < entry.size);
+        assert_eq!(
+            archive.entry("music/field.ogg").unwrap().method,
+            Method::Stored
+        );
+
+        assert_eq!(archive.read("map/level.tmx").unwrap(), text);
+        assert_eq!(archive.read("music/field.ogg").unwrap(), noise);
+        assert_eq!(archive.read("empty").unwrap(), Vec::<u8>::new());
+        assert!(matches!(
+            archive.open_entry("map/level.tmx").unwrap(),
+            AssetReader::Memory(_)
+        ));
+        assert_eq!(
+            archive.read("missing").unwrap_err(),
+            "file not found in archive: missing"
+        );
+    }
+
+    #[test]
+    fn stored_entries_seek_within_the_slice() {
+        let noise = noise();
+        // 前後に別のファイルを置き、範囲の外を読まないことを確かめる
+        let temp = TempArchive::new(
+            "archive-seek",
+            &[("before", b"AAAA"), ("noise", &noise), ("after", b"ZZZZ")],
+        );
+        let archive = Archive::open(&temp.0).unwrap();
+        let mut reader = archive.open_entry("noise").unwrap();
+        assert!(matches!(reader, AssetReader::Slice { .. }));
+
+        let mut bytes = [0; 4];
+        assert_eq!(reader.seek(SeekFrom::Start(100)).unwrap(), 100);
+        reader.read_exact(&mut bytes).unwrap();
+        assert_eq!(bytes, noise[100..104]);
+        assert_eq!(reader.seek(SeekFrom::Current(-8)).unwrap(), 96);
+        reader.read_exact(&mut bytes).unwrap();
+        assert_eq!(bytes, noise[96..100]);
+
+        // 末尾からの位置と、末尾を越えたときは読めない
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 298);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, noise[298..]);
+        reader.seek(SeekFrom::End(10)).unwrap();
+        assert_eq!(reader.read(&mut bytes).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::Current(-1000)).is_err());
+
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, noise);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("archive-bad-{}.pak", std::process::id()));
+        std::fs::write(&path, b"PK\x03\x04 not an archive").unwrap();
+        let result = Archive::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.err().unwrap().ends_with("not an archive"));
+    }
+}
@@ -0,0 +1,33 @@
+use std::ops::Deref;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use super::manager::{Asset, Assets};
+
+// RON / TOML / JSON で書いた設定ファイル (拡張子で形式を判断する)
+// 調整値をファイルに書いておくと、ゲームを動かしたまま書き換えて試せる
+pub struct Config<T>(pub T);
+
+impl<T: DeserializeOwned + 'static> Asset for Config<T> {
+    fn load(assets: &Assets, path: &str) -> Result<Config<T>, String> {
+        let source = assets.read_to_string(path)?;
+        let value = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("ron") => ron::from_str(&source).map_err(|e| format!("{}: {}", path, e))?,
+            Some("toml") => toml::from_str(&source).map_err(|e| format!("{}: {}", path, e))?,
+            Some("json") => {
+                serde_json::from_str(&source).map_err(|e| format!("{}: {}", path, e))?
+            }
+            _ => return Err(format!("unsupported config format: {}", path)),
+        };
+        Ok(Config(value))
+    }
+}
+
+impl<T> Deref for Config<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
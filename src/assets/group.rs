@@ -0,0 +1,94 @@
+use std::rc::Rc;
+
+use super::handle::{Handle, LoadState};
+
+// 型の違うハンドルをまとめて扱うため
+trait Status {
+    fn path(&self) -> &str;
+    fn state(&self) -> LoadState;
+    fn error(&self) -> Option<&str>;
+}
+
+impl<T> Status for Handle<T> {
+    fn path(&self) -> &str {
+        Handle::path(self)
+    }
+
+    fn state(&self) -> LoadState {
+        Handle::state(self)
+    }
+
+    fn error(&self) -> Option<&str> {
+        Handle::error(self)
+    }
+}
+
+// まとめて読み込みを待つアセット (ロード画面で、ステージに必要なものがそろうのを待つときなど)
+// グループもハンドルを持つので、待っている間にアセットが解放されることはない
+// clone() したものは同じハンドルを持つ (ロード画面と進み具合の表示で同じグループを見るときなど)
+#[derive(Clone, Default)]
+pub struct LoadGroup {
+    handles: Vec<Rc<dyn Status>>,
+}
+
+#[allow(dead_code)]
+impl LoadGroup {
+    pub fn new() -> LoadGroup {
+        LoadGroup::default()
+    }
+
+    pub fn add<T: 'static>(&mut self, handle: &Handle<T>) {
+        self.handles.push(Rc::new(handle.clone()));
+    }
+
+    pub fn with<T: 'static>(mut self, handle: &Handle<T>) -> LoadGroup {
+        self.add(handle);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    // 読み込みが終わったものの数 (失敗したものも含める)
+    pub fn finished_count(&self) -> usize {
+        self.handles
+            .iter()
+            .filter(|handle| handle.state() != LoadState::Loading)
+            .count()
+    }
+
+    // 0.0 ~ 1.0 (imgui::ProgressBar にそのまま渡せる)
+    pub fn progress(&self) -> f32 {
+        if self.handles.is_empty() {
+            return 1.0;
+        }
+        self.finished_count() as f32 / self.handles.len() as f32
+    }
+
+    // すべての読み込みが終わった (失敗したものがあるかどうかは failures() で調べる)
+    pub fn is_done(&self) -> bool {
+        self.finished_count() == self.handles.len()
+    }
+
+    // 読み込みに失敗したもの (パス, エラー)
+    pub fn failures(&self) -> Vec<(&str, &str)> {
+        self.handles
+            .iter()
+            .filter_map(|handle| handle.error().map(|error| (handle.path(), error)))
+            .collect()
+    }
+
+    // 読み込み中のもののパス
+    pub fn loading(&self) -> Vec<&str> {
+        self.handles
+            .iter()
+            .filter(|handle| handle.state() == LoadState::Loading)
+            .map(|handle| handle.path())
+            .collect()
+    }
+}
@@ -0,0 +1,173 @@
+use std::cell::OnceCell;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+
+// アセットの読み込みの状態
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    Loading, // バックグラウンドで読み込み中
+    Loaded,
+    Failed,
+}
+
+struct Entry<T> {
+    path: String,
+    value: OnceCell<T>,
+    error: OnceCell<String>,
+    newer: OnceCell<Handle<T>>, // ファイルが変わって読み込み直したもの
+}
+
+// 読み込んだアセットへの参照
+// 同じファイルのハンドルは同じアセットを指し、すべてのハンドルが破棄されるとアセットも解放される
+// バックグラウンドで読み込んでいるアセットは、読み込みが終わるまで中身を参照できない
+// ファイルが変わって読み込み直しても、古いハンドルは古いアセットを指したまま (refresh() で新しいものに替える)
+pub struct Handle<T> {
+    entry: Rc<Entry<T>>,
+}
+
+#[allow(dead_code)]
+impl<T> Handle<T> {
+    // ファイルから読み込んだものではないアセット (フォントのアトラスなど) を包む
+    pub fn new(name: &str, value: T) -> Handle<T> {
+        let handle = Handle::<T>::pending(name);
+        handle.set(value);
+        handle
+    }
+
+    // 読み込みが終わってから中身を入れるハンドル
+    pub(super) fn pending(name: &str) -> Handle<T> {
+        Handle {
+            entry: Rc::new(Entry {
+                path: name.to_string(),
+                value: OnceCell::new(),
+                error: OnceCell::new(),
+                newer: OnceCell::new(),
+            }),
+        }
+    }
+
+    pub(super) fn set(&self, value: T) {
+        if self.entry.value.set(value).is_err() {
+            panic!("asset is already loaded: {}", self.entry.path);
+        }
+    }
+
+    pub(super) fn fail(&self, error: String) {
+        let _ = self.entry.error.set(error);
+    }
+
+    // 読み込み直したアセットを古いハンドルから辿れるようにする
+    pub(super) fn replace(&self, newer: Handle<T>) {
+        let _ = self.entry.newer.set(newer);
+    }
+
+    // 読み込み直されて、新しいアセットがある
+    pub fn is_stale(&self) -> bool {
+        self.entry.newer.get().is_some()
+    }
+
+    // 最後に読み込み直したアセットのハンドル
+    pub fn latest(&self) -> Handle<T> {
+        let mut latest = self;
+        while let Some(newer) = latest.entry.newer.get() {
+            latest = newer;
+        }
+        latest.clone()
+    }
+
+    // 新しいアセットがあれば持ち替える (持ち替えたら true)
+    // アセットを持っているもの (マテリアルなど) が毎フレーム呼び、読み込み直されたことを知る
+    pub fn refresh(&mut self) -> bool {
+        if !self.is_stale() {
+            return false;
+        }
+        *self = self.latest();
+        true
+    }
+
+    // アセットのパス (Assets で読み込んだものはルートからの絶対パス)
+    pub fn path(&self) -> &str {
+        &self.entry.path
+    }
+
+    pub fn state(&self) -> LoadState {
+        if self.entry.value.get().is_some() {
+            LoadState::Loaded
+        } else if self.entry.error.get().is_some() {
+            LoadState::Failed
+        } else {
+            LoadState::Loading
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.state() == LoadState::Loaded
+    }
+
+    // 読み込みが終わっていなければ None
+    pub fn get(&self) -> Option<&T> {
+        self.entry.value.get()
+    }
+
+    // 読み込みに失敗したときのエラー
+    pub fn error(&self) -> Option<&str> {
+        self.entry.error.get().map(String::as_str)
+    }
+
+    pub fn ptr_eq(&self, other: &Handle<T>) -> bool {
+        Rc::ptr_eq(&self.entry, &other.entry)
+    }
+
+    // 同じアセットを指しているハンドルの数
+    pub fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.entry)
+    }
+
+    pub(super) fn downgrade(&self) -> WeakHandle<T> {
+        WeakHandle {
+            entry: Rc::downgrade(&self.entry),
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            entry: self.entry.clone(),
+        }
+    }
+}
+
+// 読み込みが終わっていないアセットを参照するとパニックする (先に is_loaded() や LoadGroup で待つこと)
+impl<T> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self.entry.value.get() {
+            Some(value) => value,
+            None => panic!("asset is not loaded: {} ({:?})", self.path(), self.state()),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({}, {:?})", self.entry.path, self.state())
+    }
+}
+
+// キャッシュが持つ参照 (アセットを解放されないようにはしない)
+pub(super) struct WeakHandle<T> {
+    entry: Weak<Entry<T>>,
+}
+
+impl<T> WeakHandle<T> {
+    pub fn upgrade(&self) -> Option<Handle<T>> {
+        self.entry.upgrade().map(|entry| Handle { entry })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.entry.strong_count() > 0
+    }
+}
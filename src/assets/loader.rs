@@ -0,0 +1,108 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+// バックグラウンドのスレッドでデコードした結果
+pub(super) type Decoded = Result<Box<dyn Any + Send>, String>;
+// バックグラウンドのスレッドで実行する処理 (ファイルの読み込みとデコード)
+pub(super) type Job = Box<dyn FnOnce() -> Decoded + Send>;
+// メインスレッドで実行する仕上げ (GLのリソースを作ってハンドルに入れる)
+pub(super) type Finish = Box<dyn FnOnce(Decoded)>;
+
+const MAX_WORKERS: usize = 4;
+
+// デコードを受け持つスレッドたち
+// GLのリソースはメインスレッドでしか作れないので、仕上げは poll() / wait() で受け取って呼び出し側で実行する
+pub(super) struct Loader {
+    jobs: Option<Sender<(u64, Job)>>,
+    results: Receiver<(u64, Decoded)>,
+    pending: HashMap<u64, Finish>,
+    next_id: u64,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Loader {
+    pub fn new() -> Loader {
+        // メインスレッドの分を1つ残す
+        let count = thread::available_parallelism()
+            .map(|n| n.get().saturating_sub(1))
+            .unwrap_or(1)
+            .clamp(1, MAX_WORKERS);
+        let (jobs, job_receiver) = channel::<(u64, Job)>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..count)
+            .map(|i| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                thread::Builder::new()
+                    .name(format!("asset loader {}", i))
+                    .spawn(move || loop {
+                        // 受け取ったらすぐにロックを手放し、他のスレッドも次の仕事を受け取れるようにする
+                        let received = job_receiver.lock().unwrap().recv();
+                        let (id, job) = match received {
+                            Ok(received) => received,
+                            Err(_) => break, // Loader が破棄された
+                        };
+                        let decoded = panic::catch_unwind(AssertUnwindSafe(job))
+                            .unwrap_or_else(|_| Err("panicked while decoding".to_string()));
+                        if result_sender.send((id, decoded)).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("failed to spawn asset loader thread")
+            })
+            .collect();
+        Loader {
+            jobs: Some(jobs),
+            results,
+            pending: HashMap::new(),
+            next_id: 0,
+            workers,
+        }
+    }
+
+    pub fn spawn(&mut self, job: Job, finish: Finish) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, finish);
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send((id, job))
+            .expect("asset loader threads have stopped");
+    }
+
+    // デコードが終わったものを1つ受け取る (なければ None)
+    pub fn poll(&mut self) -> Option<(Finish, Decoded)> {
+        let (id, decoded) = self.results.try_recv().ok()?;
+        Some((self.pending.remove(&id).unwrap(), decoded))
+    }
+
+    // デコードが終わるまで待って1つ受け取る (読み込み中のものがなければ None)
+    pub fn wait(&mut self) -> Option<(Finish, Decoded)> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let (id, decoded) = self.results.recv().ok()?;
+        Some((self.pending.remove(&id).unwrap(), decoded))
+    }
+
+    // 仕上げが済んでいないものの数
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        // 送信側を閉じるとスレッドのループが終わる
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,795 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use imgui::im_str;
+
+use super::archive::{Archive, AssetReader, ARCHIVE_EXTENSION};
+use super::handle::{Handle, LoadState, WeakHandle};
+use super::loader::{Decoded, Loader};
+use super::watcher::FileWatcher;
+use crate::shader::Shader;
+
+// ルートを指定する環境変数
+const ASSET_ROOT_ENV: &str = "ASSET_ROOT";
+// 実行ファイルの場所から親をたどってルートを探す深さ (target/debug/ から見つけられるように)
+const SEARCH_DEPTH: usize = 4;
+// update() で1フレームあたりGLのリソースを作るのに使ってよい時間 (少なくとも1つは作る)
+const UPLOAD_BUDGET: Duration = Duration::from_millis(4);
+// ファイルが変わったかを調べる間隔
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+// imguiに表示する、最近読み込み直したアセットの数
+const RECENT_RELOADS: usize = 8;
+
+// ファイルから読み込めるアセット
+pub trait Asset: Sized + 'static {
+    // path はルートを解決した後のパス (エラーメッセージや相対パスの基準に使う)
+    fn load(assets: &Assets, path: &str) -> Result<Self, String>;
+
+    // ファイルが変わって読み込み直したときに、前のアセットを渡して呼ばれる
+    // 読み込んだ後で変えた設定 (テクスチャのフィルターなど) を引き継ぐときに使う
+    fn on_reload(&self, _previous: &Self) {}
+}
+
+// バックグラウンドのスレッドで読み込めるアセット
+// ファイルのデコードは別のスレッドで行い、GLのリソースはメインスレッドで作る
+pub trait AsyncAsset: Asset {
+    // デコードした結果 (スレッド間で受け渡すので Send が必要)
+    type Data: Send + 'static;
+
+    // バックグラウンドのスレッドで呼ばれる
+    fn decode(bytes: Vec<u8>, path: &str) -> Result<Self::Data, String>;
+
+    // メインスレッド (Assets::update) で呼ばれる
+    fn create(data: Self::Data, path: &str) -> Result<Self, String>;
+}
+
+struct CacheEntry<T> {
+    handle: WeakHandle<T>,
+    files: Vec<PathBuf>, // 読み込むときに読んだファイル (どれかが変わったら読み込み直す)
+}
+
+// 種類ごとのキャッシュ (パス -> 読み込み済みのアセット)
+struct Cache<T> {
+    entries: HashMap<String, CacheEntry<T>>,
+}
+
+impl<T> Cache<T> {
+    fn new() -> Cache<T> {
+        Cache {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+// 読み込み直す処理 (成功したらアセットのパスを返す)
+type Reload = Box<dyn FnOnce(&Assets) -> Result<String, String>>;
+
+// 型の違うキャッシュをまとめて扱うため
+trait AnyCache {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn type_name(&self) -> &'static str;
+    // 誰も使っていないアセットの記録を消す
+    fn collect_garbage(&mut self);
+    fn len(&self) -> usize;
+    // changed のどれかを読んで作ったアセットを、読み込み直す処理
+    fn reloads(&self, changed: &[PathBuf]) -> Vec<Reload>;
+}
+
+impl<T: Asset> AnyCache for Cache<T> {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn type_name(&self) -> &'static str {
+        // "crate::texture::Texture2D" -> "Texture2D"
+        let name = type_name::<T>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    fn collect_garbage(&mut self) {
+        self.entries.retain(|_, entry| entry.handle.is_alive());
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn reloads(&self, changed: &[PathBuf]) -> Vec<Reload> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.files.iter().any(|file| changed.contains(file)))
+            .filter_map(|(key, entry)| {
+                let previous = entry.handle.upgrade()?;
+                // バックグラウンドで読み込み中のものは、終わってから読み込み直す
+                if previous.state() == LoadState::Loading {
+                    return None;
+                }
+                let key = key.clone();
+                Some(Box::new(move |assets: &Assets| {
+                    assets.reload(&key, &previous)?;
+                    Ok(key)
+                }) as Reload)
+            })
+            .collect()
+    }
+}
+
+struct Store {
+    root: PathBuf,
+    archive: Option<Arc<Archive>>, // ルートに置いたファイルがなければ、ここから読む
+    caches: HashMap<TypeId, Box<dyn AnyCache>>,
+    loader: Option<Loader>, // 最初に load_async() を呼んだときにスレッドを作る
+    watcher: FileWatcher,
+    hot_reload: bool,
+    last_poll: Instant,
+    changed: Vec<String>,         // 直前の update() で変わったとわかったファイル
+    recent: VecDeque<String>,     // 最近読み込み直したアセット
+    recording: Vec<Vec<PathBuf>>, // 読み込み中のアセットが読んだファイル (入れ子になる)
+}
+
+impl Store {
+    fn cache<T: Asset>(&mut self) -> &mut Cache<T> {
+        self.caches
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Cache::<T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+}
+
+// アセットの読み込みを一か所にまとめる
+// - パスはルートからの相対パスで指定する (実行時のカレントディレクトリに左右されない)
+// - 同じファイルを何度読み込んでも、読み込み済みのものを共有する
+// - アセットはハンドル (Handle<T>) で受け取り、すべてのハンドルが破棄されると解放される
+// - ホットリロードが有効なら、ファイルが変わったアセットを読み込み直す (デバッグビルドでは最初から有効)
+// clone() したものは同じキャッシュを共有する
+#[derive(Clone)]
+pub struct Assets {
+    store: Rc<RefCell<Store>>,
+}
+
+#[allow(dead_code)]
+impl Assets {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Assets {
+        Assets {
+            store: Rc::new(RefCell::new(Store {
+                root: normalize(&root.into()),
+                archive: None,
+                caches: HashMap::new(),
+                loader: None,
+                watcher: FileWatcher::new(),
+                hot_reload: cfg!(debug_assertions),
+                last_poll: Instant::now(),
+                changed: Vec::new(),
+                recent: VecDeque::new(),
+                recording: Vec::new(),
+            })),
+        }
+    }
+
+    // ルートを次の順に探す
+    // 1. 環境変数 ASSET_ROOT
+    // 2. 実行ファイルのあるディレクトリとその親のうち、marker (例: "rsc") か marker.pak を含むもの
+    // 3. カレントディレクトリ
+    // ルートに marker.pak があればマウントする (同じパスのファイルがルートにあれば、そちらを優先する)
+    pub fn locate(marker: &str) -> Assets {
+        let archive_name = format!("{}.{}", marker, ARCHIVE_EXTENSION);
+        let root = env::var_os(ASSET_ROOT_ENV)
+            .map(PathBuf::from)
+            .or_else(|| {
+                let exe = env::current_exe().ok()?;
+                let found = exe
+                    .parent()?
+                    .ancestors()
+                    .take(SEARCH_DEPTH + 1)
+                    .find(|dir| dir.join(marker).exists() || dir.join(&archive_name).is_file())?;
+                Some(found.to_path_buf())
+            })
+            .unwrap_or_else(|| env::current_dir().unwrap_or_default());
+        let assets = Assets::new(root);
+        if assets.resolve(&archive_name).is_file() {
+            if let Err(e) = assets.mount(&archive_name) {
+                eprintln!("failed to mount archive: {}", e);
+            }
+        }
+        assets
+    }
+
+    // pack ツールで作ったアーカイブから読めるようにする
+    pub fn mount(&self, path: &str) -> Result<(), String> {
+        let archive = Archive::open(self.resolve(path))?;
+        self.store.borrow_mut().archive = Some(Arc::new(archive));
+        Ok(())
+    }
+
+    pub fn with_archive(self, path: &str) -> Result<Assets, String> {
+        self.mount(path)?;
+        Ok(self)
+    }
+
+    pub fn unmount(&self) {
+        self.store.borrow_mut().archive = None;
+    }
+
+    pub fn archive(&self) -> Option<Arc<Archive>> {
+        self.store.borrow().archive.clone()
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.store.borrow().root.clone()
+    }
+
+    // ルートからの相対パスを絶対パスにする (絶対パスはそのまま、"." や ".." は取り除く)
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            normalize(path)
+        } else {
+            normalize(&self.store.borrow().root.join(path))
+        }
+    }
+
+    // 文字列で扱うとき用 (区切り文字は '/')
+    pub fn path(&self, path: &str) -> String {
+        path_string(&self.resolve(path))
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        let resolved = self.resolve(path);
+        let store = self.store.borrow();
+        resolved.exists()
+            || archive_entry(&store.root, store.archive.as_deref(), &resolved).is_some()
+    }
+
+    // ルートにファイルがなければアーカイブから読む
+    // 読んだファイルは、変わったかどうかを調べる対象になる
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        let resolved = self.resolve(path);
+        self.track(&resolved);
+        let store = self.store.borrow();
+        read_file(&store.root, store.archive.as_deref(), &resolved)
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, String> {
+        let bytes = self.read(path)?;
+        String::from_utf8(bytes).map_err(|e| format!("{}: {}", self.path(path), e))
+    }
+
+    // 少しずつ読むとき用 (BGMなど)
+    pub fn open(&self, path: &str) -> Result<AssetReader, String> {
+        let resolved = self.resolve(path);
+        self.track(&resolved);
+        if resolved.is_file() {
+            let file = File::open(&resolved)
+                .map_err(|e| format!("failed to open file: {}: {}", resolved.display(), e))?;
+            return Ok(AssetReader::File(BufReader::new(file)));
+        }
+        let store = self.store.borrow();
+        match archive_entry(&store.root, store.archive.as_deref(), &resolved) {
+            Some((archive, name)) => archive.open_entry(&name),
+            None => Err(format!("file not found: {}", resolved.display())),
+        }
+    }
+
+    // ディレクトリの中のファイル (ルートとアーカイブの両方から集め、パスの順に並べる)
+    // サブディレクトリには入らない
+    pub fn read_dir(&self, dir: &str) -> Result<Vec<String>, String> {
+        let resolved = self.resolve(dir);
+        let mut paths = Vec::new();
+        let found_loose = match fs::read_dir(&resolved) {
+            Ok(entries) => {
+                paths.extend(
+                    entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.path())
+                        .filter(|path| path.is_file())
+                        .map(|path| path_string(&path)),
+                );
+                true
+            }
+            Err(_) => false,
+        };
+        let store = self.store.borrow();
+        let mut found_archive = false;
+        if let (Some(archive), Ok(relative)) =
+            (store.archive.as_deref(), resolved.strip_prefix(&store.root))
+        {
+            let prefix = format!("{}/", path_string(relative));
+            for entry in archive.entries() {
+                match entry.name.strip_prefix(&prefix) {
+                    Some(name) if !name.contains('/') => {
+                        paths.push(path_string(&store.root.join(&entry.name)));
+                        found_archive = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if !found_loose && !found_archive {
+            return Err(format!("failed to read directory: {}", resolved.display()));
+        }
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
+
+    // Assets を通さずに読んだファイルも、変わったかどうかを調べる対象にする (is_changed() で調べる)
+    pub fn watch(&self, path: &str) {
+        let resolved = self.resolve(path);
+        self.store.borrow_mut().watcher.watch(&resolved);
+    }
+
+    // 直前の update() で、ファイルが変わったとわかった
+    pub fn is_changed(&self, path: &str) -> bool {
+        let path = self.path(path);
+        self.store.borrow().changed.contains(&path)
+    }
+
+    pub fn hot_reload(&self) -> bool {
+        self.store.borrow().hot_reload
+    }
+
+    pub fn set_hot_reload(&self, enabled: bool) {
+        self.store.borrow_mut().hot_reload = enabled;
+    }
+
+    // 読み込み済みならそれを返し、なければ読み込む
+    // バックグラウンドで読み込み中なら、終わるまで待つ
+    pub fn load<T: Asset>(&self, path: &str) -> Result<Handle<T>, String> {
+        self.load_key(&self.path(path))
+    }
+
+    // バックグラウンドのスレッドで読み込み始め、読み込み中のハンドルをすぐに返す
+    // 中身は update() の中で入るので、使う前に is_loaded() や LoadGroup で終わったことを確かめる
+    pub fn load_async<T: AsyncAsset>(&self, path: &str) -> Handle<T> {
+        let key = self.path(path);
+        if let Some(handle) = self.cached::<T>(&key) {
+            return handle;
+        }
+        let handle = Handle::<T>::pending(&key);
+        let resolved = PathBuf::from(&key);
+        let mut store = self.store.borrow_mut();
+        store.watcher.watch(&resolved);
+        let cache = store.cache::<T>();
+        cache.collect_garbage();
+        cache.entries.insert(
+            key.clone(),
+            CacheEntry {
+                handle: handle.downgrade(),
+                files: vec![resolved.clone()],
+            },
+        );
+
+        let name = key.clone();
+        let root = store.root.clone();
+        let archive = store.archive.clone();
+        let job = Box::new(move || -> Decoded {
+            let bytes = read_file(&root, archive.as_deref(), &resolved)?;
+            let data = T::decode(bytes, &name)?;
+            Ok(Box::new(data))
+        });
+        let target = handle.clone();
+        let finish = Box::new(move |decoded: Decoded| {
+            let created = decoded.and_then(|data| {
+                let data = data.downcast::<T::Data>().unwrap();
+                T::create(*data, &key)
+            });
+            match created {
+                Ok(value) => target.set(value),
+                Err(e) => {
+                    eprintln!("failed to load asset: {}", e);
+                    target.fail(e);
+                }
+            }
+        });
+        store
+            .loader
+            .get_or_insert_with(Loader::new)
+            .spawn(job, finish);
+        handle
+    }
+
+    // 毎フレーム、メインスレッドで呼ぶ
+    // - バックグラウンドでデコードが終わったアセットを仕上げる
+    //   一度に多く終わってもフレームが止まらないように、UPLOAD_BUDGET を超えたら次のフレームに回す
+    // - ホットリロードが有効なら、ファイルが変わったアセットを読み込み直す
+    //   古いハンドルを持っているものは、Handle::refresh() で新しいものに持ち替える
+    // 仕上げたアセットの数を返す
+    pub fn update(&self) -> usize {
+        let start = Instant::now();
+        let mut count = 0;
+        while count == 0 || start.elapsed() < UPLOAD_BUDGET {
+            // 仕上げの中でアセットが解放されることがあるので、ストアを借りたまま呼ばない
+            let finished = match self.store.borrow_mut().loader.as_mut() {
+                Some(loader) => loader.poll(),
+                None => None,
+            };
+            match finished {
+                Some((finish, decoded)) => finish(decoded),
+                None => break,
+            }
+            count += 1;
+        }
+
+        let reloads = {
+            let mut store = self.store.borrow_mut();
+            store.changed.clear();
+            if !store.hot_reload || store.last_poll.elapsed() < WATCH_INTERVAL {
+                return count;
+            }
+            store.last_poll = Instant::now();
+            let changed = store.watcher.poll();
+            let reloads: Vec<_> = store
+                .caches
+                .values()
+                .flat_map(|cache| cache.reloads(&changed))
+                .collect();
+            store.changed = changed.iter().map(|path| path_string(path)).collect();
+            reloads
+        };
+        for reload in reloads {
+            match reload(self) {
+                Ok(path) => {
+                    let mut store = self.store.borrow_mut();
+                    store.recent.push_front(path);
+                    store.recent.truncate(RECENT_RELOADS);
+                }
+                Err(e) => eprintln!("failed to reload asset: {}", e),
+            }
+        }
+        count
+    }
+
+    // handle の読み込みが終わるまで待つ (その間に終わった他のアセットも仕上げる)
+    pub fn wait<T>(&self, handle: &Handle<T>) {
+        while handle.state() == LoadState::Loading {
+            let finished = match self.store.borrow_mut().loader.as_mut() {
+                Some(loader) => loader.wait(),
+                None => None,
+            };
+            match finished {
+                Some((finish, decoded)) => finish(decoded),
+                None => break,
+            }
+        }
+    }
+
+    // バックグラウンドで読み込み中のアセットの数
+    pub fn loading_count(&self) -> usize {
+        self.store
+            .borrow()
+            .loader
+            .as_ref()
+            .map_or(0, Loader::pending_count)
+    }
+
+    // 頂点・フラグメント (・ジオメトリ) シェーダーの組み合わせごとに1つだけ作る
+    // どれかのファイルが変わると、まとめて作り直す
+    pub fn load_shader(
+        &self,
+        vertex: &str,
+        fragment: &str,
+        geometry: Option<&str>,
+    ) -> Result<Handle<Shader>, String> {
+        let mut key = format!("{}|{}", self.path(vertex), self.path(fragment));
+        if let Some(geometry) = geometry {
+            key = format!("{}|{}", key, self.path(geometry));
+        }
+        self.load_key(&key)
+    }
+
+    // すでに読み込まれていれば返す
+    pub fn get<T: Asset>(&self, path: &str) -> Option<Handle<T>> {
+        self.cached(&self.path(path))
+    }
+
+    fn load_key<T: Asset>(&self, key: &str) -> Result<Handle<T>, String> {
+        if let Some(handle) = self.cached::<T>(key) {
+            self.wait(&handle);
+            return match handle.error() {
+                Some(error) => Err(error.to_string()),
+                None => Ok(handle),
+            };
+        }
+        // 読み込み中に別のアセットを読み込むことがあるので、キャッシュを借りたままにしない
+        let (value, files) = self.record(|| T::load(self, key));
+        let handle = Handle::new(key, value?);
+        self.insert(key, &handle, files);
+        Ok(handle)
+    }
+
+    fn reload<T: Asset>(&self, key: &str, previous: &Handle<T>) -> Result<(), String> {
+        let (value, files) = self.record(|| T::load(self, key));
+        let value = value?;
+        if let Some(previous) = previous.get() {
+            value.on_reload(previous);
+        }
+        let handle = Handle::new(key, value);
+        previous.replace(handle.clone());
+        self.insert(key, &handle, files);
+        Ok(())
+    }
+
+    // f の中で読んだファイルを集める
+    fn record<R>(&self, f: impl FnOnce() -> R) -> (R, Vec<PathBuf>) {
+        self.store.borrow_mut().recording.push(Vec::new());
+        let result = f();
+        let files = self.store.borrow_mut().recording.pop().unwrap_or_default();
+        (result, files)
+    }
+
+    fn track(&self, resolved: &Path) {
+        let mut store = self.store.borrow_mut();
+        store.watcher.watch(resolved);
+        if let Some(files) = store.recording.last_mut() {
+            files.push(resolved.to_path_buf());
+        }
+    }
+
+    fn cached<T: Asset>(&self, key: &str) -> Option<Handle<T>> {
+        let mut store = self.store.borrow_mut();
+        store.cache::<T>().entries.get(key)?.handle.upgrade()
+    }
+
+    fn insert<T: Asset>(&self, key: &str, handle: &Handle<T>, files: Vec<PathBuf>) {
+        let mut store = self.store.borrow_mut();
+        let cache = store.cache::<T>();
+        cache.collect_garbage();
+        cache.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                handle: handle.downgrade(),
+                files,
+            },
+        );
+    }
+
+    // 種類ごとの、使われているアセットの数 (型名, 数)
+    pub fn loaded_counts(&self) -> Vec<(&'static str, usize)> {
+        let mut store = self.store.borrow_mut();
+        let mut counts: Vec<_> = store
+            .caches
+            .values_mut()
+            .map(|cache| {
+                cache.collect_garbage();
+                (cache.type_name(), cache.len())
+            })
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    pub fn edit(&self, ui: &imgui::Ui) {
+        ui.text(format!("Root: {}", self.root().display()));
+        match self.archive() {
+            Some(archive) => ui.text(format!(
+                "Archive: {} ({} files)",
+                archive.path().display(),
+                archive.len()
+            )),
+            None => ui.text("Archive: none"),
+        }
+        ui.text(format!("Loading: {}", self.loading_count()));
+        for (name, count) in self.loaded_counts() {
+            ui.text(format!("{}: {}", name, count));
+        }
+        let mut hot_reload = self.hot_reload();
+        if ui.checkbox(im_str!("Hot Reload"), &mut hot_reload) {
+            self.set_hot_reload(hot_reload);
+        }
+        let store = self.store.borrow();
+        ui.text(format!("Watching: {} files", store.watcher.len()));
+        for path in &store.recent {
+            ui.text(format!(
+                "Reloaded: {}",
+                path.rsplit('/').next().unwrap_or(path)
+            ));
+        }
+    }
+}
+
+// ルートにあるファイルを優先し、なければアーカイブから読む (バックグラウンドのスレッドからも呼ぶ)
+fn read_file(root: &Path, archive: Option<&Archive>, resolved: &Path) -> Result<Vec<u8>, String> {
+    if !resolved.exists() {
+        if let Some((archive, name)) = archive_entry(root, archive, resolved) {
+            return archive.read(&name);
+        }
+    }
+    fs::read(resolved).map_err(|e| format!("failed to open file: {}: {}", resolved.display(), e))
+}
+
+// アーカイブの中の名前はルートからの相対パス
+fn archive_entry<'a>(
+    root: &Path,
+    archive: Option<&'a Archive>,
+    resolved: &Path,
+) -> Option<(&'a Archive, String)> {
+    let archive = archive?;
+    let name = path_string(resolved.strip_prefix(root).ok()?);
+    if archive.contains(&name) {
+        Some((archive, name))
+    } else {
+        None
+    }
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+// "." や ".." を取り除く (ファイルシステムには問い合わせない)
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::archive::ArchiveWriter;
+
+    // GLを使わないアセット (ファイルの中身の文字列)
+    #[derive(Debug)]
+    struct Text(String);
+
+    impl Asset for Text {
+        fn load(assets: &Assets, path: &str) -> Result<Self, String> {
+            Ok(Text(assets.read_to_string(path)?))
+        }
+    }
+
+    // テストごとに別の一時ディレクトリをルートにする (終わったら消す)
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new(name: &str, files: &[(&str, &str)]) -> TempRoot {
+            let root = env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for (path, contents) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            fs::create_dir_all(&root).unwrap();
+            TempRoot(root)
+        }
+
+        fn assets(&self) -> Assets {
+            let assets = Assets::new(&self.0);
+            assets.set_hot_reload(false);
+            assets
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn normalize_removes_dots() {
+        assert_eq!(
+            normalize(Path::new("/game/./rsc/../rsc/image/./player.png")),
+            PathBuf::from("/game/rsc/image/player.png")
+        );
+        assert_eq!(normalize(Path::new("a/b/../../c")), PathBuf::from("c"));
+        // 取り除けない ".." は残す
+        assert_eq!(normalize(Path::new("../a/./..")), PathBuf::from(".."));
+        assert_eq!(normalize(Path::new("./")), PathBuf::new());
+
+        let assets = Assets::new("/game/./bin/..");
+        assert_eq!(assets.root(), PathBuf::from("/game"));
+        assert_eq!(assets.path("rsc/../rsc/./map.tmx"), "/game/rsc/map.tmx");
+        assert_eq!(assets.path("/other/./file"), "/other/file");
+    }
+
+    #[test]
+    fn same_path_shares_the_asset() {
+        let temp = TempRoot::new("assets-share", &[("text/hello.txt", "hello")]);
+        let assets = temp.assets();
+        let first = assets.load::<Text>("text/hello.txt").unwrap();
+        let second = assets.load::<Text>("./text/../text/hello.txt").unwrap();
+        assert!(first.ptr_eq(&second));
+        assert_eq!(first.0, "hello");
+        assert_eq!(first.path(), assets.path("text/hello.txt"));
+        assert!(assets.get::<Text>("text/hello.txt").unwrap().ptr_eq(&first));
+        // clone() した Assets も同じキャッシュを使う
+        assert!(assets
+            .clone()
+            .load::<Text>("text/hello.txt")
+            .unwrap()
+            .ptr_eq(&first));
+        assert_eq!(assets.loaded_counts(), vec![("Text", 1)]);
+
+        let error = assets.load::<Text>("text/missing.txt").unwrap_err();
+        assert!(error.starts_with("failed to open file"), "{}", error);
+        assert!(assets.get::<Text>("text/missing.txt").is_none());
+    }
+
+    #[test]
+    fn unused_assets_are_released() {
+        let temp = TempRoot::new("assets-release", &[("a.txt", "a"), ("b.txt", "b")]);
+        let assets = temp.assets();
+        let a = assets.load::<Text>("a.txt").unwrap();
+        let b = assets.load::<Text>("b.txt").unwrap();
+        let a2 = a.clone();
+        assert_eq!(assets.loaded_counts(), vec![("Text", 2)]);
+
+        drop(a);
+        assert_eq!(assets.loaded_counts(), vec![("Text", 2)]);
+        drop(a2);
+        assert_eq!(assets.loaded_counts(), vec![("Text", 1)]);
+        assert!(assets.get::<Text>("a.txt").is_none());
+        assert!(assets.get::<Text>("b.txt").unwrap().ptr_eq(&b));
+
+        // 解放されたものは読み込み直す (ファイルの変更も反映される)
+        fs::write(temp.0.join("a.txt"), "changed").unwrap();
+        assert_eq!(assets.load::<Text>("a.txt").unwrap().0, "changed");
+        drop(b);
+        assert_eq!(assets.loaded_counts(), vec![("Text", 0)]);
+    }
+
+    #[test]
+    fn loose_files_take_priority_over_the_archive() {
+        let temp = TempRoot::new(
+            "assets-archive",
+            &[("text/both.txt", "loose"), ("text/loose.txt", "loose only")],
+        );
+        let file = File::create(temp.0.join("rsc.pak")).unwrap();
+        let mut writer = ArchiveWriter::new(file, None).unwrap();
+        writer.add("text/both.txt", b"packed").unwrap();
+        writer.add("text/packed.txt", b"packed only").unwrap();
+        writer.finish().unwrap();
+
+        let assets = temp.assets().with_archive("rsc.pak").unwrap();
+        assert_eq!(assets.load::<Text>("text/both.txt").unwrap().0, "loose");
+        assert_eq!(
+            assets.load::<Text>("text/loose.txt").unwrap().0,
+            "loose only"
+        );
+        assert_eq!(
+            assets.load::<Text>("text/packed.txt").unwrap().0,
+            "packed only"
+        );
+        assert!(assets.exists("text/packed.txt"));
+        assert!(!assets.exists("text/missing.txt"));
+        assert_eq!(
+            assets.read_dir("text").unwrap(),
+            vec![
+                assets.path("text/both.txt"),
+                assets.path("text/loose.txt"),
+                assets.path("text/packed.txt"),
+            ]
+        );
+
+        // ルートのファイルを消すとアーカイブから読む
+        fs::remove_file(temp.0.join("text/both.txt")).unwrap();
+        assert_eq!(assets.read_to_string("text/both.txt").unwrap(), "packed");
+        assets.unmount();
+        assert!(assets.read("text/packed.txt").is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// ファイルの更新日時を覚えておき、変わったファイルを調べる
+// OSの通知は使わず、呼び出し側が一定の間隔で poll() する
+pub(super) struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>, // ファイルがなければ None
+}
+
+impl FileWatcher {
+    pub fn new() -> FileWatcher {
+        FileWatcher {
+            files: HashMap::new(),
+        }
+    }
+
+    // すでに見ているファイルなら何もしない
+    pub fn watch(&mut self, path: &Path) {
+        if !self.files.contains_key(path) {
+            self.files.insert(path.to_path_buf(), modified(path));
+        }
+    }
+
+    // 前に調べたときから変わったファイル (消えたファイルは、また作られたときに変わったとみなす)
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, stamp) in self.files.iter_mut() {
+            let current = modified(path);
+            if current != *stamp {
+                *stamp = current;
+                if current.is_some() {
+                    changed.push(path.clone());
+                }
+            }
+        }
+        changed.sort();
+        changed
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
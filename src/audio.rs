@@ -0,0 +1,19 @@
+mod clip;
+mod decoder;
+mod device;
+mod mixer;
+mod spatial;
+mod stream;
+
+#[allow(unused_imports)]
+pub use clip::AudioClip;
+#[allow(unused_imports)]
+pub use decoder::Decoder;
+#[allow(unused_imports)]
+pub use device::Audio;
+#[allow(unused_imports)]
+pub use mixer::{Bus, Mixer, SoundId, SoundParams, Source, DEFAULT_MAX_VOICES};
+#[allow(unused_imports)]
+pub use spatial::{spatial_audio_system, Attenuation, AudioEmitter, AudioListener, Rolloff};
+#[allow(unused_imports)]
+pub use stream::MusicStream;
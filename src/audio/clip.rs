@@ -0,0 +1,85 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use super::decoder::Decoder;
+use crate::assets::{Asset, Assets, AsyncAsset};
+
+// メモリ上にすべて読み込んだ短い音 (効果音向け)
+// 中身は共有されるので、clone() しても音のデータは複製されない
+#[derive(Clone)]
+pub struct AudioClip {
+    pub channels: u16, // 1 (モノラル) か 2 (ステレオ)
+    pub sample_rate: u32,
+    samples: Arc<[f32]>, // 左右が交互に並ぶ
+}
+
+#[allow(dead_code)]
+impl AudioClip {
+    pub fn load(path: &str) -> Result<AudioClip, String> {
+        AudioClip::from_decoder(Decoder::open(path)?, path)
+    }
+
+    // WAV / OGG ファイルの中身から作る
+    pub fn from_bytes(bytes: Vec<u8>, name: &str) -> Result<AudioClip, String> {
+        let decoder = Decoder::from_reader(Box::new(Cursor::new(bytes)))
+            .map_err(|e| format!("{}: {}", name, e))?;
+        AudioClip::from_decoder(decoder, name)
+    }
+
+    fn from_decoder(mut decoder: Decoder, name: &str) -> Result<AudioClip, String> {
+        let samples = decoder.read_all().map_err(|e| format!("{}: {}", name, e))?;
+        Ok(AudioClip::from_samples(
+            decoder.channels,
+            decoder.sample_rate,
+            samples,
+        ))
+    }
+
+    pub fn from_samples(channels: u16, sample_rate: u32, samples: Vec<f32>) -> AudioClip {
+        assert!(channels == 1 || channels == 2);
+        assert_eq!(samples.len() % channels as usize, 0);
+        AudioClip {
+            channels,
+            sample_rate,
+            samples: samples.into(),
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    // 再生にかかる秒数 (ピッチ 1.0 のとき)
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    // frame 番目のフレームの左右の値 (モノラルは左右に同じ値)
+    pub fn frame(&self, frame: usize) -> [f32; 2] {
+        if self.channels == 1 {
+            let sample = self.samples[frame];
+            [sample, sample]
+        } else {
+            [self.samples[frame * 2], self.samples[frame * 2 + 1]]
+        }
+    }
+}
+
+impl Asset for AudioClip {
+    fn load(assets: &Assets, path: &str) -> Result<AudioClip, String> {
+        AudioClip::from_bytes(assets.read(path)?, path)
+    }
+}
+
+// GLのリソースを使わないので、デコードまでバックグラウンドで済ませる
+impl AsyncAsset for AudioClip {
+    type Data = AudioClip;
+
+    fn decode(bytes: Vec<u8>, path: &str) -> Result<AudioClip, String> {
+        AudioClip::from_bytes(bytes, path)
+    }
+
+    fn create(clip: AudioClip, _path: &str) -> Result<AudioClip, String> {
+        Ok(clip)
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::mem;
+
+use lewton::inside_ogg::OggStreamReader;
+use lewton::samples::InterleavedSamples;
+
+// 一度に読み込むフレーム数 (WAV)
+const WAV_CHUNK_FRAMES: usize = 4096;
+
+// ファイルでもメモリ上のデータでも読めるようにする
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+type Reader = Box<dyn ReadSeek>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SampleFormat {
+    U8,
+    I16,
+    I24,
+    I32,
+    F32,
+}
+
+enum Format {
+    Wav {
+        reader: Reader,
+        sample_format: SampleFormat,
+        data_start: u64,
+        data_len: u64,
+        read: u64,
+    },
+    Ogg(Box<OggStreamReader<Reader>>),
+    Closed, // 巻き戻しに失敗したとき
+}
+
+// WAV / OGG (Vorbis) を少しずつ読み込む
+// 3チャンネル以上の音は前の2チャンネル (左右) だけを使う
+pub struct Decoder {
+    pub channels: u16, // 読み出す音のチャンネル数 (1 か 2)
+    pub sample_rate: u32,
+    source_channels: u16, // ファイル上のチャンネル数
+    format: Format,
+}
+
+impl Decoder {
+    pub fn open(path: &str) -> Result<Decoder, String> {
+        let file = File::open(path).map_err(|e| format!("failed to open file: {}: {}", path, e))?;
+        Decoder::from_reader(Box::new(BufReader::new(file))).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // 先頭の4バイトで形式を判別する
+    pub fn from_reader(mut reader: Reader) -> Result<Decoder, String> {
+        let mut magic = [0; 4];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|e| format!("failed to read header: {}", e))?;
+        reader.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+        match &magic {
+            b"RIFF" => open_wav(reader),
+            b"OggS" => open_ogg(reader),
+            _ => Err("unsupported audio format (expected WAV or OGG Vorbis)".to_string()),
+        }
+    }
+
+    // 続きの音を読み込む (左右が交互に並んだ -1.0 ~ 1.0 の値)。最後まで読んだら None
+    pub fn read(&mut self) -> Result<Option<Vec<f32>>, String> {
+        let samples = match &mut self.format {
+            Format::Wav {
+                reader,
+                sample_format,
+                data_len,
+                read,
+                ..
+            } => {
+                let frame_size = self.source_channels as u64 * sample_size(*sample_format);
+                let frames = ((*data_len - *read) / frame_size).min(WAV_CHUNK_FRAMES as u64);
+                if frames == 0 {
+                    return Ok(None);
+                }
+                let mut bytes = vec![0; (frames * frame_size) as usize];
+                reader
+                    .read_exact(&mut bytes)
+                    .map_err(|e| format!("failed to read wav data: {}", e))?;
+                *read += bytes.len() as u64;
+                convert(&bytes, *sample_format)
+            }
+            Format::Ogg(reader) => loop {
+                // ヘッダーの直後などは空のパケットが返ることがある
+                match reader
+                    .read_dec_packet_generic::<InterleavedSamples<f32>>()
+                    .map_err(|e| format!("failed to decode ogg: {}", e))?
+                {
+                    Some(packet) if packet.samples.is_empty() => continue,
+                    Some(packet) => break packet.samples,
+                    None => return Ok(None),
+                }
+            },
+            Format::Closed => return Ok(None),
+        };
+        Ok(Some(select_channels(samples, self.source_channels)))
+    }
+
+    // 先頭に戻る (ループ再生用)
+    pub fn rewind(&mut self) -> Result<(), String> {
+        match mem::replace(&mut self.format, Format::Closed) {
+            Format::Wav {
+                mut reader,
+                sample_format,
+                data_start,
+                data_len,
+                ..
+            } => {
+                reader
+                    .seek(SeekFrom::Start(data_start))
+                    .map_err(|e| e.to_string())?;
+                self.format = Format::Wav {
+                    reader,
+                    sample_format,
+                    data_start,
+                    data_len,
+                    read: 0,
+                };
+            }
+            Format::Ogg(reader) => {
+                // ヘッダーから読み直す
+                let mut inner = reader.into_inner().into_inner();
+                inner.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+                self.format = open_ogg(inner)?.format;
+            }
+            Format::Closed => {}
+        }
+        Ok(())
+    }
+
+    // 残りをすべて読み込む
+    pub fn read_all(&mut self) -> Result<Vec<f32>, String> {
+        let mut samples = Vec::new();
+        while let Some(chunk) = self.read()? {
+            samples.extend_from_slice(&chunk);
+        }
+        Ok(samples)
+    }
+}
+
+fn open_wav(mut reader: Reader) -> Result<Decoder, String> {
+    let mut header = [0; 12];
+    reader
+        .read_exact(&mut header)
+        .map_err(|e| format!("failed to read wav header: {}", e))?;
+    if &header[8..12] != b"WAVE" {
+        return Err("not a WAVE file".to_string());
+    }
+
+    // fmt チャンクと data チャンクを探す (それ以外のチャンクは読み飛ばす)
+    let mut format = None;
+    let mut position = 12u64;
+    loop {
+        let mut chunk = [0; 8];
+        reader
+            .read_exact(&mut chunk)
+            .map_err(|_| "wav file has no data chunk".to_string())?;
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+        position += 8;
+        match &chunk[0..4] {
+            b"fmt " => {
+                let mut fmt = vec![0; size as usize];
+                reader
+                    .read_exact(&mut fmt)
+                    .map_err(|e| format!("failed to read wav format: {}", e))?;
+                if fmt.len() < 16 {
+                    return Err("wav format chunk is too short".to_string());
+                }
+                let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+                let mut tag = u16_at(0);
+                // WAVE_FORMAT_EXTENSIBLE は SubFormat の先頭が本来の形式
+                if tag == 0xFFFE && fmt.len() >= 26 {
+                    tag = u16_at(24);
+                }
+                let channels = u16_at(2);
+                let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                let bits = u16_at(14);
+                let sample_format = match (tag, bits) {
+                    (1, 8) => SampleFormat::U8,
+                    (1, 16) => SampleFormat::I16,
+                    (1, 24) => SampleFormat::I24,
+                    (1, 32) => SampleFormat::I32,
+                    (3, 32) => SampleFormat::F32,
+                    _ => return Err(format!("unsupported wav format: tag={} bits={}", tag, bits)),
+                };
+                if channels == 0 || sample_rate == 0 {
+                    return Err("invalid wav format".to_string());
+                }
+                format = Some((channels, sample_rate, sample_format));
+            }
+            b"data" => {
+                let (channels, sample_rate, sample_format) =
+                    format.ok_or("wav data chunk appears before format chunk")?;
+                return Ok(Decoder {
+                    channels: channels.min(2),
+                    sample_rate,
+                    source_channels: channels,
+                    format: Format::Wav {
+                        reader,
+                        sample_format,
+                        data_start: position,
+                        data_len: size,
+                        read: 0,
+                    },
+                });
+            }
+            _ => {
+                reader
+                    .seek(SeekFrom::Current(size as i64))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        // チャンクの大きさは2バイト単位にそろえられている
+        if size & 1 == 1 {
+            reader
+                .seek(SeekFrom::Current(1))
+                .map_err(|e| e.to_string())?;
+        }
+        position += size + (size & 1);
+    }
+}
+
+fn open_ogg(reader: Reader) -> Result<Decoder, String> {
+    let reader =
+        OggStreamReader::new(reader).map_err(|e| format!("failed to read ogg header: {}", e))?;
+    let channels = reader.ident_hdr.audio_channels as u16;
+    let sample_rate = reader.ident_hdr.audio_sample_rate;
+    Ok(Decoder {
+        channels: channels.min(2),
+        sample_rate,
+        source_channels: channels,
+        format: Format::Ogg(Box::new(reader)),
+    })
+}
+
+fn sample_size(format: SampleFormat) -> u64 {
+    match format {
+        SampleFormat::U8 => 1,
+        SampleFormat::I16 => 2,
+        SampleFormat::I24 => 3,
+        SampleFormat::I32 | SampleFormat::F32 => 4,
+    }
+}
+
+fn convert(bytes: &[u8], format: SampleFormat) -> Vec<f32> {
+    match format {
+        SampleFormat::U8 => bytes.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+        SampleFormat::I16 => bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+        SampleFormat::I24 => bytes
+            .chunks_exact(3)
+            // 上位の3バイトに入れてから右にずらすと符号が広がる
+            .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0)
+            .collect(),
+        SampleFormat::I32 => bytes
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        SampleFormat::F32 => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    }
+}
+
+fn select_channels(samples: Vec<f32>, channels: u16) -> Vec<f32> {
+    if channels <= 2 {
+        return samples;
+    }
+    samples
+        .chunks_exact(channels as usize)
+        .flat_map(|frame| [frame[0], frame[1]])
+        .collect()
+}
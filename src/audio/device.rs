@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use imgui::im_str;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioDeviceLockGuard, AudioSpecDesired};
+use sdl2::{AudioSubsystem, Sdl};
+
+use super::clip::AudioClip;
+use super::mixer::{Bus, Mixer, SoundId, SoundParams, Source};
+use super::spatial::{Attenuation, AudioListener};
+use super::stream::MusicStream;
+use crate::ecs::Entity;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+const SAMPLE_RATE: i32 = 44100;
+const BUFFER_FRAMES: u16 = 1024; // 小さいほど遅延が少ないが、途切れやすくなる
+const MUSIC_PRIORITY: i32 = i32::MAX; // BGMは効果音に止められない
+
+impl AudioCallback for Mixer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.render(out);
+    }
+}
+
+// 音を鳴らすためのリソース
+// 音の出力先がない環境 (CIなど) では、SDLのダミードライバーで再生したことにする
+// 環境変数 SDL_AUDIODRIVER=dummy を指定すると、最初からダミードライバーを使う
+pub struct Audio {
+    device: AudioDevice<Mixer>,
+    driver: String,
+    music: Option<SoundId>,
+    pub listener: AudioListener,
+    // AudioEmitter ごとに鳴らしている音 (エンティティが削除されたら止めるため)
+    pub(super) emitter_sounds: HashMap<Entity, Vec<SoundId>>,
+}
+
+#[allow(dead_code)]
+impl Audio {
+    pub fn new(sdl_context: &Sdl) -> Result<Audio, String> {
+        let (subsystem, device) = match open_device(sdl_context) {
+            Ok(opened) => opened,
+            Err(e) => {
+                eprintln!("failed to open audio device: {} (using dummy driver)", e);
+                sdl2::hint::set("SDL_AUDIODRIVER", "dummy");
+                open_device(sdl_context)?
+            }
+        };
+        device.resume();
+        Ok(Audio {
+            device,
+            driver: subsystem.current_audio_driver().to_string(),
+            music: None,
+            listener: AudioListener::default(),
+            emitter_sounds: HashMap::new(),
+        })
+    }
+
+    // 使っているオーディオドライバーの名前 ("dummy" など)
+    pub fn driver(&self) -> &str {
+        &self.driver
+    }
+
+    // 音量やピッチの変更、バスの操作などはミキサーを直接使う
+    // ロックしている間はオーディオスレッドが止まるので、すぐに手放すこと
+    pub fn mixer(&mut self) -> AudioDeviceLockGuard<'_, Mixer> {
+        self.device.lock()
+    }
+
+    // 鳴らしたままにしてよい効果音 (同時に鳴らせる数を超えていたら None)
+    pub fn play(&mut self, clip: &AudioClip, params: SoundParams) -> Option<SoundId> {
+        self.device.lock().play_clip(clip, params)
+    }
+
+    // ワールド上の位置で鳴らす効果音 (鳴らした後で位置は変わらない)
+    // 動くものに付けて鳴らすときは AudioEmitter を使う
+    pub fn play_at(
+        &mut self,
+        clip: &AudioClip,
+        params: SoundParams,
+        position: Vector2,
+        attenuation: &Attenuation,
+    ) -> Option<SoundId> {
+        let params = self.listener.spatialize(params, position, attenuation);
+        self.device.lock().play_clip(clip, params)
+    }
+
+    // BGMを再生する。前のBGMは crossfade 秒かけて小さくしながら、新しいBGMを大きくしていく
+    pub fn play_music(
+        &mut self,
+        path: &str,
+        looping: bool,
+        crossfade: f32,
+    ) -> Result<SoundId, String> {
+        let stream = MusicStream::open(path, looping)?;
+        self.play_music_stream(stream, looping, crossfade)
+    }
+
+    // 開いておいた MusicStream を再生する (アーカイブから読むときなど)
+    pub fn play_music_stream(
+        &mut self,
+        stream: MusicStream,
+        looping: bool,
+        crossfade: f32,
+    ) -> Result<SoundId, String> {
+        let mut mixer = self.device.lock();
+        if let Some(previous) = self.music.take() {
+            mixer.stop(previous, crossfade);
+        }
+        let params = SoundParams::default()
+            .with_looping(looping)
+            .on_bus(Bus::Music)
+            .with_priority(MUSIC_PRIORITY);
+        let id = mixer
+            .play(Source::Stream(stream), params, crossfade)
+            .ok_or("no free voice for music")?;
+        self.music = Some(id);
+        Ok(id)
+    }
+
+    pub fn stop_music(&mut self, fade_out: f32) {
+        if let Some(music) = self.music.take() {
+            self.device.lock().stop(music, fade_out);
+        }
+    }
+
+    // 再生中のBGM (最後まで再生し終わったものは含めない)
+    pub fn music(&mut self) -> Option<SoundId> {
+        let music = self.music?;
+        if self.device.lock().is_playing(music) {
+            Some(music)
+        } else {
+            self.music = None;
+            None
+        }
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        let driver = self.driver.clone();
+        let mut mixer = self.device.lock();
+        ui.text(format!(
+            "Driver: {}, {} Hz, Voices: {}/{}",
+            driver,
+            mixer.sample_rate,
+            mixer.playing_count(),
+            mixer.max_voices
+        ));
+        let mut max_voices = mixer.max_voices as i32;
+        if imgui::Slider::new(im_str!("Max Voices"))
+            .range(1..=64)
+            .build(ui, &mut max_voices)
+        {
+            mixer.max_voices = max_voices as usize;
+        }
+        for bus in Bus::ALL {
+            let mut volume = mixer.bus_volume(bus);
+            if imgui::Slider::new(&im_str!("{}", bus.name()))
+                .range(0.0..=1.0)
+                .build(ui, &mut volume)
+            {
+                mixer.set_bus_volume(bus, volume);
+            }
+            ui.same_line(0.0);
+            let mut muted = mixer.is_bus_muted(bus);
+            if ui.checkbox(&im_str!("Mute##{}", bus.name()), &mut muted) {
+                mixer.set_bus_muted(bus, muted);
+            }
+        }
+        ui.checkbox(
+            im_str!("Listener Follows Camera"),
+            &mut self.listener.follow_camera,
+        );
+        ui.text(format!(
+            "Listener: ({:.2}, {:.2}), Pan Distance: {:.2}",
+            self.listener.position.x, self.listener.position.y, self.listener.pan_distance
+        ));
+    }
+}
+
+fn open_device(sdl_context: &Sdl) -> Result<(AudioSubsystem, AudioDevice<Mixer>), String> {
+    let subsystem = sdl_context.audio()?;
+    let desired = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE),
+        channels: Some(2),
+        samples: Some(BUFFER_FRAMES),
+    };
+    let device = subsystem.open_playback(None, &desired, |spec| {
+        Mixer::new(spec.freq as u32, spec.channels)
+    })?;
+    Ok((subsystem, device))
+}
+
+// ダミードライバーで開いた Audio をテストに渡す
+// Sdl は同時に1つしか作れないので、SDLを使うテストは順番に実行する
+#[cfg(test)]
+pub(super) fn with_dummy_audio(f: impl FnOnce(Audio)) {
+    use std::sync::Mutex;
+
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    std::env::set_var("SDL_AUDIODRIVER", "dummy");
+    let sdl_context = sdl2::init().unwrap();
+    f(Audio::new(&sdl_context).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    // 音の出力先がなくても、ダミードライバーで最後まで再生できる
+    #[test]
+    fn plays_a_clip_with_the_dummy_driver() {
+        with_dummy_audio(|mut audio| {
+            assert_eq!(audio.driver(), "dummy");
+
+            let clip = AudioClip::from_samples(1, SAMPLE_RATE as u32, vec![0.5; 2205]);
+            let id = audio.play(&clip, SoundParams::default()).unwrap();
+            let start = Instant::now();
+            while audio.mixer().is_playing(id) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(5),
+                    "clip did not finish"
+                );
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(audio.mixer().playing_count(), 0);
+        });
+    }
+}
@@ -0,0 +1,629 @@
+use std::f32::consts::FRAC_PI_4;
+
+use super::clip::AudioClip;
+use super::stream::MusicStream;
+
+// 同時に鳴らせる音の数
+pub const DEFAULT_MAX_VOICES: usize = 32;
+
+// 音量をまとめて調整するためのバス
+// 各音はいずれかのバスに流れ、さらに Master を通って出力される
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bus {
+    Master,
+    Music,
+    Sfx,
+    Ui,
+}
+
+impl Bus {
+    pub const ALL: [Bus; 4] = [Bus::Master, Bus::Music, Bus::Sfx, Bus::Ui];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bus::Master => "Master",
+            Bus::Music => "Music",
+            Bus::Sfx => "Sfx",
+            Bus::Ui => "Ui",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BusState {
+    volume: f32,
+    muted: bool,
+}
+
+impl BusState {
+    fn gain(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume
+        }
+    }
+}
+
+// 再生中の音を指す番号 (止まった音の番号は二度と使われない)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SoundId(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundParams {
+    pub volume: f32,
+    pub pitch: f32, // 再生速度の倍率 (2.0 で1オクターブ上がる)
+    pub pan: f32,   // -1.0: 左、0.0: 中央、1.0: 右
+    pub looping: bool,
+    pub bus: Bus,
+    pub priority: i32, // 同時に鳴らせる数を超えたとき、低いものから止める
+}
+
+impl Default for SoundParams {
+    fn default() -> Self {
+        SoundParams {
+            volume: 1.0,
+            pitch: 1.0,
+            pan: 0.0,
+            looping: false,
+            bus: Bus::Sfx,
+            priority: 0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl SoundParams {
+    pub fn with_volume(mut self, volume: f32) -> SoundParams {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_pitch(mut self, pitch: f32) -> SoundParams {
+        self.pitch = pitch;
+        self
+    }
+
+    pub fn with_pan(mut self, pan: f32) -> SoundParams {
+        self.pan = pan;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> SoundParams {
+        self.looping = looping;
+        self
+    }
+
+    pub fn on_bus(mut self, bus: Bus) -> SoundParams {
+        self.bus = bus;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> SoundParams {
+        self.priority = priority;
+        self
+    }
+}
+
+// 音のデータの出どころ
+pub enum Source {
+    Clip(AudioClip),
+    Stream(MusicStream),
+}
+
+impl Source {
+    fn channels(&self) -> u16 {
+        match self {
+            Source::Clip(clip) => clip.channels,
+            Source::Stream(stream) => stream.channels,
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self {
+            Source::Clip(clip) => clip.sample_rate,
+            Source::Stream(stream) => stream.sample_rate,
+        }
+    }
+}
+
+// 音量を少しずつ変える (フェードイン・フェードアウト)
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fade {
+    target: f32,
+    step: f32,         // 1フレームあたりの変化量
+    stop_at_end: bool, // 目標に届いたら再生をやめる
+}
+
+struct Voice {
+    id: SoundId,
+    source: Source,
+    params: SoundParams,
+    position: usize, // 次に読むフレーム (Clip のとき)
+    // 前後2つのフレームの間を補間して、ピッチや出力との周波数の違いを吸収する
+    previous: [f32; 2],
+    next: [f32; 2],
+    fraction: f32,
+    primed: bool,
+    ended: bool,
+    fade_gain: f32,
+    fade: Option<Fade>,
+    gains: [f32; 2], // 直前に使った左右の音量 (急に変わってプチッと鳴らないよう少しずつ近づける)
+}
+
+impl Voice {
+    fn next_source_frame(&mut self) -> Option<[f32; 2]> {
+        match &mut self.source {
+            Source::Clip(clip) => {
+                if self.position >= clip.frames() {
+                    if !self.params.looping || clip.frames() == 0 {
+                        return None;
+                    }
+                    self.position = 0;
+                }
+                let frame = clip.frame(self.position);
+                self.position += 1;
+                Some(frame)
+            }
+            Source::Stream(stream) => stream.next_frame(),
+        }
+    }
+
+    // 出力1フレーム分の値。最後まで再生したら None
+    fn sample(&mut self, step: f32) -> Option<[f32; 2]> {
+        if !self.primed {
+            self.primed = true;
+            self.previous = self.next_source_frame()?;
+            self.next = self.next_source_frame().unwrap_or(self.previous);
+        }
+        while self.fraction >= 1.0 {
+            if self.ended {
+                return None;
+            }
+            self.previous = self.next;
+            match self.next_source_frame() {
+                Some(frame) => self.next = frame,
+                None => self.ended = true, // 最後のフレームまで鳴らしてから終わる
+            }
+            self.fraction -= 1.0;
+        }
+        let t = self.fraction;
+        self.fraction += step;
+        Some([
+            self.previous[0] + (self.next[0] - self.previous[0]) * t,
+            self.previous[1] + (self.next[1] - self.previous[1]) * t,
+        ])
+    }
+
+    // 1フレームぶんフェードを進める。止めるときは false
+    fn advance_fade(&mut self) -> bool {
+        if let Some(fade) = self.fade {
+            self.fade_gain += fade.step;
+            let reached = if fade.step >= 0.0 {
+                self.fade_gain >= fade.target
+            } else {
+                self.fade_gain <= fade.target
+            };
+            if reached {
+                self.fade_gain = fade.target;
+                self.fade = None;
+                if fade.stop_at_end {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+// 再生中の音を混ぜ合わせて出力する
+// オーディオデバイスのコールバックから呼ばれるので、SDLに依存しない
+pub struct Mixer {
+    pub sample_rate: u32, // 出力の周波数
+    pub channels: u8,     // 出力のチャンネル数
+    pub max_voices: usize,
+    voices: Vec<Voice>,
+    buses: [BusState; 4],
+    next_id: u64,
+}
+
+#[allow(dead_code)]
+impl Mixer {
+    pub fn new(sample_rate: u32, channels: u8) -> Mixer {
+        Mixer {
+            sample_rate,
+            channels,
+            max_voices: DEFAULT_MAX_VOICES,
+            voices: Vec::new(),
+            buses: [BusState {
+                volume: 1.0,
+                muted: false,
+            }; 4],
+            next_id: 0,
+        }
+    }
+
+    // 同時に鳴らせる数を超えるときは、優先度が同じか低い音を1つ止めて鳴らす
+    // 止められる音がなければ鳴らさずに None を返す
+    // fade_in 秒かけて音量を上げる (0.0 ならすぐに最大)
+    pub fn play(&mut self, source: Source, params: SoundParams, fade_in: f32) -> Option<SoundId> {
+        while self.voices.len() >= self.max_voices {
+            if !self.steal_voice(params.priority) {
+                return None;
+            }
+        }
+        let id = SoundId(self.next_id);
+        self.next_id += 1;
+        let mut voice = Voice {
+            id,
+            source,
+            params,
+            position: 0,
+            previous: [0.0, 0.0],
+            next: [0.0, 0.0],
+            fraction: 0.0,
+            primed: false,
+            ended: false,
+            fade_gain: 1.0,
+            fade: None,
+            gains: [0.0, 0.0],
+        };
+        voice.gains = self.target_gains(&voice);
+        if fade_in > 0.0 {
+            voice.fade_gain = 0.0;
+            voice.fade = Some(Fade {
+                target: 1.0,
+                step: 1.0 / (fade_in * self.sample_rate as f32),
+                stop_at_end: false,
+            });
+            voice.gains = [0.0, 0.0];
+        }
+        self.voices.push(voice);
+        Some(id)
+    }
+
+    pub fn play_clip(&mut self, clip: &AudioClip, params: SoundParams) -> Option<SoundId> {
+        self.play(Source::Clip(clip.clone()), params, 0.0)
+    }
+
+    // fade_out 秒かけて音量を下げてから止める (0.0 ならすぐに止める)
+    pub fn stop(&mut self, id: SoundId, fade_out: f32) {
+        let sample_rate = self.sample_rate as f32;
+        if fade_out <= 0.0 {
+            self.voices.retain(|voice| voice.id != id);
+        } else if let Some(voice) = self.voice_mut(id) {
+            voice.fade = Some(Fade {
+                target: 0.0,
+                step: -voice.fade_gain.max(0.0) / (fade_out * sample_rate),
+                stop_at_end: true,
+            });
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    pub fn is_playing(&self, id: SoundId) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+    }
+
+    pub fn playing_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn params(&self, id: SoundId) -> Option<SoundParams> {
+        self.voices
+            .iter()
+            .find(|voice| voice.id == id)
+            .map(|voice| voice.params)
+    }
+
+    pub fn set_params(&mut self, id: SoundId, params: SoundParams) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.params = params;
+        }
+    }
+
+    pub fn set_volume(&mut self, id: SoundId, volume: f32) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.params.volume = volume;
+        }
+    }
+
+    pub fn set_pitch(&mut self, id: SoundId, pitch: f32) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.params.pitch = pitch;
+        }
+    }
+
+    pub fn set_pan(&mut self, id: SoundId, pan: f32) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.params.pan = pan;
+        }
+    }
+
+    pub fn bus_volume(&self, bus: Bus) -> f32 {
+        self.buses[bus as usize].volume
+    }
+
+    pub fn set_bus_volume(&mut self, bus: Bus, volume: f32) {
+        self.buses[bus as usize].volume = volume.max(0.0);
+    }
+
+    pub fn is_bus_muted(&self, bus: Bus) -> bool {
+        self.buses[bus as usize].muted
+    }
+
+    pub fn set_bus_muted(&mut self, bus: Bus, muted: bool) {
+        self.buses[bus as usize].muted = muted;
+    }
+
+    // 止める音は、優先度が低いもの、次に今の音量が小さいもの、次に古いものから選ぶ
+    fn steal_voice(&mut self, priority: i32) -> bool {
+        let victim = self
+            .voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.params.priority <= priority)
+            .map(|(index, voice)| {
+                let gains = self.target_gains(voice);
+                (
+                    index,
+                    voice.params.priority,
+                    (gains[0] + gains[1]) * voice.fade_gain,
+                )
+            })
+            .min_by(|a, b| a.1.cmp(&b.1).then(a.2.total_cmp(&b.2)))
+            .map(|(index, _, _)| index);
+        match victim {
+            Some(index) => {
+                self.voices.remove(index); // 残りは古い順のまま
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn voice_mut(&mut self, id: SoundId) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|voice| voice.id == id)
+    }
+
+    // バスとパンを反映した左右の音量 (フェードは含めない)
+    fn target_gains(&self, voice: &Voice) -> [f32; 2] {
+        let mut gain = voice.params.volume.max(0.0) * self.buses[Bus::Master as usize].gain();
+        if voice.params.bus != Bus::Master {
+            gain *= self.buses[voice.params.bus as usize].gain();
+        }
+        let pan = voice.params.pan.clamp(-1.0, 1.0);
+        if voice.source.channels() == 1 {
+            // モノラルは左右の音の強さの合計が変わらないように振り分ける
+            let angle = (pan + 1.0) * FRAC_PI_4;
+            [gain * angle.cos(), gain * angle.sin()]
+        } else {
+            // ステレオは反対側を弱める (バランス)
+            [gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0)]
+        }
+    }
+
+    // 左右が交互に並んだ出力バッファーを埋める
+    pub fn render(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = 0.0;
+        }
+        let channels = self.channels.max(1) as usize;
+        let frames = out.len() / channels;
+        if frames == 0 {
+            return;
+        }
+        let sample_rate = self.sample_rate as f32;
+        let mut voices = std::mem::take(&mut self.voices);
+        voices.retain_mut(|voice| {
+            let target = self.target_gains(voice);
+            let start = voice.gains;
+            let step =
+                voice.params.pitch.max(0.0) * voice.source.sample_rate() as f32 / sample_rate;
+            for frame in 0..frames {
+                let t = (frame + 1) as f32 / frames as f32;
+                let gains = [
+                    start[0] + (target[0] - start[0]) * t,
+                    start[1] + (target[1] - start[1]) * t,
+                ];
+                let value = match voice.sample(step) {
+                    Some(value) => value,
+                    None => return false,
+                };
+                let left = value[0] * gains[0] * voice.fade_gain;
+                let right = value[1] * gains[1] * voice.fade_gain;
+                let out = &mut out[frame * channels..(frame + 1) * channels];
+                if channels == 1 {
+                    out[0] += (left + right) * 0.5;
+                } else {
+                    out[0] += left;
+                    out[1] += right;
+                }
+                if !voice.advance_fade() {
+                    return false;
+                }
+            }
+            voice.gains = target;
+            true
+        });
+        self.voices = voices;
+
+        // 音が割れないように範囲内に収める
+        for sample in out.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 100;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    // 同じ値が続く音
+    fn constant(channels: u16, value: f32, frames: usize) -> AudioClip {
+        AudioClip::from_samples(channels, RATE, vec![value; frames * channels as usize])
+    }
+
+    // 左右それぞれのチャンネルだけを取り出す
+    fn render(mixer: &mut Mixer, frames: usize) -> (Vec<f32>, Vec<f32>) {
+        let mut out = vec![0.0; frames * 2];
+        mixer.render(&mut out);
+        (
+            out.iter().step_by(2).copied().collect(),
+            out.iter().skip(1).step_by(2).copied().collect(),
+        )
+    }
+
+    #[test]
+    fn volume_and_pan() {
+        let mut mixer = Mixer::new(RATE, 2);
+        let mono = constant(1, 1.0, 4);
+        mixer.play_clip(&mono, SoundParams::default().with_volume(0.5));
+        let (left, right) = render(&mut mixer, 4);
+        // モノラルの中央は左右とも cos(45°)
+        for (left, right) in left.iter().zip(&right) {
+            assert_close(*left, 0.5 * FRAC_PI_4.cos());
+            assert_close(*right, 0.5 * FRAC_PI_4.sin());
+        }
+
+        mixer.play_clip(&mono, SoundParams::default().with_pan(-1.0));
+        let (left, right) = render(&mut mixer, 4);
+        assert!(left.iter().all(|&v| (v - 1.0).abs() < 1e-4));
+        assert!(right.iter().all(|&v| v.abs() < 1e-4));
+
+        // ステレオは反対側だけを弱める
+        let stereo = constant(2, 0.8, 4);
+        mixer.play_clip(&stereo, SoundParams::default().with_pan(0.5));
+        let (left, right) = render(&mut mixer, 4);
+        assert!(left.iter().all(|&v| (v - 0.4).abs() < 1e-4));
+        assert!(right.iter().all(|&v| (v - 0.8).abs() < 1e-4));
+    }
+
+    #[test]
+    fn clip_plays_to_the_end() {
+        let mut mixer = Mixer::new(RATE, 2);
+        let id = mixer.play_clip(&constant(2, 0.5, 3), SoundParams::default());
+        let (left, _) = render(&mut mixer, 5);
+        assert_eq!(left, vec![0.5, 0.5, 0.5, 0.0, 0.0]);
+        assert!(!mixer.is_playing(id.unwrap()));
+    }
+
+    #[test]
+    fn pitch_and_sample_rate_change_the_step() {
+        // 0.0, 0.1, 0.2, ... と増えていく音
+        let ramp: Vec<f32> = (0..8).flat_map(|i| [i as f32 * 0.1; 2]).collect();
+        let clip = AudioClip::from_samples(2, RATE, ramp.clone());
+
+        let mut mixer = Mixer::new(RATE, 2);
+        mixer.play_clip(&clip, SoundParams::default().with_pitch(2.0));
+        let (left, _) = render(&mut mixer, 4);
+        for (value, expected) in left.iter().zip([0.0, 0.2, 0.4, 0.6]) {
+            assert_close(*value, expected);
+        }
+
+        // 出力の倍の周波数で鳴らすと、間を補間して半分ずつ進む
+        let mut mixer = Mixer::new(RATE * 2, 2);
+        mixer.play_clip(&clip, SoundParams::default());
+        let (left, _) = render(&mut mixer, 4);
+        for (value, expected) in left.iter().zip([0.0, 0.05, 0.1, 0.15]) {
+            assert_close(*value, expected);
+        }
+
+        let half = AudioClip::from_samples(2, RATE / 2, ramp);
+        let mut mixer = Mixer::new(RATE, 2);
+        mixer.play_clip(&half, SoundParams::default().with_pitch(2.0));
+        let (left, _) = render(&mut mixer, 4);
+        for (value, expected) in left.iter().zip([0.0, 0.1, 0.2, 0.3]) {
+            assert_close(*value, expected);
+        }
+    }
+
+    #[test]
+    fn fade_out_stops_the_voice() {
+        let mut mixer = Mixer::new(RATE, 2);
+        let clip = constant(2, 0.5, 4);
+        let id = mixer
+            .play_clip(&clip, SoundParams::default().with_looping(true))
+            .unwrap();
+        render(&mut mixer, 8);
+        assert!(mixer.is_playing(id));
+
+        // 0.1秒 (10フレーム) かけて小さくする
+        mixer.stop(id, 0.1);
+        let (left, _) = render(&mut mixer, 20);
+        assert_close(left[0], 0.5);
+        assert!(left.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(left[11..].iter().all(|&v| v == 0.0));
+        assert!(!mixer.is_playing(id));
+    }
+
+    #[test]
+    fn fade_in() {
+        let mut mixer = Mixer::new(RATE, 2);
+        let params = SoundParams::default().with_looping(true);
+        mixer.play(Source::Clip(constant(2, 1.0, 4)), params, 0.1);
+        let (left, _) = render(&mut mixer, 20);
+        assert_close(left[0], 0.0);
+        assert!(left.windows(2).all(|pair| pair[1] >= pair[0]));
+        let (left, _) = render(&mut mixer, 20);
+        assert!(left.iter().all(|&v| (v - 1.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn bus_volume_and_mute() {
+        let mut mixer = Mixer::new(RATE, 2);
+        let looping = SoundParams::default().with_looping(true);
+        mixer.play_clip(&constant(2, 0.25, 4), looping);
+        mixer.play_clip(&constant(2, 0.5, 4), looping.on_bus(Bus::Music));
+        let (left, _) = render(&mut mixer, 4);
+        assert!(left.iter().all(|&v| (v - 0.75).abs() < 1e-4));
+
+        // 音量の変化は1回の出力の間で少しずつ近づけるので、2回目の出力で確かめる
+        mixer.set_bus_muted(Bus::Sfx, true);
+        mixer.set_bus_volume(Bus::Music, 0.5);
+        render(&mut mixer, 4);
+        let (left, right) = render(&mut mixer, 4);
+        assert!(left.iter().all(|&v| (v - 0.25).abs() < 1e-4));
+        assert_eq!(left, right);
+
+        mixer.set_bus_muted(Bus::Master, true);
+        render(&mut mixer, 4);
+        let (left, _) = render(&mut mixer, 4);
+        assert!(left.iter().all(|&v| v == 0.0));
+        // ミュートしても再生は続ける
+        assert_eq!(mixer.playing_count(), 2);
+    }
+
+    #[test]
+    fn voice_stealing_prefers_low_priority() {
+        let mut mixer = Mixer::new(RATE, 2);
+        mixer.max_voices = 2;
+        let clip = constant(2, 0.1, 4);
+        let high = mixer.play_clip(&clip, SoundParams::default().with_priority(1));
+        let low = mixer.play_clip(&clip, SoundParams::default());
+        let newer = mixer.play_clip(&clip, SoundParams::default());
+        assert!(mixer.is_playing(high.unwrap()));
+        assert!(!mixer.is_playing(low.unwrap()));
+        assert!(mixer.is_playing(newer.unwrap()));
+        // 優先度の低い音は、高い音を止めて鳴らすことはできない
+        mixer.play_clip(&clip, SoundParams::default().with_priority(2));
+        assert_eq!(
+            mixer.play_clip(&clip, SoundParams::default().with_priority(-1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use cgmath::{vec2, InnerSpace};
+
+use super::clip::AudioClip;
+use super::device::Audio;
+use super::mixer::{SoundId, SoundParams};
+use crate::camera2d::Camera2D;
+use crate::ecs::{Read, World, Write};
+use crate::transform::Transform2D;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 距離による音量の下がり方
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rolloff {
+    // min_distance から max_distance まで一定の割合で小さくなる
+    Linear,
+    // 近くで急に小さくなり、遠くではゆっくり小さくなる (現実の音に近い)
+    Inverse { factor: f32 },
+    // factor が大きいほど急に小さくなる
+    Exponential { factor: f32 },
+    // (距離の割合, 音量) を結んだ折れ線。距離の割合は min_distance で 0.0、max_distance で 1.0
+    Custom(Vec<(f32, f32)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attenuation {
+    pub rolloff: Rolloff,
+    pub min_distance: f32, // これより近いと最大の音量
+    pub max_distance: f32, // これより遠いと聞こえない
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation {
+            rolloff: Rolloff::Inverse { factor: 1.0 },
+            min_distance: 1.0,
+            max_distance: 20.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Attenuation {
+    pub fn new(rolloff: Rolloff, min_distance: f32, max_distance: f32) -> Attenuation {
+        Attenuation {
+            rolloff,
+            min_distance,
+            max_distance,
+        }
+    }
+
+    // 距離に応じた音量の倍率 (0.0 ~ 1.0)
+    pub fn gain(&self, distance: f32) -> f32 {
+        let min = self.min_distance.max(0.0);
+        let max = self.max_distance.max(min);
+        if distance <= min {
+            return 1.0;
+        }
+        if distance >= max {
+            return 0.0;
+        }
+        let t = (distance - min) / (max - min);
+        // 逆数や指数の曲線は max_distance でちょうど0になるように縮める
+        // factor が0以下だと曲線が平らになって縮められないので、max_distance までずっと最大の音量にする
+        let curve = |f: &dyn Fn(f32) -> f32| {
+            let end = f(max);
+            if (1.0 - end).abs() < 1e-6 {
+                return 1.0;
+            }
+            ((f(distance) - end) / (1.0 - end)).clamp(0.0, 1.0)
+        };
+        match &self.rolloff {
+            Rolloff::Linear => 1.0 - t,
+            Rolloff::Inverse { factor } => {
+                curve(&|d| min.max(1e-3) / (min.max(1e-3) + factor * (d - min)))
+            }
+            Rolloff::Exponential { factor } => curve(&|d| (d / min.max(1e-3)).powf(-factor)),
+            Rolloff::Custom(points) => evaluate_curve(points, t),
+        }
+    }
+}
+
+fn evaluate_curve(points: &[(f32, f32)], t: f32) -> f32 {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return 1.0 - t,
+    };
+    if t <= first.0 {
+        return first.1.clamp(0.0, 1.0);
+    }
+    for pair in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        if t <= x1 {
+            let s = if x1 > x0 { (t - x0) / (x1 - x0) } else { 1.0 };
+            return (y0 + (y1 - y0) * s).clamp(0.0, 1.0);
+        }
+    }
+    last.1.clamp(0.0, 1.0)
+}
+
+// 音を聞く位置 (ふつうは2Dカメラの中心)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioListener {
+    pub position: Vector2,
+    pub pan_distance: f32, // 横にこれだけ離れると、完全に左右どちらかから聞こえる
+    pub follow_camera: bool, // 2Dカメラの位置と映る幅に合わせる
+}
+
+impl Default for AudioListener {
+    fn default() -> Self {
+        AudioListener {
+            position: vec2(0.0, 0.0),
+            pan_distance: 8.0,
+            follow_camera: true,
+        }
+    }
+}
+
+impl AudioListener {
+    // 位置に応じて音量とパンを変えた再生設定
+    pub fn spatialize(
+        &self,
+        params: SoundParams,
+        position: Vector2,
+        attenuation: &Attenuation,
+    ) -> SoundParams {
+        let offset = position - self.position;
+        let pan = offset.x / self.pan_distance.max(1e-3);
+        SoundParams {
+            volume: params.volume * attenuation.gain(offset.magnitude()),
+            pan: (params.pan + pan).clamp(-1.0, 1.0),
+            ..params
+        }
+    }
+}
+
+// エンティティの位置で鳴る音
+// エンティティが動くと、鳴っている間も音量とパンが変わる
+pub struct AudioEmitter {
+    pub clip: AudioClip,
+    pub params: SoundParams, // 位置の影響を受ける前の設定
+    pub attenuation: Attenuation,
+    pub autoplay: bool, // 最初のフレームで鳴らす。ループする音は他の音に止められても鳴らし直す
+    sounds: Vec<SoundId>,
+    requests: usize,
+    stop_requested: bool,
+    started: bool,
+}
+
+#[allow(dead_code)]
+impl AudioEmitter {
+    pub fn new(clip: AudioClip) -> AudioEmitter {
+        AudioEmitter {
+            clip,
+            params: SoundParams::default(),
+            attenuation: Attenuation::default(),
+            autoplay: false,
+            sounds: Vec::new(),
+            requests: 0,
+            stop_requested: false,
+            started: false,
+        }
+    }
+
+    pub fn with_params(mut self, params: SoundParams) -> AudioEmitter {
+        self.params = params;
+        self
+    }
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> AudioEmitter {
+        self.attenuation = attenuation;
+        self
+    }
+
+    pub fn with_autoplay(mut self, autoplay: bool) -> AudioEmitter {
+        self.autoplay = autoplay;
+        self
+    }
+
+    // 次の spatial_audio_system で鳴らす (前の音が鳴っていても重ねて鳴らす)
+    pub fn play(&mut self) {
+        self.requests += 1;
+        self.stop_requested = false;
+    }
+
+    // autoplay も取り消す
+    pub fn stop(&mut self) {
+        self.requests = 0;
+        self.autoplay = false;
+        self.stop_requested = true;
+    }
+
+    // この音源から鳴っている音の数 (直前の spatial_audio_system の時点)
+    pub fn playing_count(&self) -> usize {
+        self.sounds.len()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.sounds.is_empty()
+    }
+}
+
+// 聞く位置を2Dカメラに合わせ、音源の位置から音量とパンを決める (PostUpdate)
+// エンティティが削除されたり AudioEmitter が外されたりした音源の音は止める
+pub fn spatial_audio_system(world: &mut World) {
+    if !world.has_resource::<Audio>() {
+        return;
+    }
+    let mut audio = world.resource_mut::<Audio>();
+    if audio.listener.follow_camera && world.has_resource::<Camera2D>() {
+        let camera = world.resource::<Camera2D>();
+        let visible = camera.visible_rect();
+        audio.listener.position = camera.position;
+        audio.listener.pan_distance = visible.size().x * 0.5;
+    }
+    let listener = audio.listener;
+    let mut previous = std::mem::take(&mut audio.emitter_sounds);
+    let mut current = HashMap::new();
+    let mut mixer = audio.mixer();
+    world
+        .query::<(Write<AudioEmitter>, Read<Transform2D>)>()
+        .for_each(|entity, (emitter, transform)| {
+            emitter.sounds.retain(|&id| mixer.is_playing(id));
+            if emitter.stop_requested {
+                for id in emitter.sounds.drain(..) {
+                    mixer.stop(id, 0.0);
+                }
+                emitter.stop_requested = false;
+            }
+            // ループする環境音が優先度の高い音に止められたときは、空きができたら鳴らし直す
+            let restart = emitter.params.looping && emitter.sounds.is_empty();
+            if emitter.autoplay && (!emitter.started || restart) && emitter.requests == 0 {
+                emitter.requests = 1;
+            }
+            emitter.started = true;
+
+            let params = listener.spatialize(
+                emitter.params,
+                transform.world_position(),
+                &emitter.attenuation,
+            );
+            for &id in &emitter.sounds {
+                mixer.set_params(id, params);
+            }
+            for _ in 0..emitter.requests {
+                if let Some(id) = mixer.play_clip(&emitter.clip, params) {
+                    emitter.sounds.push(id);
+                }
+            }
+            emitter.requests = 0;
+            previous.remove(&entity);
+            current.insert(entity, emitter.sounds.clone());
+        });
+    for id in previous.into_values().flatten() {
+        mixer.stop(id, 0.0);
+    }
+    drop(mixer);
+    audio.emitter_sounds = current;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::device::with_dummy_audio;
+
+    // min_distance 1、max_distance 11 (距離の割合は (距離 - 1) / 10)
+    fn attenuation(rolloff: Rolloff) -> Attenuation {
+        Attenuation::new(rolloff, 1.0, 11.0)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn rolloff_shapes() {
+        let custom = Rolloff::Custom(vec![(0.0, 1.0), (0.5, 0.2), (1.0, 0.0)]);
+        // (距離の割合が 0.5 のときの音量, 0.25 のときの音量)
+        let cases = [
+            (Rolloff::Linear, 0.5, 0.75),
+            (Rolloff::Inverse { factor: 1.0 }, 1.0 / 12.0, 3.0 / 14.0),
+            (
+                Rolloff::Exponential { factor: 2.0 },
+                (1.0 / 36.0 - 1.0 / 121.0) / (1.0 - 1.0 / 121.0),
+                (1.0 / 12.25 - 1.0 / 121.0) / (1.0 - 1.0 / 121.0),
+            ),
+            (custom, 0.2, 0.6),
+        ];
+        for (rolloff, middle, quarter) in cases.iter().cloned() {
+            let attenuation = attenuation(rolloff);
+            assert_eq!(attenuation.gain(0.0), 1.0);
+            assert_eq!(attenuation.gain(1.0), 1.0);
+            assert_close(attenuation.gain(3.5), quarter);
+            assert_close(attenuation.gain(6.0), middle);
+            assert_close(attenuation.gain(11.0), 0.0);
+            assert_eq!(attenuation.gain(100.0), 0.0);
+            // 遠いほど小さい
+            let gains: Vec<f32> = (0..=20).map(|i| attenuation.gain(i as f32 * 0.6)).collect();
+            assert!(
+                gains.windows(2).all(|pair| pair[1] <= pair[0]),
+                "{:?}",
+                gains
+            );
+        }
+    }
+
+    #[test]
+    fn flat_rolloff_does_not_produce_nan() {
+        let flat = [
+            Rolloff::Inverse { factor: 0.0 },
+            Rolloff::Exponential { factor: 0.0 },
+        ];
+        for rolloff in flat.iter().cloned() {
+            let attenuation = attenuation(rolloff);
+            assert_eq!(attenuation.gain(6.0), 1.0);
+            assert_eq!(attenuation.gain(20.0), 0.0);
+        }
+        // min_distance と max_distance が同じ
+        let attenuation = Attenuation::new(Rolloff::Linear, 2.0, 2.0);
+        assert_eq!(attenuation.gain(1.0), 1.0);
+        assert_eq!(attenuation.gain(3.0), 0.0);
+    }
+
+    #[test]
+    fn curve_endpoints() {
+        let points = [(0.2, 0.8), (0.6, 0.4)];
+        assert_eq!(evaluate_curve(&points, 0.0), 0.8);
+        assert_eq!(evaluate_curve(&points, 0.2), 0.8);
+        assert_close(evaluate_curve(&points, 0.4), 0.6);
+        assert_eq!(evaluate_curve(&points, 0.6), 0.4);
+        assert_eq!(evaluate_curve(&points, 1.0), 0.4);
+        // 点がなければ直線、範囲外の音量は 0.0 ~ 1.0 に収める
+        assert_eq!(evaluate_curve(&[], 0.25), 0.75);
+        assert_eq!(evaluate_curve(&[(0.0, 2.0), (1.0, -1.0)], 0.0), 1.0);
+        assert_eq!(evaluate_curve(&[(0.0, 2.0), (1.0, -1.0)], 1.0), 0.0);
+    }
+
+    #[test]
+    fn spatialize_pans_and_clamps() {
+        let listener = AudioListener {
+            position: vec2(10.0, 0.0),
+            pan_distance: 8.0,
+            follow_camera: false,
+        };
+        let attenuation = attenuation(Rolloff::Linear);
+        let params = SoundParams::default().with_volume(0.5);
+
+        let right = listener.spatialize(params, vec2(14.0, 3.0), &attenuation);
+        assert_close(right.pan, 0.5);
+        assert_close(right.volume, 0.5 * 0.6); // 距離 5
+        let far_right = listener.spatialize(params, vec2(40.0, 0.0), &attenuation);
+        assert_eq!(far_right.pan, 1.0);
+        assert_eq!(far_right.volume, 0.0);
+        let far_left = listener.spatialize(params.with_pan(-0.5), vec2(2.0, 0.0), &attenuation);
+        assert_eq!(far_left.pan, -1.0);
+        let center = listener.spatialize(params, vec2(10.0, 0.5), &attenuation);
+        assert_eq!((center.pan, center.volume), (0.0, 0.5));
+    }
+
+    #[test]
+    fn removed_emitters_stop_their_sounds() {
+        with_dummy_audio(|audio| {
+            let mut world = World::new();
+            world.insert_resource(audio);
+            let clip = AudioClip::from_samples(1, 44100, vec![0.1; 4410]);
+            let hum = || {
+                AudioEmitter::new(clip.clone())
+                    .with_params(SoundParams::default().with_looping(true))
+                    .with_autoplay(true)
+            };
+            let spawn = |world: &mut World| {
+                let entity = world.spawn();
+                world.insert(entity, Transform2D::new(vec2(0.0, 0.0)));
+                world.insert(entity, hum());
+                entity
+            };
+            let a = spawn(&mut world);
+            let b = spawn(&mut world);
+            let c = spawn(&mut world);
+            let playing_count =
+                |world: &World| world.resource_mut::<Audio>().mixer().playing_count();
+
+            spatial_audio_system(&mut world);
+            assert_eq!(playing_count(&world), 3);
+            assert!(world.get::<AudioEmitter>(a).unwrap().is_playing());
+
+            // ループする音も、エンティティを削除すれば止まる
+            world.despawn(a);
+            spatial_audio_system(&mut world);
+            assert_eq!(playing_count(&world), 2);
+
+            world.remove::<AudioEmitter>(b);
+            spatial_audio_system(&mut world);
+            assert_eq!(playing_count(&world), 1);
+            assert!(world.get::<AudioEmitter>(c).unwrap().is_playing());
+
+            spatial_audio_system(&mut world);
+            assert_eq!(playing_count(&world), 1);
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use std::io::{Read, Seek};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use super::decoder::Decoder;
+
+// 先読みしておくかたまりの数 (OGGは1つがおよそ1000フレーム)
+const STREAM_BUFFER_CHUNKS: usize = 32;
+
+// 少しずつ読み込みながら再生する長い音 (BGM向け)
+// 別のスレッドでファイルを読み込んで展開し、再生する側は届いた分を取り出すだけにする
+// 再生を止めて MusicStream を破棄すると、読み込み用のスレッドも終わる
+pub struct MusicStream {
+    pub channels: u16,
+    pub sample_rate: u32,
+    receiver: Receiver<Vec<f32>>,
+    chunk: Vec<f32>,
+    position: usize, // chunk の中の次のサンプル
+}
+
+impl MusicStream {
+    pub fn open(path: &str, looping: bool) -> Result<MusicStream, String> {
+        MusicStream::from_decoder(Decoder::open(path)?, path, looping)
+    }
+
+    // アーカイブの中のファイルなど、ファイル以外から読むとき用
+    pub fn from_reader<R: Read + Seek + Send + 'static>(
+        reader: R,
+        name: &str,
+        looping: bool,
+    ) -> Result<MusicStream, String> {
+        let decoder =
+            Decoder::from_reader(Box::new(reader)).map_err(|e| format!("{}: {}", name, e))?;
+        MusicStream::from_decoder(decoder, name, looping)
+    }
+
+    pub fn from_decoder(
+        mut decoder: Decoder,
+        name: &str,
+        looping: bool,
+    ) -> Result<MusicStream, String> {
+        // 再生を始めたときに無音にならないよう、最初のかたまりはここで読んでおく
+        let chunk = decoder
+            .read()
+            .map_err(|e| format!("{}: {}", name, e))?
+            .unwrap_or_default();
+        let (channels, sample_rate) = (decoder.channels, decoder.sample_rate);
+        let (sender, receiver) = mpsc::sync_channel(STREAM_BUFFER_CHUNKS);
+        let thread_name = name.to_string();
+        let mut empty = chunk.is_empty(); // 巻き戻してから何も読めていない
+        thread::Builder::new()
+            .name(format!("music: {}", name))
+            .spawn(move || {
+                loop {
+                    match decoder.read() {
+                        Ok(Some(samples)) => {
+                            empty = false;
+                            // 受け取る側が破棄されたら終わる
+                            if sender.send(samples).is_err() {
+                                return;
+                            }
+                        }
+                        Ok(None) if looping && !empty => {
+                            if let Err(e) = decoder.rewind() {
+                                eprintln!("failed to rewind music: {}: {}", thread_name, e);
+                                return;
+                            }
+                            empty = true;
+                        }
+                        Ok(None) => return,
+                        Err(e) => {
+                            eprintln!("failed to stream music: {}: {}", thread_name, e);
+                            return;
+                        }
+                    }
+                }
+            })
+            .map_err(|e| format!("failed to start music thread: {}", e))?;
+        Ok(MusicStream {
+            channels,
+            sample_rate,
+            receiver,
+            chunk,
+            position: 0,
+        })
+    }
+
+    // 次のフレームの左右の値。最後まで再生したら None
+    // 読み込みが間に合わないときは無音を返す
+    pub fn next_frame(&mut self) -> Option<[f32; 2]> {
+        while self.position >= self.chunk.len() {
+            match self.receiver.try_recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Err(TryRecvError::Empty) => return Some([0.0, 0.0]),
+                Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+        let frame = if self.channels == 1 {
+            let sample = self.chunk[self.position];
+            [sample, sample]
+        } else {
+            [self.chunk[self.position], self.chunk[self.position + 1]]
+        };
+        self.position += self.channels as usize;
+        Some(frame)
+    }
+}
@@ -0,0 +1,144 @@
+// rsc ディレクトリを1つのアーカイブファイルにまとめる (配布用)
+//
+// 使い方:
+//   cargo run --bin pack -- [--compress] [--output rsc.pak] [dir]
+//   cargo run --bin pack -- --list rsc.pak
+//
+// アーカイブの中の名前は、カレントディレクトリからの相対パス (例: "rsc/texture/tiles.png")
+// ゲームは Assets のルートにある rsc.pak を読み、同じパスのファイルがルートにあればそちらを優先する
+
+#[path = "../assets/archive.rs"]
+#[allow(dead_code)]
+mod archive;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use flate2::Compression;
+
+use archive::{Archive, ArchiveWriter, Method, ARCHIVE_EXTENSION};
+
+const DEFAULT_DIR: &str = "rsc";
+
+struct Options {
+    dir: String,
+    output: Option<String>,
+    compress: bool,
+    list: Option<String>,
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("usage: pack [--compress] [--output <file>] [dir]");
+        eprintln!("       pack --list <file>");
+        process::exit(2);
+    });
+    let result = match &options.list {
+        Some(path) => list(path),
+        None => pack(&options),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        dir: DEFAULT_DIR.to_string(),
+        output: None,
+        compress: false,
+        list: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--compress" => options.compress = true,
+            "-o" | "--output" => {
+                options.output = Some(args.next().ok_or("--output needs a file name")?)
+            }
+            "-l" | "--list" => options.list = Some(args.next().ok_or("--list needs a file name")?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => options.dir = arg,
+        }
+    }
+    Ok(options)
+}
+
+fn pack(options: &Options) -> Result<(), String> {
+    let dir = options.dir.trim_end_matches('/');
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| format!("{}.{}", dir, ARCHIVE_EXTENSION));
+    let mut files = Vec::new();
+    collect_files(Path::new(dir), &mut files)?;
+    files.sort();
+
+    let file =
+        File::create(&output).map_err(|e| format!("failed to create file: {}: {}", output, e))?;
+    let compression = if options.compress {
+        Some(Compression::best())
+    } else {
+        None
+    };
+    let error = |e: std::io::Error| format!("failed to write archive: {}: {}", output, e);
+    let mut writer = ArchiveWriter::new(BufWriter::new(file), compression).map_err(error)?;
+    let (mut total_size, mut total_stored) = (0, 0);
+    for path in &files {
+        let data = fs::read(path)
+            .map_err(|e| format!("failed to open file: {}: {}", path.display(), e))?;
+        let name = path.to_string_lossy().replace('\\', "/");
+        let name = name.trim_start_matches("./");
+        let entry = writer.add(name, &data).map_err(error)?;
+        print_entry(&entry.name, entry.size, entry.stored_size, entry.method);
+        total_size += entry.size;
+        total_stored += entry.stored_size;
+    }
+    writer.finish().map_err(error)?;
+    println!(
+        "{}: {} files, {} -> {} bytes",
+        output,
+        files.len(),
+        total_size,
+        total_stored
+    );
+    Ok(())
+}
+
+// アーカイブの目次を表示する
+fn list(path: &str) -> Result<(), String> {
+    let archive = Archive::open(path)?;
+    for entry in archive.entries() {
+        print_entry(&entry.name, entry.size, entry.stored_size, entry.method);
+    }
+    println!("{}: {} files", path, archive.len());
+    Ok(())
+}
+
+fn print_entry(name: &str, size: u64, stored_size: u64, method: Method) {
+    let method = match method {
+        Method::Stored => "stored",
+        Method::Deflate => "deflate",
+    };
+    println!("{:>10} {:>10} {:<8} {}", size, stored_size, method, name);
+}
+
+// サブディレクトリの中のファイルも集める
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("failed to read directory: {}: {}", dir.display(), e))?;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
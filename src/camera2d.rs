@@ -0,0 +1,367 @@
+use cgmath::{ortho, vec2, vec3, Angle, Rad};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+#[allow(dead_code)]
+type Matrix4 = cgmath::Matrix4<f32>;
+
+// ワールド座標上の矩形 (カメラの移動範囲や画面に映る範囲を表す)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub min: Vector2,
+    pub max: Vector2,
+}
+
+#[allow(dead_code)]
+impl Rect {
+    pub fn new(min: Vector2, max: Vector2) -> Rect {
+        Rect { min, max }
+    }
+
+    pub fn center(&self) -> Vector2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vector2 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Vector2) -> bool {
+        self.min.x <= point.x
+            && point.x <= self.max.x
+            && self.min.y <= point.y
+            && point.y <= self.max.y
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+}
+
+// 2Dゲーム用のカメラ
+// ワールド座標はy軸が上向き、スクリーン座標はウィンドウ左上を原点としてy軸が下向き (SDLやimguiと同じ)
+// 行列を作る以外はOpenGLを使わないので、GLコンテキストがなくても座標変換を確かめられる
+pub struct Camera2D {
+    pub position: Vector2, // 画面の中央に映るワールド座標
+    pub zoom: f32,         // 1.0のとき、ワールドの1単位が1ピクセル
+    pub rotation: Rad<f32>,
+    pub viewport: Vector2, // 画面のサイズ (ピクセル)
+
+    // 追従
+    pub target: Option<Vector2>,
+    pub follow_speed: f32, // 大きいほど素早く追いつく (0以下ならすぐに追いつく)
+    pub dead_zone: Vector2, // ターゲットがこの半径の範囲内で動いてもカメラは動かない
+
+    // 移動範囲の制限
+    pub bounds: Option<Rect>,
+
+    // 画面の揺れ
+    pub shake_amplitude: Vector2, // 最大の揺れ幅 (ワールド単位)
+    pub shake_angle: Rad<f32>,    // 最大の回転の揺れ
+    pub shake_frequency: f32,     // 1秒あたりの揺れの細かさ
+    pub shake_decay: f32,         // 1秒あたりに減る揺れの強さ
+    trauma: f32,                  // 現在の揺れの強さ (0.0 ~ 1.0)
+    time: f32,
+    shake_offset: Vector2,
+    shake_rotation: Rad<f32>,
+}
+
+#[allow(dead_code)]
+impl Camera2D {
+    pub fn new(viewport_width: u32, viewport_height: u32) -> Camera2D {
+        Camera2D {
+            position: vec2(0.0, 0.0),
+            zoom: 1.0,
+            rotation: Rad(0.0),
+            viewport: vec2(viewport_width as f32, viewport_height as f32),
+            target: None,
+            follow_speed: 5.0,
+            dead_zone: vec2(0.0, 0.0),
+            bounds: None,
+            shake_amplitude: vec2(16.0, 16.0),
+            shake_angle: Rad(0.05),
+            shake_frequency: 20.0,
+            shake_decay: 1.5,
+            trauma: 0.0,
+            time: 0.0,
+            shake_offset: vec2(0.0, 0.0),
+            shake_rotation: Rad(0.0),
+        }
+    }
+
+    pub fn follow(&mut self, target: Vector2) {
+        self.target = Some(target);
+    }
+
+    // 揺れを加える (0.0 ~ 1.0、何度か呼ぶと強さが足し合わされる)
+    pub fn shake(&mut self, intensity: f32) {
+        self.trauma = (self.trauma + intensity).min(1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+
+        if let Some(target) = self.target {
+            // デッドゾーンの外に出た分だけ目標位置をずらす
+            let mut desired = self.position;
+            let offset = target - self.position;
+            if offset.x.abs() > self.dead_zone.x {
+                desired.x = target.x - self.dead_zone.x * offset.x.signum();
+            }
+            if offset.y.abs() > self.dead_zone.y {
+                desired.y = target.y - self.dead_zone.y * offset.y.signum();
+            }
+
+            // フレームレートに依存しないように指数関数で補間する
+            if self.follow_speed > 0.0 {
+                let t = 1.0 - (-self.follow_speed * delta_time).exp();
+                self.position += (desired - self.position) * t;
+            } else {
+                self.position = desired;
+            }
+        }
+
+        self.clamp_to_bounds();
+
+        // 揺れの強さの2乗を使うと、弱い揺れが目立たず強い揺れが際立つ
+        self.trauma = (self.trauma - self.shake_decay * delta_time).max(0.0);
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.shake_frequency;
+        self.shake_offset = vec2(
+            self.shake_amplitude.x * shake * noise(t, 0.0),
+            self.shake_amplitude.y * shake * noise(t, 100.0),
+        );
+        self.shake_rotation = self.shake_angle * (shake * noise(t, 200.0));
+    }
+
+    // カメラが範囲外を映さないように位置を制限する
+    // 範囲が画面より小さい軸では、範囲の中央に固定する
+    pub fn clamp_to_bounds(&mut self) {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let half = self.half_extents();
+        let center = bounds.center();
+        let size = bounds.size();
+
+        self.position.x = if size.x <= half.x * 2.0 {
+            center.x
+        } else {
+            self.position
+                .x
+                .max(bounds.min.x + half.x)
+                .min(bounds.max.x - half.x)
+        };
+        self.position.y = if size.y <= half.y * 2.0 {
+            center.y
+        } else {
+            self.position
+                .y
+                .max(bounds.min.y + half.y)
+                .min(bounds.max.y - half.y)
+        };
+    }
+
+    // 揺れを含めた実際の視点の位置と回転
+    fn eye(&self) -> (Vector2, Rad<f32>) {
+        (
+            self.position + self.shake_offset,
+            self.rotation + self.shake_rotation,
+        )
+    }
+
+    pub fn view_matrix(&self) -> Matrix4 {
+        let (eye, rotation) = self.eye();
+        Matrix4::from_scale(self.zoom)
+            * Matrix4::from_angle_z(-rotation)
+            * Matrix4::from_translation(vec3(-eye.x, -eye.y, 0.0))
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4 {
+        let half = self.viewport * 0.5;
+        ortho(-half.x, half.x, -half.y, half.y, -1000.0, 1000.0)
+    }
+
+    pub fn view_projection_matrix(&self) -> Matrix4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    pub fn screen_to_world(&self, screen: Vector2) -> Vector2 {
+        let (eye, rotation) = self.eye();
+        let view = vec2(
+            screen.x - self.viewport.x * 0.5,
+            self.viewport.y * 0.5 - screen.y,
+        ) / self.zoom;
+        eye + rotate(view, rotation)
+    }
+
+    pub fn world_to_screen(&self, world: Vector2) -> Vector2 {
+        let (eye, rotation) = self.eye();
+        let view = rotate(world - eye, -rotation) * self.zoom;
+        vec2(
+            view.x + self.viewport.x * 0.5,
+            self.viewport.y * 0.5 - view.y,
+        )
+    }
+
+    // 画面に映るワールド座標の範囲 (回転しているときはそれを囲む矩形)
+    pub fn visible_rect(&self) -> Rect {
+        let (eye, _) = self.eye();
+        let half = self.half_extents();
+        Rect::new(eye - half, eye + half)
+    }
+
+    fn half_extents(&self) -> Vector2 {
+        let half = self.viewport * 0.5 / self.zoom;
+        let (sin, cos) = self.rotation.sin_cos();
+        vec2(
+            cos.abs() * half.x + sin.abs() * half.y,
+            sin.abs() * half.x + cos.abs() * half.y,
+        )
+    }
+}
+
+fn rotate(v: Vector2, angle: Rad<f32>) -> Vector2 {
+    let (sin, cos) = angle.sin_cos();
+    vec2(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+// 整数の格子点に乱数を置いて滑らかにつないだノイズ (-1.0 ~ 1.0)
+fn noise(x: f32, seed: f32) -> f32 {
+    let hash = |i: f32| {
+        let h = ((i + seed) * 12.9898).sin() * 43758.547;
+        (h - h.floor()) * 2.0 - 1.0
+    };
+    let i = x.floor();
+    let f = x - i;
+    let t = f * f * (3.0 - 2.0 * f);
+    hash(i) * (1.0 - t) + hash(i + 1.0) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{vec4, InnerSpace};
+
+    fn assert_close(actual: Vector2, expected: Vector2) {
+        assert!(
+            (actual - expected).magnitude() < 1e-3,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn camera() -> Camera2D {
+        let mut camera = Camera2D::new(800, 600);
+        camera.position = vec2(120.0, -40.0);
+        camera.zoom = 2.5;
+        camera.rotation = Rad(0.6);
+        camera
+    }
+
+    #[test]
+    fn screen_world_round_trip() {
+        let camera = camera();
+        for point in [vec2(0.0, 0.0), vec2(-300.0, 75.5), vec2(1000.0, -2000.0)] {
+            let screen = camera.world_to_screen(point);
+            assert_close(camera.screen_to_world(screen), point);
+        }
+        // 画面の中央はカメラの位置
+        assert_close(camera.screen_to_world(vec2(400.0, 300.0)), camera.position);
+    }
+
+    #[test]
+    fn screen_matches_view_projection() {
+        let camera = camera();
+        let point = vec2(150.0, -10.0);
+        let clip = camera.view_projection_matrix() * vec4(point.x, point.y, 0.0, 1.0);
+        // 正規化デバイス座標をスクリーン座標に直す (yは下向き)
+        let screen = vec2(
+            (clip.x / clip.w + 1.0) * 0.5 * camera.viewport.x,
+            (1.0 - clip.y / clip.w) * 0.5 * camera.viewport.y,
+        );
+        assert_close(camera.world_to_screen(point), screen);
+    }
+
+    #[test]
+    fn clamps_to_bounds() {
+        let mut camera = Camera2D::new(800, 600);
+        camera.bounds = Some(Rect::new(vec2(0.0, 0.0), vec2(2000.0, 1000.0)));
+        camera.position = vec2(-500.0, 5000.0);
+        camera.clamp_to_bounds();
+        assert_close(camera.position, vec2(400.0, 700.0));
+        let visible = camera.visible_rect();
+        assert_close(visible.min, vec2(0.0, 400.0));
+        assert_close(visible.max, vec2(800.0, 1000.0));
+
+        // 画面より狭い範囲では中央に固定する
+        camera.zoom = 0.25;
+        camera.position = vec2(1900.0, 100.0);
+        camera.clamp_to_bounds();
+        assert_close(camera.position, vec2(1000.0, 500.0));
+
+        // 片方の軸だけ狭いときは、その軸だけ中央に固定する
+        camera.zoom = 1.0;
+        camera.bounds = Some(Rect::new(vec2(0.0, 0.0), vec2(2000.0, 300.0)));
+        camera.position = vec2(1900.0, 0.0);
+        camera.clamp_to_bounds();
+        assert_close(camera.position, vec2(1600.0, 150.0));
+    }
+
+    #[test]
+    fn dead_zone() {
+        let mut camera = Camera2D::new(800, 600);
+        camera.follow_speed = 0.0;
+        camera.dead_zone = vec2(50.0, 20.0);
+        camera.follow(vec2(40.0, -15.0));
+        camera.update(1.0 / 60.0);
+        assert_close(camera.position, vec2(0.0, 0.0));
+
+        // 外に出た分だけ動き、ターゲットはデッドゾーンの端に来る
+        camera.follow(vec2(80.0, -15.0));
+        camera.update(1.0 / 60.0);
+        assert_close(camera.position, vec2(30.0, 0.0));
+        camera.follow(vec2(0.0, 0.0));
+        camera.update(1.0 / 60.0);
+        assert_close(camera.position, vec2(30.0, 0.0));
+    }
+
+    #[test]
+    fn follow_is_smooth() {
+        let mut camera = Camera2D::new(800, 600);
+        camera.follow(vec2(100.0, 0.0));
+        camera.update(0.1);
+        let x = camera.position.x;
+        assert!(0.0 < x && x < 100.0);
+        for _ in 0..200 {
+            camera.update(0.1);
+        }
+        assert_close(camera.position, vec2(100.0, 0.0));
+    }
+
+    #[test]
+    fn shake_decays() {
+        let mut camera = Camera2D::new(800, 600);
+        camera.shake(0.7);
+        camera.shake(0.7);
+        assert_eq!(camera.trauma(), 1.0);
+        camera.update(0.1);
+        assert!(camera.trauma() < 1.0);
+        assert!(camera.eye() != (camera.position, camera.rotation));
+        // 1秒あたり1.5ずつ減るので、1秒で消える
+        for _ in 0..10 {
+            camera.update(0.1);
+        }
+        assert_eq!(camera.trauma(), 0.0);
+        assert_eq!(camera.eye(), (camera.position, camera.rotation));
+    }
+}
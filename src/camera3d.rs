@@ -0,0 +1,396 @@
+use cgmath::{vec3, Angle, Deg, InnerSpace, Rad};
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{KeyboardState, Scancode};
+
+#[allow(dead_code)]
+type Point3 = cgmath::Point3<f32>;
+#[allow(dead_code)]
+type Vector3 = cgmath::Vector3<f32>;
+#[allow(dead_code)]
+type Matrix4 = cgmath::Matrix4<f32>;
+
+// 3Dのデバッグカメラはz軸を上方向として扱う
+const UP: Vector3 = Vector3 {
+    x: 0.0,
+    y: 0.0,
+    z: 1.0,
+};
+// 真上や真下を向くと上方向ベクトルと視線が重なってしまうので、少し手前で止める
+const PITCH_LIMIT: Deg<f32> = Deg(89.0);
+
+// 仰角と方位角から向きのベクトルを求める
+fn direction(yaw: Rad<f32>, pitch: Rad<f32>) -> Vector3 {
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    vec3(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch)
+}
+
+fn clamp_pitch(pitch: Rad<f32>) -> Rad<f32> {
+    let limit: Rad<f32> = PITCH_LIMIT.into();
+    Rad(pitch.0.max(-limit.0).min(limit.0))
+}
+
+// 注視点の周りを回るカメラ (左ドラッグで回転、ホイールで拡大縮小)
+pub struct OrbitCamera {
+    pub target: Point3,
+    pub distance: f32,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub rotate_speed: f32, // 1ピクセルのドラッグで回る角度 (ラジアン)
+    pub zoom_speed: f32,   // ホイール1段で距離が変わる割合
+    pub min_distance: f32,
+    pub max_distance: f32,
+    dragging: bool,
+}
+
+impl OrbitCamera {
+    pub fn new(eye: Point3, target: Point3) -> OrbitCamera {
+        let mut camera = OrbitCamera {
+            target,
+            distance: 1.0,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            rotate_speed: 0.01,
+            zoom_speed: 0.1,
+            min_distance: 0.5,
+            max_distance: 50.0,
+            dragging: false,
+        };
+        camera.set_eye(eye);
+        camera
+    }
+
+    // 視点の位置から距離と角度を逆算する
+    pub fn set_eye(&mut self, eye: Point3) {
+        let offset = eye - self.target;
+        self.distance = offset.magnitude().max(self.min_distance);
+        self.yaw = Rad(offset.y.atan2(offset.x));
+        self.pitch = clamp_pitch(Rad((offset.z / self.distance).asin()));
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::MouseButtonDown {
+                mouse_btn: sdl2::mouse::MouseButton::Left,
+                ..
+            } => self.dragging = true,
+            Event::MouseButtonUp {
+                mouse_btn: sdl2::mouse::MouseButton::Left,
+                ..
+            } => self.dragging = false,
+            // ウィンドウの外でボタンを離すと MouseButtonUp が届かないので、移動中のボタンの状態も見る
+            Event::MouseMotion { mousestate, .. } if self.dragging && !mousestate.left() => {
+                self.dragging = false
+            }
+            Event::MouseMotion { xrel, yrel, .. } if self.dragging => {
+                self.yaw -= Rad(xrel as f32 * self.rotate_speed);
+                self.pitch = clamp_pitch(self.pitch + Rad(yrel as f32 * self.rotate_speed));
+            }
+            Event::MouseWheel { y, .. } => {
+                // ホイールを奥に回すと近づく
+                self.distance *= (1.0 - self.zoom_speed).powi(y);
+                self.distance = self.distance.max(self.min_distance).min(self.max_distance);
+            }
+            Event::Window {
+                win_event: WindowEvent::FocusLost,
+                ..
+            } => self.dragging = false,
+            _ => {}
+        }
+    }
+
+    pub fn eye(&self) -> Point3 {
+        self.target + direction(self.yaw, self.pitch) * self.distance
+    }
+
+    pub fn view_matrix(&self) -> Matrix4 {
+        Matrix4::look_at_rh(self.eye(), self.target, UP)
+    }
+}
+
+// 自由に飛び回るカメラ (WASDで移動、QとEで上下、右ドラッグで視点の向きを変える)
+pub struct FlyCamera {
+    pub position: Point3,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub move_speed: f32,       // 1秒あたりに進む距離
+    pub look_sensitivity: f32, // 1ピクセルのドラッグで回る角度 (ラジアン)
+    looking: bool,
+}
+
+impl FlyCamera {
+    pub fn new(eye: Point3, target: Point3) -> FlyCamera {
+        let mut camera = FlyCamera {
+            position: eye,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            move_speed: 3.0,
+            look_sensitivity: 0.005,
+            looking: false,
+        };
+        camera.look_at(target);
+        camera
+    }
+
+    pub fn look_at(&mut self, target: Point3) {
+        let forward = target - self.position;
+        if forward.magnitude2() == 0.0 {
+            return;
+        }
+        let forward = forward.normalize();
+        self.yaw = Rad(forward.y.atan2(forward.x));
+        self.pitch = clamp_pitch(Rad(forward.z.asin()));
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::MouseButtonDown {
+                mouse_btn: sdl2::mouse::MouseButton::Right,
+                ..
+            } => self.looking = true,
+            Event::MouseButtonUp {
+                mouse_btn: sdl2::mouse::MouseButton::Right,
+                ..
+            } => self.looking = false,
+            Event::MouseMotion { mousestate, .. } if self.looking && !mousestate.right() => {
+                self.looking = false
+            }
+            Event::MouseMotion { xrel, yrel, .. } if self.looking => {
+                self.yaw -= Rad(xrel as f32 * self.look_sensitivity);
+                self.pitch = clamp_pitch(self.pitch - Rad(yrel as f32 * self.look_sensitivity));
+            }
+            Event::Window {
+                win_event: WindowEvent::FocusLost,
+                ..
+            } => self.looking = false,
+            _ => {}
+        }
+    }
+
+    // キーが押されている間だけ移動するので、イベントではなくキーボードの状態を毎フレーム調べる
+    pub fn update(&mut self, delta_time: f32, keyboard: &KeyboardState) {
+        let forward = self.forward();
+        let right = forward.cross(UP).normalize();
+        let mut velocity = vec3(0.0, 0.0, 0.0);
+        if keyboard.is_scancode_pressed(Scancode::W) {
+            velocity += forward;
+        }
+        if keyboard.is_scancode_pressed(Scancode::S) {
+            velocity -= forward;
+        }
+        if keyboard.is_scancode_pressed(Scancode::D) {
+            velocity += right;
+        }
+        if keyboard.is_scancode_pressed(Scancode::A) {
+            velocity -= right;
+        }
+        if keyboard.is_scancode_pressed(Scancode::E) {
+            velocity += UP;
+        }
+        if keyboard.is_scancode_pressed(Scancode::Q) {
+            velocity -= UP;
+        }
+        if velocity.magnitude2() > 0.0 {
+            self.position += velocity.normalize() * self.move_speed * delta_time;
+        }
+    }
+
+    pub fn forward(&self) -> Vector3 {
+        direction(self.yaw, self.pitch)
+    }
+
+    pub fn eye(&self) -> Point3 {
+        self.position
+    }
+
+    pub fn view_matrix(&self) -> Matrix4 {
+        Matrix4::look_at_rh(self.position, self.position + self.forward(), UP)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Manual, // imguiのスライダーで視点の位置を指定する
+    Orbit,
+    Fly,
+}
+
+// imguiで操作方法を切り替えられるデバッグ用のカメラ
+// 切り替えたときは直前の視点の位置を引き継ぐ
+pub struct DebugCamera {
+    pub eye: Point3, // Manualのときの視点の位置
+    pub target: Point3,
+    pub orbit: OrbitCamera,
+    pub fly: FlyCamera,
+    mode: CameraMode,
+}
+
+impl DebugCamera {
+    pub fn new(eye: Point3, target: Point3) -> DebugCamera {
+        DebugCamera {
+            eye,
+            target,
+            orbit: OrbitCamera::new(eye, target),
+            fly: FlyCamera::new(eye, target),
+            mode: CameraMode::Manual,
+        }
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+        let eye = self.eye();
+        match mode {
+            CameraMode::Manual => self.eye = eye,
+            CameraMode::Orbit => {
+                self.orbit.target = self.target;
+                self.orbit.set_eye(eye);
+            }
+            CameraMode::Fly => {
+                self.fly.position = eye;
+                if self.mode == CameraMode::Manual {
+                    self.fly.look_at(self.target);
+                } else {
+                    self.fly.yaw = self.orbit.yaw + Rad::turn_div_2();
+                    self.fly.pitch = -self.orbit.pitch;
+                }
+            }
+        }
+        self.mode = mode;
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match self.mode {
+            CameraMode::Manual => {}
+            CameraMode::Orbit => self.orbit.handle_event(event),
+            CameraMode::Fly => self.fly.handle_event(event),
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32, keyboard: &KeyboardState) {
+        if self.mode == CameraMode::Fly {
+            self.fly.update(delta_time, keyboard);
+        }
+    }
+
+    pub fn eye(&self) -> Point3 {
+        match self.mode {
+            CameraMode::Manual => self.eye,
+            CameraMode::Orbit => self.orbit.eye(),
+            CameraMode::Fly => self.fly.eye(),
+        }
+    }
+
+    pub fn view_matrix(&self) -> Matrix4 {
+        match self.mode {
+            CameraMode::Manual => Matrix4::look_at_rh(self.eye, self.target, UP),
+            CameraMode::Orbit => self.orbit.view_matrix(),
+            CameraMode::Fly => self.fly.view_matrix(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::mouse::{MouseButton, MouseState};
+
+    const LEFT: u32 = 1;
+    const RIGHT: u32 = 1 << 2;
+
+    fn button_down(mouse_btn: MouseButton) -> Event {
+        Event::MouseButtonDown {
+            timestamp: 0,
+            window_id: 1,
+            which: 0,
+            mouse_btn,
+            clicks: 1,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    // buttons は移動中に押されているボタン
+    fn motion(buttons: u32, xrel: i32) -> Event {
+        Event::MouseMotion {
+            timestamp: 0,
+            window_id: 1,
+            which: 0,
+            mousestate: MouseState::from_sdl_state(buttons),
+            x: 0,
+            y: 0,
+            xrel,
+            yrel: 0,
+        }
+    }
+
+    fn focus_lost() -> Event {
+        Event::Window {
+            timestamp: 0,
+            window_id: 1,
+            win_event: WindowEvent::FocusLost,
+        }
+    }
+
+    fn orbit() -> OrbitCamera {
+        OrbitCamera::new(Point3::new(5.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0))
+    }
+
+    fn fly() -> FlyCamera {
+        FlyCamera::new(Point3::new(5.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn orbit_drag_rotates() {
+        let mut camera = orbit();
+        camera.handle_event(&motion(LEFT, 10));
+        assert_eq!(camera.yaw, Rad(0.0));
+        camera.handle_event(&button_down(MouseButton::Left));
+        camera.handle_event(&motion(LEFT, 10));
+        assert!((camera.yaw.0 + 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn orbit_drag_stops_when_the_button_was_released_outside() {
+        let mut camera = orbit();
+        camera.handle_event(&button_down(MouseButton::Left));
+        // MouseButtonUp が届かないまま、ボタンを押さずに動かした
+        camera.handle_event(&motion(0, 10));
+        camera.handle_event(&motion(LEFT, 10));
+        assert_eq!(camera.yaw, Rad(0.0));
+    }
+
+    #[test]
+    fn orbit_drag_stops_on_focus_lost() {
+        let mut camera = orbit();
+        camera.handle_event(&button_down(MouseButton::Left));
+        camera.handle_event(&focus_lost());
+        camera.handle_event(&motion(LEFT, 10));
+        assert_eq!(camera.yaw, Rad(0.0));
+    }
+
+    #[test]
+    fn fly_look_stops_when_the_button_was_released_outside() {
+        let mut camera = fly();
+        let yaw = camera.yaw;
+        camera.handle_event(&button_down(MouseButton::Right));
+        camera.handle_event(&motion(RIGHT, 10));
+        assert!((camera.yaw.0 - (yaw.0 - 0.05)).abs() < 1e-6);
+
+        let yaw = camera.yaw;
+        camera.handle_event(&motion(LEFT, 10));
+        camera.handle_event(&motion(RIGHT, 10));
+        assert_eq!(camera.yaw, yaw);
+
+        camera.handle_event(&button_down(MouseButton::Right));
+        camera.handle_event(&focus_lost());
+        camera.handle_event(&motion(RIGHT, 10));
+        assert_eq!(camera.yaw, yaw);
+    }
+}
@@ -0,0 +1,725 @@
+use cgmath::{vec2, InnerSpace};
+use imgui::im_str;
+use serde::Deserialize;
+
+use crate::ecs::{Read, Time, World, Write};
+use crate::physics::{
+    BodyHandle, BodyType, PhysicsBody, PhysicsWorld, QueryFilter, Shape, ShapeCastHit, ALL_LAYERS,
+};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 1回の移動で壁に沿って滑らせる回数の上限
+const MAX_SLIDES: usize = 4;
+// これより下向きの法線をもつ面は天井とみなす
+const CEILING_NORMAL_Y: f32 = -0.7;
+
+// キャラクターの動きの調整値 (imguiやファイルから変更できる)
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CharacterConfig {
+    pub max_speed: f32,        // 横方向の最高速度 (単位/秒)
+    pub acceleration: f32,     // 地上で加速するときの加速度
+    pub deceleration: f32,     // 地上で入力がないときの減速度
+    pub air_acceleration: f32, // 空中での加速度
+    pub gravity: f32,
+    pub max_fall_speed: f32,
+    pub jump_height: f32,       // ジャンプボタンを押し続けたときの高さ
+    pub jump_cut: f32,          // 上昇中にボタンを離したときに残す速度の割合 (可変ジャンプ)
+    pub coyote_time: f32,       // 足場から落ちた後もジャンプできる猶予 (秒)
+    pub jump_buffer_time: f32,  // 着地の少し前に押したジャンプを覚えておく時間 (秒)
+    pub max_slope: f32,         // 歩いて登れる坂の最大の角度 (度)
+    pub snap_distance: f32,     // 下り坂や段差で地面に吸い付く距離
+    pub skin_width: f32,        // 壁や地面との間に空けておく隙間
+    pub collision_mask: u32,    // ぶつかるレイヤー
+    pub one_way_mask: u32,      // 下からすり抜けて上に乗れる足場のレイヤー
+    pub drop_through_time: f32, // 足場をすり抜けて降りている時間 (秒)
+}
+
+impl Default for CharacterConfig {
+    fn default() -> CharacterConfig {
+        CharacterConfig {
+            max_speed: 6.0,
+            acceleration: 60.0,
+            deceleration: 50.0,
+            air_acceleration: 30.0,
+            gravity: 30.0,
+            max_fall_speed: 20.0,
+            jump_height: 2.5,
+            jump_cut: 0.5,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.1,
+            max_slope: 50.0,
+            snap_distance: 0.2,
+            skin_width: 0.01,
+            collision_mask: ALL_LAYERS,
+            one_way_mask: 0,
+            drop_through_time: 0.25,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl CharacterConfig {
+    // 重力とジャンプの高さから求めた踏み切りの速度
+    pub fn jump_velocity(&self) -> f32 {
+        (2.0 * self.gravity * self.jump_height).sqrt()
+    }
+
+    // 歩ける地面の法線のy成分の下限
+    fn min_ground_normal_y(&self) -> f32 {
+        self.max_slope.to_radians().cos()
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        imgui::Slider::new(im_str!("Max Speed"))
+            .range(0.0..=20.0)
+            .build(ui, &mut self.max_speed);
+        imgui::Slider::new(im_str!("Acceleration"))
+            .range(0.0..=200.0)
+            .build(ui, &mut self.acceleration);
+        imgui::Slider::new(im_str!("Deceleration"))
+            .range(0.0..=200.0)
+            .build(ui, &mut self.deceleration);
+        imgui::Slider::new(im_str!("Air Acceleration"))
+            .range(0.0..=200.0)
+            .build(ui, &mut self.air_acceleration);
+        imgui::Slider::new(im_str!("Gravity"))
+            .range(0.0..=100.0)
+            .build(ui, &mut self.gravity);
+        imgui::Slider::new(im_str!("Max Fall Speed"))
+            .range(0.0..=50.0)
+            .build(ui, &mut self.max_fall_speed);
+        imgui::Slider::new(im_str!("Jump Height"))
+            .range(0.0..=10.0)
+            .build(ui, &mut self.jump_height);
+        imgui::Slider::new(im_str!("Jump Cut"))
+            .range(0.0..=1.0)
+            .build(ui, &mut self.jump_cut);
+        imgui::Slider::new(im_str!("Coyote Time"))
+            .range(0.0..=0.5)
+            .build(ui, &mut self.coyote_time);
+        imgui::Slider::new(im_str!("Jump Buffer"))
+            .range(0.0..=0.5)
+            .build(ui, &mut self.jump_buffer_time);
+        imgui::Slider::new(im_str!("Max Slope"))
+            .range(0.0..=89.0)
+            .build(ui, &mut self.max_slope);
+        imgui::Slider::new(im_str!("Snap Distance"))
+            .range(0.0..=1.0)
+            .build(ui, &mut self.snap_distance);
+    }
+}
+
+// キャラクターへの入力 (ゲーム側が毎フレーム書き込む)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CharacterInput {
+    pub move_x: f32,        // -1.0 (左) ~ 1.0 (右)
+    pub jump_pressed: bool, // 押した瞬間に true にする (コントローラーが読んだら false に戻す)
+    pub jump_held: bool,
+    pub drop_pressed: bool, // すり抜けられる足場から降りる (読んだら false に戻す)
+}
+
+// Kinematic な剛体をもつエンティティを、プラットフォーマーのキャラクターとして動かす
+// 剛体の形状を衝突判定のクエリで動かしてみて、実際に進める量を速度として剛体に渡す
+#[derive(Clone, Debug)]
+pub struct CharacterController {
+    pub config: CharacterConfig,
+    pub velocity: Vector2,
+    grounded: bool,
+    ground_normal: Vector2,
+    ground_body: Option<BodyHandle>,
+    platform_velocity: Vector2, // 乗っている動く床の速度
+    coyote_timer: f32,
+    jump_buffer_timer: f32,
+    drop_timer: f32,
+    jumping: bool, // ジャンプで上昇中 (ボタンを離すと減速する)
+}
+
+#[allow(dead_code)]
+impl CharacterController {
+    pub fn new(config: CharacterConfig) -> CharacterController {
+        CharacterController {
+            config,
+            velocity: vec2(0.0, 0.0),
+            grounded: false,
+            ground_normal: vec2(0.0, 1.0),
+            ground_body: None,
+            platform_velocity: vec2(0.0, 0.0),
+            coyote_timer: 0.0,
+            jump_buffer_timer: 0.0,
+            drop_timer: 0.0,
+            jumping: false,
+        }
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    pub fn ground_normal(&self) -> Vector2 {
+        self.ground_normal
+    }
+
+    pub fn ground_body(&self) -> Option<BodyHandle> {
+        self.ground_body
+    }
+
+    // 1ステップ分動かした後の位置を返す
+    fn update(
+        &mut self,
+        input: &mut CharacterInput,
+        physics: &PhysicsWorld,
+        handle: BodyHandle,
+        shape: &Shape,
+        position: Vector2,
+        delta_time: f32,
+    ) -> Vector2 {
+        let config = self.config;
+
+        // 入力を猶予時間として覚えておく
+        if input.jump_pressed {
+            self.jump_buffer_timer = config.jump_buffer_time;
+            input.jump_pressed = false;
+        } else {
+            self.jump_buffer_timer -= delta_time;
+        }
+        if input.drop_pressed {
+            self.drop_timer = config.drop_through_time;
+            input.drop_pressed = false;
+        } else {
+            self.drop_timer -= delta_time;
+        }
+        if self.grounded {
+            self.coyote_timer = config.coyote_time;
+        } else {
+            self.coyote_timer -= delta_time;
+        }
+
+        // 横方向
+        let target = input.move_x.clamp(-1.0, 1.0) * config.max_speed;
+        let acceleration = if !self.grounded {
+            config.air_acceleration
+        } else if target != 0.0 {
+            config.acceleration
+        } else {
+            config.deceleration
+        };
+        self.velocity.x = approach(self.velocity.x, target, acceleration * delta_time);
+
+        // 縦方向 (ボタンを早く離すほど低く跳ぶ)
+        if self.jumping && !input.jump_held && self.velocity.y > 0.0 {
+            self.velocity.y *= config.jump_cut;
+            self.jumping = false;
+        }
+        if self.jump_buffer_timer > 0.0 && self.coyote_timer > 0.0 {
+            // 動く床の勢いを引き継いで跳ぶ
+            self.velocity.x += self.platform_velocity.x;
+            self.velocity.y = config.jump_velocity() + self.platform_velocity.y.max(0.0);
+            self.jump_buffer_timer = 0.0;
+            self.coyote_timer = 0.0;
+            self.jumping = true;
+            self.grounded = false;
+        }
+        if !self.grounded {
+            self.velocity.y =
+                (self.velocity.y - config.gravity * delta_time).max(-config.max_fall_speed);
+        }
+        if self.velocity.y <= 0.0 {
+            self.jumping = false;
+        }
+
+        // 地上では坂に沿って動かす
+        let mut delta = self.velocity * delta_time;
+        let mut position = position;
+        if self.grounded {
+            let tangent = vec2(self.ground_normal.y, -self.ground_normal.x);
+            delta = tangent * delta.x;
+        }
+
+        let was_grounded = self.grounded;
+        let (moved, normals) = self.move_and_slide(physics, handle, shape, position, delta);
+        position = moved;
+        for normal in normals {
+            if normal.y < CEILING_NORMAL_Y && self.velocity.y > 0.0 {
+                self.velocity.y = 0.0;
+                self.jumping = false;
+            } else if normal.y < config.min_ground_normal_y() && self.velocity.x * normal.x < 0.0 {
+                self.velocity.x = 0.0;
+            }
+        }
+
+        // 足元を調べる (地上にいたときは少し離れた地面にも吸い付かせる)
+        let probe = if was_grounded && !self.jumping {
+            config.snap_distance + config.skin_width * 2.0
+        } else {
+            config.skin_width * 2.0
+        };
+        let ground = if self.velocity.y > 0.0 {
+            None
+        } else {
+            self.cast(physics, handle, shape, position, vec2(0.0, -probe))
+                .filter(|hit| hit.normal.y >= config.min_ground_normal_y())
+        };
+        match ground {
+            Some(hit) => {
+                position.y -= (hit.distance - config.skin_width).max(0.0);
+                self.velocity.y = 0.0;
+                self.grounded = true;
+                self.jumping = false;
+                self.ground_normal = hit.normal;
+                self.ground_body = Some(hit.body);
+                self.platform_velocity = physics
+                    .body(hit.body)
+                    .filter(|body| body.body_type == BodyType::Kinematic)
+                    .map(|body| body.velocity)
+                    .unwrap_or_else(|| vec2(0.0, 0.0));
+            }
+            None => {
+                self.grounded = false;
+                self.ground_normal = vec2(0.0, 1.0);
+                self.ground_body = None;
+                self.platform_velocity = vec2(0.0, 0.0);
+            }
+        }
+
+        // 動く床と一緒に動く (床はこの後の物理演算のステップで動く)
+        // 横や上へ運ばれるときは壁や天井にめり込まないように滑らせ、下がるときは床に乗ったまま付いていく
+        if self.grounded {
+            let carry = self.platform_velocity * delta_time;
+            let (carried, _) = self.move_and_slide(
+                physics,
+                handle,
+                shape,
+                position,
+                vec2(carry.x, carry.y.max(0.0)),
+            );
+            position = carried + vec2(0.0, carry.y.min(0.0));
+        }
+        position
+    }
+
+    // 形状を動かして最初にぶつかるもの
+    // すり抜けられる足場は、上から下へ動いて上面に当たったときだけぶつかる
+    fn cast(
+        &self,
+        physics: &PhysicsWorld,
+        handle: BodyHandle,
+        shape: &Shape,
+        position: Vector2,
+        delta: Vector2,
+    ) -> Option<ShapeCastHit> {
+        let config = &self.config;
+        let solid =
+            QueryFilter::new(config.collision_mask & !config.one_way_mask).excluding(handle);
+        let mut hit = physics.shape_cast(shape, position, 0.0, delta, solid);
+
+        let one_way_mask = config.collision_mask & config.one_way_mask;
+        if one_way_mask != 0 && delta.y < 0.0 && self.drop_timer <= 0.0 {
+            let filter = QueryFilter::new(one_way_mask).excluding(handle);
+            let platform = physics
+                .shape_cast(shape, position, 0.0, delta, filter)
+                .filter(|h| h.fraction > 0.0 && h.normal.y >= config.min_ground_normal_y());
+            if let Some(platform) = platform {
+                if hit.map(|h| platform.fraction < h.fraction).unwrap_or(true) {
+                    hit = Some(platform);
+                }
+            }
+        }
+        hit
+    }
+
+    // ぶつかった面に沿って残りの移動量を滑らせる
+    // 地上では登れない急な坂を壁として扱い、押し上げられないようにする
+    fn move_and_slide(
+        &self,
+        physics: &PhysicsWorld,
+        handle: BodyHandle,
+        shape: &Shape,
+        mut position: Vector2,
+        mut delta: Vector2,
+    ) -> (Vector2, Vec<Vector2>) {
+        let skin = self.config.skin_width;
+        let mut normals = Vec::new();
+        for _ in 0..MAX_SLIDES {
+            let length = delta.magnitude();
+            if length < 1e-5 {
+                break;
+            }
+            // 隙間の分だけ先まで調べる (ちょうど接する位置まで動くと、隙間がなくなってしまう)
+            let direction = delta / length;
+            let hit = match self.cast(
+                physics,
+                handle,
+                shape,
+                position,
+                direction * (length + skin),
+            ) {
+                Some(hit) if hit.distance - skin < length => hit,
+                _ => {
+                    position += delta;
+                    break;
+                }
+            };
+            let distance = (hit.distance - skin).max(0.0);
+            position += direction * distance;
+            normals.push(hit.normal);
+
+            let mut normal = hit.normal;
+            if self.grounded && normal.y > 0.0 && normal.y < self.config.min_ground_normal_y() {
+                normal = vec2(normal.x, 0.0).normalize();
+            }
+            let remaining = direction * (length - distance);
+            delta = remaining - normal * remaining.dot(normal).min(0.0);
+        }
+        (position, normals)
+    }
+}
+
+fn approach(current: f32, target: f32, max_delta: f32) -> f32 {
+    if current < target {
+        (current + max_delta).min(target)
+    } else {
+        (current - max_delta).max(target)
+    }
+}
+
+// キャラクターを動かす (FixedUpdate、physics_step_system より前に登録する)
+// 求めた移動量を Kinematic な剛体の速度にして、物理演算のステップで実際に動かす
+pub fn character_controller_system(world: &mut World) {
+    let delta_time = world.resource::<Time>().fixed_delta_time;
+    let mut physics = world.resource_mut::<PhysicsWorld>();
+    world
+        .query::<(
+            Write<CharacterController>,
+            Write<CharacterInput>,
+            Read<PhysicsBody>,
+        )>()
+        .for_each(|_, (controller, input, body)| {
+            // 形状の位置 (回転しないものとして、剛体の原点からのずれを足す)
+            let (shape, position) = match physics.body(body.0) {
+                Some(rigid_body) => (
+                    rigid_body.collider().shape.clone(),
+                    rigid_body.position + rigid_body.collider().offset,
+                ),
+                None => return,
+            };
+            let target = controller.update(input, &physics, body.0, &shape, position, delta_time);
+            if let Some(rigid_body) = physics.body_mut(body.0) {
+                rigid_body.velocity = (target - position) / delta_time;
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Ref, RefMut};
+
+    use crate::ecs::Entity;
+    use crate::physics::{physics_step_system, Collider, RigidBody};
+
+    const DELTA_TIME: f32 = 1.0 / 60.0;
+    const ONE_WAY_LAYER: u32 = 2;
+    const HALF_HEIGHT: f32 = 0.5;
+
+    // キャラクター1人と足場だけのワールド (描画もGLも使わない)
+    struct Scene {
+        world: World,
+        player: Entity,
+        body: BodyHandle,
+    }
+
+    impl Scene {
+        fn new(position: Vector2) -> Scene {
+            let mut world = World::new();
+            world.insert_resource(Time {
+                delta_time: DELTA_TIME,
+                fixed_delta_time: DELTA_TIME,
+                elapsed: 0.0,
+                alpha: 0.0,
+                frame: 0,
+            });
+            let mut physics = PhysicsWorld::new(vec2(0.0, 0.0));
+            let player = world.spawn();
+            let body = physics.add_body(
+                RigidBody::kinematic(
+                    position,
+                    Collider::new(Shape::Aabb {
+                        half_extents: vec2(0.25, HALF_HEIGHT),
+                    }),
+                )
+                .with_entity(player),
+            );
+            world.insert_resource(physics);
+            world.insert(player, PhysicsBody(body));
+            world.insert(
+                player,
+                CharacterController::new(CharacterConfig {
+                    one_way_mask: ONE_WAY_LAYER,
+                    ..CharacterConfig::default()
+                }),
+            );
+            world.insert(player, CharacterInput::default());
+            Scene {
+                world,
+                player,
+                body,
+            }
+        }
+
+        // 左右に広い床 (上面が y = 0)
+        fn with_floor(position: Vector2) -> Scene {
+            let mut scene = Scene::new(position);
+            scene.add(block(vec2(0.0, -0.5), vec2(20.0, 0.5)));
+            scene
+        }
+
+        fn add(&mut self, body: RigidBody) -> BodyHandle {
+            self.world.resource_mut::<PhysicsWorld>().add_body(body)
+        }
+
+        fn input(&self) -> RefMut<'_, CharacterInput> {
+            self.world.get_mut::<CharacterInput>(self.player).unwrap()
+        }
+
+        fn controller(&self) -> Ref<'_, CharacterController> {
+            self.world.get::<CharacterController>(self.player).unwrap()
+        }
+
+        fn step(&mut self) {
+            character_controller_system(&mut self.world);
+            physics_step_system(&mut self.world);
+        }
+
+        fn steps(&mut self, count: usize) {
+            for _ in 0..count {
+                self.step();
+            }
+        }
+
+        fn position(&self) -> Vector2 {
+            self.world
+                .resource::<PhysicsWorld>()
+                .body(self.body)
+                .unwrap()
+                .position
+        }
+
+        fn body_position(&self, handle: BodyHandle) -> Vector2 {
+            self.world
+                .resource::<PhysicsWorld>()
+                .body(handle)
+                .unwrap()
+                .position
+        }
+
+        fn is_grounded(&self) -> bool {
+            self.controller().is_grounded()
+        }
+
+        // 足元の高さ
+        fn feet(&self) -> f32 {
+            self.position().y - HALF_HEIGHT
+        }
+
+        // ジャンプして一番高くなったときの足元の高さ (release_after ステップでボタンを離す)
+        fn jump_peak(&mut self, release_after: usize) -> f32 {
+            {
+                let mut input = self.input();
+                input.jump_pressed = true;
+                input.jump_held = true;
+            }
+            let mut peak = self.feet();
+            for i in 0..120 {
+                if i == release_after {
+                    self.input().jump_held = false;
+                }
+                self.step();
+                peak = peak.max(self.feet());
+            }
+            peak
+        }
+    }
+
+    fn block(position: Vector2, half_extents: Vector2) -> RigidBody {
+        RigidBody::fixed(position, Collider::new(Shape::rect(half_extents)))
+    }
+
+    #[test]
+    fn lands_and_walks() {
+        let mut scene = Scene::with_floor(vec2(0.0, 2.0));
+        scene.steps(60);
+        assert!(scene.is_grounded());
+        assert!(scene.feet().abs() < 0.05, "{}", scene.feet());
+
+        scene.input().move_x = 1.0;
+        scene.steps(60);
+        let config = CharacterConfig::default();
+        assert!(scene.is_grounded());
+        assert_eq!(scene.controller().velocity.x, config.max_speed);
+        assert!(scene.position().x > 4.0);
+        assert!(scene.feet().abs() < 0.05);
+    }
+
+    #[test]
+    fn coyote_time_allows_a_late_jump() {
+        for &(wait, jumps) in [(2, true), (12, false)].iter() {
+            // 床の右端 (x = 0) から歩いて落ちる
+            let mut scene = Scene::new(vec2(-1.0, HALF_HEIGHT + 0.05));
+            scene.add(block(vec2(-10.0, -0.5), vec2(10.0, 0.5)));
+            scene.steps(10);
+            assert!(scene.is_grounded());
+            scene.input().move_x = 1.0;
+            while scene.is_grounded() {
+                scene.step();
+            }
+            scene.input().move_x = 0.0;
+            scene.steps(wait);
+            scene.input().jump_pressed = true;
+            scene.step();
+            assert_eq!(scene.controller().velocity.y > 0.0, jumps, "wait {}", wait);
+        }
+    }
+
+    #[test]
+    fn jump_buffer_remembers_an_early_press() {
+        for &(frames_before, jumps) in [(3, true), (20, false)].iter() {
+            let mut scene = Scene::with_floor(vec2(0.0, 5.0));
+            // 着地する何ステップ前かを、今の落下速度から見積もって押す
+            loop {
+                let speed = -scene.controller().velocity.y;
+                if speed > 0.0 && scene.feet() < speed * DELTA_TIME * frames_before as f32 {
+                    break;
+                }
+                scene.step();
+            }
+            scene.input().jump_pressed = true;
+            let mut jumped = false;
+            for _ in 0..30 {
+                scene.step();
+                jumped |= scene.controller().velocity.y > 0.0;
+            }
+            assert_eq!(jumped, jumps, "{} frames before", frames_before);
+        }
+    }
+
+    #[test]
+    fn releasing_jump_early_cuts_the_height() {
+        let config = CharacterConfig::default();
+        let mut scene = Scene::with_floor(vec2(0.0, HALF_HEIGHT + 0.05));
+        scene.steps(10);
+        let full = scene.jump_peak(usize::MAX);
+        assert!(
+            (full - config.jump_height).abs() < 0.15,
+            "full jump: {}",
+            full
+        );
+
+        let mut scene = Scene::with_floor(vec2(0.0, HALF_HEIGHT + 0.05));
+        scene.steps(10);
+        let cut = scene.jump_peak(5);
+        assert!(cut < full * 0.6, "cut jump: {} (full {})", cut, full);
+        assert!(scene.is_grounded());
+    }
+
+    #[test]
+    fn one_way_platforms_can_be_jumped_through_and_dropped_from() {
+        let mut scene = Scene::with_floor(vec2(0.0, HALF_HEIGHT + 0.05));
+        // 上面が y = 2 のすり抜けられる足場
+        scene.add(RigidBody::fixed(
+            vec2(0.0, 1.9),
+            Collider::new(Shape::rect(vec2(2.0, 0.1)))
+                .with_layers(ONE_WAY_LAYER, crate::physics::ALL_LAYERS),
+        ));
+        scene.steps(10);
+        scene.jump_peak(usize::MAX);
+        assert!(scene.is_grounded());
+        assert!((scene.feet() - 2.0).abs() < 0.05, "{}", scene.feet());
+
+        scene.input().drop_pressed = true;
+        scene.steps(60);
+        assert!(scene.is_grounded());
+        assert!(scene.feet().abs() < 0.05, "{}", scene.feet());
+    }
+
+    #[test]
+    fn steep_slopes_block_walking() {
+        // 右へ上る坂 (床の上に置いた三角形)
+        let ramp = |angle: f32| {
+            let height = 3.0 * angle.to_radians().tan();
+            RigidBody::fixed(
+                vec2(1.0, 0.0),
+                Collider::new(Shape::polygon(vec![
+                    vec2(0.0, 0.0),
+                    vec2(3.0, 0.0),
+                    vec2(3.0, height),
+                ])),
+            )
+        };
+
+        let mut scene = Scene::with_floor(vec2(0.0, HALF_HEIGHT + 0.05));
+        scene.add(ramp(30.0));
+        scene.steps(10);
+        scene.input().move_x = 1.0;
+        scene.steps(50);
+        assert!(scene.feet() > 1.0, "walked up to {:?}", scene.position());
+
+        let mut scene = Scene::with_floor(vec2(0.0, HALF_HEIGHT + 0.05));
+        scene.add(ramp(60.0));
+        scene.steps(10);
+        scene.input().move_x = 1.0;
+        scene.steps(50);
+        assert!(scene.feet() < 0.1, "pushed up to {:?}", scene.position());
+        assert!(scene.position().x < 1.0);
+    }
+
+    #[test]
+    fn moving_platforms_carry_the_character() {
+        let mut scene = Scene::new(vec2(0.0, 0.55 + HALF_HEIGHT));
+        let mut platform =
+            RigidBody::kinematic(vec2(0.0, 0.0), Collider::new(Shape::rect(vec2(2.0, 0.5))));
+        platform.velocity = vec2(2.0, 1.0);
+        let platform = scene.add(platform);
+        scene.steps(60);
+
+        let top = scene.body_position(platform).y + 0.5;
+        assert!(scene.is_grounded());
+        assert_eq!(scene.controller().ground_body(), Some(platform));
+        assert!(
+            (scene.feet() - top).abs() < 0.05,
+            "{} {}",
+            scene.feet(),
+            top
+        );
+        assert!((scene.position().x - scene.body_position(platform).x).abs() < 0.05);
+    }
+
+    #[test]
+    fn moving_platforms_do_not_push_into_walls_or_ceilings() {
+        // 床より上だけにある壁 (左の面が x = 2)
+        let mut scene = Scene::new(vec2(0.0, 0.55 + HALF_HEIGHT));
+        scene.add(block(vec2(2.5, 3.0), vec2(0.5, 2.0)));
+        let mut platform =
+            RigidBody::kinematic(vec2(0.0, 0.0), Collider::new(Shape::rect(vec2(3.0, 0.5))));
+        platform.velocity = vec2(2.0, 0.0);
+        scene.add(platform);
+        scene.steps(60);
+        assert!(scene.position().x + 0.25 <= 2.0, "{:?}", scene.position());
+
+        // 下の面が y = 2.5 の天井
+        let mut scene = Scene::new(vec2(0.0, 0.55 + HALF_HEIGHT));
+        scene.add(block(vec2(0.0, 3.0), vec2(3.0, 0.5)));
+        let mut platform =
+            RigidBody::kinematic(vec2(0.0, 0.0), Collider::new(Shape::rect(vec2(2.0, 0.5))));
+        platform.velocity = vec2(0.0, 2.0);
+        scene.add(platform);
+        scene.steps(45);
+        assert!(
+            scene.position().y + HALF_HEIGHT <= 2.5 + 1e-3,
+            "{:?}",
+            scene.position()
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use std::rc::Rc;
+
+use cgmath::Rad;
+
+use crate::material::MaterialId;
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 1秒あたりの移動量と回転量
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity {
+    pub linear: Vector2,
+    pub angular: Rad<f32>,
+}
+
+// 描画する頂点データとマテリアル
+#[derive(Clone)]
+pub struct Sprite {
+    pub vertex: Rc<Vertex>,
+    pub material: MaterialId,
+    pub layer: i32,
+    pub visible: bool,
+}
+
+// キャラクターがセンサーに触れると拾うもの (拾うとエンティティごと消える)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pickup {
+    pub value: u32,
+}
+
+// 拾ったものの合計 (リソース)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score(pub u32);
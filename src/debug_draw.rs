@@ -0,0 +1,398 @@
+use std::f32::consts::PI;
+use std::mem;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use cgmath::{vec2, vec3, vec4, Matrix4, SquareMatrix};
+use gl::types::{GLfloat, GLsizei, GLsizeiptr};
+use imgui::im_str;
+
+use crate::camera2d::{Camera2D, Rect};
+use crate::ecs::World;
+use crate::material::{Material, MaterialId, RenderState};
+use crate::physics::{BodyType, PhysicsWorld, WorldShape};
+use crate::renderer::{DrawCommand, Renderer};
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+#[allow(dead_code)]
+type Vector3 = cgmath::Vector3<f32>;
+
+pub type Color = [f32; 4];
+
+#[allow(dead_code)]
+pub const WHITE: Color = [1.0, 1.0, 1.0, 1.0];
+#[allow(dead_code)]
+pub const GRAY: Color = [0.5, 0.5, 0.5, 1.0];
+#[allow(dead_code)]
+pub const RED: Color = [1.0, 0.2, 0.2, 1.0];
+#[allow(dead_code)]
+pub const GREEN: Color = [0.2, 0.9, 0.2, 1.0];
+#[allow(dead_code)]
+pub const BLUE: Color = [0.2, 0.4, 1.0, 1.0];
+#[allow(dead_code)]
+pub const YELLOW: Color = [1.0, 0.9, 0.1, 1.0];
+#[allow(dead_code)]
+pub const CYAN: Color = [0.1, 0.9, 0.9, 1.0];
+#[allow(dead_code)]
+pub const MAGENTA: Color = [0.9, 0.2, 0.9, 1.0];
+
+// 1頂点あたりの要素数 (位置xyz、色rgba)
+const FLOATS_PER_VERTEX: usize = 7;
+
+const DEBUG_VERTEX_SHADER: &str = "rsc/shader/debug.vs";
+const DEBUG_FRAGMENT_SHADER: &str = "rsc/shader/debug.fs";
+
+// 種類ごとに表示を切り替えられる
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugCategory {
+    General,
+    Physics,  // 剛体の形状 (自動で描画する)
+    Contacts, // 接触点と法線 (自動で描画する)
+    Camera,   // 2Dカメラの映す範囲と移動範囲 (自動で描画する)
+    Gameplay,
+}
+
+impl DebugCategory {
+    pub const ALL: [DebugCategory; 5] = [
+        DebugCategory::General,
+        DebugCategory::Physics,
+        DebugCategory::Contacts,
+        DebugCategory::Camera,
+        DebugCategory::Gameplay,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugCategory::General => "General",
+            DebugCategory::Physics => "Physics",
+            DebugCategory::Contacts => "Contacts",
+            DebugCategory::Camera => "Camera",
+            DebugCategory::Gameplay => "Gameplay",
+        }
+    }
+}
+
+struct DebugText {
+    position: Vector3,
+    text: String,
+    color: Color,
+}
+
+// 即時モードのデバッグ描画 (リソース)
+// フレームの間に呼び出した線を1つの頂点バッファーにまとめ、Renderステージで1回の描画で済ませる
+// 文字はimguiで画面の一番手前に描画する
+pub struct DebugDraw {
+    pub enabled: bool,
+    pub circle_segments: u32,
+    categories: [bool; DebugCategory::ALL.len()],
+    vertices: Vec<f32>,
+    texts: Vec<DebugText>,
+    frame_texts: Vec<DebugText>, // 直前のRenderステージで確定した文字
+    material: Option<MaterialId>,
+    vertex: Option<Rc<Vertex>>,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        DebugDraw::new()
+    }
+}
+
+#[allow(dead_code)]
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw {
+            enabled: true,
+            circle_segments: 24,
+            categories: [true; DebugCategory::ALL.len()],
+            vertices: Vec::new(),
+            texts: Vec::new(),
+            frame_texts: Vec::new(),
+            material: None,
+            vertex: None,
+        }
+    }
+
+    pub fn is_enabled(&self, category: DebugCategory) -> bool {
+        self.enabled && self.categories[category as usize]
+    }
+
+    pub fn set_enabled(&mut self, category: DebugCategory, enabled: bool) {
+        self.categories[category as usize] = enabled;
+    }
+
+    // このフレームに積まれた線の数
+    pub fn line_count(&self) -> usize {
+        self.vertices.len() / FLOATS_PER_VERTEX / 2
+    }
+
+    pub fn line_3d(&mut self, category: DebugCategory, a: Vector3, b: Vector3, color: Color) {
+        if !self.is_enabled(category) {
+            return;
+        }
+        for p in [a, b] {
+            self.vertices.extend_from_slice(&[p.x, p.y, p.z]);
+            self.vertices.extend_from_slice(&color);
+        }
+    }
+
+    pub fn line(&mut self, category: DebugCategory, a: Vector2, b: Vector2, color: Color) {
+        self.line_3d(category, vec3(a.x, a.y, 0.0), vec3(b.x, b.y, 0.0), color);
+    }
+
+    // 点を順に結んで閉じた線
+    pub fn polygon(&mut self, category: DebugCategory, points: &[Vector2], color: Color) {
+        for i in 0..points.len() {
+            self.line(category, points[i], points[(i + 1) % points.len()], color);
+        }
+    }
+
+    pub fn rect(&mut self, category: DebugCategory, min: Vector2, max: Vector2, color: Color) {
+        self.polygon(
+            category,
+            &[min, vec2(max.x, min.y), max, vec2(min.x, max.y)],
+            color,
+        );
+    }
+
+    pub fn circle(&mut self, category: DebugCategory, center: Vector2, radius: f32, color: Color) {
+        let segments = self.circle_segments.max(3);
+        let points: Vec<Vector2> = (0..segments)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f32 / segments as f32;
+                center + vec2(angle.cos(), angle.sin()) * radius
+            })
+            .collect();
+        self.polygon(category, &points, color);
+    }
+
+    // 矢じりの大きさは長さに合わせて決める
+    pub fn arrow(&mut self, category: DebugCategory, from: Vector2, to: Vector2, color: Color) {
+        self.line(category, from, to, color);
+        let direction = to - from;
+        let length = (direction.x * direction.x + direction.y * direction.y).sqrt();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let back = -direction / length * (length * 0.25).min(0.25);
+        let side = vec2(-back.y, back.x) * 0.5;
+        self.line(category, to, to + back + side, color);
+        self.line(category, to, to + back - side, color);
+    }
+
+    // 点の位置を示す十字
+    pub fn cross(&mut self, category: DebugCategory, center: Vector2, size: f32, color: Color) {
+        let half = size * 0.5;
+        self.line(
+            category,
+            center - vec2(half, 0.0),
+            center + vec2(half, 0.0),
+            color,
+        );
+        self.line(
+            category,
+            center - vec2(0.0, half),
+            center + vec2(0.0, half),
+            color,
+        );
+    }
+
+    pub fn text(&mut self, category: DebugCategory, position: Vector2, text: &str, color: Color) {
+        self.text_3d(category, vec3(position.x, position.y, 0.0), text, color);
+    }
+
+    pub fn text_3d(
+        &mut self,
+        category: DebugCategory,
+        position: Vector3,
+        text: &str,
+        color: Color,
+    ) {
+        if !self.is_enabled(category) {
+            return;
+        }
+        self.texts.push(DebugText {
+            position,
+            text: text.to_string(),
+            color,
+        });
+    }
+
+    // 剛体の形状と接触
+    // 動かない剛体は灰色、キネマティックは青、ダイナミックは緑、センサーは黄色で描く
+    pub fn draw_physics(&mut self, physics: &PhysicsWorld) {
+        if self.is_enabled(DebugCategory::Physics) {
+            for (_, body) in physics.bodies() {
+                let color = if body.collider().sensor {
+                    YELLOW
+                } else {
+                    match body.body_type {
+                        BodyType::Static => GRAY,
+                        BodyType::Kinematic => BLUE,
+                        BodyType::Dynamic => GREEN,
+                    }
+                };
+                match body.world_shape() {
+                    WorldShape::Circle { center, radius } => {
+                        self.circle(DebugCategory::Physics, center, radius, color);
+                        // 回転が分かるように半径を1本引く
+                        let (sin, cos) = body.rotation.sin_cos();
+                        self.line(
+                            DebugCategory::Physics,
+                            center,
+                            center + vec2(cos, sin) * radius,
+                            color,
+                        );
+                    }
+                    WorldShape::Polygon { vertices, .. } => {
+                        self.polygon(DebugCategory::Physics, &vertices, color);
+                    }
+                }
+            }
+        }
+        if self.is_enabled(DebugCategory::Contacts) {
+            for contact in physics.contacts() {
+                for point in &contact.manifold.points {
+                    self.cross(DebugCategory::Contacts, point.position, 0.1, RED);
+                    self.arrow(
+                        DebugCategory::Contacts,
+                        point.position,
+                        point.position + contact.manifold.normal * 0.3,
+                        RED,
+                    );
+                }
+            }
+        }
+    }
+
+    // 2Dカメラの映す範囲 (シアン)、追従のデッドゾーン (白)、移動範囲 (マゼンタ)
+    pub fn draw_camera(&mut self, camera: &Camera2D) {
+        if !self.is_enabled(DebugCategory::Camera) {
+            return;
+        }
+        let visible = camera.visible_rect();
+        self.rect(DebugCategory::Camera, visible.min, visible.max, CYAN);
+        self.cross(DebugCategory::Camera, visible.center(), 0.2, CYAN);
+        let dead_zone = Rect::new(
+            camera.position - camera.dead_zone,
+            camera.position + camera.dead_zone,
+        );
+        self.rect(DebugCategory::Camera, dead_zone.min, dead_zone.max, WHITE);
+        if let Some(bounds) = camera.bounds {
+            self.rect(DebugCategory::Camera, bounds.min, bounds.max, MAGENTA);
+        }
+    }
+
+    // 積まれた線を頂点バッファーに書き込み、レンダラーのキューに積む (GLコンテキストが必要)
+    // 線と文字はここで次のフレームの分に切り替わる
+    pub fn submit(&mut self, renderer: &mut Renderer) {
+        self.frame_texts = mem::take(&mut self.texts);
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let material = match self.material {
+            Some(material) => material,
+            None => {
+                let shader = renderer
+                    .materials
+                    .load_shader(DEBUG_VERTEX_SHADER, DEBUG_FRAGMENT_SHADER);
+                let mut material = Material::new("debug_draw", shader);
+                // 他のものに隠れないように、一番最後に深度を無視して描画する
+                material.render_state = RenderState {
+                    depth_test: false,
+                    depth_write: false,
+                    blend: true,
+                    culling: false,
+                    ..RenderState::default()
+                };
+                let id = renderer.materials.insert(material);
+                self.material = Some(id);
+                id
+            }
+        };
+
+        let size = (self.vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr;
+        let data = self.vertices.as_ptr() as *const c_void;
+        let vertex_num = (self.vertices.len() / FLOATS_PER_VERTEX) as i32;
+        // 前のフレームの描画が終わっていれば、同じバッファーを使い回す
+        let updated = match self.vertex.as_mut().and_then(Rc::get_mut) {
+            Some(vertex) => {
+                vertex.update(size, data, vertex_num);
+                true
+            }
+            None => false,
+        };
+        if !updated {
+            self.vertex = Some(Rc::new(
+                Vertex::new(
+                    size,
+                    data,
+                    gl::DYNAMIC_DRAW, // 毎フレーム書き換える
+                    vec![gl::FLOAT, gl::FLOAT],
+                    vec![3, 4],
+                    (FLOATS_PER_VERTEX * mem::size_of::<GLfloat>()) as GLsizei,
+                    vertex_num,
+                )
+                .with_mode(gl::LINES),
+            ));
+        }
+        self.vertices.clear();
+
+        renderer.submit(DrawCommand {
+            vertex: self.vertex.clone().unwrap(),
+            material,
+            model: Matrix4::identity(),
+            layer: i32::MAX,
+        });
+    }
+
+    // 直前のフレームの文字を、ワールド座標から画面上の位置に変換してimguiで描く
+    pub fn draw_text(&self, ui: &imgui::Ui, view_projection: &Matrix4<f32>) {
+        let display_size = ui.io().display_size;
+        let draw_list = ui.get_foreground_draw_list();
+        for text in &self.frame_texts {
+            let clip =
+                view_projection * vec4(text.position.x, text.position.y, text.position.z, 1.0);
+            if clip.w <= 0.0 {
+                continue; // カメラの後ろ
+            }
+            let ndc = vec2(clip.x / clip.w, clip.y / clip.w);
+            if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 {
+                continue;
+            }
+            let screen = [
+                (ndc.x + 1.0) * 0.5 * display_size[0],
+                (1.0 - ndc.y) * 0.5 * display_size[1],
+            ];
+            draw_list.add_text(screen, text.color, &text.text);
+        }
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        ui.checkbox(im_str!("Enabled"), &mut self.enabled);
+        for category in DebugCategory::ALL {
+            ui.checkbox(
+                &im_str!("{}", category.name()),
+                &mut self.categories[category as usize],
+            );
+        }
+    }
+}
+
+// 物理とカメラのデバッグ表示を加えてから、線をまとめてレンダラーに渡す (Render)
+pub fn debug_draw_render_system(world: &mut World) {
+    if !world.has_resource::<DebugDraw>() {
+        return;
+    }
+    let mut debug_draw = world.resource_mut::<DebugDraw>();
+    if world.has_resource::<PhysicsWorld>() {
+        debug_draw.draw_physics(&world.resource::<PhysicsWorld>());
+    }
+    if world.has_resource::<Camera2D>() {
+        debug_draw.draw_camera(&world.resource::<Camera2D>());
+    }
+    debug_draw.submit(&mut world.resource_mut::<Renderer>());
+}
@@ -0,0 +1,751 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+// エンティティは番号と世代の組で表す
+// 削除されたエンティティの番号は再利用されるが、世代が変わるので古いEntityは無効になる
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+#[allow(dead_code)]
+impl Entity {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+// コンポーネントの種類ごとの入れ物 (疎集合)
+// dense に詰めて並べることで、同じ種類のコンポーネントをまとめて高速に走査できる
+pub struct Storage<T> {
+    dense: Vec<T>,
+    entities: Vec<Entity>,      // dense[i] を持つエンティティ
+    sparse: Vec<Option<usize>>, // エンティティの番号 -> dense の添字
+}
+
+#[allow(dead_code)]
+impl<T> Storage<T> {
+    fn new() -> Storage<T> {
+        Storage {
+            dense: Vec::new(),
+            entities: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+
+    fn slot(&self, entity: Entity) -> Option<usize> {
+        let slot = (*self.sparse.get(entity.index as usize)?)?;
+        if self.entities[slot] == entity {
+            Some(slot)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        if let Some(slot) = self.slot(entity) {
+            return Some(std::mem::replace(&mut self.dense[slot], component));
+        }
+        let index = entity.index as usize;
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, None);
+        }
+        // 古い世代のコンポーネントが残っていれば取り除く
+        if self.sparse[index].is_some() {
+            let old = self.entities[self.sparse[index].unwrap()];
+            self.remove(old);
+        }
+        self.sparse[index] = Some(self.dense.len());
+        self.dense.push(component);
+        self.entities.push(entity);
+        None
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slot(entity)?;
+        // 末尾の要素を空いた場所へ移動して詰める
+        let last = *self.entities.last().unwrap();
+        self.sparse[last.index as usize] = Some(slot);
+        self.sparse[entity.index as usize] = None;
+        self.entities.swap_remove(slot);
+        Some(self.dense.swap_remove(slot))
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.slot(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.slot(entity).map(|slot| &self.dense[slot])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.slot(entity).map(move |slot| &mut self.dense[slot])
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().copied().zip(self.dense.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.entities.iter().copied().zip(self.dense.iter_mut())
+    }
+}
+
+// 型を消したストレージ (エンティティの削除時にすべての種類から取り除くため)
+trait AnyStorage {
+    fn remove_entity(&self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: 'static> AnyStorage for RefCell<Storage<T>> {
+    fn remove_entity(&self, entity: Entity) {
+        self.borrow_mut().remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    resources: HashMap<TypeId, RefCell<Box<dyn Any>>>,
+}
+
+#[allow(dead_code)]
+impl World {
+    pub fn new() -> World {
+        World {
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free: Vec::new(),
+            storages: HashMap::new(),
+            resources: HashMap::new(),
+        }
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                self.alive.push(false);
+                (self.generations.len() - 1) as u32
+            }
+        };
+        self.alive[index as usize] = true;
+        Entity {
+            index,
+            generation: self.generations[index as usize],
+        }
+    }
+
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        for storage in self.storages.values() {
+            storage.remove_entity(entity);
+        }
+        let index = entity.index as usize;
+        self.alive[index] = false;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free.push(entity.index);
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+        index < self.alive.len()
+            && self.alive[index]
+            && self.generations[index] == entity.generation
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive
+            .iter()
+            .enumerate()
+            .filter(|(_, alive)| **alive)
+            .map(move |(index, _)| Entity {
+                index: index as u32,
+                generation: self.generations[index],
+            })
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&RefCell<Storage<T>>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .map(|storage| storage.as_any().downcast_ref().unwrap())
+    }
+
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Option<T> {
+        assert!(self.is_alive(entity), "entity is not alive: {:?}", entity);
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(Storage::<T>::new())));
+        self.write::<T>().insert(entity, component)
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storage::<T>()?.borrow_mut().remove(entity)
+    }
+
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.storage::<T>()
+            .map(|storage| storage.borrow().contains(entity))
+            .unwrap_or(false)
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<Ref<'_, T>> {
+        let storage = self.storage::<T>()?.borrow();
+        Ref::filter_map(storage, |storage| storage.get(entity)).ok()
+    }
+
+    pub fn get_mut<T: 'static>(&self, entity: Entity) -> Option<RefMut<'_, T>> {
+        let storage = self.storage::<T>()?.borrow_mut();
+        RefMut::filter_map(storage, |storage| storage.get_mut(entity)).ok()
+    }
+
+    // ある種類のコンポーネントをまとめて借用する
+    // 種類が異なれば同時にいくつでも借用できる (同じ種類を読み書き同時に借用するとpanicする)
+    pub fn read<T: 'static>(&self) -> Ref<'_, Storage<T>> {
+        self.storage::<T>()
+            .unwrap_or_else(|| panic!("no component storage: {}", type_name::<T>()))
+            .borrow()
+    }
+
+    pub fn write<T: 'static>(&self) -> RefMut<'_, Storage<T>> {
+        self.storage::<T>()
+            .unwrap_or_else(|| panic!("no component storage: {}", type_name::<T>()))
+            .borrow_mut()
+    }
+
+    // まだ一度も追加されていない種類なら None を返す
+    pub fn try_read<T: 'static>(&self) -> Option<Ref<'_, Storage<T>>> {
+        self.storage::<T>().map(|storage| storage.borrow())
+    }
+
+    pub fn try_write<T: 'static>(&self) -> Option<RefMut<'_, Storage<T>>> {
+        self.storage::<T>().map(|storage| storage.borrow_mut())
+    }
+
+    // 指定したコンポーネントをすべて持つエンティティを走査する
+    // 例: world.query::<(Write<Transform2D>, Read<Velocity>)>().for_each(|entity, (transform, velocity)| ...)
+    pub fn query<Q: QueryParam>(&self) -> Query<'_, Q> {
+        let borrow = Q::borrow(self);
+        let entities = match Q::candidates(&borrow) {
+            Some(candidates) => candidates
+                .iter()
+                .copied()
+                .filter(|&entity| Q::contains(&borrow, entity))
+                .collect(),
+            None => Vec::new(),
+        };
+        Query { borrow, entities }
+    }
+
+    // リソース: エンティティに属さない、ワールドに1つだけのデータ (時間、レンダラー、入力など)
+    pub fn insert_resource<R: 'static>(&mut self, resource: R) {
+        self.resources
+            .insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
+    }
+
+    pub fn remove_resource<R: 'static>(&mut self) -> Option<R> {
+        self.resources
+            .remove(&TypeId::of::<R>())
+            .map(|resource| *resource.into_inner().downcast().unwrap())
+    }
+
+    pub fn has_resource<R: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+
+    pub fn resource<R: 'static>(&self) -> Ref<'_, R> {
+        let resource = self
+            .resources
+            .get(&TypeId::of::<R>())
+            .unwrap_or_else(|| panic!("no resource: {}", type_name::<R>()));
+        Ref::map(resource.borrow(), |r| r.downcast_ref().unwrap())
+    }
+
+    pub fn resource_mut<R: 'static>(&self) -> RefMut<'_, R> {
+        let resource = self
+            .resources
+            .get(&TypeId::of::<R>())
+            .unwrap_or_else(|| panic!("no resource: {}", type_name::<R>()));
+        RefMut::map(resource.borrow_mut(), |r| r.downcast_mut().unwrap())
+    }
+}
+
+// クエリで読み取り専用に借用するコンポーネント
+pub struct Read<T>(PhantomData<T>);
+// クエリで書き換えるために借用するコンポーネント
+pub struct Write<T>(PhantomData<T>);
+
+pub trait QueryParam {
+    type Borrow<'w>;
+    type Item<'b>;
+
+    fn borrow(world: &World) -> Self::Borrow<'_>;
+    // 走査の候補となるエンティティ (組の場合はもっとも数の少ないもの)
+    fn candidates<'a>(borrow: &'a Self::Borrow<'_>) -> Option<&'a [Entity]>;
+    fn contains(borrow: &Self::Borrow<'_>, entity: Entity) -> bool;
+    fn fetch<'b>(borrow: &'b mut Self::Borrow<'_>, entity: Entity) -> Option<Self::Item<'b>>;
+}
+
+impl<T: 'static> QueryParam for Read<T> {
+    type Borrow<'w> = Option<Ref<'w, Storage<T>>>;
+    type Item<'b> = &'b T;
+
+    fn borrow(world: &World) -> Self::Borrow<'_> {
+        world.storage::<T>().map(|storage| storage.borrow())
+    }
+
+    fn candidates<'a>(borrow: &'a Self::Borrow<'_>) -> Option<&'a [Entity]> {
+        borrow.as_ref().map(|storage| storage.entities())
+    }
+
+    fn contains(borrow: &Self::Borrow<'_>, entity: Entity) -> bool {
+        borrow
+            .as_ref()
+            .map(|storage| storage.contains(entity))
+            .unwrap_or(false)
+    }
+
+    fn fetch<'b>(borrow: &'b mut Self::Borrow<'_>, entity: Entity) -> Option<Self::Item<'b>> {
+        borrow.as_ref()?.get(entity)
+    }
+}
+
+impl<T: 'static> QueryParam for Write<T> {
+    type Borrow<'w> = Option<RefMut<'w, Storage<T>>>;
+    type Item<'b> = &'b mut T;
+
+    fn borrow(world: &World) -> Self::Borrow<'_> {
+        world.storage::<T>().map(|storage| storage.borrow_mut())
+    }
+
+    fn candidates<'a>(borrow: &'a Self::Borrow<'_>) -> Option<&'a [Entity]> {
+        borrow.as_ref().map(|storage| storage.entities())
+    }
+
+    fn contains(borrow: &Self::Borrow<'_>, entity: Entity) -> bool {
+        borrow
+            .as_ref()
+            .map(|storage| storage.contains(entity))
+            .unwrap_or(false)
+    }
+
+    fn fetch<'b>(borrow: &'b mut Self::Borrow<'_>, entity: Entity) -> Option<Self::Item<'b>> {
+        borrow.as_mut()?.get_mut(entity)
+    }
+}
+
+macro_rules! impl_query_param_for_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: QueryParam),+> QueryParam for ($($name,)+) {
+            type Borrow<'w> = ($($name::Borrow<'w>,)+);
+            type Item<'b> = ($($name::Item<'b>,)+);
+
+            fn borrow(world: &World) -> Self::Borrow<'_> {
+                ($($name::borrow(world),)+)
+            }
+
+            fn candidates<'a>(borrow: &'a Self::Borrow<'_>) -> Option<&'a [Entity]> {
+                let mut smallest: Option<&[Entity]> = None;
+                $(
+                    let candidates = $name::candidates(&borrow.$index)?;
+                    if smallest.map(|s| candidates.len() < s.len()).unwrap_or(true) {
+                        smallest = Some(candidates);
+                    }
+                )+
+                smallest
+            }
+
+            fn contains(borrow: &Self::Borrow<'_>, entity: Entity) -> bool {
+                $($name::contains(&borrow.$index, entity))&&+
+            }
+
+            fn fetch<'b>(borrow: &'b mut Self::Borrow<'_>, entity: Entity) -> Option<Self::Item<'b>> {
+                Some(($($name::fetch(&mut borrow.$index, entity)?,)+))
+            }
+        }
+    };
+}
+
+impl_query_param_for_tuple!(A 0);
+impl_query_param_for_tuple!(A 0, B 1);
+impl_query_param_for_tuple!(A 0, B 1, C 2);
+impl_query_param_for_tuple!(A 0, B 1, C 2, D 3);
+impl_query_param_for_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_query_param_for_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+pub struct Query<'w, Q: QueryParam> {
+    borrow: Q::Borrow<'w>,
+    entities: Vec<Entity>,
+}
+
+#[allow(dead_code)]
+impl<'w, Q: QueryParam> Query<'w, Q> {
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn get(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        Q::fetch(&mut self.borrow, entity)
+    }
+
+    pub fn for_each<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Entity, Q::Item<'_>),
+    {
+        for &entity in &self.entities {
+            if let Some(item) = Q::fetch(&mut self.borrow, entity) {
+                f(entity, item);
+            }
+        }
+    }
+}
+
+// 時間に関するリソース (Scheduleが毎フレーム更新する)
+#[derive(Clone, Copy, Debug)]
+pub struct Time {
+    pub delta_time: f32,       // 前のフレームからの経過時間 (秒)
+    pub fixed_delta_time: f32, // FixedUpdateの1ステップの時間 (秒)
+    pub elapsed: f32,          // 開始からの経過時間 (秒)
+    pub alpha: f32, // 最後のFixedUpdateから次のFixedUpdateまでの進み具合 (0.0 ~ 1.0、描画の補間に使う)
+    pub frame: u64,
+}
+
+// システム間でイベントを受け渡すためのキュー (リソースとして登録して使う)
+// 送る側が毎回 clear してから send するので、読む側は送る側と同じステージで後に登録すること
+pub struct Events<T> {
+    events: Vec<T>,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Events<T> {
+        Events { events: Vec::new() }
+    }
+}
+
+#[allow(dead_code)]
+impl<T> Events<T> {
+    pub fn new() -> Events<T> {
+        Events::default()
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.push(event);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.events.iter()
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.events.drain(..)
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    PreUpdate,   // 入力の反映など
+    FixedUpdate, // 物理演算など、一定の時間間隔で実行したい処理 (1フレームに0回以上)
+    Update,      // ゲームのロジック
+    PostUpdate,  // 変換行列の計算など、Updateの結果を整える処理
+    Render,      // 描画コマンドの発行
+}
+
+const STAGES: [Stage; 5] = [
+    Stage::PreUpdate,
+    Stage::FixedUpdate,
+    Stage::Update,
+    Stage::PostUpdate,
+    Stage::Render,
+];
+
+pub type System = Box<dyn FnMut(&mut World)>;
+
+// システムをステージごとに登録した順番で実行する
+pub struct Schedule {
+    systems: HashMap<Stage, Vec<System>>,
+    fixed_delta_time: f32,
+    max_fixed_steps: u32, // 処理落ちしたときに追いつこうとしてさらに遅くなるのを防ぐ
+    accumulator: f32,
+}
+
+#[allow(dead_code)]
+impl Schedule {
+    pub fn new(fixed_delta_time: f32) -> Schedule {
+        Schedule {
+            systems: HashMap::new(),
+            fixed_delta_time,
+            max_fixed_steps: 5,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn add_system<F>(&mut self, stage: Stage, system: F) -> &mut Schedule
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.systems
+            .entry(stage)
+            .or_default()
+            .push(Box::new(system));
+        self
+    }
+
+    pub fn fixed_delta_time(&self) -> f32 {
+        self.fixed_delta_time
+    }
+
+    fn run_stage(&mut self, stage: Stage, world: &mut World) {
+        if let Some(systems) = self.systems.get_mut(&stage) {
+            for system in systems.iter_mut() {
+                system(world);
+            }
+        }
+    }
+
+    // Render以外のステージを実行する
+    pub fn run_update(&mut self, world: &mut World, delta_time: f32) {
+        if !world.has_resource::<Time>() {
+            world.insert_resource(Time {
+                delta_time: 0.0,
+                fixed_delta_time: self.fixed_delta_time,
+                elapsed: 0.0,
+                alpha: 0.0,
+                frame: 0,
+            });
+        }
+        {
+            let mut time = world.resource_mut::<Time>();
+            time.delta_time = delta_time;
+            time.fixed_delta_time = self.fixed_delta_time;
+            time.elapsed += delta_time;
+            time.frame += 1;
+        }
+
+        for &stage in STAGES.iter() {
+            match stage {
+                Stage::FixedUpdate => {
+                    let max_time = self.fixed_delta_time * self.max_fixed_steps as f32;
+                    self.accumulator = (self.accumulator + delta_time).min(max_time);
+                    while self.accumulator >= self.fixed_delta_time {
+                        self.run_stage(Stage::FixedUpdate, world);
+                        self.accumulator -= self.fixed_delta_time;
+                    }
+                    world.resource_mut::<Time>().alpha = self.accumulator / self.fixed_delta_time;
+                }
+                Stage::Render => {}
+                _ => self.run_stage(stage, world),
+            }
+        }
+    }
+
+    pub fn run_render(&mut self, world: &mut World) {
+        self.run_stage(Stage::Render, world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[test]
+    fn despawned_indices_are_reused_with_a_new_generation() {
+        let mut world = World::new();
+        let a = world.spawn();
+        let b = world.spawn();
+        assert_eq!((a.index(), a.generation()), (0, 0));
+        assert_eq!((b.index(), b.generation()), (1, 0));
+
+        assert!(world.despawn(a));
+        assert!(!world.despawn(a));
+        let c = world.spawn();
+        assert_eq!((c.index(), c.generation()), (0, 1));
+        assert!(!world.is_alive(a));
+        assert!(world.is_alive(c));
+        assert_eq!(world.entities().collect::<Vec<_>>(), vec![c, b]);
+    }
+
+    #[test]
+    fn stale_entities_do_not_see_new_components() {
+        let mut world = World::new();
+        let a = world.spawn();
+        world.insert(a, Position(1));
+        world.despawn(a);
+        assert!(world.get::<Position>(a).is_none());
+
+        // 同じ番号を再利用したエンティティのコンポーネントは古いEntityからは見えない
+        let b = world.spawn();
+        assert_eq!(a.index(), b.index());
+        world.insert(b, Position(2));
+        assert!(world.get::<Position>(a).is_none());
+        assert!(world.get_mut::<Position>(a).is_none());
+        assert!(!world.has::<Position>(a));
+        assert_eq!(world.remove::<Position>(a), None);
+        assert_eq!(*world.get::<Position>(b).unwrap(), Position(2));
+    }
+
+    #[test]
+    fn storage_swap_remove_keeps_the_index_consistent() {
+        let mut world = World::new();
+        let entities: Vec<_> = (0..4).map(|_| world.spawn()).collect();
+        for (i, &entity) in entities.iter().enumerate() {
+            world.insert(entity, Position(i as i32));
+        }
+        // 先頭を取り除くと末尾の要素が先頭へ移動する
+        assert_eq!(world.remove::<Position>(entities[0]), Some(Position(0)));
+        assert_eq!(world.remove::<Position>(entities[0]), None);
+        {
+            let storage = world.read::<Position>();
+            assert_eq!(storage.len(), 3);
+            assert_eq!(storage.entities(), &[entities[3], entities[1], entities[2]]);
+            for &entity in &entities[1..] {
+                assert_eq!(storage.get(entity), Some(&Position(entity.index() as i32)));
+            }
+        }
+        // 末尾の要素自身を取り除く場合
+        assert_eq!(world.remove::<Position>(entities[2]), Some(Position(2)));
+        assert_eq!(world.insert(entities[3], Position(30)), Some(Position(3)));
+        world.insert(entities[0], Position(0));
+        let storage = world.read::<Position>();
+        assert_eq!(
+            storage.iter().collect::<Vec<_>>(),
+            vec![
+                (entities[3], &Position(30)),
+                (entities[1], &Position(1)),
+                (entities[0], &Position(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn query_tuples_visit_entities_with_every_component() {
+        let mut world = World::new();
+        let a = world.spawn();
+        let b = world.spawn();
+        let c = world.spawn();
+        world.insert(a, Position(0));
+        world.insert(a, Velocity(1));
+        world.insert(b, Position(10));
+        world.insert(c, Position(20));
+        world.insert(c, Velocity(2));
+        world.insert(c, Name("c"));
+
+        world
+            .query::<(Write<Position>, Read<Velocity>)>()
+            .for_each(|_, (position, velocity)| position.0 += velocity.0);
+        assert_eq!(*world.get::<Position>(a).unwrap(), Position(1));
+        assert_eq!(*world.get::<Position>(b).unwrap(), Position(10));
+        assert_eq!(*world.get::<Position>(c).unwrap(), Position(22));
+
+        let mut query = world.query::<(Read<Name>, Read<Position>, Read<Velocity>)>();
+        assert_eq!(query.entities(), &[c]);
+        assert!(query.get(a).is_none());
+        let (name, _, _) = query.get(c).unwrap();
+        assert_eq!(name.0, "c");
+        drop(query);
+
+        // 一度も追加されていない種類を含むクエリは何も返さない
+        assert!(world.query::<(Read<Position>, Read<u8>)>().is_empty());
+        world.despawn(c);
+        assert_eq!(world.query::<Read<Name>>().len(), 0);
+    }
+
+    #[test]
+    fn fixed_update_steps_are_capped() {
+        let mut world = World::new();
+        let mut schedule = Schedule::new(0.25);
+        let steps = Rc::new(RefCell::new(0));
+        let counter = steps.clone();
+        schedule.add_system(Stage::FixedUpdate, move |_| *counter.borrow_mut() += 1);
+
+        schedule.run_update(&mut world, 0.125);
+        assert_eq!(*steps.borrow(), 0);
+        assert_eq!(world.resource::<Time>().alpha, 0.5);
+        schedule.run_update(&mut world, 0.5);
+        assert_eq!(*steps.borrow(), 2);
+        assert_eq!(world.resource::<Time>().alpha, 0.5);
+
+        // 処理落ちしても1フレームに max_fixed_steps 回までしか実行せず、残りは捨てる
+        schedule.run_update(&mut world, 10.0);
+        assert_eq!(*steps.borrow(), 7);
+        assert_eq!(world.resource::<Time>().alpha, 0.0);
+        schedule.run_update(&mut world, 0.25);
+        assert_eq!(*steps.borrow(), 8);
+
+        let time = *world.resource::<Time>();
+        assert_eq!(time.frame, 4);
+        assert_eq!(time.elapsed, 10.875);
+        assert_eq!(time.delta_time, 0.25);
+    }
+
+    #[test]
+    fn stages_run_in_order() {
+        let mut world = World::new();
+        let mut schedule = Schedule::new(0.5);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for &stage in STAGES.iter().rev() {
+            let log = log.clone();
+            schedule.add_system(stage, move |_| log.borrow_mut().push(stage));
+        }
+        schedule.run_update(&mut world, 0.5);
+        schedule.run_render(&mut world);
+        assert_eq!(*log.borrow(), STAGES.to_vec());
+    }
+}
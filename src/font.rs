@@ -0,0 +1,14 @@
+mod atlas;
+mod bmfont;
+mod face;
+mod layout;
+mod render;
+
+#[allow(unused_imports)]
+pub use atlas::{AtlasPage, ATLAS_PAGE_SIZE};
+#[allow(unused_imports)]
+pub use face::{Font, Glyph};
+#[allow(unused_imports)]
+pub use layout::{layout_text, GlyphQuad, TextAlign, TextLayout, TextStyle};
+#[allow(unused_imports)]
+pub use render::{text_render_system, FontId, Fonts, Text, TextSpace};
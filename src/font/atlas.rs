@@ -0,0 +1,152 @@
+use cgmath::vec2;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// TTFから作るアトラスの1ページの大きさ (足りなくなったらページを増やす)
+pub const ATLAS_PAGE_SIZE: u32 = 512;
+// 隣のグリフの色がにじまないように空ける隙間
+const PADDING: u32 = 1;
+
+// グリフを並べた画像 (RGBA8、GPUへの転送前のCPU側のデータ)
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub(super) dirty: bool, // GPUへの転送が必要
+}
+
+#[allow(dead_code)]
+impl AtlasPage {
+    pub fn new(width: u32, height: u32) -> AtlasPage {
+        AtlasPage {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+            dirty: true,
+        }
+    }
+
+    // BMFontの画像 (アルファチャンネルがない画像は明るさを不透明度として扱う)
+    pub fn from_file(path: &str) -> Result<AtlasPage, String> {
+        let image =
+            image::open(path).map_err(|e| format!("failed to load image: {}: {}", path, e))?;
+        Ok(AtlasPage::from_image(image))
+    }
+
+    // ファイルの中身から作る (name はエラーメッセージに使う)
+    pub fn from_bytes(bytes: &[u8], name: &str) -> Result<AtlasPage, String> {
+        let image = image::load_from_memory(bytes)
+            .map_err(|e| format!("failed to load image: {}: {}", name, e))?;
+        Ok(AtlasPage::from_image(image))
+    }
+
+    fn from_image(image: image::DynamicImage) -> AtlasPage {
+        let has_alpha = image.color().has_alpha();
+        let mut rgba = image.to_rgba8();
+        if !has_alpha {
+            for pixel in rgba.pixels_mut() {
+                let luma = ((pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3) as u8;
+                *pixel = image::Rgba([255, 255, 255, luma]);
+            }
+        }
+        let (width, height) = rgba.dimensions();
+        AtlasPage {
+            width,
+            height,
+            pixels: rgba.into_raw(),
+            dirty: true,
+        }
+    }
+
+    // 白色で、グリフの覆う割合を不透明度として書き込む
+    fn blit_coverage(&mut self, x: u32, y: u32, width: u32, height: u32, coverage: &[u8]) {
+        for row in 0..height {
+            for column in 0..width {
+                let alpha = coverage[(row * width + column) as usize];
+                let index = (((y + row) * self.width + x + column) * 4) as usize;
+                self.pixels[index..index + 4].copy_from_slice(&[255, 255, 255, alpha]);
+            }
+        }
+        self.dirty = true;
+    }
+}
+
+struct Shelf {
+    page: usize,
+    y: u32,
+    height: u32,
+    x: u32, // 次に置ける位置
+}
+
+// 高さの近いグリフを横一列 (棚) に並べていく単純な詰め込み
+pub(super) struct ShelfPacker {
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    pub(super) fn new() -> ShelfPacker {
+        ShelfPacker {
+            shelves: Vec::new(),
+        }
+    }
+
+    // グリフの画像をページに書き込み、ページの番号とテクスチャ座標を返す
+    pub(super) fn insert(
+        &mut self,
+        pages: &mut Vec<AtlasPage>,
+        width: u32,
+        height: u32,
+        coverage: &[u8],
+    ) -> Result<(usize, Vector2, Vector2), String> {
+        let (page, x, y) = self.allocate(pages, width, height)?;
+        let atlas = &mut pages[page];
+        atlas.blit_coverage(x, y, width, height, coverage);
+        let size = vec2(atlas.width as f32, atlas.height as f32);
+        Ok((
+            page,
+            vec2(x as f32 / size.x, y as f32 / size.y),
+            vec2((x + width) as f32 / size.x, (y + height) as f32 / size.y),
+        ))
+    }
+
+    fn allocate(
+        &mut self,
+        pages: &mut Vec<AtlasPage>,
+        width: u32,
+        height: u32,
+    ) -> Result<(usize, u32, u32), String> {
+        if width + PADDING * 2 > ATLAS_PAGE_SIZE || height + PADDING * 2 > ATLAS_PAGE_SIZE {
+            return Err(format!("glyph is too large: {}x{}", width, height));
+        }
+
+        // 高さが合い、右側に空きのある棚 (高すぎる棚に小さなグリフを置くと無駄が多いので避ける)
+        if let Some(shelf) = self.shelves.iter_mut().find(|shelf| {
+            shelf.height >= height
+                && shelf.height <= height * 2 + PADDING
+                && shelf.x + width + PADDING <= ATLAS_PAGE_SIZE
+        }) {
+            let x = shelf.x;
+            shelf.x += width + PADDING;
+            return Ok((shelf.page, x, shelf.y));
+        }
+
+        // 最後のページの下に新しい棚を作る。入らなければページを増やす
+        let (mut page, mut y) = match self.shelves.last() {
+            Some(last) => (last.page, last.y + last.height + PADDING),
+            None => (pages.len(), PADDING),
+        };
+        if page >= pages.len() || y + height + PADDING > ATLAS_PAGE_SIZE {
+            pages.push(AtlasPage::new(ATLAS_PAGE_SIZE, ATLAS_PAGE_SIZE));
+            page = pages.len() - 1;
+            y = PADDING;
+        }
+        self.shelves.push(Shelf {
+            page,
+            y,
+            height,
+            x: PADDING + width + PADDING,
+        });
+        Ok((page, PADDING, y))
+    }
+}
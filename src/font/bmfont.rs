@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use cgmath::vec2;
+use roxmltree::Document;
+
+use super::atlas::AtlasPage;
+use super::face::Font;
+
+#[allow(dead_code)]
+impl Font {
+    pub fn from_bmfont(path: &str) -> Result<Font, String> {
+        load(path, &|path| {
+            fs::read(path).map_err(|e| format!("failed to open file: {}: {}", path, e))
+        })
+    }
+
+    // .fnt とページの画像を read で読む (Assets::read を渡せばアーカイブからも読める)
+    pub fn from_bmfont_with(
+        path: &str,
+        read: &dyn Fn(&str) -> Result<Vec<u8>, String>,
+    ) -> Result<Font, String> {
+        load(path, read)
+    }
+}
+
+// BMFont (AngelCode) 形式のフォント (.fnt) を読み込む
+// テキスト形式とXML形式に対応する (バイナリ形式は扱わない)
+pub fn load(path: &str, read: &dyn Fn(&str) -> Result<Vec<u8>, String>) -> Result<Font, String> {
+    let source = read(path)?;
+    if source.starts_with(b"BMF") {
+        return Err(format!("{}: binary BMFont files are not supported", path));
+    }
+    let source = String::from_utf8(source).map_err(|e| format!("{}: {}", path, e))?;
+    let tags = if source.trim_start().starts_with('<') {
+        parse_xml(&source)
+    } else {
+        Ok(parse_text(&source))
+    }
+    .map_err(|e| format!("{}: {}", path, e))?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    build(&tags, dir, read).map_err(|e| format!("{}: {}", path, e))
+}
+
+// どちらの形式も「タグ名と属性の組」の並びにしてから処理する
+type Tag = (String, HashMap<String, String>);
+
+// 例: char id=65 x=0 y=0 width=10 ... / page id=0 file="font_0.png"
+fn parse_text(source: &str) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    for line in source.lines() {
+        let mut rest = line.trim();
+        let name_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let name = rest[..name_end].to_string();
+        rest = rest[name_end..].trim_start();
+        let mut attributes = HashMap::new();
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq].trim().to_string();
+            rest = &rest[eq + 1..];
+            let value = if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                rest = quoted.get(end + 1..).unwrap_or("");
+                quoted[..end].to_string()
+            } else {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                let value = rest[..end].to_string();
+                rest = &rest[end..];
+                value
+            };
+            attributes.insert(key, value);
+            rest = rest.trim_start();
+        }
+        if !name.is_empty() {
+            tags.push((name, attributes));
+        }
+    }
+    tags
+}
+
+fn parse_xml(source: &str) -> Result<Vec<Tag>, String> {
+    let document = Document::parse(source).map_err(|e| e.to_string())?;
+    Ok(document
+        .descendants()
+        .filter(|node| node.is_element())
+        .map(|node| {
+            let attributes = node
+                .attributes()
+                .map(|a| (a.name().to_string(), a.value().to_string()))
+                .collect();
+            (node.tag_name().name().to_string(), attributes)
+        })
+        .collect())
+}
+
+fn number(attributes: &HashMap<String, String>, key: &str) -> Result<f32, String> {
+    match attributes.get(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("invalid value {}={}", key, value)),
+        None => Ok(0.0),
+    }
+}
+
+fn build(
+    tags: &[Tag],
+    dir: &Path,
+    read: &dyn Fn(&str) -> Result<Vec<u8>, String>,
+) -> Result<Font, String> {
+    let attributes_of = |name: &str| tags.iter().find(|(tag, _)| tag == name).map(|(_, a)| a);
+    let info = attributes_of("info").ok_or("missing <info>")?;
+    let common = attributes_of("common").ok_or("missing <common>")?;
+    let size = number(info, "size")?.abs(); // 負の値は「文字の高さで合わせた大きさ」を表す
+    let line_height = number(common, "lineHeight")?;
+    let base = number(common, "base")?;
+
+    // ページ番号の順に画像を読み込む
+    let mut page_files: Vec<(u32, String)> = tags
+        .iter()
+        .filter(|(tag, _)| tag == "page")
+        .map(|(_, a)| {
+            let id = number(a, "id")? as u32;
+            let file = a.get("file").ok_or("page has no file")?;
+            Ok((id, dir.join(file).to_string_lossy().replace('\\', "/")))
+        })
+        .collect::<Result<_, String>>()?;
+    page_files.sort();
+    let pages = page_files
+        .iter()
+        .map(|(_, file)| AtlasPage::from_bytes(&read(file)?, file))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut glyphs = HashMap::new();
+    for (_, a) in tags.iter().filter(|(tag, _)| tag == "char") {
+        let id = number(a, "id")?;
+        let c = match char::from_u32(id as u32) {
+            Some(c) if id >= 0.0 => c,
+            _ => continue, // -1 (フォントにない文字の代わりに使うグリフ) など
+        };
+        let page = number(a, "page")? as usize;
+        let atlas = pages
+            .get(page)
+            .ok_or_else(|| format!("char {} refers to missing page {}", id, page))?;
+        let position = vec2(number(a, "x")?, number(a, "y")?);
+        let size = vec2(number(a, "width")?, number(a, "height")?);
+        let atlas_size = vec2(atlas.width as f32, atlas.height as f32);
+        glyphs.insert(
+            c,
+            Font::bitmap_glyph(
+                page,
+                vec2(position.x / atlas_size.x, position.y / atlas_size.y),
+                vec2(
+                    (position.x + size.x) / atlas_size.x,
+                    (position.y + size.y) / atlas_size.y,
+                ),
+                // yoffset は行の上端から、ベースラインは上端から base の位置
+                vec2(number(a, "xoffset")?, number(a, "yoffset")? - base),
+                size,
+                number(a, "xadvance")?,
+            ),
+        );
+    }
+
+    let mut kerning = HashMap::new();
+    for (_, a) in tags.iter().filter(|(tag, _)| tag == "kerning") {
+        let first = char::from_u32(number(a, "first")? as u32);
+        let second = char::from_u32(number(a, "second")? as u32);
+        if let (Some(first), Some(second)) = (first, second) {
+            kerning.insert((first, second), number(a, "amount")?);
+        }
+    }
+
+    Ok(Font::from_bitmap(
+        size,
+        line_height,
+        base,
+        pages,
+        glyphs,
+        kerning,
+    ))
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fs;
+
+use cgmath::vec2;
+use fontdue::FontSettings;
+
+use super::atlas::{AtlasPage, ShelfPacker};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// アトラス上のグリフ1つ (単位はピクセル)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    pub page: usize,
+    pub uv_min: Vector2, // 画像の左上
+    pub uv_max: Vector2, // 画像の右下
+    pub offset: Vector2, // ベースライン上のペンの位置から画像の左上まで (y軸は下向き)
+    pub size: Vector2,
+    pub advance: f32, // 次の文字までの距離
+    source: usize,    // 何番目のTTFから作ったか (カーニングは同じフォント同士でだけ行う)
+    index: u16,       // TTF内のグリフ番号
+}
+
+// ゲーム内で文字を描くためのフォント
+// TTF/OTFは使う文字だけを必要になったときにアトラスへ書き込むので、日本語のように文字数が多くても扱える
+// 複数のTTFを指定すると、前のフォントにない文字を後ろのフォントから探す (欧文用 + 日本語用など)
+// BMFontはあらかじめ作られた画像とグリフ情報をそのまま使う
+pub struct Font {
+    pub size: f32,        // ピクセル単位の文字の大きさ
+    pub line_height: f32, // 行の間隔
+    pub ascent: f32,      // 行の上端からベースラインまで
+    pub(super) pages: Vec<AtlasPage>,
+    glyphs: HashMap<char, Option<Glyph>>, // None: どのフォントにもない文字
+    kerning: HashMap<(char, char), f32>,  // BMFontのカーニング
+    sources: Vec<fontdue::Font>,
+    packer: ShelfPacker,
+}
+
+#[allow(dead_code)]
+impl Font {
+    pub fn from_files(paths: &[&str], size: f32) -> Result<Font, String> {
+        let data = paths
+            .iter()
+            .map(|path| fs::read(path).map_err(|e| format!("failed to open file: {}: {}", path, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Font::from_bytes(data, size)
+    }
+
+    pub fn from_bytes(data: Vec<Vec<u8>>, size: f32) -> Result<Font, String> {
+        if data.is_empty() {
+            return Err("no font data".to_string());
+        }
+        let settings = FontSettings {
+            scale: size, // この大きさで使うときに最もきれいになるように読み込む
+            ..FontSettings::default()
+        };
+        let sources = data
+            .into_iter()
+            .map(|bytes| fontdue::Font::from_bytes(bytes, settings))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("failed to parse font: {}", e))?;
+        let metrics = sources[0]
+            .horizontal_line_metrics(size)
+            .ok_or("font has no horizontal metrics")?;
+        Ok(Font {
+            size,
+            line_height: metrics.new_line_size.ceil(),
+            ascent: metrics.ascent.ceil(),
+            pages: Vec::new(),
+            glyphs: HashMap::new(),
+            kerning: HashMap::new(),
+            sources,
+            packer: ShelfPacker::new(),
+        })
+    }
+
+    // BMFontの読み込み結果から作る
+    pub(super) fn from_bitmap(
+        size: f32,
+        line_height: f32,
+        ascent: f32,
+        pages: Vec<AtlasPage>,
+        glyphs: HashMap<char, Glyph>,
+        kerning: HashMap<(char, char), f32>,
+    ) -> Font {
+        Font {
+            size,
+            line_height,
+            ascent,
+            pages,
+            glyphs: glyphs.into_iter().map(|(c, g)| (c, Some(g))).collect(),
+            kerning,
+            sources: Vec::new(),
+            packer: ShelfPacker::new(),
+        }
+    }
+
+    pub(super) fn bitmap_glyph(
+        page: usize,
+        uv_min: Vector2,
+        uv_max: Vector2,
+        offset: Vector2,
+        size: Vector2,
+        advance: f32,
+    ) -> Glyph {
+        Glyph {
+            page,
+            uv_min,
+            uv_max,
+            offset,
+            size,
+            advance,
+            source: 0,
+            index: 0,
+        }
+    }
+
+    pub fn pages(&self) -> &[AtlasPage] {
+        &self.pages
+    }
+
+    // 文字のグリフ (TTFならまだアトラスにない文字をここで書き込む)
+    pub fn glyph(&mut self, c: char) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return *glyph;
+        }
+        let glyph = self.rasterize(c).unwrap_or_else(|e| {
+            eprintln!("failed to rasterize glyph {:?}: {}", c, e);
+            None
+        });
+        self.glyphs.insert(c, glyph);
+        glyph
+    }
+
+    // フォントにない文字は代替文字 (U+FFFD、なければ '?') で表示する
+    pub fn glyph_or_fallback(&mut self, c: char) -> Option<Glyph> {
+        self.glyph(c)
+            .or_else(|| self.glyph('\u{FFFD}'))
+            .or_else(|| self.glyph('?'))
+    }
+
+    pub fn has_glyph(&mut self, c: char) -> bool {
+        self.glyph(c).is_some()
+    }
+
+    // 画面に出す前に、使う文字をまとめてアトラスに書き込んでおく
+    pub fn preload(&mut self, text: &str) {
+        for c in text.chars() {
+            self.glyph(c);
+        }
+    }
+
+    // 2つの文字の間隔の調整 (どちらもglyph()で一度取得してある必要がある)
+    pub fn kerning(&self, left: char, right: char) -> f32 {
+        if let Some(&amount) = self.kerning.get(&(left, right)) {
+            return amount;
+        }
+        match (self.glyphs.get(&left), self.glyphs.get(&right)) {
+            (Some(Some(l)), Some(Some(r))) if l.source == r.source && !self.sources.is_empty() => {
+                self.sources[l.source]
+                    .horizontal_kern_indexed(l.index, r.index, self.size)
+                    .unwrap_or(0.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn rasterize(&mut self, c: char) -> Result<Option<Glyph>, String> {
+        let found = self.sources.iter().enumerate().find_map(|(source, font)| {
+            match font.lookup_glyph_index(c) {
+                0 => None, // 0番は「グリフがない」を表す
+                index => Some((source, index)),
+            }
+        });
+        let (source, index) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let (metrics, coverage) = self.sources[source].rasterize_indexed(index, self.size);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+
+        // 空白のように見た目のない文字はアトラスに置かない
+        let (page, uv_min, uv_max) = if width == 0 || height == 0 {
+            (0, vec2(0.0, 0.0), vec2(0.0, 0.0))
+        } else {
+            self.packer
+                .insert(&mut self.pages, width, height, &coverage)?
+        };
+        Ok(Some(Glyph {
+            page,
+            uv_min,
+            uv_max,
+            // fontdueの ymin はベースラインから画像の下端まで (上向き)
+            offset: vec2(metrics.xmin as f32, -(metrics.ymin as f32 + height as f32)),
+            size: vec2(width as f32, height as f32),
+            advance: metrics.advance_width,
+            source,
+            index,
+        }))
+    }
+}
@@ -0,0 +1,361 @@
+use cgmath::vec2;
+
+use super::face::{Font, Glyph};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 行頭に来てはいけない文字 (句読点、閉じ括弧、小書きの仮名など)
+const NO_LINE_START: &str = "、。，．・：；？！ー―‐〜…‥）］｝」』】〕〉》〙〗’”ゝゞヽヾ々ぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶ,.:;!?)]}";
+// 行末に来てはいけない文字 (開き括弧)
+const NO_LINE_END: &str = "（［｛「『【〔〈《〘〖‘“([{";
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    pub align: TextAlign,
+    pub max_width: Option<f32>, // この幅を超える行は折り返す (ピクセル)
+    pub line_spacing: f32,      // 行の間隔の倍率
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+// 文字1つ分の四角形 (ピクセル単位、文章の左上を原点としてy軸が下向き)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphQuad {
+    pub page: usize,
+    pub min: Vector2,
+    pub max: Vector2,
+    pub uv_min: Vector2,
+    pub uv_max: Vector2,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLayout {
+    pub quads: Vec<GlyphQuad>,
+    pub size: Vector2, // 文章全体の大きさ (折り返し幅を指定したときはその幅)
+    pub line_count: usize,
+}
+
+type Line = Vec<(char, Glyph)>;
+
+// 文字列を行に分けて並べる
+// 改行文字で必ず改行し、max_width を超えるときは空白の後ろか、和文の文字の間で折り返す
+// 折り返す場所がない長い単語は文字の途中で折り返す
+pub fn layout_text(font: &mut Font, text: &str, style: &TextStyle) -> TextLayout {
+    let mut lines: Vec<Line> = Vec::new();
+    for paragraph in text.split('\n') {
+        let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+        wrap_paragraph(font, paragraph, style.max_width, &mut lines);
+    }
+
+    let widths: Vec<f32> = lines.iter().map(|line| line_width(font, line)).collect();
+    let block_width = style
+        .max_width
+        .unwrap_or_else(|| widths.iter().cloned().fold(0.0, f32::max));
+    let line_advance = font.line_height * style.line_spacing;
+
+    let mut quads = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let baseline = font.ascent + line_advance * index as f32;
+        let start = match style.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => (block_width - widths[index]) * 0.5,
+            TextAlign::Right => block_width - widths[index],
+        };
+        let mut pen = 0.0;
+        for (i, &(c, glyph)) in line.iter().enumerate() {
+            if i > 0 {
+                pen += font.kerning(line[i - 1].0, c);
+            }
+            if glyph.size.x > 0.0 && glyph.size.y > 0.0 {
+                // ピクセルの境界にそろえると、小さな文字がぼやけない
+                let min = vec2((start + pen).round(), baseline.round()) + glyph.offset;
+                quads.push(GlyphQuad {
+                    page: glyph.page,
+                    min,
+                    max: min + glyph.size,
+                    uv_min: glyph.uv_min,
+                    uv_max: glyph.uv_max,
+                });
+            }
+            pen += glyph.advance;
+        }
+    }
+
+    let line_count = lines.len();
+    TextLayout {
+        quads,
+        size: vec2(
+            block_width,
+            line_advance * (line_count as f32 - 1.0) + font.line_height,
+        ),
+        line_count,
+    }
+}
+
+fn wrap_paragraph(font: &mut Font, paragraph: &str, max_width: Option<f32>, lines: &mut Vec<Line>) {
+    let mut line: Line = Vec::new();
+    let mut pen = 0.0;
+    for c in paragraph.chars() {
+        let glyph = match font.glyph_or_fallback(c) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        if let Some(max_width) = max_width {
+            // 空白は行末にはみ出してもよい (次の行頭に来ないように、折り返し時に取り除く)
+            while !c.is_whitespace() && !line.is_empty() {
+                let kerning = font.kerning(line[line.len() - 1].0, c);
+                if pen + kerning + glyph.offset.x + glyph.size.x <= max_width {
+                    break;
+                }
+                let split = last_break(&line, c).unwrap_or(line.len());
+                let rest = line.split_off(split);
+                lines.push(trim_end(line));
+                line = rest
+                    .into_iter()
+                    .skip_while(|(c, _)| c.is_whitespace())
+                    .collect();
+                pen = advance(font, &line);
+            }
+        }
+        if let Some(&(previous, _)) = line.last() {
+            pen += font.kerning(previous, c);
+        }
+        pen += glyph.advance;
+        line.push((c, glyph));
+    }
+    lines.push(line);
+}
+
+// 行の中で最も後ろの、折り返してよい位置 (その位置の文字から次の行になる)
+// 行の長さを返したときは、これから置く文字 next から次の行になる
+fn last_break(line: &[(char, Glyph)], next: char) -> Option<usize> {
+    (1..=line.len()).rev().find(|&i| {
+        let after = line.get(i).map_or(next, |&(c, _)| c);
+        can_break(line[i - 1].0, after)
+    })
+}
+
+pub fn can_break(previous: char, next: char) -> bool {
+    if next.is_whitespace() {
+        return false; // 空白は前の行の末尾に残す
+    }
+    if previous.is_whitespace() {
+        return true;
+    }
+    if NO_LINE_START.contains(next) || NO_LINE_END.contains(previous) {
+        return false;
+    }
+    is_cjk(previous) || is_cjk(next)
+}
+
+// 単語を空白で区切らない文字 (かな、漢字、全角記号、ハングル)
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x30FF // 和文の記号、ひらがな、カタカナ
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF // 漢字
+        | 0xF900..=0xFAFF
+        | 0xFF00..=0xFFEF // 全角・半角形
+        | 0xAC00..=0xD7AF // ハングル
+    )
+}
+
+fn trim_end(mut line: Line) -> Line {
+    while line.last().is_some_and(|(c, _)| c.is_whitespace()) {
+        line.pop();
+    }
+    line
+}
+
+fn advance(font: &Font, line: &[(char, Glyph)]) -> f32 {
+    let mut pen = 0.0;
+    for (i, &(c, glyph)) in line.iter().enumerate() {
+        if i > 0 {
+            pen += font.kerning(line[i - 1].0, c);
+        }
+        pen += glyph.advance;
+    }
+    pen
+}
+
+// 揃えに使う行の幅 (行末の空白は含めない)
+fn line_width(font: &Font, line: &[(char, Glyph)]) -> f32 {
+    let end = line
+        .iter()
+        .rposition(|(c, _)| !c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    advance(font, &line[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // 欧文は幅10、和文は幅20の等幅のBMFont (アトラスの画像は使わない)
+    fn font() -> Font {
+        let mut glyphs = HashMap::new();
+        let ascii = ('!'..='~').map(|c| (c, 10.0));
+        let japanese = "あいうえおかきくけこ。、「」ー".chars().map(|c| (c, 20.0));
+        for (c, width) in ascii.chain(japanese) {
+            let glyph = Font::bitmap_glyph(
+                0,
+                vec2(0.0, 0.0),
+                vec2(1.0, 1.0),
+                vec2(0.0, -10.0),
+                vec2(width, 12.0),
+                width,
+            );
+            glyphs.insert(c, glyph);
+        }
+        let space = Font::bitmap_glyph(
+            0,
+            vec2(0.0, 0.0),
+            vec2(0.0, 0.0),
+            vec2(0.0, 0.0),
+            vec2(0.0, 0.0),
+            10.0,
+        );
+        glyphs.insert(' ', space);
+        let kerning = [(('A', 'V'), -2.0)].iter().copied().collect();
+        Font::from_bitmap(16.0, 16.0, 12.0, Vec::new(), glyphs, kerning)
+    }
+
+    // 行ごとの文字の左端 (空白は四角形を作らないので含まない)
+    fn lines(layout: &TextLayout) -> Vec<Vec<f32>> {
+        let mut lines: Vec<Vec<f32>> = vec![Vec::new(); layout.line_count];
+        for quad in &layout.quads {
+            let line = ((quad.min.y + 10.0 - 12.0) / 16.0) as usize;
+            lines[line].push(quad.min.x);
+        }
+        lines
+    }
+
+    // 折り返した結果を行ごとの文字列にする
+    fn wrap(text: &str, max_width: f32) -> Vec<String> {
+        let mut font = font();
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            wrap_paragraph(&mut font, paragraph, Some(max_width), &mut lines);
+        }
+        lines
+            .iter()
+            .map(|line| line.iter().map(|&(c, _)| c).collect())
+            .collect()
+    }
+
+    #[test]
+    fn single_line() {
+        let layout = layout_text(&mut font(), "AVa b", &TextStyle::default());
+        assert_eq!(layout.line_count, 1);
+        // A と V の間はカーニングで詰める
+        assert_eq!(lines(&layout), vec![vec![0.0, 8.0, 18.0, 38.0]]);
+        assert_eq!(layout.size, vec2(48.0, 16.0));
+    }
+
+    #[test]
+    fn newlines_and_line_spacing() {
+        let style = TextStyle {
+            line_spacing: 1.5,
+            ..TextStyle::default()
+        };
+        let layout = layout_text(&mut font(), "ab\r\n\ncd", &style);
+        assert_eq!(layout.line_count, 3);
+        let tops: Vec<f32> = layout.quads.iter().map(|quad| quad.min.y).collect();
+        assert_eq!(tops, vec![2.0, 2.0, 50.0, 50.0]);
+        assert_eq!(layout.size, vec2(20.0, 64.0));
+    }
+
+    #[test]
+    fn word_wrap() {
+        assert_eq!(wrap("aaa bbb ccc", 75.0), vec!["aaa bbb", "ccc"]);
+        assert_eq!(wrap("aaa bbb ccc", 70.0), vec!["aaa bbb", "ccc"]);
+        assert_eq!(wrap("aaa bbb ccc", 69.0), vec!["aaa", "bbb", "ccc"]);
+        // 行頭の空白は取り除く
+        assert_eq!(wrap("aaa    bbb", 50.0), vec!["aaa", "bbb"]);
+        // 折り返す場所のない長い単語は文字の途中で折り返す
+        assert_eq!(wrap("abcdefg hi", 30.0), vec!["abc", "def", "g", "hi"]);
+    }
+
+    #[test]
+    fn japanese_wrap_and_kinsoku() {
+        assert_eq!(wrap("あいうえお", 40.0), vec!["あい", "うえ", "お"]);
+        // 句読点は行頭に来ないように、前の文字と一緒に次の行へ送る
+        assert_eq!(wrap("あい。うえ", 40.0), vec!["あ", "い。", "うえ"]);
+        assert_eq!(wrap("あいうー", 60.0), vec!["あい", "うー"]);
+        // 開き括弧は行末に残さない
+        assert_eq!(wrap("あ「いう」", 40.0), vec!["あ", "「い", "う」"]);
+        // 欧文と和文の間でも折り返せる
+        assert_eq!(wrap("abcか", 45.0), vec!["abc", "か"]);
+    }
+
+    #[test]
+    fn can_break_rules() {
+        assert!(can_break('あ', 'い'));
+        assert!(can_break('a', 'あ'));
+        assert!(can_break(' ', 'a'));
+        assert!(!can_break('a', 'b'));
+        assert!(!can_break('a', ' '));
+        assert!(!can_break('あ', '。'));
+        assert!(!can_break('あ', 'ー'));
+        assert!(!can_break('「', 'あ'));
+        assert!(!can_break('a', ','));
+    }
+
+    #[test]
+    fn alignment() {
+        let mut font = font();
+        let style = |align| TextStyle {
+            align,
+            ..TextStyle::default()
+        };
+        // 幅は最も長い行にそろえる
+        let layout = layout_text(&mut font, "ab\nabcd", &style(TextAlign::Center));
+        assert_eq!(layout.size.x, 40.0);
+        assert_eq!(lines(&layout)[0], vec![10.0, 20.0]);
+        let layout = layout_text(&mut font, "ab\nabcd", &style(TextAlign::Right));
+        assert_eq!(lines(&layout)[0], vec![20.0, 30.0]);
+        assert_eq!(lines(&layout)[1], vec![0.0, 10.0, 20.0, 30.0]);
+
+        // 折り返し幅を指定したときはその幅にそろえ、行末の空白は数えない
+        let wrapped = TextStyle {
+            max_width: Some(100.0),
+            ..style(TextAlign::Right)
+        };
+        let layout = layout_text(&mut font, "ab  ", &wrapped);
+        assert_eq!(layout.size.x, 100.0);
+        assert_eq!(lines(&layout)[0], vec![80.0, 90.0]);
+        let wrapped = TextStyle {
+            align: TextAlign::Center,
+            ..wrapped
+        };
+        let layout = layout_text(&mut font, "あい", &wrapped);
+        assert_eq!(lines(&layout)[0], vec![30.0, 50.0]);
+    }
+
+    #[test]
+    fn missing_characters_use_the_fallback() {
+        let layout = layout_text(&mut font(), "a☃b", &TextStyle::default());
+        // U+FFFD がないので '?' で表示する
+        assert_eq!(lines(&layout), vec![vec![0.0, 10.0, 20.0]]);
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::mem;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use cgmath::{ortho, vec2, vec3, Matrix4};
+use gl::types::{GLfloat, GLsizei, GLsizeiptr};
+
+use super::face::Font;
+use super::layout::{layout_text, TextStyle};
+use crate::assets::Handle;
+use crate::ecs::{Read, World, Write};
+use crate::material::{Material, MaterialId, MaterialLibrary, RenderState, UniformValue};
+use crate::renderer::{DrawCommand, Renderer};
+use crate::texture::Texture2D;
+use crate::transform::Transform2D;
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 1頂点あたりの要素数 (位置xy、テクスチャ座標uv、色rgba)
+const FLOATS_PER_VERTEX: usize = 8;
+
+const TEXT_VERTEX_SHADER: &str = "rsc/shader/text.vs";
+const TEXT_FRAGMENT_SHADER: &str = "rsc/shader/text.fs";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+// フォントのページごとのテクスチャと、画面用・ワールド用のマテリアル
+struct PageResources {
+    texture: Handle<Texture2D>,
+    screen: MaterialId,
+    world: MaterialId,
+}
+
+// 読み込んだフォントをまとめて持つリソース
+pub struct Fonts {
+    pub viewport: Vector2, // 画面に描く文字の座標の基準にするウィンドウの大きさ
+    fonts: Vec<Font>,
+    names: HashMap<String, FontId>,
+    pages: Vec<Vec<PageResources>>, // フォントごと
+}
+
+#[allow(dead_code)]
+impl Fonts {
+    pub fn new(viewport_width: u32, viewport_height: u32) -> Fonts {
+        Fonts {
+            viewport: vec2(viewport_width as f32, viewport_height as f32),
+            fonts: Vec::new(),
+            names: HashMap::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    // 同名のフォントがすでにあれば置き換える
+    pub fn insert(&mut self, name: &str, font: Font) -> FontId {
+        if let Some(&id) = self.names.get(name) {
+            self.fonts[id.0] = font;
+            self.pages[id.0].clear();
+            return id;
+        }
+        let id = FontId(self.fonts.len());
+        self.fonts.push(font);
+        self.pages.push(Vec::new());
+        self.names.insert(name.to_string(), id);
+        id
+    }
+
+    // 後ろのファイルは前のファイルにない文字の代わりに使う
+    pub fn load_ttf(&mut self, name: &str, paths: &[&str], size: f32) -> Result<FontId, String> {
+        Ok(self.insert(name, Font::from_files(paths, size)?))
+    }
+
+    pub fn load_bmfont(&mut self, name: &str, path: &str) -> Result<FontId, String> {
+        Ok(self.insert(name, Font::from_bmfont(path)?))
+    }
+
+    pub fn id(&self, name: &str) -> Option<FontId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get(&self, id: FontId) -> &Font {
+        &self.fonts[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: FontId) -> &mut Font {
+        &mut self.fonts[id.0]
+    }
+
+    // 書き換えられたアトラスをGPUへ転送し、新しいページのマテリアルを作る (GLコンテキストが必要)
+    pub fn sync(&mut self, materials: &mut MaterialLibrary) {
+        for (index, font) in self.fonts.iter_mut().enumerate() {
+            let resources = &mut self.pages[index];
+            for (page_index, page) in font.pages.iter_mut().enumerate() {
+                if page_index < resources.len() {
+                    if page.dirty {
+                        resources[page_index].texture.update_rgba(&page.pixels);
+                    }
+                    page.dirty = false;
+                    continue;
+                }
+                let texture = Handle::new(
+                    &format!("font:{}:{}", index, page_index),
+                    Texture2D::from_rgba(page.width, page.height, &page.pixels),
+                );
+                // アトラスの隣のグリフがにじまないようにミップマップを使わない
+                texture.set_filter(gl::LINEAR, gl::LINEAR);
+                page.dirty = false;
+                let shader = materials.load_shader(TEXT_VERTEX_SHADER, TEXT_FRAGMENT_SHADER);
+                let mut create = |space: &str, screen: bool| {
+                    let mut material = Material::new(
+                        &format!("font:{}:{}:{}", index, page_index, space),
+                        shader.clone(),
+                    );
+                    material.set_texture("uTexture", texture.clone());
+                    material.set_uniform("uScreenSpace", UniformValue::Bool(screen));
+                    material.render_state = RenderState {
+                        depth_test: !screen, // 画面に描く文字は常に一番手前
+                        depth_write: false,
+                        blend: true,
+                        culling: false,
+                        ..RenderState::default()
+                    };
+                    materials.insert(material)
+                };
+                let screen = create("screen", true);
+                let world = create("world", false);
+                resources.push(PageResources {
+                    texture,
+                    screen,
+                    world,
+                });
+            }
+        }
+    }
+}
+
+// 文字を描く場所
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextSpace {
+    // ウィンドウの左上を原点とするピクセル座標 (y軸は下向き)。Transform2Dの位置をピクセルとして扱う
+    Screen,
+    // ワールド座標。Transform2Dの行列で変換する
+    World { pixels_per_unit: f32 },
+}
+
+// 文字列を描くコンポーネント
+// 文字列や見た目を変えたときだけ文字を並べ直して頂点データを作る
+pub struct Text {
+    pub font: FontId,
+    pub style: TextStyle,
+    pub color: [f32; 4],
+    pub space: TextSpace,
+    pub pivot: Vector2, // 文章のどこを位置に合わせるか ((0, 0): 左上、(1, 1): 右下)
+    pub layer: i32,
+    pub visible: bool,
+    text: String,
+    dirty: bool,
+    built: Option<(TextStyle, [f32; 4], TextSpace, Vector2)>, // 頂点データを作ったときの見た目
+    size: Vector2,
+    meshes: Vec<(usize, Rc<Vertex>)>, // ページごと
+}
+
+#[allow(dead_code)]
+impl Text {
+    pub fn new(font: FontId, text: &str) -> Text {
+        Text {
+            font,
+            style: TextStyle::default(),
+            color: [1.0, 1.0, 1.0, 1.0],
+            space: TextSpace::Screen,
+            pivot: vec2(0.0, 0.0),
+            layer: 1000, // 画面の文字は他のものより後に描く
+            visible: true,
+            text: text.to_string(),
+            dirty: true,
+            built: None,
+            size: vec2(0.0, 0.0),
+            meshes: Vec::new(),
+        }
+    }
+
+    pub fn with_style(mut self, style: TextStyle) -> Text {
+        self.style = style;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Text {
+        self.color = color;
+        self
+    }
+
+    pub fn with_pivot(mut self, pivot: Vector2) -> Text {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: i32) -> Text {
+        self.layer = layer;
+        self
+    }
+
+    pub fn in_world(mut self, pixels_per_unit: f32) -> Text {
+        self.space = TextSpace::World { pixels_per_unit };
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // 同じ文字列なら何もしない (毎フレーム呼んでもよい)
+    pub fn set_text(&mut self, text: &str) {
+        if self.text != text {
+            self.text.clear();
+            self.text.push_str(text);
+            self.dirty = true;
+        }
+    }
+
+    // 直前に並べたときの文章の大きさ (ピクセル)
+    pub fn size(&self) -> Vector2 {
+        self.size
+    }
+
+    fn rebuild(&mut self, fonts: &mut Fonts) {
+        let appearance = (self.style, self.color, self.space, self.pivot);
+        if !self.dirty && self.built == Some(appearance) {
+            return;
+        }
+        self.dirty = false;
+        self.built = Some(appearance);
+
+        let layout = layout_text(fonts.get_mut(self.font), &self.text, &self.style);
+        self.size = layout.size;
+        let origin = vec2(layout.size.x * self.pivot.x, layout.size.y * self.pivot.y);
+        let to_local = |p: Vector2| match self.space {
+            TextSpace::Screen => p - origin,
+            // ワールド座標はy軸が上向き
+            TextSpace::World { pixels_per_unit } => {
+                let p = (p - origin) / pixels_per_unit;
+                vec2(p.x, -p.y)
+            }
+        };
+
+        // ページごとに四角形をまとめる
+        let mut geometries: Vec<(usize, Vec<f32>, Vec<u32>)> = Vec::new();
+        for quad in &layout.quads {
+            let index = match geometries
+                .iter()
+                .position(|(page, _, _)| *page == quad.page)
+            {
+                Some(index) => index,
+                None => {
+                    geometries.push((quad.page, Vec::new(), Vec::new()));
+                    geometries.len() - 1
+                }
+            };
+            let (_, vertices, indices) = &mut geometries[index];
+            let base = (vertices.len() / FLOATS_PER_VERTEX) as u32;
+            let corners = [
+                (
+                    vec2(quad.min.x, quad.max.y),
+                    vec2(quad.uv_min.x, quad.uv_max.y),
+                ),
+                (quad.max, quad.uv_max),
+                (
+                    vec2(quad.max.x, quad.min.y),
+                    vec2(quad.uv_max.x, quad.uv_min.y),
+                ),
+                (quad.min, quad.uv_min),
+            ];
+            for (position, uv) in corners {
+                let position = to_local(position);
+                vertices.extend_from_slice(&[position.x, position.y, uv.x, uv.y]);
+                vertices.extend_from_slice(&self.color);
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        let mut old_meshes = mem::take(&mut self.meshes);
+        for (page, vertices, indices) in geometries {
+            let size = (vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr;
+            let data = vertices.as_ptr() as *const c_void;
+            let vertex_num = (vertices.len() / FLOATS_PER_VERTEX) as i32;
+            // 同じページの頂点バッファーが使われていなければ、中身だけ書き換える
+            let reused = old_meshes
+                .iter()
+                .position(|(p, _)| *p == page)
+                .map(|index| old_meshes.swap_remove(index))
+                .and_then(|(page, mut vertex)| {
+                    let mesh = Rc::get_mut(&mut vertex)?;
+                    mesh.update(size, data, vertex_num);
+                    mesh.update_indices(&indices);
+                    Some((page, vertex))
+                });
+            let mesh = reused.unwrap_or_else(|| {
+                let vertex = Vertex::new(
+                    size,
+                    data,
+                    gl::DYNAMIC_DRAW, // 文字列が変わるたびに書き換える
+                    vec![gl::FLOAT, gl::FLOAT, gl::FLOAT],
+                    vec![2, 2, 4],
+                    (FLOATS_PER_VERTEX * mem::size_of::<GLfloat>()) as GLsizei,
+                    vertex_num,
+                )
+                .with_indices(&indices);
+                (page, Rc::new(vertex))
+            });
+            self.meshes.push(mesh);
+        }
+    }
+}
+
+// 文字を並べ直してからアトラスをGPUへ転送し、描画コマンドを積む (Render)
+pub fn text_render_system(world: &mut World) {
+    if !world.has_resource::<Fonts>() {
+        return;
+    }
+    let mut fonts = world.resource_mut::<Fonts>();
+    let mut renderer = world.resource_mut::<Renderer>();
+
+    // 新しい文字が使われるとアトラスに書き込まれるので、先にすべて並べておく
+    world
+        .query::<Write<Text>>()
+        .for_each(|_, text| text.rebuild(&mut fonts));
+    fonts.sync(&mut renderer.materials);
+
+    let viewport = fonts.viewport;
+    world
+        .query::<(Read<Text>, Read<Transform2D>)>()
+        .for_each(|_, (text, transform)| {
+            if !text.visible {
+                return;
+            }
+            let (model, screen) = match text.space {
+                TextSpace::Screen => {
+                    let position = transform.world_position();
+                    (
+                        ortho(0.0, viewport.x, viewport.y, 0.0, -1.0, 1.0)
+                            * Matrix4::from_translation(vec3(position.x, position.y, 0.0)),
+                        true,
+                    )
+                }
+                TextSpace::World { .. } => (transform.world_matrix(), false),
+            };
+            let pages = &fonts.pages[text.font.0];
+            for (page, vertex) in &text.meshes {
+                let resources = &pages[*page];
+                renderer.submit(DrawCommand {
+                    vertex: vertex.clone(),
+                    material: if screen {
+                        resources.screen
+                    } else {
+                        resources.world
+                    },
+                    model,
+                    layer: text.layer,
+                });
+            }
+        });
+}
@@ -0,0 +1,224 @@
+use std::ffi::CString;
+
+use c_str_macro::c_str;
+use cgmath::InnerSpace;
+use imgui::im_str;
+
+use crate::shader::Shader;
+
+#[allow(dead_code)]
+type Vector3 = cgmath::Vector3<f32>;
+
+// シェーダー (rsc/shader/lit.fs) の配列の大きさと合わせる
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 8;
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
+// 太陽のように、どこでも同じ向きから当たる光
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: [f32; 3], // 光が進む向き
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+// 電球のように、1点から全方向に広がる光 (range で0になる)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+}
+
+// 懐中電灯のように、円すいの中だけを照らす光
+// inner_angle の内側は明るさそのまま、outer_angle に向かって暗くなる (度)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadingModel {
+    Phong,      // 反射ベクトルと視線の角度でハイライトを求める
+    BlinnPhong, // ハーフベクトルを使う (斜めから見たときにハイライトが途切れない)
+}
+
+// シーンの光
+// Renderer が、uAmbientLight を持つシェーダーを使うときにユニフォーム変数の配列として渡す
+// 光沢 (uShininess) と鏡面反射の色 (uSpecularColor) はマテリアルごとにユニフォーム変数で指定する
+pub struct Lighting {
+    pub ambient: [f32; 3], // 環境光 (どの光も当たらない面の明るさ)
+    pub model: ShadingModel,
+    pub directional_lights: Vec<DirectionalLight>,
+    pub point_lights: Vec<PointLight>,
+    pub spot_lights: Vec<SpotLight>,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Lighting {
+    pub fn new() -> Lighting {
+        Lighting {
+            ambient: [0.2, 0.2, 0.2],
+            model: ShadingModel::BlinnPhong,
+            directional_lights: Vec::new(),
+            point_lights: Vec::new(),
+            spot_lights: Vec::new(),
+        }
+    }
+
+    // 上限を超えた光はシェーダーに渡さない
+    pub fn add_directional(&mut self, light: DirectionalLight) -> &mut Lighting {
+        self.directional_lights.push(light);
+        self
+    }
+
+    pub fn add_point(&mut self, light: PointLight) -> &mut Lighting {
+        self.point_lights.push(light);
+        self
+    }
+
+    pub fn add_spot(&mut self, light: SpotLight) -> &mut Lighting {
+        self.spot_lights.push(light);
+        self
+    }
+
+    // shader が光を使うかどうか (uAmbientLight があるかで判断する)
+    pub unsafe fn is_lit(shader: &Shader) -> bool {
+        gl::GetUniformLocation(shader.id, c_str!("uAmbientLight").as_ptr()) >= 0
+    }
+
+    // 使用中のシェーダーにユニフォーム変数を設定する (camera_position は鏡面反射に使う)
+    pub unsafe fn apply(&self, shader: &Shader, camera_position: Vector3) {
+        shader.set_vector3(c_str!("uAmbientLight"), &self.ambient.into());
+        shader.set_vector3(c_str!("uCameraPosition"), &camera_position);
+        shader.set_bool(
+            c_str!("uBlinnPhong"),
+            self.model == ShadingModel::BlinnPhong,
+        );
+
+        let count = self.directional_lights.len().min(MAX_DIRECTIONAL_LIGHTS);
+        shader.set_int(c_str!("uDirectionalLightCount"), count as i32);
+        for (index, light) in self.directional_lights.iter().take(count).enumerate() {
+            let field = |name| uniform_name("uDirectionalLights", index, name);
+            shader.set_vector3(&field("direction"), &normalized(light.direction));
+            shader.set_vector3(&field("color"), &radiance(light.color, light.intensity));
+        }
+
+        let count = self.point_lights.len().min(MAX_POINT_LIGHTS);
+        shader.set_int(c_str!("uPointLightCount"), count as i32);
+        for (index, light) in self.point_lights.iter().take(count).enumerate() {
+            let field = |name| uniform_name("uPointLights", index, name);
+            shader.set_vector3(&field("position"), &light.position.into());
+            shader.set_vector3(&field("color"), &radiance(light.color, light.intensity));
+            shader.set_float(&field("range"), light.range.max(0.001));
+        }
+
+        let count = self.spot_lights.len().min(MAX_SPOT_LIGHTS);
+        shader.set_int(c_str!("uSpotLightCount"), count as i32);
+        for (index, light) in self.spot_lights.iter().take(count).enumerate() {
+            let field = |name| uniform_name("uSpotLights", index, name);
+            let outer = light.outer_angle.max(light.inner_angle);
+            shader.set_vector3(&field("position"), &light.position.into());
+            shader.set_vector3(&field("direction"), &normalized(light.direction));
+            shader.set_vector3(&field("color"), &radiance(light.color, light.intensity));
+            shader.set_float(&field("range"), light.range.max(0.001));
+            // 角度の比較はシェーダーで cos のまま行う
+            shader.set_float(&field("innerCos"), light.inner_angle.to_radians().cos());
+            shader.set_float(&field("outerCos"), outer.to_radians().cos());
+        }
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        imgui::ColorEdit::new(im_str!("Ambient"), &mut self.ambient).build(ui);
+        let mut blinn = self.model == ShadingModel::BlinnPhong;
+        if ui.checkbox(im_str!("Blinn-Phong"), &mut blinn) {
+            self.model = if blinn {
+                ShadingModel::BlinnPhong
+            } else {
+                ShadingModel::Phong
+            };
+        }
+        for (index, light) in self.directional_lights.iter_mut().enumerate() {
+            let id = ui.push_id(&*format!("directional{}", index));
+            ui.text(format!("Directional Light {}", index));
+            imgui::Drag::new(im_str!("Direction"))
+                .speed(0.01)
+                .build_array(ui, &mut light.direction);
+            imgui::ColorEdit::new(im_str!("Color"), &mut light.color).build(ui);
+            imgui::Slider::new(im_str!("Intensity"))
+                .range(0.0..=4.0)
+                .build(ui, &mut light.intensity);
+            id.pop(ui);
+        }
+        for (index, light) in self.point_lights.iter_mut().enumerate() {
+            let id = ui.push_id(&*format!("point{}", index));
+            ui.text(format!("Point Light {}", index));
+            imgui::Drag::new(im_str!("Position"))
+                .speed(0.05)
+                .build_array(ui, &mut light.position);
+            imgui::ColorEdit::new(im_str!("Color"), &mut light.color).build(ui);
+            imgui::Slider::new(im_str!("Intensity"))
+                .range(0.0..=4.0)
+                .build(ui, &mut light.intensity);
+            imgui::Slider::new(im_str!("Range"))
+                .range(0.0..=50.0)
+                .build(ui, &mut light.range);
+            id.pop(ui);
+        }
+        for (index, light) in self.spot_lights.iter_mut().enumerate() {
+            let id = ui.push_id(&*format!("spot{}", index));
+            ui.text(format!("Spot Light {}", index));
+            imgui::Drag::new(im_str!("Position"))
+                .speed(0.05)
+                .build_array(ui, &mut light.position);
+            imgui::Drag::new(im_str!("Direction"))
+                .speed(0.01)
+                .build_array(ui, &mut light.direction);
+            imgui::ColorEdit::new(im_str!("Color"), &mut light.color).build(ui);
+            imgui::Slider::new(im_str!("Intensity"))
+                .range(0.0..=4.0)
+                .build(ui, &mut light.intensity);
+            imgui::Slider::new(im_str!("Range"))
+                .range(0.0..=50.0)
+                .build(ui, &mut light.range);
+            imgui::Slider::new(im_str!("Inner Angle"))
+                .range(0.0..=90.0)
+                .build(ui, &mut light.inner_angle);
+            imgui::Slider::new(im_str!("Outer Angle"))
+                .range(0.0..=90.0)
+                .build(ui, &mut light.outer_angle);
+            id.pop(ui);
+        }
+    }
+}
+
+// "uPointLights[0].position"
+fn uniform_name(array: &str, index: usize, field: &str) -> CString {
+    CString::new(format!("{}[{}].{}", array, index, field)).unwrap()
+}
+
+fn radiance(color: [f32; 3], intensity: f32) -> Vector3 {
+    Vector3::from(color) * intensity
+}
+
+fn normalized(direction: [f32; 3]) -> Vector3 {
+    let direction = Vector3::from(direction);
+    if direction.magnitude2() > 0.0 {
+        direction.normalize()
+    } else {
+        Vector3::new(0.0, -1.0, 0.0)
+    }
+}
@@ -0,0 +1,10 @@
+mod light;
+mod light_map;
+mod shadow;
+
+#[allow(unused_imports)]
+pub use light::{Light2D, NormalMap, Occluder};
+#[allow(unused_imports)]
+pub use light_map::{lighting2d_render_system, Lighting2D, LightingStats};
+#[allow(unused_imports)]
+pub use shadow::shadow_geometry;
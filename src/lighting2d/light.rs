@@ -0,0 +1,116 @@
+use cgmath::vec2;
+use imgui::im_str;
+
+use crate::assets::Handle;
+use crate::texture::Texture2D;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 2Dの点光源 (Transform2D の pivot の位置から光る)
+// radius で明るさが0になり、falloff が大きいほど中心の近くに光が集まる (1.0で直線的に暗くなる)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light2D {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub radius: f32,
+    pub falloff: f32,
+    pub height: f32, // 法線マップで照らすときの、光源の画面からの高さ (低いほど凹凸が目立つ)
+    pub cast_shadows: bool,
+    pub enabled: bool,
+}
+
+#[allow(dead_code)]
+impl Light2D {
+    pub fn new(color: [f32; 3], radius: f32) -> Light2D {
+        Light2D {
+            color,
+            intensity: 1.0,
+            radius,
+            falloff: 2.0,
+            height: 1.0,
+            cast_shadows: true,
+            enabled: true,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Light2D {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_falloff(mut self, falloff: f32) -> Light2D {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn with_height(mut self, height: f32) -> Light2D {
+        self.height = height;
+        self
+    }
+
+    pub fn with_shadows(mut self, cast_shadows: bool) -> Light2D {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        ui.checkbox(im_str!("Enabled"), &mut self.enabled);
+        imgui::ColorEdit::new(im_str!("Color"), &mut self.color).build(ui);
+        imgui::Slider::new(im_str!("Intensity"))
+            .range(0.0..=4.0)
+            .build(ui, &mut self.intensity);
+        imgui::Slider::new(im_str!("Radius"))
+            .range(0.1..=20.0)
+            .build(ui, &mut self.radius);
+        imgui::Slider::new(im_str!("Falloff"))
+            .range(0.1..=8.0)
+            .build(ui, &mut self.falloff);
+        imgui::Slider::new(im_str!("Height"))
+            .range(0.0..=5.0)
+            .build(ui, &mut self.height);
+        ui.checkbox(im_str!("Cast Shadows"), &mut self.cast_shadows);
+    }
+}
+
+// 光をさえぎる多角形 (Transform2D のローカル座標、時計回りでも反時計回りでもよい)
+// 多角形そのものは照らされ、光から見て裏側の辺から先に影ができる
+// 同じエンティティの光はさえぎらない (キャラクターが持つ光など)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Occluder {
+    pub polygons: Vec<Vec<Vector2>>,
+    pub enabled: bool,
+}
+
+#[allow(dead_code)]
+impl Occluder {
+    pub fn new() -> Occluder {
+        Occluder {
+            polygons: Vec::new(),
+            enabled: true,
+        }
+    }
+
+    pub fn rect(min: Vector2, max: Vector2) -> Occluder {
+        Occluder::new().with_rect(min, max)
+    }
+
+    pub fn with_polygon(mut self, points: Vec<Vector2>) -> Occluder {
+        if points.len() >= 3 {
+            self.polygons.push(points);
+        }
+        self
+    }
+
+    pub fn with_rect(self, min: Vector2, max: Vector2) -> Occluder {
+        self.with_polygon(vec![min, vec2(max.x, min.y), max, vec2(min.x, max.y)])
+    }
+}
+
+// スプライトの法線マップ (Sprite と同じエンティティに付ける)
+// スプライトのUVで読み、RGBを -1.0 ~ 1.0 の法線として使う (緑が上向き、青が画面の手前向き)
+// アルファが 0.5 未満のところは法線を書き込まない
+#[derive(Clone)]
+pub struct NormalMap {
+    pub texture: Handle<Texture2D>,
+}
@@ -0,0 +1,494 @@
+use std::mem;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use c_str_macro::c_str;
+use cgmath::{vec2, vec3, vec4, SquareMatrix};
+use gl::types::{GLenum, GLfloat, GLsizei, GLsizeiptr};
+use imgui::im_str;
+
+use super::light::{Light2D, NormalMap, Occluder};
+use super::shadow::shadow_geometry;
+use crate::assets::Handle;
+use crate::components::Sprite;
+use crate::ecs::{Entity, Read, World};
+use crate::material::{BlendMode, Material, MaterialId, RenderState};
+use crate::render_target::RenderTarget;
+use crate::renderer::{DrawCommand, Renderer};
+use crate::shader::Shader;
+use crate::texture::Texture2D;
+use crate::transform::Transform2D;
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+#[allow(dead_code)]
+type Vector3 = cgmath::Vector3<f32>;
+#[allow(dead_code)]
+type Matrix4 = cgmath::Matrix4<f32>;
+
+const LIGHT_VERTEX_SHADER: &str = "rsc/shader/light2d.vs";
+const LIGHT_FRAGMENT_SHADER: &str = "rsc/shader/light2d.fs";
+const SHADOW_FRAGMENT_SHADER: &str = "rsc/shader/shadow2d.fs";
+const NORMAL_VERTEX_SHADER: &str = "rsc/shader/normal2d.vs";
+const NORMAL_FRAGMENT_SHADER: &str = "rsc/shader/normal2d.fs";
+const LIGHT_MAP_VERTEX_SHADER: &str = "rsc/shader/light_map.vs";
+const LIGHT_MAP_FRAGMENT_SHADER: &str = "rsc/shader/light_map.fs";
+
+// 1フレーム分の2Dライティングの統計
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LightingStats {
+    pub lights: u32,
+    pub shadowed_lights: u32, // 影を描いた光の数
+    pub shadow_triangles: u32,
+    pub normal_sprites: u32, // 法線マップを持つスプライトの数
+}
+
+struct Shaders {
+    light: Handle<Shader>,
+    shadow: Handle<Shader>,
+    normal: Handle<Shader>,
+}
+
+// このフレームで描く光
+struct LightDraw {
+    position: Vector2,
+    light: Light2D,
+    shadow_first: i32, // 影の頂点バッファーの中の、この光の影の範囲
+    shadow_count: i32,
+}
+
+// 法線バッファーに描くスプライト
+struct NormalDraw {
+    vertex: Rc<Vertex>,
+    model: Matrix4,
+    layer: i32,
+    normal_map: Option<Handle<Texture2D>>,
+}
+
+// 2Dのライティング (リソース)
+// Renderステージで光をライトマップに描き、シーンの上に乗算で重ねる描画コマンドを積む
+// - ライトマップは環境光の色で塗りつぶしてから、光ごとに加算で描く
+// - 影は光ごとにステンシルに描き、影になったところには光を描かない
+// - 法線マップを持つスプライトは先に法線バッファーに描き、光の向きで明るさを変える
+// layer より小さいレイヤーのもの (スプライトやタイルマップ) が照らされ、画面の文字などは照らされない
+pub struct Lighting2D {
+    pub enabled: bool,
+    pub ambient: [f32; 3],
+    pub resolution: f32, // 画面に対するライトマップの大きさ (小さくすると軽くなり、影の縁がぼける)
+    pub normal_mapping: bool,
+    pub layer: i32,
+    viewport: (u32, u32),
+    light_map: Option<RenderTarget>,
+    normal_buffer: Option<RenderTarget>,
+    shaders: Option<Shaders>,
+    material: Option<MaterialId>,
+    light_quad: Option<Vertex>,
+    screen_quad: Option<Rc<Vertex>>,
+    shadow_vertex: Option<Vertex>,
+    shadow_vertices: Vec<f32>,
+    stats: LightingStats,
+}
+
+#[allow(dead_code)]
+impl Lighting2D {
+    pub fn new(viewport_width: u32, viewport_height: u32) -> Lighting2D {
+        Lighting2D {
+            enabled: true,
+            ambient: [0.3, 0.3, 0.35],
+            resolution: 1.0,
+            normal_mapping: true,
+            layer: 100,
+            viewport: (viewport_width, viewport_height),
+            light_map: None,
+            normal_buffer: None,
+            shaders: None,
+            material: None,
+            light_quad: None,
+            screen_quad: None,
+            shadow_vertex: None,
+            shadow_vertices: Vec::new(),
+            stats: LightingStats::default(),
+        }
+    }
+
+    // ウィンドウの大きさが変わったときに呼ぶ (ライトマップは次のフレームで作り直す)
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = (width, height);
+    }
+
+    // 直前のフレームで描いたライトマップ
+    pub fn light_map(&self) -> Option<&RenderTarget> {
+        self.light_map.as_ref()
+    }
+
+    pub fn stats(&self) -> LightingStats {
+        self.stats
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        ui.checkbox(im_str!("Enabled"), &mut self.enabled);
+        imgui::ColorEdit::new(im_str!("Ambient"), &mut self.ambient).build(ui);
+        imgui::Slider::new(im_str!("Resolution"))
+            .range(0.25..=1.0)
+            .build(ui, &mut self.resolution);
+        ui.checkbox(im_str!("Normal Mapping"), &mut self.normal_mapping);
+        ui.text(format!(
+            "Lights: {} ({} with shadows)",
+            self.stats.lights, self.stats.shadowed_lights
+        ));
+        ui.text(format!(
+            "Shadow Triangles: {}, Normal Sprites: {}",
+            self.stats.shadow_triangles, self.stats.normal_sprites
+        ));
+    }
+
+    // ライトマップとシェーダー、重ねるためのマテリアルを用意する (GLコンテキストが必要)
+    fn prepare(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        let scale = self.resolution.clamp(0.1, 1.0);
+        let size = (
+            ((self.viewport.0 as f32 * scale) as u32).max(1),
+            ((self.viewport.1 as f32 * scale) as u32).max(1),
+        );
+        if self
+            .light_map
+            .as_ref()
+            .map(|target| (target.width(), target.height()))
+            != Some(size)
+        {
+            let light_map = RenderTarget::new("light_map", size.0, size.1, true)?;
+            self.normal_buffer = Some(RenderTarget::new("normal_buffer", size.0, size.1, false)?);
+            if let Some(material) = self.material {
+                renderer
+                    .materials
+                    .get_mut(material)
+                    .set_texture("uLightMap", light_map.texture.clone());
+            }
+            self.light_map = Some(light_map);
+        }
+
+        match &mut self.shaders {
+            Some(shaders) => {
+                // 読み込み直されたシェーダーに持ち替える
+                shaders.light.refresh();
+                shaders.shadow.refresh();
+                shaders.normal.refresh();
+            }
+            None => {
+                let materials = &mut renderer.materials;
+                self.shaders = Some(Shaders {
+                    light: materials.load_shader(LIGHT_VERTEX_SHADER, LIGHT_FRAGMENT_SHADER),
+                    shadow: materials.load_shader(LIGHT_VERTEX_SHADER, SHADOW_FRAGMENT_SHADER),
+                    normal: materials.load_shader(NORMAL_VERTEX_SHADER, NORMAL_FRAGMENT_SHADER),
+                });
+            }
+        }
+
+        if self.material.is_none() {
+            let shader = renderer
+                .materials
+                .load_shader(LIGHT_MAP_VERTEX_SHADER, LIGHT_MAP_FRAGMENT_SHADER);
+            let mut material = Material::new("light_map", shader);
+            material.render_state = RenderState {
+                depth_test: false,
+                depth_write: false,
+                blend: true,
+                blend_mode: BlendMode::Multiply,
+                culling: false,
+                ..RenderState::default()
+            };
+            material.set_texture(
+                "uLightMap",
+                self.light_map.as_ref().unwrap().texture.clone(),
+            );
+            self.material = Some(renderer.materials.insert(material));
+        }
+        if self.light_quad.is_none() {
+            let corners = [
+                -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
+            ];
+            self.light_quad = Some(position_vertex(&corners, gl::STATIC_DRAW));
+            // 画面全体 (クリップ座標) に重ねる四角形も同じ形
+            self.screen_quad = Some(Rc::new(position_vertex(&corners, gl::STATIC_DRAW)));
+        }
+        Ok(())
+    }
+
+    // 光ごとの影の三角形を1つの頂点バッファーにまとめる
+    fn build_shadows(
+        &mut self,
+        lights: &[(Entity, Vector2, Light2D)],
+        occluders: &[(Entity, Vec<Vector2>)],
+    ) -> Vec<LightDraw> {
+        self.shadow_vertices.clear();
+        let draws = lights
+            .iter()
+            .map(|&(entity, position, light)| {
+                let first = self.shadow_vertices.len() / 2;
+                if light.cast_shadows {
+                    for (owner, polygon) in occluders {
+                        if *owner != entity {
+                            shadow_geometry(
+                                position,
+                                light.radius,
+                                polygon,
+                                &mut self.shadow_vertices,
+                            );
+                        }
+                    }
+                }
+                LightDraw {
+                    position,
+                    light,
+                    shadow_first: first as i32,
+                    shadow_count: (self.shadow_vertices.len() / 2 - first) as i32,
+                }
+            })
+            .collect();
+
+        if !self.shadow_vertices.is_empty() {
+            let size = mem::size_of_val(self.shadow_vertices.as_slice()) as GLsizeiptr;
+            let data = self.shadow_vertices.as_ptr() as *const c_void;
+            let vertex_num = (self.shadow_vertices.len() / 2) as i32;
+            match &mut self.shadow_vertex {
+                Some(vertex) => vertex.update(size, data, vertex_num),
+                None => {
+                    self.shadow_vertex =
+                        Some(position_vertex(&self.shadow_vertices, gl::DYNAMIC_DRAW))
+                }
+            }
+        }
+        draws
+    }
+
+    // 法線マップを持つスプライトと、その手前にある法線マップのないスプライトを法線バッファーに描く
+    unsafe fn render_normals(&self, renderer: &Renderer, sprites: &mut [NormalDraw]) {
+        let (shaders, target) = match (&self.shaders, &self.normal_buffer) {
+            (Some(shaders), Some(target)) => (shaders, target),
+            _ => return,
+        };
+        target.bind();
+        // アルファが0のところは法線マップのないところ (光の向きを考えずに照らす)
+        gl::ClearColor(0.5, 0.5, 1.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::Disable(gl::BLEND);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::CULL_FACE);
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+
+        shaders.normal.use_program();
+        shaders.normal.set_mat4(c_str!("uView"), &renderer.view());
+        shaders
+            .normal
+            .set_mat4(c_str!("uProjection"), &renderer.projection());
+        shaders.normal.set_int(c_str!("uNormalMap"), 0);
+        sprites.sort_by_key(|sprite| sprite.layer);
+        for sprite in sprites.iter() {
+            shaders.normal.set_mat4(c_str!("uModel"), &sprite.model);
+            shaders
+                .normal
+                .set_bool(c_str!("uHasNormalMap"), sprite.normal_map.is_some());
+            if let Some(texture) = &sprite.normal_map {
+                texture.bind(0);
+            }
+            sprite.vertex.draw();
+        }
+    }
+
+    // 環境光で塗りつぶし、光を1つずつ加算する
+    unsafe fn render_lights(&mut self, renderer: &Renderer, draws: &[LightDraw], normals: bool) {
+        let (shaders, target, quad) = match (&self.shaders, &self.light_map, &self.light_quad) {
+            (Some(shaders), Some(target), Some(quad)) => (shaders, target, quad),
+            _ => return,
+        };
+        target.bind();
+        let [r, g, b] = self.ambient;
+        gl::ClearColor(r, g, b, 1.0);
+        gl::StencilMask(0xFF);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::CULL_FACE);
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+
+        let (view, projection) = (renderer.view(), renderer.projection());
+        shaders.shadow.use_program();
+        shaders.shadow.set_mat4(c_str!("uView"), &view);
+        shaders.shadow.set_mat4(c_str!("uProjection"), &projection);
+        shaders
+            .shadow
+            .set_mat4(c_str!("uModel"), &Matrix4::identity());
+
+        shaders.light.use_program();
+        shaders.light.set_mat4(c_str!("uView"), &view);
+        shaders.light.set_mat4(c_str!("uProjection"), &projection);
+        shaders.light.set_vector2(
+            c_str!("uTargetSize"),
+            &vec2(target.width() as f32, target.height() as f32),
+        );
+        shaders.light.set_bool(c_str!("uUseNormals"), normals);
+        shaders.light.set_int(c_str!("uNormals"), 0);
+        if let (true, Some(normal_buffer)) = (normals, &self.normal_buffer) {
+            normal_buffer.texture.bind(0);
+        }
+
+        let mut stats = LightingStats::default();
+        for draw in draws {
+            if draw.shadow_count > 0 {
+                if let Some(shadow_vertex) = &self.shadow_vertex {
+                    // 影をステンシルに1として描き、色は書き込まない
+                    gl::Enable(gl::STENCIL_TEST);
+                    gl::Clear(gl::STENCIL_BUFFER_BIT);
+                    gl::Disable(gl::BLEND);
+                    gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                    gl::StencilFunc(gl::ALWAYS, 1, 0xFF);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+                    shaders.shadow.use_program();
+                    shadow_vertex.draw_range(draw.shadow_first, draw.shadow_count);
+                    // ステンシルが0のところ (影でないところ) にだけ光を描く
+                    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                    gl::StencilFunc(gl::EQUAL, 0, 0xFF);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+                    shaders.light.use_program();
+                    stats.shadowed_lights += 1;
+                    stats.shadow_triangles += (draw.shadow_count / 3) as u32;
+                }
+            } else {
+                gl::Disable(gl::STENCIL_TEST);
+            }
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+
+            let light = &draw.light;
+            let model = Matrix4::from_translation(vec3(draw.position.x, draw.position.y, 0.0))
+                * Matrix4::from_scale(light.radius);
+            shaders.light.set_mat4(c_str!("uModel"), &model);
+            shaders
+                .light
+                .set_vector2(c_str!("uLightPosition"), &draw.position);
+            shaders.light.set_vector3(
+                c_str!("uLightColor"),
+                &(Vector3::from(light.color) * light.intensity),
+            );
+            shaders
+                .light
+                .set_float(c_str!("uLightRadius"), light.radius);
+            shaders
+                .light
+                .set_float(c_str!("uFalloff"), light.falloff.max(0.01));
+            shaders
+                .light
+                .set_float(c_str!("uLightHeight"), light.height.max(0.0));
+            quad.draw();
+            stats.lights += 1;
+        }
+
+        gl::Disable(gl::STENCIL_TEST);
+        gl::Disable(gl::BLEND);
+        stats.normal_sprites = self.stats.normal_sprites;
+        self.stats = stats;
+    }
+}
+
+// ライトマップを描き、シーンに重ねる描画コマンドを積む (Render)
+// 光は Transform2D と Light2D を、影は Transform2D と Occluder を持つエンティティから集める
+pub fn lighting2d_render_system(world: &mut World) {
+    if !world.has_resource::<Lighting2D>() {
+        return;
+    }
+    let mut lighting = world.resource_mut::<Lighting2D>();
+    if !lighting.enabled {
+        return;
+    }
+    let mut renderer = world.resource_mut::<Renderer>();
+    if let Err(e) = lighting.prepare(&mut renderer) {
+        eprintln!("failed to prepare 2D lighting: {}", e);
+        lighting.enabled = false;
+        return;
+    }
+
+    let mut lights = Vec::new();
+    world
+        .query::<(Read<Transform2D>, Read<Light2D>)>()
+        .for_each(|entity, (transform, light)| {
+            if light.enabled && light.radius > 0.0 && light.intensity > 0.0 {
+                lights.push((entity, transform.world_position(), *light));
+            }
+        });
+    let mut occluders = Vec::new();
+    world
+        .query::<(Read<Transform2D>, Read<Occluder>)>()
+        .for_each(|entity, (transform, occluder)| {
+            if !occluder.enabled {
+                return;
+            }
+            let matrix = transform.world_matrix();
+            for polygon in &occluder.polygons {
+                let points = polygon
+                    .iter()
+                    .map(|p| {
+                        let p = matrix * vec4(p.x, p.y, 0.0, 1.0);
+                        vec2(p.x, p.y)
+                    })
+                    .collect();
+                occluders.push((entity, points));
+            }
+        });
+
+    // 法線マップを持つスプライトがなければ、法線バッファーは描かない
+    let mut sprites = Vec::new();
+    let has_normal_maps = lighting.normal_mapping
+        && world
+            .try_read::<NormalMap>()
+            .is_some_and(|normal_maps| !normal_maps.is_empty());
+    if has_normal_maps {
+        world.query::<(Read<Transform2D>, Read<Sprite>)>().for_each(
+            |entity, (transform, sprite)| {
+                if !sprite.visible {
+                    return;
+                }
+                sprites.push(NormalDraw {
+                    vertex: sprite.vertex.clone(),
+                    model: transform.world_matrix(),
+                    layer: sprite.layer,
+                    normal_map: world
+                        .get::<NormalMap>(entity)
+                        .map(|normal_map| normal_map.texture.clone()),
+                });
+            },
+        );
+    }
+    lighting.stats.normal_sprites = sprites
+        .iter()
+        .filter(|sprite| sprite.normal_map.is_some())
+        .count() as u32;
+
+    let draws = lighting.build_shadows(&lights, &occluders);
+    unsafe {
+        if has_normal_maps {
+            lighting.render_normals(&renderer, &mut sprites);
+        }
+        lighting.render_lights(&renderer, &draws, has_normal_maps);
+        RenderTarget::unbind();
+        gl::Viewport(0, 0, lighting.viewport.0 as i32, lighting.viewport.1 as i32);
+    }
+
+    renderer.submit(DrawCommand {
+        vertex: lighting.screen_quad.clone().unwrap(),
+        material: lighting.material.unwrap(),
+        model: Matrix4::identity(),
+        layer: lighting.layer,
+    });
+}
+
+// 位置 (x, y) だけの頂点データ
+fn position_vertex(vertices: &[f32], usage: GLenum) -> Vertex {
+    Vertex::new(
+        mem::size_of_val(vertices) as GLsizeiptr,
+        vertices.as_ptr() as *const c_void,
+        usage,
+        vec![gl::FLOAT],
+        vec![2],
+        (2 * mem::size_of::<GLfloat>()) as GLsizei,
+        (vertices.len() / 2) as i32,
+    )
+}
@@ -0,0 +1,62 @@
+use cgmath::InnerSpace;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 光源 light から見て多角形 (ワールド座標) の裏側にある辺を、光の届く範囲 radius の外まで引き伸ばし、
+// 影になる三角形の頂点 (x, y) を out に追加する。追加した頂点の数を返す
+// 表側の辺は引き伸ばさないので、多角形そのものは照らされる
+pub fn shadow_geometry(
+    light: Vector2,
+    radius: f32,
+    polygon: &[Vector2],
+    out: &mut Vec<f32>,
+) -> usize {
+    if polygon.len() < 3 || !reaches(light, radius, polygon) {
+        return 0;
+    }
+    // 符号付き面積で向きを調べ、辺の外向きの法線をそろえる
+    let area: f32 = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    let orientation = if area >= 0.0 { 1.0 } else { -1.0 };
+
+    // 光源から 2 * radius 以上離れた点まで伸ばす
+    // 辺の中点も伸ばして5角形にすると、光から見て辺が広がっていても範囲の内側に切れ目ができない
+    let far = radius * 2.0;
+    let extrude = |p: Vector2| {
+        let direction = p - light;
+        if direction.magnitude2() > 0.0 {
+            p + direction.normalize() * far
+        } else {
+            p
+        }
+    };
+    let start = out.len();
+    for (&a, &b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        let edge = b - a;
+        let outward = Vector2::new(edge.y, -edge.x) * orientation;
+        if outward.dot(a - light) <= 0.0 {
+            continue; // 光の当たる側の辺
+        }
+        let (far_a, far_b) = (extrude(a), extrude(b));
+        let far_middle = extrude((a + b) * 0.5);
+        for p in [a, b, far_b, a, far_b, far_middle, a, far_middle, far_a] {
+            out.extend_from_slice(&[p.x, p.y]);
+        }
+    }
+    (out.len() - start) / 2
+}
+
+// 多角形を囲む箱が光の届く範囲にかかっているか
+fn reaches(light: Vector2, radius: f32, polygon: &[Vector2]) -> bool {
+    let (mut min, mut max) = (polygon[0], polygon[0]);
+    for p in polygon {
+        min = Vector2::new(min.x.min(p.x), min.y.min(p.y));
+        max = Vector2::new(max.x.max(p.x), max.y.max(p.y));
+    }
+    let closest = Vector2::new(light.x.clamp(min.x, max.x), light.y.clamp(min.y, max.y));
+    (closest - light).magnitude2() <= radius * radius
+}
@@ -1,26 +1,61 @@
-use std::mem;
-use std::os::raw::c_void;
-use std::time::Duration;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use c_str_macro::c_str;
-use cgmath::{perspective, vec3};
+use cgmath::{perspective, vec2};
 // use cgmath::prelude::SquareMatrix;
 
-use gl::types::{GLfloat, GLsizei, GLsizeiptr};
 use imgui::im_str;
 
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Scancode};
 // use sdl2::pixels::Color;
 
 // use cgmath::num_traits::Float;
 use std::f32;
 
+mod assets;
+mod audio;
+mod camera2d;
+mod camera3d;
+mod character;
+mod components;
+mod debug_draw;
+mod ecs;
+mod font;
+mod lighting;
+mod lighting2d;
+mod material;
+mod mesh;
+mod particles;
+mod physics;
+mod render_target;
+mod renderer;
 mod shader;
+mod systems;
+mod texture;
+mod tilemap;
+mod transform;
 mod vertex;
 
-use shader::Shader;
-use vertex::Vertex;
+use assets::{Assets, Config, LoadGroup};
+use audio::{Attenuation, Audio, AudioClip, AudioEmitter, MusicStream, Rolloff, SoundParams};
+use camera2d::{Camera2D, Rect};
+use camera3d::{CameraMode, DebugCamera};
+use character::{CharacterConfig, CharacterController, CharacterInput};
+use components::{Pickup, Score, Sprite, Velocity};
+use debug_draw::{DebugCategory, DebugDraw};
+use ecs::{Events, Schedule, Stage, World};
+use font::{Font, Fonts, Text, TextAlign, TextStyle};
+use lighting::{DirectionalLight, PointLight, SpotLight};
+use lighting2d::{Light2D, Lighting2D, NormalMap, Occluder};
+use material::MaterialLibrary;
+use mesh::{MeshData, Model};
+use particles::{ParticleConfig, ParticleEmitter};
+use physics::{BodyHandle, Collider, CollisionEvent, PhysicsBody, PhysicsWorld, RigidBody, Shape};
+use renderer::Renderer;
+use texture::Texture2D;
+use tilemap::{TiledMap, Tilemap};
+use transform::Transform2D;
 
 #[allow(dead_code)]
 type Point3 = cgmath::Point3<f32>;
@@ -31,9 +66,24 @@ type Matrix4 = cgmath::Matrix4<f32>;
 
 const WINDOW_WIDTH: u32 = 900;
 const WINDOW_HEIGHT: u32 = 480;
-const FLOAT_NUM: usize = 3;
-const VERTEX_NUM: usize = 36;
-const BUF_SIZE: usize = FLOAT_NUM * VERTEX_NUM;
+// HUDのフォント (DejaVu Sansにない日本語の文字は Noto Sans JP から探す)
+const HUD_FONTS: [&str; 2] = ["rsc/font/DejaVuSans.ttf", "rsc/font/NotoSansJP-Regular.otf"];
+// Mキーで切り替えるBGM
+const MUSIC_TRACKS: [&str; 2] = ["rsc/music/field.ogg", "rsc/music/cave.ogg"];
+const MUSIC_CROSSFADE: f32 = 2.0;
+// キャラクターの調整値 (ゲームを動かしたまま書き換えると反映される)
+const CHARACTER_CONFIG: &str = "rsc/config/character.ron";
+// パーティクルの設定 (エディターで調整して保存できる)
+const FIRE_PARTICLES: &str = "rsc/particles/fire.ron";
+const DUST_PARTICLES: &str = "rsc/particles/dust.ron";
+// 下からすり抜けて上に乗れる足場のレイヤー
+const ONE_WAY_LAYER: u32 = 2;
+
+// ロード画面を表示している間は、ゲームを進めない
+enum GameState {
+    Loading(LoadGroup), // グループのアセットがすべて読み込まれるのを待つ
+    Playing,
+}
 
 fn main() {
     // SDL本体の初期化
@@ -51,8 +101,9 @@ fn main() {
         // Don't use deprecated OpenGL functions (OpenGLコンテキストのプロファイルを指定)
         gl_attribute.set_context_profile(sdl2::video::GLProfile::Core);
 
-        // Set the OpenGL context version (OpenGLコンテキストのバージョンを指定 : OpenGL 3.2)
-        gl_attribute.set_context_version(3, 2);
+        // Set the OpenGL context version (OpenGLコンテキストのバージョンを指定 : OpenGL 3.3)
+        // インスタンスごとの頂点属性 (glVertexAttribDivisor) は3.3から使える
+        gl_attribute.set_context_version(3, 3);
         let (major, minor) = gl_attribute.context_version();
         println!("init OpenGL: version={}.{}", major, minor);
     } // 変数 gl_attribute が自動で破棄されるように、ブロック{}でスコープを明示的に指定している
@@ -83,65 +134,46 @@ fn main() {
     let _gl_context = window.gl_create_context().unwrap(); // OpenGLコンテキストを作成する
     gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as _); // OpenGL APIの関数ポインタを取得する
 
-    let shader = Shader::new("rsc/shader/shader.vs", "rsc/shader/shader.fs");
-
-    // set buffer
-    #[rustfmt::skip]
-    let buffer_array: [f32; BUF_SIZE] = [
-        // 1
-        0.0, 0.0, 0.0,
-        0.0, 1.0, 0.0,
-        1.0, 1.0, 0.0,
-
-        0.0, 0.0, 0.0,
-        1.0, 1.0, 0.0,
-        1.0, 0.0, 0.0,
-
-        // 2
-        0.0, 0.0, 1.0,
-        0.0, 0.0, 0.0,
-        1.0, 0.0, 0.0,
-
-        0.0, 0.0, 1.0,
-        1.0, 0.0, 0.0,
-        1.0, 0.0, 1.0,
-
-        // 3
-        0.0, 1.0, 1.0,
-        0.0, 0.0, 1.0,
-        1.0, 0.0, 1.0,
-
-        0.0, 1.0, 1.0,
-        1.0, 0.0, 1.0,
-        1.0, 1.0, 1.0,
-
-        // 4
-        0.0, 1.0, 0.0,
-        0.0, 1.0, 1.0,
-        1.0, 1.0, 1.0,
-
-        0.0, 1.0, 0.0,
-        1.0, 1.0, 1.0,
-        1.0, 1.0, 0.0,
-
-        // 5
-        1.0, 0.0, 1.0,
-        1.0, 0.0, 0.0,
-        1.0, 1.0, 0.0,
-
-        1.0, 0.0, 1.0,
-        1.0, 1.0, 0.0,
-        1.0, 1.0, 1.0,
-
-        // 6
-        0.0, 1.0, 1.0,
-        0.0, 1.0, 0.0,
-        0.0, 0.0, 0.0,
-
-        0.0, 1.0, 1.0,
-        0.0, 0.0, 0.0,
-        0.0, 0.0, 1.0,
-    ];
+    // アセットのパスは rsc ディレクトリを含むルートからの相対パスで指定する
+    // (実行ファイルの場所から探すので、別のディレクトリから起動しても読み込める)
+    let assets = Assets::locate("rsc");
+    println!("asset root: {}", assets.root().display());
+    // 大きな画像は先にバックグラウンドで読み込み始めておく (マテリアルやマップが使うときには読み込み済みになる)
+    let tiles_texture = assets.load_async::<Texture2D>("rsc/texture/tiles.png");
+
+    // rsc/material 以下のマテリアルファイルをすべて読み込み、名前で参照できるようにする
+    let mut materials = MaterialLibrary::new(assets.clone());
+    materials
+        .load_dir("rsc/material")
+        .unwrap_or_else(|e| panic!("failed to load materials: {}", e));
+    let mut renderer = Renderer::new(materials);
+    // 立方体などを照らす光 (3Dのカメラで見ると分かりやすい)
+    renderer
+        .lighting
+        .add_directional(DirectionalLight {
+            direction: [-0.3, -0.5, -1.0],
+            color: [1.0, 1.0, 1.0],
+            intensity: 0.7,
+        })
+        .add_point(PointLight {
+            position: [2.5, 2.0, 2.0],
+            color: [1.0, 0.6, 0.2],
+            intensity: 1.5,
+            range: 6.0,
+        })
+        .add_spot(SpotLight {
+            position: [0.5, 0.5, 4.0],
+            direction: [0.0, 0.0, -1.0],
+            color: [0.4, 1.0, 0.6],
+            intensity: 1.0,
+            range: 10.0,
+            inner_angle: 10.0,
+            outer_angle: 20.0,
+        });
+    let cube_material = renderer
+        .materials
+        .id("cube")
+        .expect("material not found: cube");
 
     // キャンバスの取得と塗りつぶし
     // let mut canvas = window.into_canvas().build().unwrap();
@@ -149,15 +181,8 @@ fn main() {
     // canvas.clear(); // 指定した色で塗りつぶしてバッファーをクリアする
     // canvas.present(); // バッファーを切り替えて描画内容を画面に表示する
 
-    let vertex = Vertex::new(
-        (BUF_SIZE * mem::size_of::<GLfloat>()) as GLsizeiptr, // 頂点データのデータサイズ
-        buffer_array.as_ptr() as *const c_void,               // 頂点データへのポインタ
-        gl::STATIC_DRAW,                                      // 頂点データへのアクセス頻度
-        vec![gl::FLOAT],        // 各頂点属性のデータ型を格納したベクター型
-        vec![FLOAT_NUM as i32], // 各頂点属性のデータサイズを格納したベクター型
-        FLOAT_NUM as i32 * mem::size_of::<GLfloat>() as GLsizei, // 各頂点データの始まりが何個おきに並んでいるか
-        VERTEX_NUM as i32,                                       // 頂点の数
-    );
+    // 原点を角にした1辺1の立方体 (Transform2D の pivot で中心を合わせる)
+    let vertex = Rc::new(MeshData::cube(1.0).translated([0.5, 0.5, 0.5]).upload());
 
     // init imgui
     let mut imgui_context = imgui::Context::create();
@@ -166,27 +191,362 @@ fn main() {
 
     // init imgui sdl2
     let mut imgui_sdl2_context = imgui_sdl2::ImguiSdl2::new(&mut imgui_context, &window);
-    let renderer = imgui_opengl_renderer::Renderer::new(&mut imgui_context, |s| {
+    let imgui_renderer = imgui_opengl_renderer::Renderer::new(&mut imgui_context, |s| {
         video_subsystem.gl_get_proc_address(s) as _
     });
 
-    let mut depth_test: bool = true;
-    let mut blend: bool = true;
-    let mut wireframe: bool = true;
-    let mut culling: bool = true;
-    let mut camera_x: f32 = 3.0f32;
-    let mut camera_y: f32 = -3.0f32;
-    let mut camera_z: f32 = 3.0f32;
+    // ゲームのオブジェクトはエンティティとコンポーネントで表す
+    let mut world = World::new();
+    world.insert_resource(renderer);
+    world.insert_resource(PhysicsWorld::new(vec2(0.0, -9.8)));
+    world.insert_resource(Events::<CollisionEvent>::new());
+    world.insert_resource(Score::default());
+    world.insert_resource(DebugDraw::new());
+    world.insert_resource(Lighting2D::new(WINDOW_WIDTH, WINDOW_HEIGHT));
+    let cube = world.spawn();
+    world.insert(
+        cube,
+        Transform2D::new(vec2(0.5, 0.5)).with_pivot(vec2(0.5, 0.5)), // 立方体の中心を軸に回転させる
+    );
+    world.insert(
+        cube,
+        Velocity {
+            linear: vec2(0.0, 0.0),
+            angular: cgmath::Rad(f32::consts::PI / 3.0), // 1秒あたり60度
+        },
+    );
+    world.insert(
+        cube,
+        Sprite {
+            vertex: vertex.clone(),
+            material: cube_material,
+            layer: 0,
+            visible: true,
+        },
+    );
+    world.insert(cube, Occluder::rect(vec2(0.0, 0.0), vec2(1.0, 1.0)));
+    // OBJから読み込んだモデルを立方体の隣で回す
+    let pyramid_model = assets
+        .load::<Model>("rsc/model/pyramid.obj")
+        .unwrap_or_else(|e| panic!("failed to load model: {}", e));
+    let pyramid = world.spawn();
+    world.insert(
+        pyramid,
+        Transform2D::new(vec2(2.0, 0.5)).with_pivot(vec2(0.5, 0.5)),
+    );
+    world.insert(
+        pyramid,
+        Velocity {
+            linear: vec2(0.0, 0.0),
+            angular: cgmath::Rad(-f32::consts::PI / 4.0),
+        },
+    );
+    world.insert(
+        pyramid,
+        Sprite {
+            vertex: pyramid_model.meshes[0].vertex.clone(),
+            material: cube_material,
+            layer: 0,
+            visible: true,
+        },
+    );
+
+    // 足場はTiledで作ったマップから読み込み、solid / one_way のタイルを当たり判定にする
+    let mut tilemap = {
+        let mut renderer = world.resource_mut::<Renderer>();
+        Tilemap::load("rsc/map/sample.tmx", &mut renderer.materials)
+            .unwrap_or_else(|e| panic!("failed to load tilemap: {}", e))
+    };
+    let map_transform = Transform2D::new(vec2(-8.0, -2.5));
+    let player_spawn = tilemap
+        .map
+        .object_layer("objects")
+        .and_then(|layer| layer.object("player_spawn"))
+        .map(|object| map_transform.position() + tilemap.map.to_world(object.position))
+        .unwrap_or(vec2(-2.0, -0.5))
+        + vec2(0.0, 0.5); // 足元の位置なので、キャラクターの中心まで持ち上げる
+    let mut map_colliders = build_map_colliders(
+        &mut world.resource_mut::<PhysicsWorld>(),
+        &tilemap.map,
+        &map_transform,
+    );
+    // 2Dカメラはキャラクターを追いかけ、マップの外を映さない
+    let mut camera2d = Camera2D::new(WINDOW_WIDTH, WINDOW_HEIGHT);
+    camera2d.zoom = 64.0;
+    camera2d.follow_speed = 5.0;
+    camera2d.dead_zone = vec2(1.0, 0.5);
+    camera2d.bounds = Some(Rect::new(
+        map_transform.position(),
+        map_transform.position() + vec2(tilemap.map.width as f32, tilemap.map.height as f32),
+    ));
+    camera2d.position = player_spawn;
+    world.insert_resource(camera2d);
+    let map = world.spawn();
+    world.insert(map, map_transform);
+    tilemap.layer = -10; // キャラクターより奥に描画する
+    world.insert(map, build_map_occluder(&tilemap.map));
+    world.insert(map, tilemap);
+
+    // マップを照らす光 (2Dカメラで見ると分かりやすい)
+    // 光のところでは炎が燃える
+    let mut fire_config = assets
+        .load::<Config<ParticleConfig>>(FIRE_PARTICLES)
+        .unwrap_or_else(|e| panic!("failed to load config: {}", e));
+    let mut fires = Vec::new();
+    for (position, color) in [
+        (vec2(-6.0, 2.5), [0.4, 0.6, 1.0]),
+        (vec2(5.5, 2.5), [1.0, 0.5, 0.2]),
+    ] {
+        let light = world.spawn();
+        world.insert(light, Transform2D::new(position));
+        world.insert(light, Light2D::new(color, 7.0).with_intensity(1.2));
+        world.insert(light, ParticleEmitter::new((**fire_config).clone()));
+        fires.push(light);
+    }
+    // 法線マップで凹凸を付けたレンガの壁 (キャラクターの奥に描く)
+    let bricks_material = world
+        .resource::<Renderer>()
+        .materials
+        .id("bricks")
+        .expect("material not found: bricks");
+    let wall = world.spawn();
+    world.insert(wall, Transform2D::new(vec2(-5.0, -0.5)));
+    world.insert(
+        wall,
+        Sprite {
+            vertex: Rc::new(
+                MeshData::quad(2.0, 2.0)
+                    .translated([1.0, 1.0, 0.0])
+                    .upload(),
+            ),
+            material: bricks_material,
+            layer: -5,
+            visible: true,
+        },
+    );
+    world.insert(
+        wall,
+        NormalMap {
+            texture: assets
+                .load::<Texture2D>("rsc/texture/bricks_n.png")
+                .unwrap_or_else(|e| panic!("failed to load texture: {}", e)),
+        },
+    );
+
+    // 矢印キーとスペースキーで動かすキャラクター (立方体を縦長にして表示する)
+    let mut character_config = assets
+        .load::<Config<CharacterConfig>>(CHARACTER_CONFIG)
+        .unwrap_or_else(|e| panic!("failed to load config: {}", e));
+    let player = world.spawn();
+    let player_body = world.resource_mut::<PhysicsWorld>().add_body(
+        RigidBody::kinematic(
+            player_spawn,
+            Collider::new(Shape::Aabb {
+                half_extents: vec2(0.25, 0.5),
+            }),
+        )
+        .with_entity(player),
+    );
+    world.insert(
+        player,
+        Transform2D::new(player_spawn)
+            .with_pivot(vec2(0.5, 0.5))
+            .with_scale(vec2(0.5, 1.0)),
+    );
+    world.insert(player, PhysicsBody(player_body));
+    world.insert(
+        player,
+        CharacterController::new(CharacterConfig {
+            one_way_mask: ONE_WAY_LAYER,
+            ..**character_config
+        }),
+    );
+    world.insert(player, CharacterInput::default());
+    // キャラクターが持つ明かり (自分の形では影を作らない)
+    world.insert(
+        player,
+        Light2D::new([1.0, 0.9, 0.7], 4.0)
+            .with_intensity(1.5)
+            .with_height(0.5),
+    );
+    world.insert(player, Occluder::rect(vec2(0.0, 0.0), vec2(1.0, 1.0)));
+    // 着地したときに足元から出す砂ぼこり (キャラクターの子にして一緒に動かす)
+    let mut dust_config = assets
+        .load::<Config<ParticleConfig>>(DUST_PARTICLES)
+        .unwrap_or_else(|e| panic!("failed to load config: {}", e));
+    let dust = world.spawn();
+    world.insert(dust, Transform2D::new(vec2(0.5, 0.0)));
+    world.insert(dust, ParticleEmitter::new((**dust_config).clone()).paused());
+    transform::set_parent(&mut world, dust, Some(player));
+    world.insert(
+        player,
+        Sprite {
+            vertex: vertex.clone(),
+            material: cube_material,
+            layer: 0,
+            visible: true,
+        },
+    );
+    // 回りながら浮かぶコイン (センサーに触れると拾う)
+    // 剛体の回転がTransform2Dに書き戻されるので、回す見た目は子にする
+    for position in [vec2(-4.5, 0.0), vec2(-0.5, 0.0), vec2(2.0, 2.0)] {
+        let coin = world.spawn();
+        let coin_body = world.resource_mut::<PhysicsWorld>().add_body(
+            RigidBody::fixed(
+                position,
+                Collider::new(Shape::Circle { radius: 0.2 }).with_sensor(true),
+            )
+            .with_entity(coin),
+        );
+        world.insert(coin, Transform2D::new(position));
+        world.insert(coin, PhysicsBody(coin_body));
+        world.insert(coin, Pickup { value: 1 });
+        let coin_sprite = world.spawn();
+        world.insert(
+            coin_sprite,
+            Transform2D::new(vec2(0.0, 0.0))
+                .with_pivot(vec2(0.5, 0.5))
+                .with_scale(vec2(0.3, 0.3)),
+        );
+        world.insert(
+            coin_sprite,
+            Velocity {
+                linear: vec2(0.0, 0.0),
+                angular: cgmath::Rad(f32::consts::PI),
+            },
+        );
+        world.insert(
+            coin_sprite,
+            Sprite {
+                vertex: vertex.clone(),
+                material: cube_material,
+                layer: 0,
+                visible: true,
+            },
+        );
+        transform::set_parent(&mut world, coin_sprite, Some(coin));
+    }
+
+    // システムは登録したステージで毎フレーム実行される
+    let mut schedule = Schedule::new(1.0 / 60.0);
+    schedule
+        .add_system(Stage::FixedUpdate, systems::movement_system)
+        .add_system(Stage::FixedUpdate, character::character_controller_system)
+        .add_system(Stage::FixedUpdate, physics::physics_step_system)
+        .add_system(Stage::FixedUpdate, systems::pickup_system)
+        .add_system(Stage::PostUpdate, transform::transform_propagate_system)
+        .add_system(Stage::PostUpdate, particles::particle_update_system)
+        .add_system(Stage::PostUpdate, audio::spatial_audio_system)
+        .add_system(Stage::Render, tilemap::tilemap_render_system)
+        .add_system(Stage::Render, systems::sprite_render_system)
+        .add_system(Stage::Render, particles::particle_render_system)
+        .add_system(Stage::Render, lighting2d::lighting2d_render_system)
+        .add_system(Stage::Render, debug_draw::debug_draw_render_system)
+        .add_system(Stage::Render, font::text_render_system);
+
+    // 観測者の位置と見ているものの位置
+    let mut debug_camera =
+        DebugCamera::new(Point3::new(3.0, -3.0, 3.0), Point3::new(0.5, 0.5, 0.5));
+
+    // 画面に重ねて表示する文字
+    let mut fonts = Fonts::new(WINDOW_WIDTH, WINDOW_HEIGHT);
+    // フォントは Assets を通して読む (アーカイブにまとめたものも読める)
+    let font_data = HUD_FONTS
+        .iter()
+        .map(|path| assets.read(path))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| panic!("failed to load font: {}", e));
+    let hud_font = fonts.insert(
+        "hud",
+        Font::from_bytes(font_data, 20.0).unwrap_or_else(|e| panic!("failed to load font: {}", e)),
+    );
+    let small_font = fonts.insert(
+        "small",
+        Font::from_bmfont_with(&assets.path("rsc/font/dejavu_16.fnt"), &|path| {
+            assets.read(path)
+        })
+        .unwrap_or_else(|e| panic!("failed to load font: {}", e)),
+    );
+    world.insert_resource(fonts);
+    let title = world.spawn();
+    world.insert(title, Transform2D::new(vec2(16.0, 16.0)));
+    world.insert(
+        title,
+        Text::new(
+            hud_font,
+            "Tilemap Demo\n矢印キーで移動、スペースでジャンプ、下を押しながらスペースで足場から降りる。",
+        )
+        .with_style(TextStyle {
+            max_width: Some(360.0),
+            ..TextStyle::default()
+        })
+        .with_color([0.1, 0.1, 0.1, 1.0]),
+    );
+    let fps_text = world.spawn();
+    world.insert(
+        fps_text,
+        Transform2D::new(vec2(WINDOW_WIDTH as f32 - 16.0, 16.0)),
+    );
+    world.insert(
+        fps_text,
+        Text::new(small_font, "")
+            .with_style(TextStyle {
+                align: TextAlign::Right,
+                ..TextStyle::default()
+            })
+            .with_pivot(vec2(1.0, 0.0))
+            .with_color([0.1, 0.1, 0.1, 1.0]),
+    );
+    let spawn_label = world.spawn();
+    world.insert(spawn_label, Transform2D::new(player_spawn + vec2(0.0, 1.2)));
+    world.insert(
+        spawn_label,
+        Text::new(hud_font, "START / スタート")
+            .with_pivot(vec2(0.5, 1.0))
+            .with_color([0.8, 0.3, 0.1, 1.0])
+            .in_world(64.0)
+            .with_layer(0),
+    );
+    let mut fps = 60.0;
+
+    let mut use_camera2d = false;
+
+    // 効果音とBGM (音の出力先がなければダミードライバーで動かす)
+    let mut audio =
+        Audio::new(&sdl_context).unwrap_or_else(|e| panic!("failed to init audio: {}", e));
+    // 効果音はバックグラウンドでデコードし、そろうまでロード画面を表示する
+    let jump_sound = assets.load_async::<AudioClip>("rsc/sound/jump.wav");
+    let land_sound = assets.load_async::<AudioClip>("rsc/sound/land.wav");
+    let hum_sound = assets.load_async::<AudioClip>("rsc/sound/hum.wav");
+    let startup_assets = LoadGroup::new()
+        .with(&tiles_texture)
+        .with(&jump_sound)
+        .with(&land_sound)
+        .with(&hum_sound);
+    let mut state = GameState::Loading(startup_assets.clone());
+    let mut music_track = 0;
+    open_music(&assets, MUSIC_TRACKS[music_track])
+        .and_then(|stream| audio.play_music_stream(stream, true, 0.0))
+        .unwrap_or_else(|e| panic!("failed to play music: {}", e));
+    world.insert_resource(audio);
+    let mut was_grounded = true;
+    let mut fall_speed = 0.0f32; // 着地の音の大きさに使う
 
     let mut event_pump = sdl_context.event_pump().unwrap();
-    let mut loops: i32 = 0;
+    let mut last_frame = Instant::now();
     'running: loop {
+        let now = Instant::now();
+        let delta_time = (now - last_frame).as_secs_f32();
+        last_frame = now;
+
+        let mut space_pressed = false;
         for event in event_pump.poll_iter() {
             imgui_sdl2_context.handle_event(&mut imgui_context, &event);
             if imgui_sdl2_context.ignore_event(&event) {
                 continue;
             }
-            // イベントキューにたまってるイベントをひとつづつ処理する
+            debug_camera.handle_event(&event); // カメラの操作
+                                               // イベントキューにたまってるイベントをひとつづつ処理する
             match event {
                 // 終了イベントかエスケープキーの押下イベントが発生したとき、runningラベルのついたループを抜ける
                 Event::Quit { .. }
@@ -194,37 +554,211 @@ fn main() {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Space),
+                    repeat: false,
+                    ..
+                } => space_pressed = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    repeat: false,
+                    ..
+                } => {
+                    music_track = (music_track + 1) % MUSIC_TRACKS.len();
+                    let played =
+                        open_music(&assets, MUSIC_TRACKS[music_track]).and_then(|stream| {
+                            world.resource_mut::<Audio>().play_music_stream(
+                                stream,
+                                true,
+                                MUSIC_CROSSFADE,
+                            )
+                        });
+                    if let Err(e) = played {
+                        eprintln!("failed to play music: {}", e);
+                    }
+                }
                 _ => {}
             }
         }
-        // canvas.present();
-        unsafe {
-            // C言語由来の処理をunsafe{}で囲む
-            if depth_test {
-                gl::Enable(gl::DEPTH_TEST);
-            } else {
-                gl::Disable(gl::DEPTH_TEST);
+        assets.update(); // バックグラウンドで読み込んだアセットを仕上げ、変わったファイルを読み込み直す
+        {
+            // 読み込み直したアセットを使っているものに知らせる
+            let mut renderer = world.resource_mut::<Renderer>();
+            renderer.materials.hot_reload();
+            let mut tilemap = world.get_mut::<Tilemap>(map).unwrap();
+            match tilemap.hot_reload(&mut renderer.materials) {
+                Ok(true) => {
+                    let mut physics = world.resource_mut::<PhysicsWorld>();
+                    for body in map_colliders.drain(..) {
+                        physics.remove_body(body);
+                    }
+                    let transform = world.get::<Transform2D>(map).unwrap();
+                    map_colliders = build_map_colliders(&mut physics, &tilemap.map, &transform);
+                    *world.get_mut::<Occluder>(map).unwrap() = build_map_occluder(&tilemap.map);
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("failed to reload tilemap: {}", e),
             }
-
-            if blend {
-                gl::Enable(gl::BLEND);
-                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-            } else {
-                gl::Disable(gl::BLEND);
+            if character_config.refresh() {
+                let mut controller = world.get_mut::<CharacterController>(player).unwrap();
+                controller.config = CharacterConfig {
+                    one_way_mask: ONE_WAY_LAYER,
+                    ..**character_config
+                };
             }
-
-            if wireframe {
-                gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-            } else {
-                gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            if fire_config.refresh() {
+                for &fire in &fires {
+                    let mut emitter = world.get_mut::<ParticleEmitter>(fire).unwrap();
+                    emitter.set_config((**fire_config).clone());
+                }
             }
-
-            if culling {
-                gl::Enable(gl::CULL_FACE);
-            } else {
-                gl::Disable(gl::CULL_FACE);
+            if dust_config.refresh() {
+                let mut emitter = world.get_mut::<ParticleEmitter>(dust).unwrap();
+                emitter.set_config((**dust_config).clone());
+            }
+        }
+        if let GameState::Loading(group) = &state {
+            if !group.is_done() {
+                unsafe {
+                    gl::Viewport(0, 0, WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32);
+                    gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+                }
+                imgui_sdl2_context.prepare_frame(
+                    imgui_context.io_mut(),
+                    &window,
+                    &event_pump.mouse_state(),
+                );
+                let ui = imgui_context.frame();
+                imgui::Window::new(im_str!("Loading"))
+                    .position(
+                        [WINDOW_WIDTH as f32 * 0.5, WINDOW_HEIGHT as f32 * 0.5],
+                        imgui::Condition::Always,
+                    )
+                    .position_pivot([0.5, 0.5])
+                    .size([320.0, 0.0], imgui::Condition::Always)
+                    .title_bar(false)
+                    .resizable(false)
+                    .movable(false)
+                    .build(&ui, || {
+                        imgui::ProgressBar::new(group.progress())
+                            .size([-1.0, 20.0])
+                            .overlay_text(&im_str!("{}/{}", group.finished_count(), group.len()))
+                            .build(&ui);
+                        for path in group.loading() {
+                            ui.text(path.rsplit('/').next().unwrap_or(path));
+                        }
+                    });
+                imgui_sdl2_context.prepare_render(&ui, &window);
+                imgui_renderer.render(ui);
+                window.gl_swap_window();
+                ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+                continue;
+            }
+            let failures = group.failures();
+            if !failures.is_empty() {
+                panic!("failed to load assets: {:?}", failures);
+            }
+            // 回る立方体から鳴り続ける音 (離れると小さくなり、左右の位置で聞こえ方が変わる)
+            world.insert(
+                cube,
+                AudioEmitter::new((*hum_sound).clone())
+                    .with_params(SoundParams::default().with_looping(true).with_volume(0.5))
+                    .with_attenuation(Attenuation::new(
+                        Rolloff::Inverse { factor: 1.0 },
+                        1.0,
+                        12.0,
+                    ))
+                    .with_autoplay(true),
+            );
+            state = GameState::Playing;
+        }
+        debug_camera.update(delta_time, &event_pump.keyboard_state()); // キーボードによるカメラの移動
+        {
+            let keyboard = event_pump.keyboard_state();
+            let mut input = world.get_mut::<CharacterInput>(player).unwrap();
+            input.move_x = 0.0;
+            if keyboard.is_scancode_pressed(Scancode::Left) {
+                input.move_x -= 1.0;
+            }
+            if keyboard.is_scancode_pressed(Scancode::Right) {
+                input.move_x += 1.0;
+            }
+            input.jump_held = keyboard.is_scancode_pressed(Scancode::Space);
+            // 下を押しながらジャンプすると足場から降りる
+            if space_pressed {
+                if keyboard.is_scancode_pressed(Scancode::Down) {
+                    input.drop_pressed = true;
+                } else {
+                    input.jump_pressed = true;
+                }
+            }
+        }
+        schedule.run_update(&mut world, delta_time); // ゲームの状態を更新する
+        {
+            let position = world.get::<Transform2D>(player).unwrap().position();
+            let mut camera2d = world.resource_mut::<Camera2D>();
+            camera2d.follow(position);
+            camera2d.update(delta_time);
+
+            if delta_time > 0.0 {
+                fps += (1.0 / delta_time - fps) * 0.1; // 表示がちらつかないように少しずつ追いかける
+            }
+            world.get_mut::<Text>(fps_text).unwrap().set_text(&format!(
+                "{:.0} FPS\n{} entities\n{} coins",
+                fps,
+                world.entities().count(),
+                world.resource::<Score>().0
+            ));
+
+            // キャラクターの速度と接地状態
+            let controller = world.get::<CharacterController>(player).unwrap();
+
+            // ジャンプと着地の効果音 (着地は落ちる速さで音量とピッチを変える)
+            let grounded = controller.is_grounded();
+            let mut audio = world.resource_mut::<Audio>();
+            if was_grounded && !grounded && controller.velocity.y > 0.0 {
+                audio.play(
+                    &jump_sound,
+                    SoundParams::default().with_volume(0.6).with_priority(1),
+                );
+            } else if !was_grounded && grounded {
+                let impact = (fall_speed / 10.0).clamp(0.2, 1.0);
+                audio.play_at(
+                    &land_sound,
+                    SoundParams::default()
+                        .with_volume(impact)
+                        .with_pitch(1.2 - impact * 0.4),
+                    position,
+                    &Attenuation::default(),
+                );
+                let mut emitter = world.get_mut::<ParticleEmitter>(dust).unwrap();
+                emitter.emit((impact * 16.0) as u32);
             }
+            was_grounded = grounded;
+            fall_speed = -controller.velocity.y;
+            let mut debug_draw = world.resource_mut::<DebugDraw>();
+            debug_draw.arrow(
+                DebugCategory::Gameplay,
+                position,
+                position + controller.velocity * 0.2,
+                debug_draw::MAGENTA,
+            );
+            debug_draw.text(
+                DebugCategory::Gameplay,
+                position + vec2(0.3, 0.6),
+                if controller.is_grounded() {
+                    "grounded"
+                } else {
+                    "airborne"
+                },
+                debug_draw::RED,
+            );
+        }
 
+        // canvas.present();
+        unsafe {
+            // C言語由来の処理をunsafe{}で囲む
             gl::Viewport(0, 0, WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32);
 
             // clear screen
@@ -232,91 +766,175 @@ fn main() {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT); // COLOR_BUFFER_BIT : 描画する際にカラーバッファーを初期化する
                                                                     // DEPTH_BUFFER_BIT : 描画する際にデプスバッファーを初期化する(DEPTH_TESTを有効にするときは忘れずに！)
 
-            // init matrice for model, view and projection
-            // let model_matrix = Matrix4::identity();
-            let buf: Vector3 = vec3( 0.5, 0.5, 0.0 );
-            let model_matrix =
-                Matrix4::from_translation(buf) * Matrix4::from_angle_z(cgmath::Rad(f32::consts::PI) * loops as f32 / 180f32) * Matrix4::from_translation(-buf);
-            loops += 1;
-            let view_matrix = Matrix4::look_at_rh(
-                Point3 {
-                    // 観測者の位置
-                    x: camera_x,
-                    y: camera_y,
-                    z: camera_z,
-                },
-                Point3 {
-                    // 見ているものの位置
-                    x: 0.5,
-                    y: 0.5,
-                    z: 0.5,
-                },
-                Vector3 {
-                    // 上下方向
-                    x: 0.0,
-                    y: 0.0,
-                    z: 1.0,
-                },
-            );
-            let projection_matrix: Matrix4 = perspective(
-                cgmath::Deg(45.0f32),
-                WINDOW_WIDTH as f32 / WINDOW_HEIGHT as f32,
-                0.1,
-                100.0,
-            );
-
-            // shader use matrices (set_mat4メソッドで行列をユニフォーム変数としてシェーダーの中で使えるようにする)
-            shader.use_program();
-            shader.set_mat4(c_str!("uModel"), &model_matrix);
-            shader.set_mat4(c_str!("uView"), &view_matrix);
-            shader.set_mat4(c_str!("uProjection"), &projection_matrix);
-
-            vertex.draw(); // OpenGLによる描画
+            // init matrice for view and projection
+            let (view_matrix, projection_matrix) = if use_camera2d {
+                let camera2d = world.resource::<Camera2D>();
+                (camera2d.view_matrix(), camera2d.projection_matrix())
+            } else {
+                let projection_matrix: Matrix4 = perspective(
+                    cgmath::Deg(45.0f32),
+                    WINDOW_WIDTH as f32 / WINDOW_HEIGHT as f32,
+                    0.1,
+                    100.0,
+                );
+                (debug_camera.view_matrix(), projection_matrix)
+            };
+
+            // 描画コマンドをキューに積み、並べ替えてからまとめて描画する
+            world
+                .resource_mut::<Renderer>()
+                .begin_frame(&view_matrix, &projection_matrix);
+            schedule.run_render(&mut world);
+            let mut renderer = world.resource_mut::<Renderer>();
+            renderer.flush();
+            let render_stats = renderer.stats();
 
             imgui_sdl2_context.prepare_frame(
                 imgui_context.io_mut(),
                 &window,
-                &event_pump.mouse_state()
+                &event_pump.mouse_state(),
             );
             let ui = imgui_context.frame();
+            let renderer = &mut *renderer;
+            let render_state = &mut renderer.materials.get_mut(cube_material).render_state;
+            let lighting = &mut renderer.lighting;
             imgui::Window::new(im_str!("Information"))
                 .size([300.0, 300.0], imgui::Condition::FirstUseEver)
                 .build(&ui, || {
                     ui.text(im_str!("OpenGL Test App ver0.1"));
                     ui.separator();
                     ui.text(im_str!("FPS: {:.1}", ui.io().framerate));
+                    ui.text(format!(
+                        "Draw Calls: {}, Vertices: {}",
+                        render_stats.draw_calls, render_stats.vertices
+                    ));
+                    ui.text(format!(
+                        "State Changes: {}, Texture Binds: {}",
+                        render_stats.state_changes, render_stats.texture_binds
+                    ));
                     let display_size = ui.io().display_size;
                     ui.text(format!(
-                            "Display Size: ({:.1}, {:.1})",
-                            display_size[0], display_size[1]
+                        "Display Size: ({:.1}, {:.1})",
+                        display_size[0], display_size[1]
                     ));
                     let mouse_pos = ui.io().mouse_pos;
                     ui.text(format!(
-                            "Mouse Position: ({:.1}, {:.1})",
-                            mouse_pos[0], mouse_pos[1]
+                        "Mouse Position: ({:.1}, {:.1})",
+                        mouse_pos[0], mouse_pos[1]
                     ));
                     ui.separator();
-                    ui.checkbox(im_str!("Depth Test"), &mut depth_test);
-                    ui.checkbox(im_str!("Blend"), &mut blend);
-                    ui.checkbox(im_str!("Wireframe"), &mut wireframe);
-                    ui.checkbox(im_str!("Culling"), &mut culling);
+                    ui.checkbox(im_str!("Depth Test"), &mut render_state.depth_test);
+                    ui.checkbox(im_str!("Blend"), &mut render_state.blend);
+                    ui.checkbox(im_str!("Wireframe"), &mut render_state.wireframe);
+                    ui.checkbox(im_str!("Culling"), &mut render_state.culling);
+                    ui.separator();
+                    let mut camera_mode = debug_camera.mode();
+                    ui.radio_button(im_str!("Manual"), &mut camera_mode, CameraMode::Manual);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Orbit"), &mut camera_mode, CameraMode::Orbit);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Fly"), &mut camera_mode, CameraMode::Fly);
+                    debug_camera.set_mode(camera_mode);
+                    match camera_mode {
+                        CameraMode::Manual => {
+                            #[rustfmt::skip]
+                            imgui::Slider::new(im_str!("Camera X"))
+                                .range(-5.0..=5.0)
+                                .build(&ui, &mut debug_camera.eye.x);
+                            #[rustfmt::skip]
+                            imgui::Slider::new(im_str!("Camera Y"))
+                                .range(-5.0..=5.0)
+                                .build(&ui, &mut debug_camera.eye.y);
+                            #[rustfmt::skip]
+                            imgui::Slider::new(im_str!("Camera Z"))
+                                .range(-5.0..=5.0)
+                                .build(&ui, &mut debug_camera.eye.z);
+                        }
+                        CameraMode::Orbit => {
+                            ui.text(im_str!("Drag: rotate, Wheel: zoom"));
+                        }
+                        CameraMode::Fly => {
+                            ui.text(im_str!("WASD/QE: move, Right Drag: look"));
+                            imgui::Slider::new(im_str!("Speed"))
+                                .range(0.5..=20.0)
+                                .build(&ui, &mut debug_camera.fly.move_speed);
+                        }
+                    }
+                    let eye = debug_camera.eye();
+                    ui.text(format!(
+                        "Camera Position: ({:.2}, {:.2}, {:.2})",
+                        eye.x, eye.y, eye.z
+                    ));
                     ui.separator();
-                    #[rustfmt::skip]
-                    imgui::Slider::new(im_str!("Camera X"))
-                        .range(-5.0..=5.0)
-                        .build(&ui, &mut camera_x);
-                    #[rustfmt::skip]
-                    imgui::Slider::new(im_str!("Camera Y"))
-                        .range(-5.0..=5.0)
-                        .build(&ui, &mut camera_y);
-                    #[rustfmt::skip]
-                    imgui::Slider::new(im_str!("Camera Z"))
-                        .range(-5.0..=5.0)
-                        .build(&ui, &mut camera_z);
+                    if imgui::CollapsingHeader::new(im_str!("Character")).build(&ui) {
+                        let mut controller = world.get_mut::<CharacterController>(player).unwrap();
+                        ui.text(im_str!("Arrows: move, Space: jump, Down+Space: drop"));
+                        ui.text(format!(
+                            "Grounded: {}, Velocity: ({:.2}, {:.2})",
+                            controller.is_grounded(),
+                            controller.velocity.x,
+                            controller.velocity.y
+                        ));
+                        controller.config.edit(&ui);
+                    }
+                    if imgui::CollapsingHeader::new(im_str!("Lighting")).build(&ui) {
+                        lighting.edit(&ui);
+                    }
+                    if imgui::CollapsingHeader::new(im_str!("2D Lighting")).build(&ui) {
+                        let id = ui.push_id("lighting2d");
+                        world.resource_mut::<Lighting2D>().edit(&ui);
+                        ui.separator();
+                        ui.text(im_str!("Player Light"));
+                        world.get_mut::<Light2D>(player).unwrap().edit(&ui);
+                        id.pop(&ui);
+                    }
+                    if imgui::CollapsingHeader::new(im_str!("Particles")).build(&ui) {
+                        // 炎は2つとも同じ設定にする
+                        let id = ui.push_id("fire");
+                        ui.text(im_str!("Fire"));
+                        let mut emitter = world.get_mut::<ParticleEmitter>(fires[0]).unwrap();
+                        emitter.edit(&ui);
+                        if ui.small_button(im_str!("Save")) {
+                            if let Err(e) = emitter.config.save(&assets.path(FIRE_PARTICLES)) {
+                                eprintln!("failed to save particles: {}", e);
+                            }
+                        }
+                        for &fire in &fires[1..] {
+                            world.get_mut::<ParticleEmitter>(fire).unwrap().config =
+                                emitter.config.clone();
+                        }
+                        id.pop(&ui);
+                        ui.separator();
+                        let id = ui.push_id("dust");
+                        ui.text(im_str!("Dust"));
+                        let mut emitter = world.get_mut::<ParticleEmitter>(dust).unwrap();
+                        emitter.edit(&ui);
+                        if ui.small_button(im_str!("Save")) {
+                            if let Err(e) = emitter.config.save(&assets.path(DUST_PARTICLES)) {
+                                eprintln!("failed to save particles: {}", e);
+                            }
+                        }
+                        id.pop(&ui);
+                    }
+                    if imgui::CollapsingHeader::new(im_str!("Debug Draw")).build(&ui) {
+                        ui.checkbox(im_str!("2D Camera"), &mut use_camera2d);
+                        world.resource_mut::<DebugDraw>().edit(&ui);
+                    }
+                    if imgui::CollapsingHeader::new(im_str!("Audio")).build(&ui) {
+                        ui.text(format!("Music: {} (M: switch)", MUSIC_TRACKS[music_track]));
+                        world.resource_mut::<Audio>().edit(&ui);
+                    }
+                    if imgui::CollapsingHeader::new(im_str!("Assets")).build(&ui) {
+                        assets.edit(&ui);
+                    }
                     ui.separator();
-                    imgui::ProgressBar::new(0.6)
+                    imgui::ProgressBar::new(startup_assets.progress())
                         .size([200.0, 20.0])
-                        .overlay_text(im_str!("Progress!"))
+                        .overlay_text(&im_str!(
+                            "Assets: {}/{}",
+                            startup_assets.finished_count(),
+                            startup_assets.len()
+                        ))
                         .build(&ui);
                     let arr = [0.6f32, 0.1f32, 1.0f32, 0.5f32, 0.92f32, 0.1f32, 0.2f32];
                     ui.plot_lines(im_str!("lines"), &arr)
@@ -326,11 +944,57 @@ fn main() {
                         .graph_size([200.0, 40.0])
                         .build();
                 });
+            world
+                .resource::<DebugDraw>()
+                .draw_text(&ui, &renderer.view_projection());
             imgui_sdl2_context.prepare_render(&ui, &window);
-            renderer.render(ui);
+            imgui_renderer.render(ui);
 
             window.gl_swap_window(); // 描画結果をウィンドウ上に表示
         }
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60)); // フレームレート : 60FPS
     }
 }
+
+// BGMは少しずつ読むので、ファイルを開いたまま再生する (アーカイブの中のものも読める)
+fn open_music(assets: &Assets, path: &str) -> Result<MusicStream, String> {
+    MusicStream::from_reader(assets.open(path)?, &assets.path(path), true)
+}
+
+// solid と one_way のタイルを、光をさえぎる矩形にする (マップのローカル座標、y軸は上向き)
+fn build_map_occluder(map: &TiledMap) -> Occluder {
+    let solid = map.flagged_cells("solid");
+    let one_way = map.flagged_cells("one_way");
+    let cells: Vec<bool> = solid.iter().zip(&one_way).map(|(a, b)| *a || *b).collect();
+    map.merge_cells(&cells)
+        .into_iter()
+        .fold(Occluder::new(), |occluder, rect| {
+            occluder.with_rect(
+                vec2(rect.x as f32, (map.height - rect.y - rect.height) as f32),
+                vec2((rect.x + rect.width) as f32, (map.height - rect.y) as f32),
+            )
+        })
+}
+
+// solid / one_way のプロパティをもつタイルから足場の剛体を作る
+fn build_map_colliders(
+    physics: &mut PhysicsWorld,
+    map: &TiledMap,
+    transform: &Transform2D,
+) -> Vec<BodyHandle> {
+    let template = Collider::new(Shape::Aabb {
+        half_extents: vec2(0.5, 0.5),
+    });
+    let mut bodies = map.build_colliders(physics, transform, "solid", &template);
+    bodies.extend(
+        map.build_colliders(
+            physics,
+            transform,
+            "one_way",
+            &template
+                .clone()
+                .with_layers(ONE_WAY_LAYER, physics::ALL_LAYERS),
+        ),
+    );
+    bodies
+}
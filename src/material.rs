@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::CString;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::assets::{Assets, Handle};
+use crate::shader::Shader;
+use crate::texture::Texture2D;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BlendMode {
+    #[default]
+    Alpha, // 通常の半透明合成
+    Additive, // 加算合成 (光やパーティクル向け)
+    Multiply, // 乗算合成 (ライトマップ向け)
+}
+
+// 描画時に切り替えるOpenGLの状態
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RenderState {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub blend: bool,
+    pub blend_mode: BlendMode,
+    pub wireframe: bool,
+    pub culling: bool,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        RenderState {
+            depth_test: true,
+            depth_write: true,
+            blend: false,
+            blend_mode: BlendMode::Alpha,
+            wireframe: false,
+            culling: true,
+        }
+    }
+}
+
+impl RenderState {
+    pub unsafe fn apply(&self) {
+        if self.depth_test {
+            gl::Enable(gl::DEPTH_TEST);
+        } else {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        gl::DepthMask(if self.depth_write {
+            gl::TRUE
+        } else {
+            gl::FALSE
+        });
+
+        if self.blend {
+            gl::Enable(gl::BLEND);
+            match self.blend_mode {
+                BlendMode::Alpha => gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+                BlendMode::Additive => gl::BlendFunc(gl::SRC_ALPHA, gl::ONE),
+                BlendMode::Multiply => gl::BlendFunc(gl::DST_COLOR, gl::ZERO),
+            }
+        } else {
+            gl::Disable(gl::BLEND);
+        }
+
+        if self.wireframe {
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+        } else {
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        }
+
+        if self.culling {
+            gl::Enable(gl::CULL_FACE);
+        } else {
+            gl::Disable(gl::CULL_FACE);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum UniformValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+}
+
+// マテリアルファイル (RON / TOML) の中身
+#[derive(Deserialize)]
+struct ShaderDesc {
+    vertex: String,
+    fragment: String,
+    #[serde(default)]
+    geometry: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MaterialDesc {
+    shader: ShaderDesc,
+    #[serde(default)]
+    textures: BTreeMap<String, String>, // サンプラー名 -> 画像ファイルのパス
+    #[serde(default)]
+    uniforms: BTreeMap<String, UniformValue>,
+    #[serde(default)]
+    render_state: RenderState,
+}
+
+pub struct Material {
+    pub name: String,
+    pub shader: Handle<Shader>,
+    pub textures: Vec<(CString, Handle<Texture2D>)>,
+    pub uniforms: Vec<(CString, UniformValue)>,
+    pub render_state: RenderState,
+}
+
+#[allow(dead_code)]
+impl Material {
+    pub fn new(name: &str, shader: Handle<Shader>) -> Material {
+        Material {
+            name: name.to_string(),
+            shader,
+            textures: Vec::new(),
+            uniforms: Vec::new(),
+            render_state: RenderState::default(),
+        }
+    }
+
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) {
+        let name = CString::new(name).unwrap();
+        match self.uniforms.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.uniforms.push((name, value)),
+        }
+    }
+
+    pub fn set_texture(&mut self, name: &str, texture: Handle<Texture2D>) {
+        let name = CString::new(name).unwrap();
+        match self.textures.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, t)) => *t = texture,
+            None => self.textures.push((name, texture)),
+        }
+    }
+
+    // 描画の直前に呼び出し、状態・シェーダー・テクスチャ・ユニフォーム変数をまとめて設定する
+    pub unsafe fn bind(&self) {
+        self.render_state.apply();
+        self.shader.use_program();
+        for unit in 0..self.textures.len() {
+            self.bind_texture(unit);
+        }
+        self.set_uniforms();
+    }
+
+    pub unsafe fn bind_texture(&self, unit: usize) {
+        let (name, texture) = &self.textures[unit];
+        texture.bind(unit as u32);
+        self.shader.set_int(name, unit as i32); // サンプラーにテクスチャユニットの番号を渡す
+    }
+
+    pub unsafe fn set_uniforms(&self) {
+        for (name, value) in &self.uniforms {
+            match *value {
+                UniformValue::Bool(v) => self.shader.set_bool(name, v),
+                UniformValue::Int(v) => self.shader.set_int(name, v),
+                UniformValue::Float(v) => self.shader.set_float(name, v),
+                UniformValue::Vec2(v) => self.shader.set_vector2(name, &v.into()),
+                UniformValue::Vec3(v) => self.shader.set_vector3(name, &v.into()),
+                UniformValue::Vec4(v) => self.shader.set_vector4(name, &v.into()),
+            }
+        }
+    }
+
+    // シェーダーやテクスチャが読み込み直されていたら持ち替える (持ち替えたら true)
+    pub fn refresh(&mut self) -> bool {
+        let mut refreshed = self.shader.refresh();
+        for (_, texture) in &mut self.textures {
+            refreshed |= texture.refresh();
+        }
+        refreshed
+    }
+
+    // ブレンドが有効なマテリアルは半透明として奥から手前の順に描画する
+    pub fn is_transparent(&self) -> bool {
+        self.render_state.blend
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(usize);
+
+// シーンからは名前でマテリアルを参照する
+// シェーダーとテクスチャは Assets から読み込むので、同じファイルはマテリアル間で共有される
+pub struct MaterialLibrary {
+    materials: Vec<Material>,
+    names: HashMap<String, MaterialId>,
+    sources: Vec<(MaterialId, String)>, // マテリアルファイルから読み込んだもの
+    assets: Assets,
+}
+
+#[allow(dead_code)]
+impl MaterialLibrary {
+    pub fn new(assets: Assets) -> MaterialLibrary {
+        MaterialLibrary {
+            materials: Vec::new(),
+            names: HashMap::new(),
+            sources: Vec::new(),
+            assets,
+        }
+    }
+
+    pub fn assets(&self) -> &Assets {
+        &self.assets
+    }
+
+    // ディレクトリ内の *.ron / *.toml をすべて読み込む (ファイル名の拡張子を除いた部分がマテリアル名)
+    pub fn load_dir(&mut self, dir: &str) -> Result<(), String> {
+        for path in self.assets.read_dir(dir)? {
+            let path = Path::new(&path);
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("ron") | Some("toml") => {}
+                _ => continue,
+            }
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            self.load(&name, path.to_str().unwrap())?;
+        }
+        Ok(())
+    }
+
+    pub fn load(&mut self, name: &str, path: &str) -> Result<MaterialId, String> {
+        let source = self.assets.read_to_string(path)?;
+        let desc: MaterialDesc = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("ron") => ron::from_str(&source).map_err(|e| format!("{}: {}", path, e))?,
+            Some("toml") => toml::from_str(&source).map_err(|e| format!("{}: {}", path, e))?,
+            _ => return Err(format!("unsupported material format: {}", path)),
+        };
+
+        let shader = self.shader(desc.shader)?;
+        let mut material = Material::new(name, shader);
+        material.render_state = desc.render_state;
+        for (sampler, texture_path) in &desc.textures {
+            let texture = self.load_texture(texture_path)?;
+            material.set_texture(sampler, texture);
+        }
+        for (uniform, value) in desc.uniforms {
+            material.set_uniform(&uniform, value);
+        }
+        let id = self.insert(material);
+        if !self.sources.iter().any(|(source, _)| *source == id) {
+            self.sources.push((id, path.to_string()));
+        }
+        Ok(id)
+    }
+
+    // ファイルが変わったものを使い直す (Assets::update() の後に毎フレーム呼ぶ)
+    // - マテリアルファイルが変わったら読み込み直す (imguiなどで変えた設定は元に戻る)
+    // - シェーダーやテクスチャが読み込み直されていたら持ち替える
+    // 変わったマテリアルを返す
+    pub fn hot_reload(&mut self) -> Vec<MaterialId> {
+        let mut changed = Vec::new();
+        let sources: Vec<_> = self
+            .sources
+            .iter()
+            .filter(|(_, path)| self.assets.is_changed(path))
+            .cloned()
+            .collect();
+        for (id, path) in sources {
+            let name = self.materials[id.0].name.clone();
+            match self.load(&name, &path) {
+                Ok(_) => changed.push(id),
+                Err(e) => eprintln!("failed to reload material: {}", e),
+            }
+        }
+        for (index, material) in self.materials.iter_mut().enumerate() {
+            let id = MaterialId(index);
+            if material.refresh() && !changed.contains(&id) {
+                changed.push(id);
+            }
+        }
+        changed
+    }
+
+    pub fn insert(&mut self, material: Material) -> MaterialId {
+        // 同名のマテリアルがすでにあれば置き換える
+        if let Some(&id) = self.names.get(&material.name) {
+            self.materials[id.0] = material;
+            return id;
+        }
+        let id = MaterialId(self.materials.len());
+        self.names.insert(material.name.clone(), id);
+        self.materials.push(material);
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<MaterialId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: MaterialId) -> &mut Material {
+        &mut self.materials[id.0]
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&Material> {
+        self.id(name).map(|id| self.get(id))
+    }
+
+    fn shader(&mut self, desc: ShaderDesc) -> Result<Handle<Shader>, String> {
+        self.assets
+            .load_shader(&desc.vertex, &desc.fragment, desc.geometry.as_deref())
+    }
+
+    // マテリアルファイルを使わずにマテリアルを組み立てるときも、読み込み済みのものを共有する
+    pub fn load_shader(&mut self, vertex: &str, fragment: &str) -> Handle<Shader> {
+        self.assets
+            .load_shader(vertex, fragment, None)
+            .unwrap_or_else(|e| panic!("failed to load shader: {}", e))
+    }
+
+    pub fn load_texture(&mut self, path: &str) -> Result<Handle<Texture2D>, String> {
+        self.assets.load(path)
+    }
+}
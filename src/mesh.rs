@@ -0,0 +1,10 @@
+mod data;
+mod gltf;
+mod model;
+mod obj;
+mod shapes;
+
+#[allow(unused_imports)]
+pub use data::{MeshData, MeshVertex, ModelData, MESH_VERTEX_FLOATS};
+#[allow(unused_imports)]
+pub use model::{Mesh, Model};
@@ -0,0 +1,210 @@
+use std::fs;
+use std::mem;
+use std::os::raw::c_void;
+use std::path::Path;
+
+use cgmath::{InnerSpace, Zero};
+use gl::types::{GLfloat, GLsizei, GLsizeiptr};
+
+use super::{gltf, obj};
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector3 = cgmath::Vector3<f32>;
+
+// 頂点あたりの要素数 (位置3 + 法線3 + UV2)
+pub const MESH_VERTEX_FLOATS: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2], // 左上が原点 (テクスチャの1行目が v = 0)
+}
+
+// GPUへ転送する前のメッシュ (GLを使わないので、ウィンドウがなくても読み込める)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub name: String,
+    pub material: Option<String>, // ファイルに書かれたマテリアル名
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>, // 3つずつで1つの三角形
+}
+
+#[allow(dead_code)]
+impl MeshData {
+    pub fn new(name: &str) -> MeshData {
+        MeshData {
+            name: name.to_string(),
+            ..MeshData::default()
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // 面の法線を頂点ごとに足し合わせて、なめらかな法線を作る (ファイルに法線がないとき用)
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector3::zero(); self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+            let p = |i: usize| Vector3::from(self.vertices[i].position);
+            // 外積の長さは面積に比例するので、大きい面ほど強く効く
+            let normal = (p(b) - p(a)).cross(p(c) - p(a));
+            for i in [a, b, c] {
+                normals[i] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            if normal.magnitude2() > 0.0 {
+                vertex.normal = normal.normalize().into();
+            }
+        }
+    }
+
+    // 頂点を囲む箱 (最小, 最大)
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let first = self.vertices.first()?.position;
+        Some(self.vertices.iter().fold((first, first), |(min, max), v| {
+            let p = v.position;
+            (
+                [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+                [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])],
+            )
+        }))
+    }
+
+    // インデックスの範囲を確かめる (ファイルが壊れていると描画でGPUが範囲外を読むため)
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(format!("{}: index count is not a multiple of 3", self.name));
+        }
+        match self
+            .indices
+            .iter()
+            .find(|&&i| i as usize >= self.vertices.len())
+        {
+            Some(i) => Err(format!("{}: index out of range: {}", self.name, i)),
+            None => Ok(()),
+        }
+    }
+
+    // 位置・法線・UVを交互に並べた配列
+    pub fn interleaved(&self) -> Vec<f32> {
+        let mut data = Vec::with_capacity(self.vertices.len() * MESH_VERTEX_FLOATS);
+        for v in &self.vertices {
+            data.extend_from_slice(&v.position);
+            data.extend_from_slice(&v.normal);
+            data.extend_from_slice(&v.uv);
+        }
+        data
+    }
+
+    // GPUへ転送する (頂点属性は location 0: 位置, 1: 法線, 2: UV)
+    pub fn upload(&self) -> Vertex {
+        let data = self.interleaved();
+        Vertex::new(
+            (data.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+            data.as_ptr() as *const c_void,
+            gl::STATIC_DRAW,
+            vec![gl::FLOAT, gl::FLOAT, gl::FLOAT],
+            vec![3, 3, 2],
+            (MESH_VERTEX_FLOATS * mem::size_of::<GLfloat>()) as GLsizei,
+            self.vertices.len() as i32,
+        )
+        .with_indices(&self.indices)
+    }
+}
+
+// 1つのファイルから読み込んだメッシュ (OBJのオブジェクトやglTFのプリミティブごとに分かれる)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+}
+
+#[allow(dead_code)]
+impl ModelData {
+    pub fn load(path: &str) -> Result<ModelData, String> {
+        ModelData::load_with(path, &|path| {
+            fs::read(path).map_err(|e| format!("failed to open file: {}: {}", path, e))
+        })
+    }
+
+    // モデルと、glTFが参照する .bin を read で読む (アーカイブから読み込むときなど)
+    pub fn load_with(
+        path: &str,
+        read: &dyn Fn(&str) -> Result<Vec<u8>, String>,
+    ) -> Result<ModelData, String> {
+        let bytes = read(path)?;
+        let meshes = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("obj") => {
+                let source = String::from_utf8(bytes).map_err(|e| format!("{}: {}", path, e))?;
+                obj::parse(&source)
+            }
+            Some("gltf") => gltf::parse(&bytes, base_dir(path), read),
+            Some("glb") => gltf::parse_glb(&bytes, base_dir(path), read),
+            _ => return Err(format!("unsupported model format: {}", path)),
+        }
+        .map_err(|e| format!("{}: {}", path, e))?;
+        for mesh in &meshes {
+            mesh.validate().map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(ModelData { meshes })
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.meshes.iter().map(|mesh| mesh.vertices.len()).sum()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.meshes.iter().map(MeshData::triangle_count).sum()
+    }
+}
+
+fn base_dir(path: &str) -> &Path {
+    Path::new(path).parent().unwrap_or_else(|| Path::new(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> MeshData {
+        let mut mesh = MeshData::new("triangle");
+        mesh.vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .iter()
+            .map(|&position| MeshVertex {
+                position,
+                ..MeshVertex::default()
+            })
+            .collect();
+        mesh.indices = vec![0, 1, 2];
+        mesh
+    }
+
+    #[test]
+    fn validate() {
+        let mut mesh = triangle();
+        assert_eq!(mesh.validate(), Ok(()));
+        mesh.indices = vec![0, 1, 3];
+        assert_eq!(
+            mesh.validate(),
+            Err("triangle: index out of range: 3".to_string())
+        );
+        mesh.indices = vec![0, 1];
+        assert_eq!(
+            mesh.validate(),
+            Err("triangle: index count is not a multiple of 3".to_string())
+        );
+    }
+
+    #[test]
+    fn normals_and_bounds() {
+        let mut mesh = triangle();
+        mesh.compute_normals();
+        assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+        assert_eq!(mesh.bounds(), Some(([0.0; 3], [1.0, 1.0, 0.0])));
+        assert_eq!(MeshData::new("empty").bounds(), None);
+    }
+}
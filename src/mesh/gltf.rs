@@ -0,0 +1,497 @@
+use std::path::Path;
+
+use base64::Engine;
+use serde_json::Value;
+
+use super::data::{MeshData, MeshVertex};
+
+// .glb の先頭とチャンクの種類
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_HEADER_SIZE: usize = 12;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+// アクセサーの componentType
+const BYTE: u64 = 5120;
+const UNSIGNED_BYTE: u64 = 5121;
+const SHORT: u64 = 5122;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+const FLOAT: u64 = 5126;
+
+// プリミティブの mode (三角形のリストだけを扱う)
+const TRIANGLES: u64 = 4;
+
+type Read<'a> = &'a dyn Fn(&str) -> Result<Vec<u8>, String>;
+
+// glTF 2.0 (.gltf) を読み込む
+// - メッシュのプリミティブごとに1つの MeshData を作る (位置・法線・UV・インデックス)
+// - バッファーは data URI か、dir からの相対パスの .bin を read で読む
+// - ノードの変換やアニメーション、スキン、モーフターゲットは扱わない (メッシュのローカル座標のまま)
+pub fn parse(bytes: &[u8], dir: &Path, read: Read) -> Result<Vec<MeshData>, String> {
+    let root: Value = serde_json::from_slice(bytes).map_err(|e| e.to_string())?;
+    parse_document(&root, None, dir, read)
+}
+
+// バイナリ形式 (.glb): JSONのチャンクと、1つ目のバッファーになるBINチャンク
+pub fn parse_glb(bytes: &[u8], dir: &Path, read: Read) -> Result<Vec<MeshData>, String> {
+    if bytes.len() < GLB_HEADER_SIZE || &bytes[0..4] != GLB_MAGIC {
+        return Err("not a glb file".to_string());
+    }
+    let version = u32_at(bytes, 4);
+    if version != 2 {
+        return Err(format!("unsupported glTF version: {}", version));
+    }
+    let length = (u32_at(bytes, 8) as usize).min(bytes.len());
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = GLB_HEADER_SIZE;
+    while offset + 8 <= length {
+        let chunk_length = u32_at(bytes, offset) as usize;
+        let chunk_type = u32_at(bytes, offset + 4);
+        let start = offset + 8;
+        let chunk = bytes
+            .get(start..start + chunk_length)
+            .ok_or("chunk is out of range")?;
+        match chunk_type {
+            CHUNK_JSON if json.is_none() => json = Some(chunk),
+            CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            _ => {} // 知らないチャンクは飛ばす
+        }
+        offset = start + chunk_length;
+    }
+    let json = json.ok_or("missing JSON chunk")?;
+    let root: Value = serde_json::from_slice(json).map_err(|e| e.to_string())?;
+    parse_document(&root, bin, dir, read)
+}
+
+fn parse_document(
+    root: &Value,
+    bin: Option<&[u8]>,
+    dir: &Path,
+    read: Read,
+) -> Result<Vec<MeshData>, String> {
+    let version = root["asset"]["version"].as_str().unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(format!("unsupported glTF version: {}", version));
+    }
+    let buffers = load_buffers(root, bin, dir, read)?;
+    let mut meshes = Vec::new();
+    for (mesh_index, mesh) in array(root, "meshes").iter().enumerate() {
+        let name = match mesh["name"].as_str() {
+            Some(name) => name.to_string(),
+            None => format!("mesh{}", mesh_index),
+        };
+        let primitives = array(mesh, "primitives");
+        for (index, primitive) in primitives.iter().enumerate() {
+            let name = if primitives.len() > 1 {
+                format!("{}.{}", name, index)
+            } else {
+                name.clone()
+            };
+            let data = parse_primitive(root, &buffers, primitive, &name)
+                .map_err(|e| format!("{}: {}", name, e))?;
+            meshes.push(data);
+        }
+    }
+    Ok(meshes)
+}
+
+fn parse_primitive(
+    root: &Value,
+    buffers: &[Vec<u8>],
+    primitive: &Value,
+    name: &str,
+) -> Result<MeshData, String> {
+    let mode = primitive["mode"].as_u64().unwrap_or(TRIANGLES);
+    if mode != TRIANGLES {
+        return Err(format!("unsupported primitive mode: {}", mode));
+    }
+    let attributes = &primitive["attributes"];
+    let positions = match attributes["POSITION"].as_u64() {
+        Some(index) => Accessor::new(root, buffers, index)?.floats::<3>()?,
+        None => return Err("primitive has no POSITION".to_string()),
+    };
+    let optional = |key: &str| attributes[key].as_u64();
+    let normals = match optional("NORMAL") {
+        Some(index) => Some(Accessor::new(root, buffers, index)?.floats::<3>()?),
+        None => None,
+    };
+    let uvs = match optional("TEXCOORD_0") {
+        Some(index) => Some(Accessor::new(root, buffers, index)?.floats::<2>()?),
+        None => None,
+    };
+    for (key, len) in [
+        ("NORMAL", normals.as_ref().map(Vec::len)),
+        ("TEXCOORD_0", uvs.as_ref().map(Vec::len)),
+    ] {
+        if matches!(len, Some(len) if len != positions.len()) {
+            return Err(format!("{} count does not match POSITION", key));
+        }
+    }
+
+    let mut mesh = MeshData::new(name);
+    mesh.material = primitive["material"]
+        .as_u64()
+        .and_then(|index| root["materials"][index as usize]["name"].as_str())
+        .map(str::to_string);
+    mesh.vertices = (0..positions.len())
+        .map(|i| MeshVertex {
+            position: positions[i],
+            normal: normals.as_ref().map_or([0.0; 3], |normals| normals[i]),
+            uv: uvs.as_ref().map_or([0.0; 2], |uvs| uvs[i]),
+        })
+        .collect();
+    // インデックスがなければ、頂点を3つずつ三角形にする
+    mesh.indices = match primitive["indices"].as_u64() {
+        Some(index) => Accessor::new(root, buffers, index)?.indices()?,
+        None => (0..positions.len() as u32).collect(),
+    };
+    if normals.is_none() {
+        mesh.compute_normals();
+    }
+    Ok(mesh)
+}
+
+fn load_buffers(
+    root: &Value,
+    bin: Option<&[u8]>,
+    dir: &Path,
+    read: Read,
+) -> Result<Vec<Vec<u8>>, String> {
+    let mut buffers = Vec::new();
+    for (index, buffer) in array(root, "buffers").iter().enumerate() {
+        let data = match buffer["uri"].as_str() {
+            Some(uri) if uri.starts_with("data:") => decode_data_uri(uri)?,
+            Some(uri) => read(&dir.join(uri).to_string_lossy().replace('\\', "/"))?,
+            // .glb のBINチャンクは、URIのない1つ目のバッファー
+            None if index == 0 => bin.ok_or("buffer 0 has no data")?.to_vec(),
+            None => return Err(format!("buffer {} has no uri", index)),
+        };
+        let length = buffer["byteLength"].as_u64().unwrap_or(0) as usize;
+        if data.len() < length {
+            return Err(format!("buffer {} is shorter than byteLength", index));
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+// "data:application/octet-stream;base64,..."
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, String> {
+    let (header, data) = uri.split_once(',').ok_or("invalid data uri")?;
+    if !header.ends_with(";base64") {
+        return Err("data uri is not base64".to_string());
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| format!("invalid base64 buffer: {}", e))
+}
+
+// バッファーの中の、同じ形の要素の並び
+struct Accessor<'a> {
+    data: &'a [u8],
+    stride: usize,
+    count: usize,
+    component_type: u64,
+    component_size: usize,
+    components: usize,
+    normalized: bool,
+}
+
+impl<'a> Accessor<'a> {
+    fn new(root: &Value, buffers: &'a [Vec<u8>], index: u64) -> Result<Accessor<'a>, String> {
+        let accessor = &root["accessors"][index as usize];
+        if accessor.is_null() {
+            return Err(format!("accessor not found: {}", index));
+        }
+        if !accessor["sparse"].is_null() {
+            return Err("sparse accessors are not supported".to_string());
+        }
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            other => return Err(format!("unsupported accessor type: {:?}", other)),
+        };
+        let component_type = accessor["componentType"].as_u64().unwrap_or(0);
+        let component_size = match component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            other => return Err(format!("unsupported component type: {}", other)),
+        };
+        let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+        let view_index = accessor["bufferView"]
+            .as_u64()
+            .ok_or("accessors without a buffer view are not supported")?;
+        let view = &root["bufferViews"][view_index as usize];
+        let buffer = view["buffer"]
+            .as_u64()
+            .and_then(|buffer| buffers.get(buffer as usize))
+            .ok_or_else(|| format!("invalid buffer view: {}", view_index))?;
+        let view_offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let view_length = view["byteLength"].as_u64().unwrap_or(0) as usize;
+        let element_size = component_size * components;
+        let stride = view["byteStride"]
+            .as_u64()
+            .map_or(element_size, |stride| stride as usize);
+        let start = view_offset + accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let end = match count {
+            0 => start,
+            _ => start + stride * (count - 1) + element_size,
+        };
+        if end > view_offset + view_length || end > buffer.len() {
+            return Err(format!("accessor {} is out of range", index));
+        }
+        Ok(Accessor {
+            data: &buffer[start..end],
+            stride,
+            count,
+            component_type,
+            component_size,
+            components,
+            normalized: accessor["normalized"].as_bool().unwrap_or(false),
+        })
+    }
+
+    fn component(&self, element: usize, component: usize) -> f32 {
+        let at = element * self.stride + component * self.component_size;
+        let bytes = &self.data[at..at + self.component_size];
+        // 正規化された整数は 0.0 ~ 1.0 (符号付きは -1.0 ~ 1.0) にする
+        let (value, max) = match self.component_type {
+            BYTE => (bytes[0] as i8 as f32, i8::MAX as f32),
+            UNSIGNED_BYTE => (bytes[0] as f32, u8::MAX as f32),
+            SHORT => (
+                i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                i16::MAX as f32,
+            ),
+            UNSIGNED_SHORT => (
+                u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                u16::MAX as f32,
+            ),
+            UNSIGNED_INT => (u32_at(bytes, 0) as f32, u32::MAX as f32),
+            _ => return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        };
+        if self.normalized {
+            (value / max).max(-1.0)
+        } else {
+            value
+        }
+    }
+
+    fn floats<const N: usize>(&self) -> Result<Vec<[f32; N]>, String> {
+        if self.components != N {
+            return Err(format!(
+                "expected {} components, found {}",
+                N, self.components
+            ));
+        }
+        Ok((0..self.count)
+            .map(|element| {
+                let mut value = [0.0; N];
+                for (component, v) in value.iter_mut().enumerate() {
+                    *v = self.component(element, component);
+                }
+                value
+            })
+            .collect())
+    }
+
+    fn indices(&self) -> Result<Vec<u32>, String> {
+        if self.components != 1 {
+            return Err("indices must be SCALAR".to_string());
+        }
+        (0..self.count)
+            .map(|element| {
+                let at = element * self.stride;
+                let bytes = &self.data[at..at + self.component_size];
+                match self.component_type {
+                    UNSIGNED_BYTE => Ok(bytes[0] as u32),
+                    UNSIGNED_SHORT => Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as u32),
+                    UNSIGNED_INT => Ok(u32_at(bytes, 0)),
+                    other => Err(format!("unsupported index type: {}", other)),
+                }
+            })
+            .collect()
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value[key].as_array().map_or(&[], Vec::as_slice)
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 四角形 (4頂点、2つの三角形)
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+    fn no_files(path: &str) -> Result<Vec<u8>, String> {
+        Err(format!("unexpected read: {}", path))
+    }
+
+    // 位置とUVを1つのバッファービューに交互に並べ (byteStride 24)、続けてu16のインデックスを置く
+    fn buffer() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (i, position) in POSITIONS.iter().enumerate() {
+            for value in position {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            for value in [i as f32 * 0.25, 0.5] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&[0xff; 4]); // 詰め物
+        }
+        for index in INDICES {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn document(uri: Option<String>) -> Value {
+        let length = buffer().len();
+        let mut buffer = json!({ "byteLength": length });
+        if let Some(uri) = uri {
+            buffer["uri"] = json!(uri);
+        }
+        json!({
+            "asset": { "version": "2.0" },
+            "buffers": [buffer],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 96, "byteStride": 24 },
+                { "buffer": 0, "byteOffset": 96, "byteLength": 12 },
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": FLOAT, "count": 4, "type": "VEC3" },
+                { "bufferView": 0, "byteOffset": 12, "componentType": FLOAT, "count": 4, "type": "VEC2" },
+                { "bufferView": 1, "componentType": UNSIGNED_SHORT, "count": 6, "type": "SCALAR" },
+            ],
+            "materials": [{ "name": "stone" }],
+            "meshes": [{
+                "name": "quad",
+                "primitives": [{
+                    "attributes": { "POSITION": 0, "TEXCOORD_0": 1 },
+                    "indices": 2,
+                    "material": 0,
+                }],
+            }],
+        })
+    }
+
+    fn assert_quad(meshes: &[MeshData]) {
+        assert_eq!(meshes.len(), 1);
+        let mesh = &meshes[0];
+        assert_eq!(mesh.name, "quad");
+        assert_eq!(mesh.material.as_deref(), Some("stone"));
+        let positions: Vec<_> = mesh.vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, POSITIONS);
+        let uvs: Vec<_> = mesh.vertices.iter().map(|v| v.uv).collect();
+        assert_eq!(uvs, vec![[0.0, 0.5], [0.25, 0.5], [0.5, 0.5], [0.75, 0.5]]);
+        assert_eq!(mesh.indices, INDICES.map(u32::from));
+        // NORMAL がないので計算する
+        assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+    }
+
+    fn glb(json: &[u8], bin: &[u8]) -> Vec<u8> {
+        // チャンクは4バイト境界にそろえる (JSONは空白、BINは0で埋める)
+        let pad = |data: &[u8], fill: u8| {
+            let mut data = data.to_vec();
+            data.resize(data.len().div_ceil(4) * 4, fill);
+            data
+        };
+        let (json, bin) = (pad(json, b' '), pad(bin, 0));
+        let length = GLB_HEADER_SIZE + 8 + json.len() + 8 + bin.len();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(GLB_MAGIC);
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&(length as u32).to_le_bytes());
+        for (chunk_type, chunk) in [(CHUNK_JSON, json), (CHUNK_BIN, bin)] {
+            bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&chunk_type.to_le_bytes());
+            bytes.extend_from_slice(&chunk);
+        }
+        bytes
+    }
+
+    #[test]
+    fn data_uri_with_strided_view_and_u16_indices() {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(buffer())
+        );
+        let source = serde_json::to_vec(&document(Some(uri))).unwrap();
+        let meshes = parse(&source, Path::new(""), &no_files).unwrap();
+        assert_quad(&meshes);
+    }
+
+    #[test]
+    fn external_buffer_is_read_relative_to_dir() {
+        let source = serde_json::to_vec(&document(Some("quad.bin".to_string()))).unwrap();
+        let read = |path: &str| {
+            assert_eq!(path, "models/quad.bin");
+            Ok(buffer())
+        };
+        let meshes = parse(&source, Path::new("models"), &read).unwrap();
+        assert_quad(&meshes);
+    }
+
+    #[test]
+    fn glb_chunks() {
+        let json = serde_json::to_vec(&document(None)).unwrap();
+        let bytes = glb(&json, &buffer());
+        let meshes = parse_glb(&bytes, Path::new(""), &no_files).unwrap();
+        assert_quad(&meshes);
+    }
+
+    #[test]
+    fn glb_errors() {
+        let json = serde_json::to_vec(&document(None)).unwrap();
+        let mut bytes = glb(&json, &buffer());
+        bytes[0] = b'x';
+        assert_eq!(
+            parse_glb(&bytes, Path::new(""), &no_files).unwrap_err(),
+            "not a glb file"
+        );
+        let mut bytes = glb(&json, &buffer());
+        bytes[4] = 1;
+        assert_eq!(
+            parse_glb(&bytes, Path::new(""), &no_files).unwrap_err(),
+            "unsupported glTF version: 1"
+        );
+        // BINチャンクがないと、URIのないバッファーを読めない
+        let mut bytes = glb(&json, &[]);
+        bytes.truncate(bytes.len() - 8);
+        assert_eq!(
+            parse_glb(&bytes, Path::new(""), &no_files).unwrap_err(),
+            "buffer 0 has no data"
+        );
+    }
+
+    #[test]
+    fn accessor_out_of_range() {
+        let mut root = document(Some(format!(
+            "data:application/octet-stream;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(buffer())
+        )));
+        root["accessors"][2]["count"] = json!(7);
+        let source = serde_json::to_vec(&root).unwrap();
+        assert_eq!(
+            parse(&source, Path::new(""), &no_files).unwrap_err(),
+            "quad: accessor 2 is out of range"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::rc::Rc;
+
+use super::data::ModelData;
+use crate::assets::{Asset, Assets};
+use crate::vertex::Vertex;
+
+// GPUへ転送したメッシュ
+#[allow(dead_code)]
+pub struct Mesh {
+    pub name: String,
+    pub material: Option<String>,
+    pub vertex: Rc<Vertex>, // Sprite や DrawCommand にそのまま渡せる
+    pub bounds: ([f32; 3], [f32; 3]),
+}
+
+// OBJ / glTF から読み込んだメッシュの集まり
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+}
+
+#[allow(dead_code)]
+impl Model {
+    pub fn from_data(data: &ModelData) -> Model {
+        Model {
+            meshes: data
+                .meshes
+                .iter()
+                .map(|mesh| Mesh {
+                    name: mesh.name.clone(),
+                    material: mesh.material.clone(),
+                    vertex: Rc::new(mesh.upload()),
+                    bounds: mesh.bounds().unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<&Mesh> {
+        self.meshes.iter().find(|mesh| mesh.name == name)
+    }
+}
+
+// glTFが参照する .bin も Assets を通して読むので、どれかが変わると読み込み直す
+impl Asset for Model {
+    fn load(assets: &Assets, path: &str) -> Result<Model, String> {
+        let data = ModelData::load_with(path, &|path| assets.read(path))?;
+        Ok(Model::from_data(&data))
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use super::data::{MeshData, MeshVertex};
+
+// Wavefront OBJ (.obj) を読み込む
+// - v / vt / vn / f に対応し、四角形以上の面は扇形に三角形へ分ける
+// - o / g と usemtl が変わるところでメッシュを分ける (.mtl は読まず、マテリアル名だけを残す)
+// - 法線のない面があるメッシュは、法線を計算する
+pub fn parse(source: &str) -> Result<Vec<MeshData>, String> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut builder = Builder::new("default");
+    let mut meshes = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |e: String| format!("line {}: {}", number + 1, e);
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+        match keyword {
+            "v" => positions.push(floats(&args).map_err(error)?),
+            "vt" => {
+                let [u, v] = floats(&args).map_err(error)?;
+                uvs.push([u, 1.0 - v]); // OBJは左下が原点
+            }
+            "vn" => normals.push(floats(&args).map_err(error)?),
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error("face needs at least 3 vertices".to_string()));
+                }
+                let corners = args
+                    .iter()
+                    .map(|arg| {
+                        let indices = parse_corner(arg, positions.len(), uvs.len(), normals.len())?;
+                        Ok(builder.vertex(indices, &positions, &uvs, &normals))
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+                    .map_err(error)?;
+                for i in 1..corners.len() - 1 {
+                    builder.mesh.indices.extend_from_slice(&[
+                        corners[0],
+                        corners[i],
+                        corners[i + 1],
+                    ]);
+                }
+            }
+            "o" | "g" => {
+                let name = args.join(" ");
+                let material = builder.mesh.material.clone();
+                meshes.extend(builder.finish());
+                builder = Builder::new(&name);
+                builder.mesh.material = material;
+            }
+            "usemtl" => {
+                let material = Some(args.join(" "));
+                if builder.mesh.material != material {
+                    let name = builder.mesh.name.clone();
+                    meshes.extend(builder.finish());
+                    builder = Builder::new(&name);
+                    builder.mesh.material = material;
+                }
+            }
+            // mtllib, s (スムージンググループ), l (線) などは使わない
+            _ => {}
+        }
+    }
+    meshes.extend(builder.finish());
+    Ok(meshes)
+}
+
+// 作っている途中のメッシュ
+struct Builder {
+    mesh: MeshData,
+    // (位置, UV, 法線) の組ごとに1つの頂点を作り、同じ組は使い回す
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    missing_normals: bool,
+}
+
+impl Builder {
+    fn new(name: &str) -> Builder {
+        Builder {
+            mesh: MeshData::new(name),
+            vertices: HashMap::new(),
+            missing_normals: false,
+        }
+    }
+
+    fn vertex(
+        &mut self,
+        key: (usize, Option<usize>, Option<usize>),
+        positions: &[[f32; 3]],
+        uvs: &[[f32; 2]],
+        normals: &[[f32; 3]],
+    ) -> u32 {
+        if let Some(&index) = self.vertices.get(&key) {
+            return index;
+        }
+        let (position, uv, normal) = key;
+        self.missing_normals |= normal.is_none();
+        let index = self.mesh.vertices.len() as u32;
+        self.mesh.vertices.push(MeshVertex {
+            position: positions[position],
+            normal: normal.map_or([0.0; 3], |i| normals[i]),
+            uv: uv.map_or([0.0; 2], |i| uvs[i]),
+        });
+        self.vertices.insert(key, index);
+        index
+    }
+
+    // 面がなければ None
+    fn finish(mut self) -> Option<MeshData> {
+        if self.mesh.indices.is_empty() {
+            return None;
+        }
+        if self.missing_normals {
+            self.mesh.compute_normals();
+        }
+        Some(self.mesh)
+    }
+}
+
+fn floats<const N: usize>(args: &[&str]) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    if args.len() < N {
+        return Err(format!("expected {} numbers", N));
+    }
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| format!("invalid number: {}", arg))?;
+    }
+    Ok(values)
+}
+
+// "v", "v/vt", "v//vn", "v/vt/vn" (1から始まり、負の値は後ろから数える)
+fn parse_corner(
+    arg: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = arg.split('/');
+    let position = parts
+        .next()
+        .filter(|part| !part.is_empty())
+        .ok_or_else(|| format!("invalid face vertex: {}", arg))?;
+    let position = index(position, position_count)?;
+    let uv = match parts.next() {
+        Some(part) if !part.is_empty() => Some(index(part, uv_count)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(part) if !part.is_empty() => Some(index(part, normal_count)?),
+        _ => None,
+    };
+    Ok((position, uv, normal))
+}
+
+fn index(value: &str, count: usize) -> Result<usize, String> {
+    let index: i64 = value
+        .parse()
+        .map_err(|_| format!("invalid index: {}", value))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index out of range: {}", value));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{InnerSpace, Vector3};
+
+    const SQUARE: &str = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 1 1 0
+";
+
+    fn positions(mesh: &MeshData) -> Vec<[f32; 3]> {
+        mesh.vertices.iter().map(|v| v.position).collect()
+    }
+
+    #[test]
+    fn position_uv_normal() {
+        let source = format!(
+            "{}vt 0 0\nvt 1 0\nvt 0 0.25\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\n",
+            SQUARE
+        );
+        let meshes = parse(&source).unwrap();
+        assert_eq!(meshes.len(), 1);
+        let mesh = &meshes[0];
+        assert_eq!(mesh.name, "default");
+        assert_eq!(mesh.material, None);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(
+            positions(mesh),
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        // v は上下を反転する
+        let uvs: Vec<_> = mesh.vertices.iter().map(|v| v.uv).collect();
+        assert_eq!(uvs, vec![[0.0, 1.0], [1.0, 1.0], [0.0, 0.75]]);
+        assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn normal_without_uv_and_negative_indices() {
+        let source = format!("{}vn 0 0 -1\nf -4//-1 -3//1 -2//-1\n", SQUARE);
+        let meshes = parse(&source).unwrap();
+        let mesh = &meshes[0];
+        assert_eq!(
+            positions(mesh),
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        // ファイルの法線をそのまま使い、UVは 0
+        for vertex in &mesh.vertices {
+            assert_eq!(vertex.normal, [0.0, 0.0, -1.0]);
+            assert_eq!(vertex.uv, [0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn quad_is_split_into_a_fan() {
+        let source = format!("{}f 1 2 4 3\n", SQUARE);
+        let meshes = parse(&source).unwrap();
+        let mesh = &meshes[0];
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.triangle_count(), 2);
+        // 法線がないので面から計算する (反時計回りなので +Z)
+        for vertex in &mesh.vertices {
+            assert!((Vector3::from(vertex.normal) - Vector3::unit_z()).magnitude() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn shared_corners_are_reused() {
+        let source = format!("{}f 1 2 3\nf 2 4 3\n", SQUARE);
+        let mesh = &parse(&source).unwrap()[0];
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 1, 3, 2]);
+    }
+
+    #[test]
+    fn objects_and_materials_split_meshes() {
+        let source = format!(
+            "{}
+o first
+usemtl red
+f 1 2 3
+usemtl blue
+f 1 3 4
+o second
+f 2 4 3
+usemtl blue
+f 1 2 4
+o empty
+",
+            SQUARE
+        );
+        let meshes = parse(&source).unwrap();
+        let summary: Vec<_> = meshes
+            .iter()
+            .map(|mesh| {
+                (
+                    mesh.name.as_str(),
+                    mesh.material.as_deref(),
+                    mesh.triangle_count(),
+                )
+            })
+            .collect();
+        // 面のないメッシュ (o の前と empty) は残らず、同じマテリアルの usemtl では分けない
+        assert_eq!(
+            summary,
+            vec![
+                ("first", Some("red"), 1),
+                ("first", Some("blue"), 1),
+                ("second", Some("blue"), 2),
+            ]
+        );
+        for mesh in &meshes {
+            mesh.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn out_of_range_index() {
+        let source = format!("{}f 1 2 5\n", SQUARE);
+        assert_eq!(parse(&source).unwrap_err(), "line 6: index out of range: 5");
+        let source = format!("{}f 0 1 2\n", SQUARE);
+        assert_eq!(parse(&source).unwrap_err(), "line 6: index out of range: 0");
+        let source = format!("{}vt 0 0\nf 1/2 2/1 3/1\n", SQUARE);
+        assert_eq!(parse(&source).unwrap_err(), "line 7: index out of range: 2");
+        let source = format!("{}f 1 -5 2\n", SQUARE);
+        assert_eq!(
+            parse(&source).unwrap_err(),
+            "line 6: index out of range: -5"
+        );
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert_eq!(parse("v 0 0\n").unwrap_err(), "line 1: expected 3 numbers");
+        assert_eq!(parse("v 0 x 0\n").unwrap_err(), "line 1: invalid number: x");
+        let source = format!("{}f 1 2\n", SQUARE);
+        assert_eq!(
+            parse(&source).unwrap_err(),
+            "line 6: face needs at least 3 vertices"
+        );
+    }
+}
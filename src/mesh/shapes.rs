@@ -0,0 +1,258 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use super::data::{MeshData, MeshVertex};
+
+// よく使う形のメッシュを作る
+// - どれも原点が中心 (必要なら translated() でずらす)
+// - 表から見て反時計回りが表面 (カリングを有効にしても見える)
+// - 平面の形 (quad / polygon / circle / rounded_rect) はXY平面で +Z 向き、grid はXZ平面で +Y 向き
+// - UVは左上が原点
+#[allow(dead_code)]
+impl MeshData {
+    pub fn quad(width: f32, height: f32) -> MeshData {
+        let (x, y) = (width / 2.0, height / 2.0);
+        let mut mesh = MeshData::new("quad");
+        for &(px, py, u, v) in &[
+            (-x, -y, 0.0, 1.0),
+            (x, -y, 1.0, 1.0),
+            (x, y, 1.0, 0.0),
+            (-x, y, 0.0, 0.0),
+        ] {
+            mesh.vertices.push(MeshVertex {
+                position: [px, py, 0.0],
+                normal: [0.0, 0.0, 1.0],
+                uv: [u, v],
+            });
+        }
+        mesh.indices = vec![0, 1, 2, 0, 2, 3];
+        mesh
+    }
+
+    pub fn cube(size: f32) -> MeshData {
+        let mut mesh = MeshData::cuboid([size; 3]);
+        mesh.name = "cube".to_string();
+        mesh
+    }
+
+    // 面ごとに頂点を分けるので、角でも法線とUVがその面のものになる
+    pub fn cuboid(size: [f32; 3]) -> MeshData {
+        let half = [size[0] / 2.0, size[1] / 2.0, size[2] / 2.0];
+        // (法線, 右, 上) 右 × 上 = 法線
+        let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+            ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+            ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+            ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ];
+        let mut mesh = MeshData::new("cuboid");
+        for (normal, right, up) in faces.iter() {
+            let base = mesh.vertices.len() as u32;
+            for &(sx, sy, u, v) in &[
+                (-1.0, -1.0, 0.0, 1.0),
+                (1.0, -1.0, 1.0, 1.0),
+                (1.0, 1.0, 1.0, 0.0),
+                (-1.0, 1.0, 0.0, 0.0),
+            ] {
+                let mut position = [0.0; 3];
+                for axis in 0..3 {
+                    position[axis] = (normal[axis] + right[axis] * sx + up[axis] * sy) * half[axis];
+                }
+                mesh.vertices.push(MeshVertex {
+                    position,
+                    normal: *normal,
+                    uv: [u, v],
+                });
+            }
+            mesh.indices
+                .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        mesh
+    }
+
+    // 経度 segments 分割、緯度 rings 分割の球 (Y軸が極)
+    pub fn sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+        let (segments, rings) = (segments.max(3), rings.max(2));
+        let mut mesh = MeshData::new("sphere");
+        for ring in 0..=rings {
+            let phi = PI * ring as f32 / rings as f32; // 0 が上の極
+            for segment in 0..=segments {
+                let theta = TAU * segment as f32 / segments as f32;
+                let normal = [phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos()];
+                mesh.vertices.push(MeshVertex {
+                    position: normal.map(|n| n * radius),
+                    normal,
+                    uv: [segment as f32 / segments as f32, ring as f32 / rings as f32],
+                });
+            }
+        }
+        let columns = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let top_left = ring * columns + segment;
+                let bottom_left = top_left + columns;
+                // 極では三角形がつぶれるので作らない
+                if ring != rings - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[top_left, bottom_left, bottom_left + 1]);
+                }
+                if ring != 0 {
+                    mesh.indices
+                        .extend_from_slice(&[top_left, bottom_left + 1, top_left + 1]);
+                }
+            }
+        }
+        mesh
+    }
+
+    // Y軸に沿った、ふたのある円柱
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshData {
+        let segments = segments.max(3);
+        let half = height / 2.0;
+        let mut mesh = MeshData::new("cylinder");
+        // 側面 (継ぎ目でUVが切り替わるように、最初と最後の列は別の頂点にする)
+        for segment in 0..=segments {
+            let theta = TAU * segment as f32 / segments as f32;
+            let (sin, cos) = theta.sin_cos();
+            let u = segment as f32 / segments as f32;
+            for &(y, v) in &[(half, 0.0), (-half, 1.0)] {
+                mesh.vertices.push(MeshVertex {
+                    position: [sin * radius, y, cos * radius],
+                    normal: [sin, 0.0, cos],
+                    uv: [u, v],
+                });
+            }
+        }
+        for segment in 0..segments {
+            let top = segment * 2;
+            let bottom = top + 1;
+            mesh.indices
+                .extend_from_slice(&[top, bottom, bottom + 2, top, bottom + 2, top + 2]);
+        }
+        // ふた
+        for &(y, normal) in &[(half, 1.0), (-half, -1.0)] {
+            let center = mesh.vertices.len() as u32;
+            mesh.vertices.push(MeshVertex {
+                position: [0.0, y, 0.0],
+                normal: [0.0, normal, 0.0],
+                uv: [0.5, 0.5],
+            });
+            for segment in 0..segments {
+                let theta = TAU * segment as f32 / segments as f32;
+                let (sin, cos) = theta.sin_cos();
+                mesh.vertices.push(MeshVertex {
+                    position: [sin * radius, y, cos * radius],
+                    normal: [0.0, normal, 0.0],
+                    uv: [0.5 + sin * 0.5, 0.5 - cos * 0.5 * normal],
+                });
+            }
+            for segment in 0..segments {
+                let current = center + 1 + segment;
+                let next = center + 1 + (segment + 1) % segments;
+                if normal > 0.0 {
+                    mesh.indices.extend_from_slice(&[center, current, next]);
+                } else {
+                    mesh.indices.extend_from_slice(&[center, next, current]);
+                }
+            }
+        }
+        mesh
+    }
+
+    // XZ平面の格子 (地面など)。UVは全体で 0.0 ~ 1.0
+    pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> MeshData {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let mut mesh = MeshData::new("grid");
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+                mesh.vertices.push(MeshVertex {
+                    position: [(u - 0.5) * width, 0.0, (v - 0.5) * depth],
+                    normal: [0.0, 1.0, 0.0],
+                    uv: [u, v],
+                });
+            }
+        }
+        let stride = columns + 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = row * stride + column;
+                let b = a + stride;
+                mesh.indices
+                    .extend_from_slice(&[a, b, b + 1, a, b + 1, a + 1]);
+            }
+        }
+        mesh
+    }
+
+    // 正多角形 (最初の頂点が真上にくる)
+    pub fn polygon(radius: f32, sides: u32) -> MeshData {
+        let sides = sides.max(3);
+        let points: Vec<[f32; 2]> = (0..sides)
+            .map(|side| {
+                let theta = FRAC_PI_2 + TAU * side as f32 / sides as f32;
+                [theta.cos() * radius, theta.sin() * radius]
+            })
+            .collect();
+        fan("polygon", &points, [radius * 2.0, radius * 2.0])
+    }
+
+    pub fn circle(radius: f32, segments: u32) -> MeshData {
+        let mut mesh = MeshData::polygon(radius, segments);
+        mesh.name = "circle".to_string();
+        mesh
+    }
+
+    // 角を丸めた長方形 (corner_segments は角1つあたりの分割数)
+    pub fn rounded_rect(
+        width: f32,
+        height: f32,
+        corner_radius: f32,
+        corner_segments: u32,
+    ) -> MeshData {
+        let radius = corner_radius.max(0.0).min(width.min(height) / 2.0);
+        let segments = corner_segments.max(1);
+        let (x, y) = (width / 2.0 - radius, height / 2.0 - radius);
+        // 右上の角から反時計回りに、角ごとの円弧をつなぐ
+        let corners = [(x, y), (-x, y), (-x, -y), (x, -y)];
+        let mut points = Vec::new();
+        for (index, (cx, cy)) in corners.iter().enumerate() {
+            for segment in 0..=segments {
+                let theta = FRAC_PI_2 * (index as f32 + segment as f32 / segments as f32);
+                points.push([cx + theta.cos() * radius, cy + theta.sin() * radius]);
+            }
+        }
+        fan("rounded_rect", &points, [width, height])
+    }
+
+    // すべての頂点をずらす (原点を角にしたいときなど)
+    pub fn translated(mut self, offset: [f32; 3]) -> MeshData {
+        for vertex in &mut self.vertices {
+            for (position, offset) in vertex.position.iter_mut().zip(offset) {
+                *position += offset;
+            }
+        }
+        self
+    }
+}
+
+// 中心から反時計回りの外周へ扇形に三角形を張る (XY平面、+Z 向き)
+// UVは size の長方形に合わせる
+fn fan(name: &str, points: &[[f32; 2]], size: [f32; 2]) -> MeshData {
+    let mut mesh = MeshData::new(name);
+    let vertex = |x: f32, y: f32| MeshVertex {
+        position: [x, y, 0.0],
+        normal: [0.0, 0.0, 1.0],
+        uv: [0.5 + x / size[0], 0.5 - y / size[1]],
+    };
+    mesh.vertices.push(vertex(0.0, 0.0));
+    mesh.vertices
+        .extend(points.iter().map(|&[x, y]| vertex(x, y)));
+    let count = points.len() as u32;
+    for index in 0..count {
+        mesh.indices
+            .extend_from_slice(&[0, 1 + index, 1 + (index + 1) % count]);
+    }
+    mesh
+}
@@ -0,0 +1,13 @@
+mod config;
+mod curve;
+mod emitter;
+mod render;
+
+#[allow(unused_imports)]
+pub use config::{EmitMode, EmitterShape, ParticleConfig, SpriteAnimation};
+#[allow(unused_imports)]
+pub use curve::{Curve, Lerp};
+#[allow(unused_imports)]
+pub use emitter::{Particle, ParticleEmitter};
+#[allow(unused_imports)]
+pub use render::{particle_render_system, particle_update_system};
@@ -0,0 +1,369 @@
+use std::fs;
+
+use imgui::im_str;
+use serde::{Deserialize, Serialize};
+
+use super::curve::{Curve, Lerp};
+use crate::material::BlendMode;
+
+// 粒子を出す間隔
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum EmitMode {
+    Continuous { rate: f32 },            // 1秒あたりに出す数
+    Burst { count: u32, interval: f32 }, // interval 秒ごとにまとめて出す (0以下なら最初の1回だけ)
+}
+
+// 粒子を出す場所と向き
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum EmitterShape {
+    Point,                              // 1点から全方向へ
+    Circle { radius: f32, edge: bool }, // 円の中 (edge なら円周上) から外向きへ
+    Cone { angle: f32 },                // 1点から direction を中心とした angle 度の範囲へ
+    Rect { width: f32, height: f32 },   // 矩形の中から direction へ
+}
+
+// テクスチャを格子に分けたコマ送り (左上のコマから横に並べる)
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpriteAnimation {
+    pub columns: u32,
+    pub rows: u32,
+    pub frames: u32,        // 使うコマの数 (0ならすべて)
+    pub fps: f32,           // 0以下なら寿命の間に1回だけ再生する
+    pub random_start: bool, // 粒子ごとに違うコマから始める
+}
+
+impl Default for SpriteAnimation {
+    fn default() -> SpriteAnimation {
+        SpriteAnimation {
+            columns: 1,
+            rows: 1,
+            frames: 0,
+            fps: 0.0,
+            random_start: false,
+        }
+    }
+}
+
+impl SpriteAnimation {
+    pub fn frame_count(&self) -> u32 {
+        let cells = self.columns.max(1) * self.rows.max(1);
+        if self.frames == 0 {
+            cells
+        } else {
+            self.frames.min(cells)
+        }
+    }
+}
+
+// パーティクルの設定 (RON / TOML / JSON のファイルから Config<ParticleConfig> として読み込む)
+// 範囲で書いた値 (lifetime など) は、粒子ごとに (最小, 最大) の間から選ぶ
+// 角度は度で、direction は 0 が右、90 が上
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ParticleConfig {
+    pub max_particles: usize,
+    pub mode: EmitMode,
+    pub duration: f32, // 出し続ける時間 (0以下ならずっと)
+    pub shape: EmitterShape,
+    pub direction: f32,
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    pub size: (f32, f32), // 生まれたときの大きさ (ワールド単位)。size_over_life を掛ける
+    pub rotation: (f32, f32),
+    pub angular_velocity: (f32, f32), // 度/秒
+    pub gravity: [f32; 2],
+    pub drag: f32, // 1秒あたりに失う速度の割合
+    pub color_over_life: Curve<[f32; 4]>,
+    pub size_over_life: Curve<f32>,
+    pub texture: Option<String>, // なければ丸くぼかした点を描く
+    pub animation: Option<SpriteAnimation>,
+    pub blend_mode: BlendMode,
+    pub layer: i32,
+}
+
+impl Default for ParticleConfig {
+    fn default() -> ParticleConfig {
+        ParticleConfig {
+            max_particles: 256,
+            mode: EmitMode::Continuous { rate: 20.0 },
+            duration: 0.0,
+            shape: EmitterShape::Point,
+            direction: 90.0,
+            lifetime: (1.0, 1.0),
+            speed: (1.0, 2.0),
+            size: (0.2, 0.2),
+            rotation: (0.0, 0.0),
+            angular_velocity: (0.0, 0.0),
+            gravity: [0.0, 0.0],
+            drag: 0.0,
+            color_over_life: Curve::new(vec![
+                (0.0, [1.0, 1.0, 1.0, 1.0]),
+                (1.0, [1.0, 1.0, 1.0, 0.0]),
+            ]),
+            size_over_life: Curve::constant(1.0),
+            texture: None,
+            animation: None,
+            blend_mode: BlendMode::Alpha,
+            layer: 50,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ParticleConfig {
+    // RONで書き出す (エディターで調整した値をファイルに残す)
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("{}: {}", path, e))?;
+        fs::write(path, source).map_err(|e| format!("failed to write file: {}: {}", path, e))
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        let mut max_particles = self.max_particles as i32;
+        if imgui::Slider::new(im_str!("Max Particles"))
+            .range(1..=5000)
+            .build(ui, &mut max_particles)
+        {
+            self.max_particles = max_particles.max(1) as usize;
+        }
+
+        // 出し方
+        let mut mode = match self.mode {
+            EmitMode::Continuous { .. } => 0,
+            EmitMode::Burst { .. } => 1,
+        };
+        if imgui::ComboBox::new(im_str!("Mode")).build_simple_string(
+            ui,
+            &mut mode,
+            &[im_str!("Continuous"), im_str!("Burst")],
+        ) {
+            self.mode = match mode {
+                0 => EmitMode::Continuous { rate: 20.0 },
+                _ => EmitMode::Burst {
+                    count: 20,
+                    interval: 1.0,
+                },
+            };
+        }
+        match &mut self.mode {
+            EmitMode::Continuous { rate } => {
+                imgui::Slider::new(im_str!("Rate"))
+                    .range(0.0..=500.0)
+                    .build(ui, rate);
+            }
+            EmitMode::Burst { count, interval } => {
+                imgui::Slider::new(im_str!("Count"))
+                    .range(0..=500)
+                    .build(ui, count);
+                imgui::Slider::new(im_str!("Interval"))
+                    .range(0.0..=10.0)
+                    .build(ui, interval);
+            }
+        }
+        imgui::Slider::new(im_str!("Duration"))
+            .range(0.0..=10.0)
+            .build(ui, &mut self.duration);
+
+        // 形
+        let mut shape = match self.shape {
+            EmitterShape::Point => 0,
+            EmitterShape::Circle { .. } => 1,
+            EmitterShape::Cone { .. } => 2,
+            EmitterShape::Rect { .. } => 3,
+        };
+        if imgui::ComboBox::new(im_str!("Shape")).build_simple_string(
+            ui,
+            &mut shape,
+            &[
+                im_str!("Point"),
+                im_str!("Circle"),
+                im_str!("Cone"),
+                im_str!("Rect"),
+            ],
+        ) {
+            self.shape = match shape {
+                0 => EmitterShape::Point,
+                1 => EmitterShape::Circle {
+                    radius: 0.5,
+                    edge: false,
+                },
+                2 => EmitterShape::Cone { angle: 30.0 },
+                _ => EmitterShape::Rect {
+                    width: 1.0,
+                    height: 1.0,
+                },
+            };
+        }
+        match &mut self.shape {
+            EmitterShape::Point => {}
+            EmitterShape::Circle { radius, edge } => {
+                imgui::Slider::new(im_str!("Radius"))
+                    .range(0.0..=5.0)
+                    .build(ui, radius);
+                ui.checkbox(im_str!("Edge"), edge);
+            }
+            EmitterShape::Cone { angle } => {
+                imgui::Slider::new(im_str!("Angle"))
+                    .range(0.0..=360.0)
+                    .build(ui, angle);
+            }
+            EmitterShape::Rect { width, height } => {
+                imgui::Slider::new(im_str!("Width"))
+                    .range(0.0..=20.0)
+                    .build(ui, width);
+                imgui::Slider::new(im_str!("Height"))
+                    .range(0.0..=20.0)
+                    .build(ui, height);
+            }
+        }
+        imgui::Slider::new(im_str!("Direction"))
+            .range(-180.0..=180.0)
+            .build(ui, &mut self.direction);
+
+        // 粒子
+        range(ui, im_str!("Lifetime"), &mut self.lifetime, 0.01, 0.0, 20.0);
+        range(ui, im_str!("Speed"), &mut self.speed, 0.05, 0.0, 50.0);
+        range(ui, im_str!("Size"), &mut self.size, 0.01, 0.0, 10.0);
+        range(
+            ui,
+            im_str!("Rotation"),
+            &mut self.rotation,
+            1.0,
+            -360.0,
+            360.0,
+        );
+        range(
+            ui,
+            im_str!("Angular Velocity"),
+            &mut self.angular_velocity,
+            1.0,
+            -1080.0,
+            1080.0,
+        );
+        imgui::Drag::new(im_str!("Gravity"))
+            .speed(0.05)
+            .build_array(ui, &mut self.gravity);
+        imgui::Slider::new(im_str!("Drag"))
+            .range(0.0..=10.0)
+            .build(ui, &mut self.drag);
+
+        // 寿命に対する変化
+        if imgui::CollapsingHeader::new(im_str!("Color over Life")).build(ui) {
+            edit_keys(
+                ui,
+                "color",
+                &mut self.color_over_life,
+                [1.0; 4],
+                |ui, value| {
+                    imgui::ColorEdit::new(im_str!("Color"), value).build(ui);
+                },
+            );
+        }
+        if imgui::CollapsingHeader::new(im_str!("Size over Life")).build(ui) {
+            edit_keys(ui, "size", &mut self.size_over_life, 1.0, |ui, value| {
+                imgui::Slider::new(im_str!("Size"))
+                    .range(0.0..=4.0)
+                    .build(ui, value);
+            });
+        }
+
+        // 見た目
+        ui.text(format!(
+            "Texture: {}",
+            self.texture.as_deref().unwrap_or("(none)")
+        ));
+        let mut animated = self.animation.is_some();
+        if ui.checkbox(im_str!("Animation"), &mut animated) {
+            self.animation = if animated {
+                Some(SpriteAnimation::default())
+            } else {
+                None
+            };
+        }
+        if let Some(animation) = &mut self.animation {
+            let mut grid = [animation.columns as i32, animation.rows as i32];
+            if imgui::Drag::new(im_str!("Columns / Rows"))
+                .range(1..=64)
+                .build_array(ui, &mut grid)
+            {
+                animation.columns = grid[0].max(1) as u32;
+                animation.rows = grid[1].max(1) as u32;
+            }
+            imgui::Slider::new(im_str!("Frames"))
+                .range(0..=256)
+                .build(ui, &mut animation.frames);
+            imgui::Slider::new(im_str!("FPS"))
+                .range(0.0..=60.0)
+                .build(ui, &mut animation.fps);
+            ui.checkbox(im_str!("Random Start"), &mut animation.random_start);
+        }
+        let mut blend = match self.blend_mode {
+            BlendMode::Alpha => 0,
+            BlendMode::Additive => 1,
+            BlendMode::Multiply => 2,
+        };
+        if imgui::ComboBox::new(im_str!("Blend")).build_simple_string(
+            ui,
+            &mut blend,
+            &[im_str!("Alpha"), im_str!("Additive"), im_str!("Multiply")],
+        ) {
+            self.blend_mode = match blend {
+                0 => BlendMode::Alpha,
+                1 => BlendMode::Additive,
+                _ => BlendMode::Multiply,
+            };
+        }
+        imgui::Slider::new(im_str!("Layer"))
+            .range(-100..=1000)
+            .build(ui, &mut self.layer);
+    }
+}
+
+fn range(
+    ui: &imgui::Ui,
+    label: &imgui::ImStr,
+    value: &mut (f32, f32),
+    speed: f32,
+    min: f32,
+    max: f32,
+) {
+    imgui::DragRange::new(label)
+        .speed(speed)
+        .range(min..=max)
+        .build(ui, &mut value.0, &mut value.1);
+}
+
+// キーを1行ずつ編集する (時間を変えたら並べ直す)
+fn edit_keys<T: Lerp>(
+    ui: &imgui::Ui,
+    id: &str,
+    curve: &mut Curve<T>,
+    default: T,
+    edit_value: impl Fn(&imgui::Ui, &mut T),
+) {
+    let mut remove = None;
+    let mut moved = false;
+    for (index, (time, value)) in curve.keys.iter_mut().enumerate() {
+        let token = ui.push_id(&*format!("{}{}", id, index));
+        moved |= imgui::Slider::new(im_str!("Time"))
+            .range(0.0..=1.0)
+            .build(ui, time);
+        edit_value(ui, value);
+        // 最初のキーは消さない (キーが1つもないと値が決まらない)
+        if index > 0 && ui.small_button(im_str!("Remove")) {
+            remove = Some(index);
+        }
+        token.pop(ui);
+    }
+    if let Some(index) = remove {
+        curve.keys.remove(index);
+    }
+    if moved {
+        curve.sort();
+    }
+    if ui.small_button(&im_str!("Add Key##{}", id)) {
+        let value = curve.sample(1.0).unwrap_or(default);
+        curve.keys.push((1.0, value));
+    }
+}
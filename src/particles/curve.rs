@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+// 2つの値の間を補間できる型
+pub trait Lerp: Copy {
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+}
+
+impl<const N: usize> Lerp for [f32; N] {
+    fn lerp(a: [f32; N], b: [f32; N], t: f32) -> [f32; N] {
+        let mut value = a;
+        for (v, b) in value.iter_mut().zip(b) {
+            *v += (b - *v) * t;
+        }
+        value
+    }
+}
+
+// 寿命の割合 (0.0 ~ 1.0) に対する値の変化
+// キーの間は直線でつなぎ、最初のキーより前と最後のキーより後は端の値のまま
+// ファイルには [(0.0, 1.0), (1.0, 0.0)] のように (割合, 値) の並びで書く
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Curve<T> {
+    pub keys: Vec<(f32, T)>,
+}
+
+#[allow(dead_code)]
+impl<T: Lerp> Curve<T> {
+    pub fn new(mut keys: Vec<(f32, T)>) -> Curve<T> {
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Curve { keys }
+    }
+
+    pub fn constant(value: T) -> Curve<T> {
+        Curve {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    // キーがなければ None
+    pub fn sample(&self, t: f32) -> Option<T> {
+        let (first, last) = (self.keys.first()?, self.keys.last()?);
+        if t <= first.0 {
+            return Some(first.1);
+        }
+        if t >= last.0 {
+            return Some(last.1);
+        }
+        // エディターで並び順が崩れても、隣り合うキーの区間から探す
+        let index = self.keys.windows(2).position(|pair| t < pair[1].0)?;
+        let ((t0, a), (t1, b)) = (self.keys[index], self.keys[index + 1]);
+        let span = t1 - t0;
+        Some(if span > 0.0 {
+            T::lerp(a, b, (t - t0) / span)
+        } else {
+            b
+        })
+    }
+
+    // キーの時間の順に並べ直す (エディターで変えた後に呼ぶ)
+    pub fn sort(&mut self) {
+        self.keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+}
@@ -0,0 +1,362 @@
+use std::f32::consts::TAU;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use cgmath::{vec2, InnerSpace};
+use imgui::im_str;
+
+use super::config::{EmitMode, EmitterShape, ParticleConfig};
+use crate::material::MaterialId;
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 乱数の種 (エミッターごとに違う並びにする)
+static NEXT_SEED: AtomicU32 = AtomicU32::new(0x2545_f491);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub position: Vector2, // ワールド座標
+    pub velocity: Vector2,
+    pub rotation: f32, // ラジアン
+    pub angular_velocity: f32,
+    pub size: f32, // 生まれたときの大きさ
+    pub age: f32,
+    pub lifetime: f32,
+    pub frame: u32, // コマ送りの最初のコマ
+}
+
+impl Particle {
+    // 寿命のうち過ぎた割合 (0.0 ~ 1.0)
+    pub fn life(&self) -> f32 {
+        (self.age / self.lifetime).min(1.0)
+    }
+}
+
+// 粒子を出すコンポーネント (Transform2D の pivot の位置から出す)
+// 出した粒子はワールド座標で動くので、エミッターが動いても置いていかれる
+pub struct ParticleEmitter {
+    pub config: ParticleConfig,
+    pub playing: bool,
+    particles: Vec<Particle>,
+    time: f32, // 再生を始めてからの時間
+    spawn_accumulator: f32,
+    next_burst: f32,
+    pending: u32, // emit() で頼まれた数
+    random: Random,
+    last_position: Option<Vector2>,
+    pub(super) instances: Vec<f32>, // 描画するときの粒子ごとの属性
+    pub(super) vertex: Option<Rc<Vertex>>,
+    pub(super) material: Option<(String, MaterialId)>, // マテリアルの名前 (テクスチャと合成方法で決まる)
+}
+
+#[allow(dead_code)]
+impl ParticleEmitter {
+    pub fn new(config: ParticleConfig) -> ParticleEmitter {
+        ParticleEmitter {
+            config,
+            playing: true,
+            particles: Vec::new(),
+            time: 0.0,
+            spawn_accumulator: 0.0,
+            next_burst: 0.0,
+            pending: 0,
+            random: Random::new(NEXT_SEED.fetch_add(0x9e37_79b9, Ordering::Relaxed)),
+            last_position: None,
+            instances: Vec::new(),
+            vertex: None,
+            material: None,
+        }
+    }
+
+    // 自動では出さず、emit() で出すときだけ使う
+    pub fn paused(mut self) -> ParticleEmitter {
+        self.playing = false;
+        self
+    }
+
+    // 最初から出し直す
+    pub fn play(&mut self) {
+        self.playing = true;
+        self.time = 0.0;
+        self.spawn_accumulator = 0.0;
+        self.next_burst = 0.0;
+        self.last_position = None;
+    }
+
+    // 新しく出すのをやめる (出ている粒子は寿命まで動く)
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    // 次の更新で count 個をまとめて出す
+    pub fn emit(&mut self, count: u32) {
+        self.pending += count;
+    }
+
+    // 出ている粒子を消す (移動中に瞬間移動させたときなど、前の位置からつながないようにする)
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.pending = 0;
+        self.last_position = None;
+    }
+
+    // ファイルを読み込み直したときなど (出ている粒子はそのまま)
+    pub fn set_config(&mut self, config: ParticleConfig) {
+        self.config = config;
+        self.particles.truncate(self.config.max_particles);
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    // 出し続けているか、まだ粒子が残っている
+    pub fn is_alive(&self) -> bool {
+        self.playing || self.pending > 0 || !self.particles.is_empty()
+    }
+
+    // 粒子を動かしてから、新しい粒子を出す
+    // position と rotation はエミッターのワールド座標での位置と回転 (ラジアン)
+    pub fn update(&mut self, delta_time: f32, position: Vector2, rotation: f32) {
+        let config = &self.config;
+        let gravity = Vector2::from(config.gravity);
+        let drag = (1.0 - config.drag * delta_time).max(0.0);
+        self.particles.retain_mut(|particle| {
+            particle.age += delta_time;
+            if particle.age >= particle.lifetime {
+                return false;
+            }
+            particle.velocity = (particle.velocity + gravity * delta_time) * drag;
+            particle.position += particle.velocity * delta_time;
+            particle.rotation += particle.angular_velocity * delta_time;
+            true
+        });
+
+        // 続けて出す分は前の位置から今の位置の間に並べて、エミッターが速く動いても途切れないようにする
+        // emit() で頼まれた分は今の位置から出す
+        let count = self.spawn_count(delta_time);
+        let pending = std::mem::take(&mut self.pending);
+        let from = self.last_position.unwrap_or(position);
+        for index in 0..count + pending {
+            if self.particles.len() >= self.config.max_particles {
+                break;
+            }
+            let origin = if index < count {
+                from + (position - from) * ((index + 1) as f32 / count as f32)
+            } else {
+                position
+            };
+            let particle = self.spawn(origin, rotation);
+            self.particles.push(particle);
+        }
+        self.last_position = Some(position);
+    }
+
+    // 再生中にこのフレームで出す数
+    fn spawn_count(&mut self, delta_time: f32) -> u32 {
+        if !self.playing {
+            return 0;
+        }
+        let count = match self.config.mode {
+            EmitMode::Continuous { rate } => {
+                self.spawn_accumulator += rate.max(0.0) * delta_time;
+                let spawned = self.spawn_accumulator.floor();
+                self.spawn_accumulator -= spawned;
+                spawned as u32
+            }
+            EmitMode::Burst {
+                count: burst,
+                interval,
+            } => {
+                if self.time < self.next_burst {
+                    0
+                } else {
+                    if interval > 0.0 {
+                        self.next_burst += interval;
+                    } else {
+                        self.playing = false;
+                    }
+                    burst
+                }
+            }
+        };
+        self.time += delta_time;
+        if self.config.duration > 0.0 && self.time >= self.config.duration {
+            self.playing = false;
+        }
+        count
+    }
+
+    fn spawn(&mut self, origin: Vector2, rotation: f32) -> Particle {
+        let config = &self.config;
+        let random = &mut self.random;
+        let direction = config.direction.to_radians() + rotation;
+        let (offset, angle) = match config.shape {
+            EmitterShape::Point => (vec2(0.0, 0.0), random.range((0.0, TAU))),
+            EmitterShape::Circle { radius, edge } => {
+                let angle = random.range((0.0, TAU));
+                // 面積あたりの数がそろうように、半径は平方根で選ぶ
+                let distance = if edge {
+                    radius
+                } else {
+                    radius * random.next().sqrt()
+                };
+                (from_angle(angle) * distance, angle)
+            }
+            EmitterShape::Cone { angle } => {
+                let half = angle.to_radians() * 0.5;
+                (vec2(0.0, 0.0), direction + random.range((-half, half)))
+            }
+            EmitterShape::Rect { width, height } => {
+                let local = vec2(
+                    random.range((-0.5, 0.5)) * width,
+                    random.range((-0.5, 0.5)) * height,
+                );
+                let (sin, cos) = rotation.sin_cos();
+                (
+                    vec2(local.x * cos - local.y * sin, local.x * sin + local.y * cos),
+                    direction,
+                )
+            }
+        };
+        let frame = match config.animation {
+            Some(animation) if animation.random_start => {
+                (random.next() * animation.frame_count() as f32) as u32
+            }
+            _ => 0,
+        };
+        Particle {
+            position: origin + offset,
+            velocity: from_angle(angle) * random.range(config.speed),
+            rotation: random.range(config.rotation).to_radians(),
+            angular_velocity: random.range(config.angular_velocity).to_radians(),
+            size: random.range(config.size),
+            age: 0.0,
+            lifetime: random.range(config.lifetime).max(0.001),
+            frame,
+        }
+    }
+
+    pub fn edit(&mut self, ui: &imgui::Ui) {
+        ui.text(format!(
+            "Particles: {} / {}",
+            self.particles.len(),
+            self.config.max_particles
+        ));
+        if ui.small_button(im_str!("Play")) {
+            self.play();
+        }
+        ui.same_line(0.0);
+        if ui.small_button(im_str!("Stop")) {
+            self.stop();
+        }
+        ui.same_line(0.0);
+        if ui.small_button(im_str!("Burst")) {
+            self.emit(20);
+        }
+        ui.same_line(0.0);
+        if ui.small_button(im_str!("Clear")) {
+            self.clear();
+        }
+        self.config.edit(ui);
+    }
+}
+
+fn from_angle(angle: f32) -> Vector2 {
+    let (sin, cos) = angle.sin_cos();
+    vec2(cos, sin).normalize()
+}
+
+// 軽い乱数 (xorshift)
+struct Random(u32);
+
+impl Random {
+    fn new(seed: u32) -> Random {
+        Random(seed.max(1))
+    }
+
+    // 0.0 以上 1.0 未満
+    fn next(&mut self) -> f32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        (x >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    fn range(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + (max - min) * self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 速さ0で1秒に10個出す (粒子は生まれた位置から動かない)
+    fn emitter() -> ParticleEmitter {
+        ParticleEmitter::new(ParticleConfig {
+            mode: EmitMode::Continuous { rate: 10.0 },
+            speed: (0.0, 0.0),
+            lifetime: (10.0, 10.0),
+            ..ParticleConfig::default()
+        })
+    }
+
+    fn xs(emitter: &ParticleEmitter) -> Vec<f32> {
+        emitter.particles().iter().map(|p| p.position.x).collect()
+    }
+
+    #[test]
+    fn continuous_particles_fill_the_path() {
+        let mut emitter = emitter();
+        emitter.update(0.1, vec2(0.0, 0.0), 0.0);
+        emitter.update(0.5, vec2(5.0, 0.0), 0.0);
+        assert_eq!(xs(&emitter), vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn play_does_not_connect_to_the_old_position() {
+        let mut emitter = emitter();
+        emitter.update(0.1, vec2(0.0, 0.0), 0.0);
+        emitter.stop();
+        emitter.update(0.1, vec2(0.0, 0.0), 0.0);
+        emitter.play();
+        emitter.update(0.3, vec2(100.0, 0.0), 0.0);
+        assert_eq!(xs(&emitter), vec![0.0, 100.0, 100.0, 100.0]);
+    }
+
+    #[test]
+    fn clear_does_not_connect_to_the_old_position() {
+        let mut emitter = emitter();
+        emitter.update(0.1, vec2(0.0, 0.0), 0.0);
+        emitter.clear();
+        emitter.update(0.3, vec2(100.0, 0.0), 0.0);
+        assert_eq!(xs(&emitter), vec![100.0, 100.0, 100.0]);
+    }
+
+    #[test]
+    fn emit_and_max_particles() {
+        let mut emitter = emitter().paused();
+        emitter.config.max_particles = 5;
+        emitter.update(1.0, vec2(0.0, 0.0), 0.0);
+        assert_eq!(emitter.particle_count(), 0);
+        emitter.emit(3);
+        emitter.emit(4);
+        assert!(emitter.is_alive());
+        emitter.update(0.1, vec2(2.0, 0.0), 0.0);
+        assert_eq!(xs(&emitter), vec![2.0; 5]);
+
+        // 寿命が来たら消える
+        emitter.update(10.0, vec2(2.0, 0.0), 0.0);
+        assert_eq!(emitter.particle_count(), 0);
+        assert!(!emitter.is_alive());
+    }
+}
@@ -0,0 +1,213 @@
+use std::mem;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use cgmath::{vec2, SquareMatrix};
+use gl::types::{GLfloat, GLsizei, GLsizeiptr};
+
+use super::config::ParticleConfig;
+use super::emitter::{Particle, ParticleEmitter};
+use crate::ecs::{Read, Time, World, Write};
+use crate::material::{Material, MaterialId, MaterialLibrary, RenderState, UniformValue};
+use crate::renderer::{DrawCommand, Renderer};
+use crate::transform::Transform2D;
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+#[allow(dead_code)]
+type Matrix4 = cgmath::Matrix4<f32>;
+
+// 1粒子 (インスタンス) あたりの要素数 (位置xy、大きさ、回転、UVの範囲、色rgba)
+const FLOATS_PER_INSTANCE: usize = 12;
+// 粒子の四角形の角 (左下、右下、右上、左上)
+const QUAD_CORNERS: [f32; 8] = [-0.5, -0.5, 0.5, -0.5, 0.5, 0.5, -0.5, 0.5];
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+const PARTICLE_VERTEX_SHADER: &str = "rsc/shader/particle.vs";
+const PARTICLE_FRAGMENT_SHADER: &str = "rsc/shader/particle.fs";
+
+// 粒子を動かし、新しい粒子を出す (PostUpdate、transform_propagate_system の後)
+pub fn particle_update_system(world: &mut World) {
+    let delta_time = world.resource::<Time>().delta_time;
+    world
+        .query::<(Read<Transform2D>, Write<ParticleEmitter>)>()
+        .for_each(|_, (transform, emitter)| {
+            let matrix = transform.world_matrix();
+            let rotation = matrix.x.y.atan2(matrix.x.x);
+            emitter.update(delta_time, transform.world_position(), rotation);
+        });
+}
+
+// エミッターごとに1つの四角形を粒子の数だけインスタンス描画し、1回の描画で済ませる (Render)
+// 粒子ごとの位置や色はインスタンスの属性で渡し、回転はシェーダーで行う
+// 同じテクスチャと合成方法のエミッターはマテリアルを共有する
+pub fn particle_render_system(world: &mut World) {
+    let mut renderer = world.resource_mut::<Renderer>();
+    world
+        .query::<Write<ParticleEmitter>>()
+        .for_each(|_, emitter| {
+            if emitter.particle_count() == 0 {
+                return;
+            }
+            let material = resolve_material(emitter, &mut renderer);
+            build_instances(emitter);
+            if emitter.instances.is_empty() {
+                return;
+            }
+
+            let size = mem::size_of_val(emitter.instances.as_slice()) as GLsizeiptr;
+            let data = emitter.instances.as_ptr() as *const c_void;
+            let instance_num = (emitter.instances.len() / FLOATS_PER_INSTANCE) as i32;
+            // 前のフレームの描画が終わっていれば、同じバッファーを使い回す
+            let updated = match emitter.vertex.as_mut().and_then(Rc::get_mut) {
+                Some(vertex) => {
+                    vertex.update_instances(0, size, data, instance_num);
+                    true
+                }
+                None => false,
+            };
+            if !updated {
+                let stride = (FLOATS_PER_INSTANCE * mem::size_of::<GLfloat>()) as GLsizei;
+                emitter.vertex = Some(Rc::new(
+                    Vertex::new(
+                        mem::size_of_val(&QUAD_CORNERS) as GLsizeiptr,
+                        QUAD_CORNERS.as_ptr() as *const c_void,
+                        gl::STATIC_DRAW,
+                        vec![gl::FLOAT],
+                        vec![2],
+                        (2 * mem::size_of::<GLfloat>()) as GLsizei,
+                        (QUAD_CORNERS.len() / 2) as i32,
+                    )
+                    .with_indices(&QUAD_INDICES)
+                    .with_instances(
+                        size,
+                        data,
+                        gl::DYNAMIC_DRAW, // 毎フレーム書き換える
+                        vec![gl::FLOAT, gl::FLOAT, gl::FLOAT],
+                        vec![4, 4, 4],
+                        stride,
+                    ),
+                ));
+            }
+
+            // 粒子はワールド座標で持っているので、モデル行列は単位行列
+            renderer.submit(DrawCommand {
+                vertex: emitter.vertex.clone().unwrap(),
+                material,
+                model: Matrix4::identity(),
+                layer: emitter.config.layer,
+            });
+        });
+}
+
+// テクスチャと合成方法が変わったときだけマテリアルを探し直す
+fn resolve_material(emitter: &mut ParticleEmitter, renderer: &mut Renderer) -> MaterialId {
+    let config = &emitter.config;
+    let name = format!(
+        "particles:{}:{:?}",
+        config.texture.as_deref().unwrap_or(""),
+        config.blend_mode
+    );
+    if let Some((cached, id)) = &emitter.material {
+        if *cached == name {
+            return *id;
+        }
+    }
+    let materials = &mut renderer.materials;
+    let id = match materials.id(&name) {
+        Some(id) => id,
+        None => {
+            let material = create_material(&name, config, materials);
+            materials.insert(material)
+        }
+    };
+    emitter.material = Some((name, id));
+    id
+}
+
+fn create_material(
+    name: &str,
+    config: &ParticleConfig,
+    materials: &mut MaterialLibrary,
+) -> Material {
+    let shader = materials.load_shader(PARTICLE_VERTEX_SHADER, PARTICLE_FRAGMENT_SHADER);
+    let mut material = Material::new(name, shader);
+    // 粒子どうしは深度で隠さず、描いた順に重ねる
+    material.render_state = RenderState {
+        depth_test: false,
+        depth_write: false,
+        blend: true,
+        blend_mode: config.blend_mode,
+        culling: false,
+        ..RenderState::default()
+    };
+    let texture = config
+        .texture
+        .as_deref()
+        .and_then(|path| match materials.load_texture(path) {
+            Ok(texture) => Some(texture),
+            Err(e) => {
+                eprintln!("failed to load particle texture: {}", e);
+                None
+            }
+        });
+    material.set_uniform("uHasTexture", UniformValue::Bool(texture.is_some()));
+    if let Some(texture) = texture {
+        material.set_texture("uTexture", texture);
+    }
+    material
+}
+
+// 粒子ごとのインスタンスの属性を並べる
+fn build_instances(emitter: &mut ParticleEmitter) {
+    let mut instances = mem::take(&mut emitter.instances);
+    instances.clear();
+    let config = &emitter.config;
+    for particle in emitter.particles() {
+        let life = particle.life();
+        let color = config
+            .color_over_life
+            .sample(life)
+            .unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        let size = particle.size * config.size_over_life.sample(life).unwrap_or(1.0);
+        if size <= 0.0 || color[3] <= 0.0 {
+            continue;
+        }
+        let (uv_min, uv_max) = frame_uv(config, particle);
+        instances.extend_from_slice(&[
+            particle.position.x,
+            particle.position.y,
+            size,
+            particle.rotation,
+            uv_min.x,
+            uv_min.y,
+            uv_max.x,
+            uv_max.y,
+        ]);
+        instances.extend_from_slice(&color);
+    }
+    emitter.instances = instances;
+}
+
+// コマ送りの今のコマのUVの範囲 (コマ送りがなければテクスチャ全体)
+fn frame_uv(config: &ParticleConfig, particle: &Particle) -> (Vector2, Vector2) {
+    let animation = match config.animation {
+        Some(animation) => animation,
+        None => return (vec2(0.0, 0.0), vec2(1.0, 1.0)),
+    };
+    let count = animation.frame_count();
+    let frame = if animation.fps > 0.0 {
+        (particle.frame + (particle.age * animation.fps) as u32) % count
+    } else {
+        // 寿命の間に1回だけ再生する
+        (particle.frame + (particle.life() * count as f32) as u32).min(count - 1)
+    };
+    let (columns, rows) = (animation.columns.max(1), animation.rows.max(1));
+    let cell = vec2(1.0 / columns as f32, 1.0 / rows as f32);
+    let min = vec2(
+        (frame % columns) as f32 * cell.x,
+        (frame / columns) as f32 * cell.y,
+    );
+    (min, min + cell)
+}
@@ -0,0 +1,61 @@
+mod body;
+mod broadphase;
+mod collision;
+mod events;
+mod query;
+mod shape;
+mod world;
+
+#[allow(unused_imports)]
+pub use body::{BodyHandle, BodyType, Collider, RigidBody, ALL_LAYERS, DEFAULT_LAYER};
+#[allow(unused_imports)]
+pub use collision::{ContactPoint, Manifold};
+#[allow(unused_imports)]
+pub use events::{CollisionEvent, EventPhase};
+#[allow(unused_imports)]
+pub use query::{QueryFilter, RaycastHit, ShapeCastHit};
+#[allow(unused_imports)]
+pub use shape::{Aabb, ConvexPolygon, Shape, WorldShape};
+#[allow(unused_imports)]
+pub use world::{Contact, PhysicsWorld};
+
+use cgmath::Rad;
+
+use crate::ecs::{Events, Read, Time, World, Write};
+use crate::transform::Transform2D;
+
+// エンティティと剛体を結びつけるコンポーネント
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhysicsBody(pub BodyHandle);
+
+// 物理演算を1ステップ進め、剛体の位置をTransform2Dへ書き戻す (FixedUpdate)
+// Events<CollisionEvent> リソースがあれば、このステップの衝突イベントを入れ直す
+pub fn physics_step_system(world: &mut World) {
+    let delta_time = world.resource::<Time>().fixed_delta_time;
+    let mut physics = world.resource_mut::<PhysicsWorld>();
+    physics.step(delta_time);
+
+    if world.has_resource::<Events<CollisionEvent>>() {
+        let mut events = world.resource_mut::<Events<CollisionEvent>>();
+        events.clear();
+        for event in physics.events() {
+            events.send(*event);
+        }
+    }
+
+    world
+        .query::<(Write<Transform2D>, Read<PhysicsBody>)>()
+        .for_each(|_, (transform, body)| {
+            let body = match physics.body(body.0) {
+                Some(body) => body,
+                None => return,
+            };
+            // 値が変わったときだけ書き換えて、無駄に行列を計算し直さないようにする
+            if transform.position() != body.position {
+                transform.set_position(body.position);
+            }
+            if transform.rotation() != Rad(body.rotation) {
+                transform.set_rotation(Rad(body.rotation));
+            }
+        });
+}
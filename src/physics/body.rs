@@ -0,0 +1,274 @@
+use cgmath::{vec2, InnerSpace};
+
+use super::shape::{Shape, WorldShape};
+use crate::ecs::Entity;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BodyHandle {
+    pub(super) index: u32,
+    pub(super) generation: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyType {
+    Static,    // 動かない (地面や壁)
+    Dynamic,   // 力や衝突によって動く
+    Kinematic, // 速度を直接指定して動かす (動く床など)。衝突しても押し戻されない
+}
+
+pub const DEFAULT_LAYER: u32 = 1;
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+// 剛体の形状と表面の性質
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub shape: Shape,
+    pub offset: Vector2, // 剛体の原点からのずれ
+    pub density: f32,
+    pub friction: f32,
+    pub restitution: f32, // 反発係数 (0.0: 跳ねない ~ 1.0: 完全に跳ね返る)
+    pub layer: u32,       // 自分が属するレイヤー (ビットの組み合わせ)
+    pub mask: u32,        // 衝突する相手のレイヤー
+    pub sensor: bool,     // 押し返さずに、重なったことをイベントで知らせるだけにする
+}
+
+#[allow(dead_code)]
+impl Collider {
+    pub fn new(shape: Shape) -> Collider {
+        Collider {
+            shape,
+            offset: vec2(0.0, 0.0),
+            density: 1.0,
+            friction: 0.5,
+            restitution: 0.0,
+            layer: DEFAULT_LAYER,
+            mask: ALL_LAYERS,
+            sensor: false,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vector2) -> Collider {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Collider {
+        self.density = density;
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Collider {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Collider {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_layers(mut self, layer: u32, mask: u32) -> Collider {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+
+    pub fn with_sensor(mut self, sensor: bool) -> Collider {
+        self.sensor = sensor;
+        self
+    }
+
+    // お互いのマスクに相手のレイヤーが含まれているときだけ衝突する
+    pub fn can_collide(&self, other: &Collider) -> bool {
+        self.layer & other.mask != 0 && other.layer & self.mask != 0
+    }
+}
+
+pub struct RigidBody {
+    pub body_type: BodyType,
+    pub position: Vector2,
+    pub rotation: f32, // ラジアン
+    pub velocity: Vector2,
+    pub angular_velocity: f32,
+    pub gravity_scale: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub entity: Option<Entity>, // 対応するエンティティ (Transform2Dとの同期に使う)
+    collider: Collider,
+    fixed_rotation: bool,
+    force: Vector2,
+    torque: f32,
+    mass: f32,
+    inv_mass: f32,
+    inertia: f32,
+    inv_inertia: f32,
+}
+
+#[allow(dead_code)]
+impl RigidBody {
+    pub fn new(body_type: BodyType, position: Vector2, collider: Collider) -> RigidBody {
+        let mut body = RigidBody {
+            body_type,
+            position,
+            rotation: 0.0,
+            velocity: vec2(0.0, 0.0),
+            angular_velocity: 0.0,
+            gravity_scale: 1.0,
+            linear_damping: 0.0,
+            angular_damping: 0.01,
+            entity: None,
+            collider,
+            fixed_rotation: false,
+            force: vec2(0.0, 0.0),
+            torque: 0.0,
+            mass: 0.0,
+            inv_mass: 0.0,
+            inertia: 0.0,
+            inv_inertia: 0.0,
+        };
+        body.update_mass();
+        body
+    }
+
+    pub fn dynamic(position: Vector2, collider: Collider) -> RigidBody {
+        RigidBody::new(BodyType::Dynamic, position, collider)
+    }
+
+    pub fn fixed(position: Vector2, collider: Collider) -> RigidBody {
+        RigidBody::new(BodyType::Static, position, collider)
+    }
+
+    pub fn kinematic(position: Vector2, collider: Collider) -> RigidBody {
+        RigidBody::new(BodyType::Kinematic, position, collider)
+    }
+
+    pub fn with_entity(mut self, entity: Entity) -> RigidBody {
+        self.entity = Some(entity);
+        self
+    }
+
+    pub fn with_fixed_rotation(mut self, fixed_rotation: bool) -> RigidBody {
+        self.fixed_rotation = fixed_rotation;
+        self.update_mass();
+        self
+    }
+
+    pub fn collider(&self) -> &Collider {
+        &self.collider
+    }
+
+    pub fn set_collider(&mut self, collider: Collider) {
+        self.collider = collider;
+        self.update_mass();
+    }
+
+    pub fn set_fixed_rotation(&mut self, fixed_rotation: bool) {
+        self.fixed_rotation = fixed_rotation;
+        self.update_mass();
+    }
+
+    pub fn is_fixed_rotation(&self) -> bool {
+        // 軸に平行な箱は回転できない
+        self.fixed_rotation || matches!(self.collider.shape, Shape::Aabb { .. })
+    }
+
+    fn update_mass(&mut self) {
+        if self.body_type != BodyType::Dynamic {
+            self.mass = 0.0;
+            self.inv_mass = 0.0;
+            self.inertia = 0.0;
+            self.inv_inertia = 0.0;
+            return;
+        }
+        let mass_data = self
+            .collider
+            .shape
+            .compute_mass(self.collider.density, self.collider.offset);
+        self.mass = mass_data.mass;
+        self.inv_mass = if self.mass > 0.0 {
+            1.0 / self.mass
+        } else {
+            0.0
+        };
+        self.inertia = mass_data.inertia;
+        self.inv_inertia = if self.inertia > 0.0 && !self.is_fixed_rotation() {
+            1.0 / self.inertia
+        } else {
+            0.0
+        };
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn inv_mass(&self) -> f32 {
+        self.inv_mass
+    }
+
+    pub fn inv_inertia(&self) -> f32 {
+        self.inv_inertia
+    }
+
+    pub fn apply_force(&mut self, force: Vector2) {
+        self.force += force;
+    }
+
+    pub fn apply_torque(&mut self, torque: f32) {
+        self.torque += torque;
+    }
+
+    // 力積を加えて速度を即座に変える (point はワールド座標)
+    pub fn apply_impulse(&mut self, impulse: Vector2, point: Vector2) {
+        self.velocity += impulse * self.inv_mass;
+        self.angular_velocity += cross(point - self.position, impulse) * self.inv_inertia;
+    }
+
+    pub(super) fn clear_forces(&mut self) {
+        self.force = vec2(0.0, 0.0);
+        self.torque = 0.0;
+    }
+
+    pub(super) fn force(&self) -> Vector2 {
+        self.force
+    }
+
+    pub(super) fn torque(&self) -> f32 {
+        self.torque
+    }
+
+    // 剛体のある点の速度
+    pub fn velocity_at(&self, point: Vector2) -> Vector2 {
+        self.velocity + cross_scalar(self.angular_velocity, point - self.position)
+    }
+
+    pub fn world_shape(&self) -> WorldShape {
+        let (sin, cos) = self.rotation.sin_cos();
+        let offset = self.collider.offset;
+        let offset = match self.collider.shape {
+            Shape::Aabb { .. } => offset,
+            _ => vec2(
+                offset.x * cos - offset.y * sin,
+                offset.x * sin + offset.y * cos,
+            ),
+        };
+        self.collider
+            .shape
+            .to_world(self.position + offset, self.rotation)
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.velocity.magnitude2() > 0.0 || self.angular_velocity != 0.0
+    }
+}
+
+pub(super) fn cross(a: Vector2, b: Vector2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+pub(super) fn cross_scalar(w: f32, r: Vector2) -> Vector2 {
+    vec2(-w * r.y, w * r.x)
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use super::shape::Aabb;
+
+// これより多くのセルにまたがる大きな物体は、セルに入れずに全体と比較する
+const MAX_CELLS_PER_OBJECT: i64 = 64;
+
+// 空間ハッシュによるブロードフェーズ
+// 同じセルに入った物体どうしだけを衝突判定の候補にする
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<u32>>,
+    large: Vec<u32>,
+    aabbs: HashMap<u32, Aabb>,
+}
+
+#[allow(dead_code)]
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> SpatialHash {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+            large: Vec::new(),
+            aabbs: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        // 前のステップで使われたセルのVecは使い回し、空いたままのセルは取り除く
+        // (物体が広いマップを移動しても、通り過ぎたセルが残り続けないように)
+        self.cells.retain(|_, cell| !cell.is_empty());
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.large.clear();
+        self.aabbs.clear();
+    }
+
+    fn cell_range(&self, aabb: &Aabb) -> (i32, i32, i32, i32) {
+        (
+            (aabb.min.x / self.cell_size).floor() as i32,
+            (aabb.min.y / self.cell_size).floor() as i32,
+            (aabb.max.x / self.cell_size).floor() as i32,
+            (aabb.max.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, id: u32, aabb: Aabb) {
+        self.aabbs.insert(id, aabb);
+        let (x0, y0, x1, y1) = self.cell_range(&aabb);
+        let count = (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1);
+        if count > MAX_CELLS_PER_OBJECT {
+            self.large.push(id);
+            return;
+        }
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    // AABBが重なっている組 (小さいidが先、重複なし)
+    pub fn pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+        for ids in self.cells.values() {
+            for (i, &a) in ids.iter().enumerate() {
+                for &b in &ids[i + 1..] {
+                    self.push_pair(&mut pairs, a, b);
+                }
+            }
+        }
+        for &a in &self.large {
+            for &b in self.aabbs.keys() {
+                if b != a {
+                    self.push_pair(&mut pairs, a, b);
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    fn push_pair(&self, pairs: &mut Vec<(u32, u32)>, a: u32, b: u32) {
+        if self.aabbs[&a].overlaps(&self.aabbs[&b]) {
+            pairs.push((a.min(b), a.max(b)));
+        }
+    }
+
+    // 指定した範囲とAABBが重なっている物体
+    pub fn query(&self, aabb: &Aabb) -> Vec<u32> {
+        let mut result: Vec<u32> = Vec::new();
+        let (x0, y0, x1, y1) = self.cell_range(aabb);
+        let count = (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1);
+        if count > MAX_CELLS_PER_OBJECT {
+            result.extend(self.aabbs.keys());
+        } else {
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    if let Some(ids) = self.cells.get(&(x, y)) {
+                        result.extend(ids);
+                    }
+                }
+            }
+            result.extend(&self.large);
+        }
+        result.sort_unstable();
+        result.dedup();
+        result.retain(|id| self.aabbs[id].overlaps(aabb));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::vec2;
+
+    fn aabb(x: f32, y: f32, size: f32) -> Aabb {
+        Aabb::new(vec2(x, y), vec2(x + size, y + size))
+    }
+
+    #[test]
+    fn pairs_and_query() {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert(0, aabb(0.0, 0.0, 0.5));
+        hash.insert(1, aabb(0.25, 0.25, 0.5));
+        hash.insert(2, aabb(3.0, 3.0, 0.5));
+        hash.insert(3, aabb(-100.0, -100.0, 200.0)); // セルに入らない大きな物体
+        assert_eq!(hash.pairs(), vec![(0, 1), (0, 3), (1, 3), (2, 3)]);
+        assert_eq!(hash.query(&aabb(2.5, 2.5, 1.0)), vec![2, 3]);
+    }
+
+    #[test]
+    fn cells_left_behind_are_removed() {
+        let mut hash = SpatialHash::new(1.0);
+        for step in 0..100 {
+            hash.clear();
+            hash.insert(0, aabb(step as f32, 0.0, 0.5));
+            hash.insert(1, aabb(step as f32 + 0.25, 0.0, 0.5));
+            assert_eq!(hash.pairs(), vec![(0, 1)]);
+        }
+        // 空になったセルは次の clear で取り除かれるので、直前に使ったセルまでしか残らない
+        assert!(hash.cells.len() <= 2, "{} cells", hash.cells.len());
+    }
+}
@@ -0,0 +1,377 @@
+use cgmath::{vec2, InnerSpace};
+
+use super::shape::WorldShape;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContactPoint {
+    pub position: Vector2,
+    pub penetration: f32,
+}
+
+// 2つの形状の接触情報
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifold {
+    pub normal: Vector2, // AからBへ向かう法線
+    pub points: Vec<ContactPoint>,
+}
+
+#[allow(dead_code)]
+impl Manifold {
+    pub fn penetration(&self) -> f32 {
+        self.points
+            .iter()
+            .map(|p| p.penetration)
+            .fold(0.0, f32::max)
+    }
+
+    // 接触点の平均
+    pub fn center(&self) -> Vector2 {
+        let sum = self
+            .points
+            .iter()
+            .fold(vec2(0.0, 0.0), |sum, point| sum + point.position);
+        sum / self.points.len().max(1) as f32
+    }
+
+    fn flipped(mut self) -> Manifold {
+        self.normal = -self.normal;
+        self
+    }
+}
+
+// 2つの形状が重なっていれば接触情報を返す
+pub fn collide(a: &WorldShape, b: &WorldShape) -> Option<Manifold> {
+    match (a, b) {
+        (
+            WorldShape::Circle {
+                center: ca,
+                radius: ra,
+            },
+            WorldShape::Circle {
+                center: cb,
+                radius: rb,
+            },
+        ) => collide_circles(*ca, *ra, *cb, *rb),
+        (WorldShape::Polygon { vertices, normals }, WorldShape::Circle { center, radius }) => {
+            collide_polygon_circle(vertices, normals, *center, *radius)
+        }
+        (WorldShape::Circle { center, radius }, WorldShape::Polygon { vertices, normals }) => {
+            collide_polygon_circle(vertices, normals, *center, *radius).map(Manifold::flipped)
+        }
+        (
+            WorldShape::Polygon {
+                vertices: va,
+                normals: na,
+            },
+            WorldShape::Polygon {
+                vertices: vb,
+                normals: nb,
+            },
+        ) => collide_polygons(va, na, vb, nb),
+    }
+}
+
+fn collide_circles(ca: Vector2, ra: f32, cb: Vector2, rb: f32) -> Option<Manifold> {
+    let d = cb - ca;
+    let distance2 = d.magnitude2();
+    if distance2 > (ra + rb) * (ra + rb) {
+        return None;
+    }
+    let distance = distance2.sqrt();
+    let normal = if distance > f32::EPSILON {
+        d / distance
+    } else {
+        vec2(0.0, 1.0) // 中心が完全に重なっているときは上方向に押し出す
+    };
+    Some(Manifold {
+        normal,
+        points: vec![ContactPoint {
+            position: ca + normal * ra,
+            penetration: ra + rb - distance,
+        }],
+    })
+}
+
+fn collide_polygon_circle(
+    vertices: &[Vector2],
+    normals: &[Vector2],
+    center: Vector2,
+    radius: f32,
+) -> Option<Manifold> {
+    // 円の中心からもっとも離れている辺を探す
+    let mut edge = 0;
+    let mut separation = f32::MIN;
+    for i in 0..vertices.len() {
+        let s = normals[i].dot(center - vertices[i]);
+        if s > radius {
+            return None;
+        }
+        if s > separation {
+            separation = s;
+            edge = i;
+        }
+    }
+
+    let v1 = vertices[edge];
+    let v2 = vertices[(edge + 1) % vertices.len()];
+
+    // 中心が多角形の内側にある
+    if separation < f32::EPSILON {
+        let normal = normals[edge];
+        return Some(Manifold {
+            normal,
+            points: vec![ContactPoint {
+                position: center - normal * separation,
+                penetration: radius - separation,
+            }],
+        });
+    }
+
+    // 中心が辺と頂点のどの領域にあるかで場合分けする
+    let u1 = (center - v1).dot(v2 - v1);
+    let u2 = (center - v2).dot(v1 - v2);
+    let (normal, position, distance) = if u1 <= 0.0 {
+        let d = center - v1;
+        let distance = d.magnitude();
+        if distance > radius {
+            return None;
+        }
+        (d / distance, v1, distance)
+    } else if u2 <= 0.0 {
+        let d = center - v2;
+        let distance = d.magnitude();
+        if distance > radius {
+            return None;
+        }
+        (d / distance, v2, distance)
+    } else {
+        let normal = normals[edge];
+        (normal, center - normal * separation, separation)
+    };
+    Some(Manifold {
+        normal,
+        points: vec![ContactPoint {
+            position,
+            penetration: radius - distance,
+        }],
+    })
+}
+
+// poly1 の各辺の法線を分離軸とみなしたときに、もっとも離れている辺とその距離
+fn find_max_separation(
+    vertices1: &[Vector2],
+    normals1: &[Vector2],
+    vertices2: &[Vector2],
+) -> (usize, f32) {
+    let mut best_edge = 0;
+    let mut max_separation = f32::MIN;
+    for i in 0..vertices1.len() {
+        let separation = vertices2
+            .iter()
+            .map(|&v| normals1[i].dot(v - vertices1[i]))
+            .fold(f32::MAX, f32::min);
+        if separation > max_separation {
+            max_separation = separation;
+            best_edge = i;
+        }
+    }
+    (best_edge, max_separation)
+}
+
+// 線分を直線 normal・x <= offset の側に切り取る
+fn clip_segment(points: [Vector2; 2], normal: Vector2, offset: f32) -> Option<[Vector2; 2]> {
+    let d0 = normal.dot(points[0]) - offset;
+    let d1 = normal.dot(points[1]) - offset;
+    if d0 > 0.0 && d1 > 0.0 {
+        return None;
+    }
+    let mut clipped = points;
+    if d0 * d1 < 0.0 {
+        let t = d0 / (d0 - d1);
+        let intersection = points[0] + (points[1] - points[0]) * t;
+        if d0 > 0.0 {
+            clipped[0] = intersection;
+        } else {
+            clipped[1] = intersection;
+        }
+    }
+    Some(clipped)
+}
+
+// 分離軸定理で重なりを調べ、接触点は参照面で相手の辺を切り取って求める
+fn collide_polygons(
+    va: &[Vector2],
+    na: &[Vector2],
+    vb: &[Vector2],
+    nb: &[Vector2],
+) -> Option<Manifold> {
+    let (edge_a, separation_a) = find_max_separation(va, na, vb);
+    if separation_a > 0.0 {
+        return None;
+    }
+    let (edge_b, separation_b) = find_max_separation(vb, nb, va);
+    if separation_b > 0.0 {
+        return None;
+    }
+
+    // 参照面は分離がより大きいほう (わずかな差ならAを優先して結果を安定させる)
+    let (ref_vertices, ref_normals, inc_vertices, inc_normals, ref_edge, flip) =
+        if separation_b > separation_a + 0.001 {
+            (vb, nb, va, na, edge_b, true)
+        } else {
+            (va, na, vb, nb, edge_a, false)
+        };
+    let ref_normal = ref_normals[ref_edge];
+
+    // 参照面の法線ともっとも向かい合っている辺を接触辺とする
+    let mut inc_edge = 0;
+    let mut min_dot = f32::MAX;
+    for (i, n) in inc_normals.iter().enumerate() {
+        let d = ref_normal.dot(*n);
+        if d < min_dot {
+            min_dot = d;
+            inc_edge = i;
+        }
+    }
+    let incident = [
+        inc_vertices[inc_edge],
+        inc_vertices[(inc_edge + 1) % inc_vertices.len()],
+    ];
+
+    let r1 = ref_vertices[ref_edge];
+    let r2 = ref_vertices[(ref_edge + 1) % ref_vertices.len()];
+    let tangent = (r2 - r1).normalize();
+
+    let clipped = clip_segment(incident, -tangent, -tangent.dot(r1))?;
+    let clipped = clip_segment(clipped, tangent, tangent.dot(r2))?;
+
+    let points: Vec<ContactPoint> = clipped
+        .iter()
+        .filter_map(|&p| {
+            let separation = ref_normal.dot(p - r1);
+            if separation <= 0.0 {
+                Some(ContactPoint {
+                    position: p,
+                    penetration: -separation,
+                })
+            } else {
+                None
+            }
+        })
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+
+    Some(Manifold {
+        normal: if flip { -ref_normal } else { ref_normal },
+        points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use cgmath::vec2;
+
+    use super::*;
+    use crate::physics::shape::Shape;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_near(actual: Vector2, expected: Vector2) {
+        assert!(
+            (actual - expected).magnitude() < EPSILON,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn aabb(x: f32, y: f32, hx: f32, hy: f32) -> WorldShape {
+        Shape::Aabb {
+            half_extents: vec2(hx, hy),
+        }
+        .to_world(vec2(x, y), 0.0)
+    }
+
+    fn circle(x: f32, y: f32, radius: f32) -> WorldShape {
+        Shape::Circle { radius }.to_world(vec2(x, y), 0.0)
+    }
+
+    #[test]
+    fn aabb_aabb() {
+        let manifold = collide(&aabb(0.0, 0.0, 1.0, 1.0), &aabb(1.5, 0.25, 1.0, 1.0)).unwrap();
+        assert_near(manifold.normal, vec2(1.0, 0.0));
+        assert!((manifold.penetration() - 0.5).abs() < EPSILON);
+        // 重なっている辺の両端 (y = -0.75 と y = 1.0) が接触点になる
+        assert_eq!(manifold.points.len(), 2);
+        for point in &manifold.points {
+            assert!((point.position.x - 0.5).abs() < EPSILON);
+            assert!(point.position.y >= -0.75 - EPSILON && point.position.y <= 1.0 + EPSILON);
+        }
+
+        assert!(collide(&aabb(0.0, 0.0, 1.0, 1.0), &aabb(2.5, 0.0, 1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn circle_circle() {
+        let manifold = collide(&circle(0.0, 0.0, 1.0), &circle(0.0, 1.5, 1.0)).unwrap();
+        assert_near(manifold.normal, vec2(0.0, 1.0));
+        assert!((manifold.penetration() - 0.5).abs() < EPSILON);
+        assert_near(manifold.points[0].position, vec2(0.0, 1.0));
+
+        assert!(collide(&circle(0.0, 0.0, 1.0), &circle(2.1, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn polygon_circle_face_and_corner() {
+        let square = aabb(0.0, 0.0, 1.0, 1.0);
+
+        // 上の辺に乗っている円
+        let manifold = collide(&square, &circle(0.2, 1.75, 1.0)).unwrap();
+        assert_near(manifold.normal, vec2(0.0, 1.0));
+        assert!((manifold.penetration() - 0.25).abs() < EPSILON);
+
+        // 順番を入れ替えると法線が逆になる
+        let flipped = collide(&circle(0.2, 1.75, 1.0), &square).unwrap();
+        assert_near(flipped.normal, vec2(0.0, -1.0));
+        assert!((flipped.penetration() - 0.25).abs() < EPSILON);
+
+        // 右上の角に斜めから当たる円
+        let offset = 1.0 + 0.5 / 2.0f32.sqrt();
+        let manifold = collide(&square, &circle(offset, offset, 1.0)).unwrap();
+        let diagonal = vec2(1.0, 1.0).normalize();
+        assert_near(manifold.normal, diagonal);
+        assert!((manifold.penetration() - 0.5).abs() < EPSILON);
+        assert_near(manifold.points[0].position, vec2(1.0, 1.0));
+
+        // 角の近くでも離れていれば当たらない
+        assert!(collide(&square, &circle(1.8, 1.8, 1.0)).is_none());
+    }
+
+    #[test]
+    fn polygon_polygon_rotated() {
+        let floor = aabb(0.0, 0.0, 1.0, 1.0);
+        // 45度回した正方形を角から床に0.1だけめり込ませる
+        let half_diagonal = 0.5 * 2.0f32.sqrt();
+        let diamond =
+            Shape::rect(vec2(0.5, 0.5)).to_world(vec2(0.0, 1.0 + half_diagonal - 0.1), FRAC_PI_4);
+        let manifold = collide(&floor, &diamond).unwrap();
+        assert_near(manifold.normal, vec2(0.0, 1.0));
+        assert!((manifold.penetration() - 0.1).abs() < EPSILON);
+        assert_eq!(manifold.points.len(), 1);
+        assert_near(manifold.points[0].position, vec2(0.0, 0.9));
+
+        let flipped = collide(&diamond, &floor).unwrap();
+        assert_near(flipped.normal, vec2(0.0, -1.0));
+        assert!((flipped.penetration() - 0.1).abs() < EPSILON);
+
+        let lifted = Shape::rect(vec2(0.5, 0.5)).to_world(vec2(0.0, 2.0), FRAC_PI_4);
+        assert!(collide(&floor, &lifted).is_none());
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use super::body::BodyHandle;
+use crate::ecs::Entity;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventPhase {
+    Enter, // このステップで重なり始めた
+    Stay,  // 前のステップから重なり続けている
+    Exit,  // このステップで離れた (どちらかが削除されたときも含む)
+}
+
+// 1ステップの間に起きた衝突のイベント
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionEvent {
+    // センサーと他の剛体の重なり
+    Trigger {
+        phase: EventPhase,
+        sensor: BodyHandle,
+        other: BodyHandle,
+        sensor_entity: Option<Entity>,
+        other_entity: Option<Entity>,
+    },
+    // センサーでない剛体どうしの接触 (Exitのときは normal と point が0になる)
+    Contact {
+        phase: EventPhase,
+        a: BodyHandle,
+        b: BodyHandle,
+        entity_a: Option<Entity>,
+        entity_b: Option<Entity>,
+        normal: Vector2, // AからBへ向かう法線
+        point: Vector2,
+    },
+}
+
+#[allow(dead_code)]
+impl CollisionEvent {
+    pub fn phase(&self) -> EventPhase {
+        match *self {
+            CollisionEvent::Trigger { phase, .. } => phase,
+            CollisionEvent::Contact { phase, .. } => phase,
+        }
+    }
+
+    pub fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        match *self {
+            CollisionEvent::Trigger { sensor, other, .. } => (sensor, other),
+            CollisionEvent::Contact { a, b, .. } => (a, b),
+        }
+    }
+
+    pub fn entities(&self) -> (Option<Entity>, Option<Entity>) {
+        match *self {
+            CollisionEvent::Trigger {
+                sensor_entity,
+                other_entity,
+                ..
+            } => (sensor_entity, other_entity),
+            CollisionEvent::Contact {
+                entity_a, entity_b, ..
+            } => (entity_a, entity_b),
+        }
+    }
+
+    // entity が関わっていれば相手のエンティティを返す
+    pub fn other_entity(&self, entity: Entity) -> Option<Entity> {
+        match self.entities() {
+            (Some(a), b) if a == entity => b,
+            (a, Some(b)) if b == entity => a,
+            _ => None,
+        }
+    }
+}
+
+// 1ステップで見つかった重なり (イベントを作るための材料)
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Overlap {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub entity_a: Option<Entity>,
+    pub entity_b: Option<Entity>,
+    pub sensor: bool,
+    pub normal: Vector2,
+    pub point: Vector2,
+}
+
+impl Overlap {
+    fn event(&self, phase: EventPhase) -> CollisionEvent {
+        if self.sensor {
+            CollisionEvent::Trigger {
+                phase,
+                sensor: self.a,
+                other: self.b,
+                sensor_entity: self.entity_a,
+                other_entity: self.entity_b,
+            }
+        } else {
+            CollisionEvent::Contact {
+                phase,
+                a: self.a,
+                b: self.b,
+                entity_a: self.entity_a,
+                entity_b: self.entity_b,
+                normal: self.normal,
+                point: self.point,
+            }
+        }
+    }
+}
+
+// 前のステップの重なりと比べて Enter / Stay / Exit を判定する
+#[derive(Default)]
+pub(super) struct EventTracker {
+    active: HashMap<(BodyHandle, BodyHandle), Overlap>,
+}
+
+impl EventTracker {
+    pub fn update(&mut self, overlaps: Vec<Overlap>, events: &mut Vec<CollisionEvent>) {
+        events.clear();
+        let mut active = HashMap::with_capacity(overlaps.len());
+        for overlap in overlaps {
+            let key = (overlap.a, overlap.b);
+            let phase = if self.active.remove(&key).is_some() {
+                EventPhase::Stay
+            } else {
+                EventPhase::Enter
+            };
+            events.push(overlap.event(phase));
+            active.insert(key, overlap);
+        }
+
+        // 残ったものは離れた組
+        let mut exited: Vec<Overlap> = self.active.drain().map(|(_, overlap)| overlap).collect();
+        exited.sort_by_key(|overlap| (overlap.a, overlap.b));
+        for mut overlap in exited {
+            overlap.normal = Vector2::new(0.0, 0.0);
+            overlap.point = Vector2::new(0.0, 0.0);
+            events.push(overlap.event(EventPhase::Exit));
+        }
+        self.active = active;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec2;
+
+    use super::*;
+    use crate::physics::body::{Collider, RigidBody};
+    use crate::physics::shape::Shape;
+    use crate::physics::world::PhysicsWorld;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn handle(index: u32) -> BodyHandle {
+        BodyHandle {
+            index,
+            generation: 0,
+        }
+    }
+
+    fn overlap(a: u32, b: u32, sensor: bool) -> Overlap {
+        Overlap {
+            a: handle(a),
+            b: handle(b),
+            entity_a: None,
+            entity_b: None,
+            sensor,
+            normal: vec2(0.0, 1.0),
+            point: vec2(1.0, 2.0),
+        }
+    }
+
+    fn phases(events: &[CollisionEvent]) -> Vec<(EventPhase, (BodyHandle, BodyHandle))> {
+        events
+            .iter()
+            .map(|event| (event.phase(), event.bodies()))
+            .collect()
+    }
+
+    #[test]
+    fn enter_stay_exit() {
+        let mut tracker = EventTracker::default();
+        let mut events = Vec::new();
+        tracker.update(vec![overlap(0, 1, true)], &mut events);
+        assert_eq!(
+            phases(&events),
+            vec![(EventPhase::Enter, (handle(0), handle(1)))]
+        );
+        tracker.update(vec![overlap(0, 1, true), overlap(2, 3, false)], &mut events);
+        assert_eq!(
+            phases(&events),
+            vec![
+                (EventPhase::Stay, (handle(0), handle(1))),
+                (EventPhase::Enter, (handle(2), handle(3))),
+            ]
+        );
+        assert!(matches!(events[0], CollisionEvent::Trigger { .. }));
+        assert!(matches!(
+            events[1],
+            CollisionEvent::Contact { normal, .. } if normal == vec2(0.0, 1.0)
+        ));
+
+        // 離れた組は Exit を1回だけ出し、接触の位置は0になる
+        tracker.update(Vec::new(), &mut events);
+        assert_eq!(
+            phases(&events),
+            vec![
+                (EventPhase::Exit, (handle(0), handle(1))),
+                (EventPhase::Exit, (handle(2), handle(3))),
+            ]
+        );
+        assert!(matches!(
+            events[1],
+            CollisionEvent::Contact { normal, point, .. }
+                if normal == vec2(0.0, 0.0) && point == vec2(0.0, 0.0)
+        ));
+        tracker.update(Vec::new(), &mut events);
+        assert!(events.is_empty());
+
+        // 離れた後にまた重なると Enter から始まる
+        tracker.update(vec![overlap(0, 1, true)], &mut events);
+        assert_eq!(events[0].phase(), EventPhase::Enter);
+    }
+
+    #[test]
+    fn other_entity() {
+        let mut world = crate::ecs::World::new();
+        let (sensor, other, unrelated) = (world.spawn(), world.spawn(), world.spawn());
+        let mut overlap = overlap(0, 1, true);
+        overlap.entity_a = Some(sensor);
+        overlap.entity_b = Some(other);
+        let event = overlap.event(EventPhase::Enter);
+        assert_eq!(event.other_entity(sensor), Some(other));
+        assert_eq!(event.other_entity(other), Some(sensor));
+        assert_eq!(event.other_entity(unrelated), None);
+    }
+
+    // センサーを横切る剛体と、重なったまま削除した剛体
+    #[test]
+    fn sensor_events_in_the_world() {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        let sensor = world.add_body(RigidBody::fixed(
+            vec2(0.0, 0.0),
+            Collider::new(Shape::Circle { radius: 1.0 }).with_sensor(true),
+        ));
+        let mover = world.add_body(RigidBody::kinematic(
+            vec2(-3.0, 0.0),
+            Collider::new(Shape::Circle { radius: 0.5 }),
+        ));
+        let removed = world.add_body(RigidBody::dynamic(
+            vec2(0.5, 0.0),
+            Collider::new(Shape::Circle { radius: 0.5 }),
+        ));
+        world.step(DT);
+        assert_eq!(
+            phases(world.events()),
+            vec![(EventPhase::Enter, (sensor, removed))]
+        );
+
+        // 重なったまま削除すると、次のステップで Exit が出る
+        world.remove_body(removed);
+        world.step(DT);
+        assert_eq!(
+            phases(world.events()),
+            vec![(EventPhase::Exit, (sensor, removed))]
+        );
+        world.step(DT);
+        assert!(world.events().is_empty());
+
+        let mut seen = Vec::new();
+        for x in [-1.0, 0.0, 1.0, 3.0] {
+            world.body_mut(mover).unwrap().position = vec2(x, 0.0);
+            world.step(DT);
+            seen.extend(phases(world.events()));
+        }
+        assert_eq!(
+            seen,
+            vec![
+                (EventPhase::Enter, (sensor, mover)),
+                (EventPhase::Stay, (sensor, mover)),
+                (EventPhase::Stay, (sensor, mover)),
+                (EventPhase::Exit, (sensor, mover)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use cgmath::InnerSpace;
+
+use super::body::{BodyHandle, RigidBody, ALL_LAYERS};
+use super::collision::collide;
+use super::shape::{Aabb, Shape, WorldShape};
+use super::world::PhysicsWorld;
+use crate::ecs::Entity;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 形状を動かす判定でぶつかった位置を二分探索で絞り込む回数
+const SHAPE_CAST_ITERATIONS: u32 = 16;
+
+// クエリで調べる剛体の条件
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueryFilter {
+    pub mask: u32,                   // 調べるレイヤー
+    pub exclude: Option<BodyHandle>, // 除外する剛体 (自分自身など)
+    pub include_sensors: bool,
+}
+
+impl Default for QueryFilter {
+    fn default() -> QueryFilter {
+        QueryFilter {
+            mask: ALL_LAYERS,
+            exclude: None,
+            include_sensors: false,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl QueryFilter {
+    pub fn new(mask: u32) -> QueryFilter {
+        QueryFilter {
+            mask,
+            exclude: None,
+            include_sensors: false,
+        }
+    }
+
+    pub fn excluding(mut self, handle: BodyHandle) -> QueryFilter {
+        self.exclude = Some(handle);
+        self
+    }
+
+    pub fn with_sensors(mut self) -> QueryFilter {
+        self.include_sensors = true;
+        self
+    }
+
+    fn accepts(&self, handle: BodyHandle, body: &RigidBody) -> bool {
+        body.collider().layer & self.mask != 0
+            && self.exclude != Some(handle)
+            && (self.include_sensors || !body.collider().sensor)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub body: BodyHandle,
+    pub entity: Option<Entity>,
+    pub point: Vector2,
+    pub normal: Vector2, // 当たった面の外向き法線
+    pub distance: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapeCastHit {
+    pub body: BodyHandle,
+    pub entity: Option<Entity>,
+    pub point: Vector2,
+    pub normal: Vector2, // 当たった剛体から動かした形状へ向かう法線
+    pub fraction: f32,   // 移動量のうちぶつかるまでに進める割合 (0.0 ~ 1.0)
+    pub distance: f32,
+}
+
+// 衝突判定のクエリ (描画やGLを使わないのでヘッドレスで使える)
+// 形状の候補は直前のステップ後の位置で作ったブロードフェーズから探すので、
+// ステップの間に剛体を大きく瞬間移動させたときは次のステップまで古い位置で候補を選ぶ
+#[allow(dead_code)]
+impl PhysicsWorld {
+    // AABBが重なっている剛体と、現在の位置でのワールド座標の形状
+    fn candidates(
+        &self,
+        aabb: &Aabb,
+        filter: &QueryFilter,
+    ) -> Vec<(BodyHandle, &RigidBody, WorldShape)> {
+        let mut indices = self.broadphase.query(aabb);
+        // 前回のステップの後に追加された剛体はブロードフェーズに入っていない
+        // (削除した剛体の番号を使い回したときは、古い剛体のセルで見つかることもあるので重複を除く)
+        indices.extend_from_slice(&self.added);
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .into_iter()
+            .filter_map(|index| {
+                let handle = self.handle(index as usize)?;
+                let body = self.body(handle)?;
+                if !filter.accepts(handle, body) {
+                    return None;
+                }
+                let shape = body.world_shape();
+                if !shape.aabb().overlaps(aabb) {
+                    return None;
+                }
+                Some((handle, body, shape))
+            })
+            .collect()
+    }
+
+    // もっとも近くで当たった剛体 (direction は正規化しなくてよい)
+    pub fn raycast(
+        &self,
+        origin: Vector2,
+        direction: Vector2,
+        max_distance: f32,
+        filter: QueryFilter,
+    ) -> Option<RaycastHit> {
+        self.raycast_all(origin, direction, max_distance, filter)
+            .into_iter()
+            .next()
+    }
+
+    // 当たったすべての剛体 (近い順)
+    pub fn raycast_all(
+        &self,
+        origin: Vector2,
+        direction: Vector2,
+        max_distance: f32,
+        filter: QueryFilter,
+    ) -> Vec<RaycastHit> {
+        if direction.magnitude2() == 0.0 {
+            return Vec::new();
+        }
+        let direction = direction.normalize();
+        let end = origin + direction * max_distance;
+        let aabb = Aabb::new(origin, origin).union(&Aabb::new(end, end));
+
+        let mut hits: Vec<RaycastHit> = self
+            .candidates(&aabb, &filter)
+            .into_iter()
+            .filter_map(|(handle, body, shape)| {
+                let (distance, normal) = shape.raycast(origin, direction, max_distance)?;
+                Some(RaycastHit {
+                    body: handle,
+                    entity: body.entity,
+                    point: origin + direction * distance,
+                    normal,
+                    distance,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    // 指定した形状と重なっている剛体
+    pub fn overlap(
+        &self,
+        shape: &Shape,
+        position: Vector2,
+        rotation: f32,
+        filter: QueryFilter,
+    ) -> Vec<BodyHandle> {
+        let shape = shape.to_world(position, rotation);
+        self.candidates(&shape.aabb(), &filter)
+            .into_iter()
+            .filter(|(_, _, other)| collide(&shape, other).is_some())
+            .map(|(handle, _, _)| handle)
+            .collect()
+    }
+
+    // 指定した点を含む剛体
+    pub fn overlap_point(&self, point: Vector2, filter: QueryFilter) -> Vec<BodyHandle> {
+        self.candidates(&Aabb::new(point, point), &filter)
+            .into_iter()
+            .filter(|(_, _, shape)| shape.contains_point(point))
+            .map(|(handle, _, _)| handle)
+            .collect()
+    }
+
+    // 形状を translation だけ動かしたときに最初にぶつかる剛体
+    // 最初から重なっている剛体は、そちらへ向かって動かすときだけ fraction 0 で当たる
+    pub fn shape_cast(
+        &self,
+        shape: &Shape,
+        position: Vector2,
+        rotation: f32,
+        translation: Vector2,
+        filter: QueryFilter,
+    ) -> Option<ShapeCastHit> {
+        let start = shape.to_world(position, rotation);
+        let start_aabb = start.aabb();
+        let end_aabb = Aabb::new(start_aabb.min + translation, start_aabb.max + translation);
+        let length = translation.magnitude();
+
+        // すり抜けないように、形状の小さい方の幅の半分ずつ進めて調べる
+        let half = start_aabb.half_extents();
+        let step = half.x.min(half.y).max(1e-3);
+        let steps = ((length / step).ceil() as u32).max(1);
+
+        let mut best: Option<ShapeCastHit> = None;
+        for (handle, body, other) in self.candidates(&start_aabb.union(&end_aabb), &filter) {
+            let hit_at = |t: f32| {
+                collide(&start.translated(translation * t), &other)
+                    .filter(|manifold| manifold.penetration() > 0.0)
+            };
+
+            if let Some(manifold) = hit_at(0.0) {
+                if translation.dot(manifold.normal) > 0.0 {
+                    let hit = ShapeCastHit {
+                        body: handle,
+                        entity: body.entity,
+                        point: manifold.center(),
+                        normal: -manifold.normal,
+                        fraction: 0.0,
+                        distance: 0.0,
+                    };
+                    if best.map(|b| b.fraction > 0.0).unwrap_or(true) {
+                        best = Some(hit);
+                    }
+                }
+                continue;
+            }
+
+            let limit = best.map(|b| b.fraction).unwrap_or(1.0);
+            let mut lower = 0.0;
+            let mut upper = None;
+            for i in 1..=steps {
+                let t = (i as f32 / steps as f32).min(limit);
+                if hit_at(t).is_some() {
+                    upper = Some(t);
+                    break;
+                }
+                lower = t;
+                if t >= limit {
+                    break;
+                }
+            }
+            let mut upper = match upper {
+                Some(upper) => upper,
+                None => continue,
+            };
+            for _ in 0..SHAPE_CAST_ITERATIONS {
+                let middle = (lower + upper) * 0.5;
+                if hit_at(middle).is_some() {
+                    upper = middle;
+                } else {
+                    lower = middle;
+                }
+            }
+
+            let manifold = hit_at(upper).unwrap();
+            best = Some(ShapeCastHit {
+                body: handle,
+                entity: body.entity,
+                point: manifold.center(),
+                normal: -manifold.normal,
+                fraction: lower,
+                distance: lower * length,
+            });
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::body::Collider;
+    use cgmath::vec2;
+
+    fn block(position: Vector2) -> RigidBody {
+        RigidBody::fixed(position, Collider::new(Shape::rect(vec2(1.0, 1.0))))
+    }
+
+    // 指定した位置にある剛体を3種類のクエリで探す
+    fn find(world: &PhysicsWorld, position: Vector2) -> [Option<BodyHandle>; 3] {
+        let filter = QueryFilter::default();
+        let probe = Shape::Circle { radius: 0.5 };
+        [
+            world
+                .raycast(position - vec2(5.0, 0.0), vec2(1.0, 0.0), 10.0, filter)
+                .map(|hit| hit.body),
+            world
+                .overlap(&probe, position, 0.0, filter)
+                .first()
+                .copied(),
+            world
+                .shape_cast(
+                    &probe,
+                    position + vec2(0.0, 5.0),
+                    0.0,
+                    vec2(0.0, -10.0),
+                    filter,
+                )
+                .map(|hit| hit.body),
+        ]
+    }
+
+    #[test]
+    fn bodies_added_into_reused_slots_are_found_before_the_next_step() {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        let first = world.add_body(block(vec2(0.0, 0.0)));
+        let removed = world.add_body(block(vec2(20.0, 0.0)));
+        world.step(1.0 / 60.0);
+
+        world.remove_body(removed);
+        let reused = world.add_body(block(vec2(-20.0, 0.0)));
+        assert_eq!(reused.index, removed.index);
+        let appended = world.add_body(block(vec2(0.0, 20.0)));
+
+        assert_eq!(find(&world, vec2(-20.0, 0.0)), [Some(reused); 3]);
+        assert_eq!(find(&world, vec2(0.0, 20.0)), [Some(appended); 3]);
+        assert_eq!(find(&world, vec2(0.0, 0.0)), [Some(first); 3]);
+        // 削除した剛体の古いセルで見つかっても、今の位置で確かめるので当たらない
+        assert_eq!(find(&world, vec2(20.0, 0.0)), [None; 3]);
+
+        world.step(1.0 / 60.0);
+        assert_eq!(find(&world, vec2(-20.0, 0.0)), [Some(reused); 3]);
+        assert_eq!(find(&world, vec2(20.0, 0.0)), [None; 3]);
+    }
+
+    fn layered_block(position: Vector2, layer: u32) -> RigidBody {
+        RigidBody::fixed(
+            position,
+            Collider::new(Shape::rect(vec2(1.0, 1.0))).with_layers(layer, ALL_LAYERS),
+        )
+    }
+
+    fn sensor_block(position: Vector2) -> RigidBody {
+        RigidBody::fixed(
+            position,
+            Collider::new(Shape::rect(vec2(1.0, 1.0))).with_sensor(true),
+        )
+    }
+
+    fn close(a: Vector2, b: Vector2) -> bool {
+        (a - b).magnitude() < 1e-3
+    }
+
+    #[test]
+    fn raycast_hits_are_sorted_by_distance() {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        let far = world.add_body(block(vec2(10.0, 0.0)));
+        let near = world.add_body(block(vec2(5.0, 0.0)));
+        let filter = QueryFilter::default();
+
+        // direction は正規化しなくてよい
+        let hits = world.raycast_all(vec2(0.0, 0.0), vec2(2.0, 0.0), 20.0, filter);
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[0].body, hits[1].body), (near, far));
+        assert!((hits[0].distance - 4.0).abs() < 1e-4);
+        assert!((hits[1].distance - 9.0).abs() < 1e-4);
+        assert!(close(hits[0].point, vec2(4.0, 0.0)));
+        assert!(close(hits[0].normal, vec2(-1.0, 0.0)));
+
+        let hit = world
+            .raycast(vec2(5.0, 5.0), vec2(0.0, -1.0), 20.0, filter)
+            .unwrap();
+        assert_eq!(hit.body, near);
+        assert!(close(hit.point, vec2(5.0, 1.0)));
+        assert!(close(hit.normal, vec2(0.0, 1.0)));
+
+        assert_eq!(
+            world.raycast(vec2(0.0, 0.0), vec2(1.0, 0.0), 3.0, filter),
+            None
+        );
+        assert!(world
+            .raycast_all(vec2(0.0, 0.0), vec2(0.0, 0.0), 20.0, filter)
+            .is_empty());
+    }
+
+    #[test]
+    fn filters_select_layers_and_exclude_bodies() {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        let a = world.add_body(layered_block(vec2(5.0, 0.0), 0b01));
+        let b = world.add_body(layered_block(vec2(10.0, 0.0), 0b10));
+        let ray = |filter| {
+            world
+                .raycast(vec2(0.0, 0.0), vec2(1.0, 0.0), 20.0, filter)
+                .map(|hit| hit.body)
+        };
+        assert_eq!(ray(QueryFilter::default()), Some(a));
+        assert_eq!(ray(QueryFilter::new(0b10)), Some(b));
+        assert_eq!(ray(QueryFilter::new(0b11)), Some(a));
+        assert_eq!(ray(QueryFilter::new(0b100)), None);
+        assert_eq!(ray(QueryFilter::default().excluding(a)), Some(b));
+
+        let probe = Shape::rect(vec2(4.0, 1.0));
+        assert_eq!(
+            world.overlap(&probe, vec2(7.5, 0.0), 0.0, QueryFilter::default()),
+            vec![a, b]
+        );
+        assert_eq!(
+            world.overlap(&probe, vec2(7.5, 0.0), 0.0, QueryFilter::new(0b10)),
+            vec![b]
+        );
+        assert_eq!(
+            world.overlap_point(vec2(5.5, 0.5), QueryFilter::new(0b10)),
+            vec![]
+        );
+        assert_eq!(
+            world.overlap_point(vec2(5.5, 0.5), QueryFilter::new(0b01)),
+            vec![a]
+        );
+    }
+
+    #[test]
+    fn sensors_are_found_only_when_included() {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        let sensor = world.add_body(sensor_block(vec2(5.0, 0.0)));
+        let solid = world.add_body(block(vec2(10.0, 0.0)));
+        let ray = |filter| {
+            world
+                .raycast(vec2(0.0, 0.0), vec2(1.0, 0.0), 20.0, filter)
+                .map(|hit| hit.body)
+        };
+        assert_eq!(ray(QueryFilter::default()), Some(solid));
+        assert_eq!(ray(QueryFilter::default().with_sensors()), Some(sensor));
+
+        assert!(world
+            .overlap_point(vec2(5.0, 0.0), QueryFilter::default())
+            .is_empty());
+        assert_eq!(
+            world.overlap_point(vec2(5.0, 0.0), QueryFilter::default().with_sensors()),
+            vec![sensor]
+        );
+
+        let probe = Shape::Circle { radius: 0.5 };
+        let cast = |filter| {
+            world
+                .shape_cast(&probe, vec2(0.0, 0.0), 0.0, vec2(20.0, 0.0), filter)
+                .map(|hit| hit.body)
+        };
+        assert_eq!(cast(QueryFilter::default()), Some(solid));
+        assert_eq!(cast(QueryFilter::default().with_sensors()), Some(sensor));
+    }
+
+    #[test]
+    fn shape_cast_stops_at_the_first_contact() {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        let wall = world.add_body(block(vec2(5.0, 0.0)));
+        world.add_body(block(vec2(8.0, 0.0)));
+        let probe = Shape::Circle { radius: 0.5 };
+        let filter = QueryFilter::default();
+
+        // 円の右端が壁の左の面 (x = 4) に触れるまで 3.5 進める
+        let hit = world
+            .shape_cast(&probe, vec2(0.0, 0.0), 0.0, vec2(10.0, 0.0), filter)
+            .unwrap();
+        assert_eq!(hit.body, wall);
+        assert!((hit.fraction - 0.35).abs() < 1e-3, "{}", hit.fraction);
+        assert!((hit.distance - 3.5).abs() < 1e-2, "{}", hit.distance);
+        assert!(close(hit.normal, vec2(-1.0, 0.0)));
+        assert!((hit.point.x - 4.0).abs() < 1e-2);
+
+        // 届かなければ当たらない
+        assert_eq!(
+            world.shape_cast(&probe, vec2(0.0, 0.0), 0.0, vec2(3.0, 0.0), filter),
+            None
+        );
+        // 最初から重なっているときは、そちらへ向かうときだけ fraction 0 で当たる
+        let start = vec2(3.75, 0.0);
+        let toward = world
+            .shape_cast(&probe, start, 0.0, vec2(1.0, 0.0), filter)
+            .unwrap();
+        assert_eq!((toward.body, toward.fraction), (wall, 0.0));
+        assert_eq!(
+            world.shape_cast(&probe, start, 0.0, vec2(-1.0, 0.0), filter),
+            None
+        );
+    }
+}
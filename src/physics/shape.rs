@@ -0,0 +1,346 @@
+use cgmath::{vec2, InnerSpace};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// 軸に平行な境界の矩形 (ブロードフェーズや範囲の判定に使う)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector2,
+    pub max: Vector2,
+}
+
+#[allow(dead_code)]
+impl Aabb {
+    pub fn new(min: Vector2, max: Vector2) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn from_center(center: Vector2, half_extents: Vector2) -> Aabb {
+        Aabb::new(center - half_extents, center + half_extents)
+    }
+
+    pub fn center(&self) -> Vector2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vector2 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    pub fn contains(&self, point: Vector2) -> bool {
+        self.min.x <= point.x
+            && point.x <= self.max.x
+            && self.min.y <= point.y
+            && point.y <= self.max.y
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            vec2(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            vec2(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        )
+    }
+
+    pub fn expand(&self, margin: f32) -> Aabb {
+        Aabb::new(
+            self.min - vec2(margin, margin),
+            self.max + vec2(margin, margin),
+        )
+    }
+}
+
+// 凸多角形 (頂点は反時計回り)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvexPolygon {
+    vertices: Vec<Vector2>,
+    normals: Vec<Vector2>, // normals[i] は vertices[i] -> vertices[i + 1] の辺の外向き法線
+}
+
+#[allow(dead_code)]
+impl ConvexPolygon {
+    // 頂点は時計回りでも反時計回りでもよい (凸であることは呼び出し側が保証する)
+    pub fn new(mut vertices: Vec<Vector2>) -> ConvexPolygon {
+        assert!(vertices.len() >= 3, "polygon needs at least 3 vertices");
+        if signed_area(&vertices) < 0.0 {
+            vertices.reverse();
+        }
+        let normals = (0..vertices.len())
+            .map(|i| {
+                let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+                vec2(edge.y, -edge.x).normalize()
+            })
+            .collect();
+        ConvexPolygon { vertices, normals }
+    }
+
+    // 中心が原点の長方形
+    pub fn rect(half_extents: Vector2) -> ConvexPolygon {
+        let (hx, hy) = (half_extents.x, half_extents.y);
+        ConvexPolygon::new(vec![
+            vec2(-hx, -hy),
+            vec2(hx, -hy),
+            vec2(hx, hy),
+            vec2(-hx, hy),
+        ])
+    }
+
+    pub fn vertices(&self) -> &[Vector2] {
+        &self.vertices
+    }
+
+    pub fn normals(&self) -> &[Vector2] {
+        &self.normals
+    }
+}
+
+fn signed_area(vertices: &[Vector2]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..vertices.len() {
+        let a = vertices[i];
+        let b = vertices[(i + 1) % vertices.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area * 0.5
+}
+
+// 衝突判定に使う形状 (剛体の位置を原点とするローカル座標で表す)
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    // 剛体が回転しても常に軸に平行なままの箱
+    Aabb { half_extents: Vector2 },
+    Circle { radius: f32 },
+    Polygon(ConvexPolygon),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MassData {
+    pub mass: f32,
+    pub inertia: f32, // 剛体の原点まわりの慣性モーメント
+}
+
+#[allow(dead_code)]
+impl Shape {
+    pub fn rect(half_extents: Vector2) -> Shape {
+        Shape::Polygon(ConvexPolygon::rect(half_extents))
+    }
+
+    pub fn polygon(vertices: Vec<Vector2>) -> Shape {
+        Shape::Polygon(ConvexPolygon::new(vertices))
+    }
+
+    pub fn compute_mass(&self, density: f32, offset: Vector2) -> MassData {
+        match self {
+            Shape::Aabb { half_extents } => {
+                let w = half_extents.x * 2.0;
+                let h = half_extents.y * 2.0;
+                let mass = density * w * h;
+                MassData {
+                    mass,
+                    inertia: mass * (w * w + h * h) / 12.0 + mass * offset.magnitude2(),
+                }
+            }
+            Shape::Circle { radius } => {
+                let mass = density * std::f32::consts::PI * radius * radius;
+                MassData {
+                    mass,
+                    inertia: mass * (0.5 * radius * radius + offset.magnitude2()),
+                }
+            }
+            Shape::Polygon(polygon) => {
+                // 原点と各辺からなる三角形に分けて足し合わせる
+                let mut mass = 0.0;
+                let mut inertia = 0.0;
+                let vertices = polygon.vertices();
+                for i in 0..vertices.len() {
+                    let a = vertices[i] + offset;
+                    let b = vertices[(i + 1) % vertices.len()] + offset;
+                    let cross = a.x * b.y - a.y * b.x;
+                    let triangle_mass = density * cross * 0.5;
+                    mass += triangle_mass;
+                    inertia += triangle_mass * (a.dot(a) + a.dot(b) + b.dot(b)) / 6.0;
+                }
+                MassData { mass, inertia }
+            }
+        }
+    }
+
+    // 位置と回転を反映したワールド座標の形状
+    pub fn to_world(&self, position: Vector2, rotation: f32) -> WorldShape {
+        match self {
+            Shape::Aabb { half_extents } => WorldShape::Polygon {
+                vertices: vec![
+                    position + vec2(-half_extents.x, -half_extents.y),
+                    position + vec2(half_extents.x, -half_extents.y),
+                    position + vec2(half_extents.x, half_extents.y),
+                    position + vec2(-half_extents.x, half_extents.y),
+                ],
+                normals: vec![
+                    vec2(0.0, -1.0),
+                    vec2(1.0, 0.0),
+                    vec2(0.0, 1.0),
+                    vec2(-1.0, 0.0),
+                ],
+            },
+            Shape::Circle { radius } => WorldShape::Circle {
+                center: position,
+                radius: *radius,
+            },
+            Shape::Polygon(polygon) => {
+                let (sin, cos) = rotation.sin_cos();
+                let rotate = |v: Vector2| vec2(v.x * cos - v.y * sin, v.x * sin + v.y * cos);
+                WorldShape::Polygon {
+                    vertices: polygon
+                        .vertices()
+                        .iter()
+                        .map(|&v| position + rotate(v))
+                        .collect(),
+                    normals: polygon.normals().iter().map(|&n| rotate(n)).collect(),
+                }
+            }
+        }
+    }
+}
+
+// ワールド座標に変換済みの形状 (1ステップの間だけ使う)
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorldShape {
+    Circle {
+        center: Vector2,
+        radius: f32,
+    },
+    Polygon {
+        vertices: Vec<Vector2>,
+        normals: Vec<Vector2>,
+    },
+}
+
+#[allow(dead_code)]
+impl WorldShape {
+    pub fn aabb(&self) -> Aabb {
+        match self {
+            WorldShape::Circle { center, radius } => {
+                Aabb::from_center(*center, vec2(*radius, *radius))
+            }
+            WorldShape::Polygon { vertices, .. } => {
+                let mut aabb = Aabb::new(vertices[0], vertices[0]);
+                for v in &vertices[1..] {
+                    aabb = aabb.union(&Aabb::new(*v, *v));
+                }
+                aabb
+            }
+        }
+    }
+
+    pub fn contains_point(&self, point: Vector2) -> bool {
+        match self {
+            WorldShape::Circle { center, radius } => {
+                (point - center).magnitude2() <= radius * radius
+            }
+            WorldShape::Polygon { vertices, normals } => vertices
+                .iter()
+                .zip(normals)
+                .all(|(v, n)| (point - v).dot(*n) <= 0.0),
+        }
+    }
+
+    // 指定した方向にもっとも遠い点 (サポート写像)
+    pub fn support(&self, direction: Vector2) -> Vector2 {
+        match self {
+            WorldShape::Circle { center, radius } => {
+                if direction.magnitude2() == 0.0 {
+                    *center
+                } else {
+                    center + direction.normalize() * *radius
+                }
+            }
+            WorldShape::Polygon { vertices, .. } => {
+                let mut best = vertices[0];
+                let mut best_distance = best.dot(direction);
+                for &v in &vertices[1..] {
+                    let distance = v.dot(direction);
+                    if distance > best_distance {
+                        best = v;
+                        best_distance = distance;
+                    }
+                }
+                best
+            }
+        }
+    }
+
+    // 光線との交差 (direction は正規化しておくこと)
+    // 当たった距離と表面の法線を返す。始点が形状の内側にあるときは当たらない扱いにする
+    pub fn raycast(
+        &self,
+        origin: Vector2,
+        direction: Vector2,
+        max_distance: f32,
+    ) -> Option<(f32, Vector2)> {
+        match self {
+            WorldShape::Circle { center, radius } => {
+                let offset = origin - center;
+                let c = offset.magnitude2() - radius * radius;
+                if c < 0.0 {
+                    return None;
+                }
+                let b = offset.dot(direction);
+                let discriminant = b * b - c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let distance = -b - discriminant.sqrt();
+                if distance < 0.0 || distance > max_distance {
+                    return None;
+                }
+                let normal = (origin + direction * distance - center).normalize();
+                Some((distance, normal))
+            }
+            WorldShape::Polygon { vertices, normals } => {
+                // 各辺の半平面で光線を切り取っていく
+                let mut lower = 0.0;
+                let mut upper = max_distance;
+                let mut hit_edge = None;
+                for (v, n) in vertices.iter().zip(normals) {
+                    let numerator = n.dot(v - origin);
+                    let denominator = n.dot(direction);
+                    if denominator == 0.0 {
+                        if numerator < 0.0 {
+                            return None;
+                        }
+                    } else if denominator < 0.0 && numerator < lower * denominator {
+                        lower = numerator / denominator;
+                        hit_edge = Some(*n);
+                    } else if denominator > 0.0 && numerator < upper * denominator {
+                        upper = numerator / denominator;
+                    }
+                    if upper < lower {
+                        return None;
+                    }
+                }
+                hit_edge.map(|normal| (lower, normal))
+            }
+        }
+    }
+
+    pub fn translated(&self, offset: Vector2) -> WorldShape {
+        match self {
+            WorldShape::Circle { center, radius } => WorldShape::Circle {
+                center: center + offset,
+                radius: *radius,
+            },
+            WorldShape::Polygon { vertices, normals } => WorldShape::Polygon {
+                vertices: vertices.iter().map(|v| v + offset).collect(),
+                normals: normals.clone(),
+            },
+        }
+    }
+}
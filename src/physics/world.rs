@@ -0,0 +1,644 @@
+use cgmath::{vec2, InnerSpace};
+
+use super::body::{cross, cross_scalar, BodyHandle, BodyType, RigidBody};
+use super::broadphase::SpatialHash;
+use super::collision::{collide, Manifold};
+use super::events::{CollisionEvent, EventTracker, Overlap};
+use super::shape::WorldShape;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// これより遅くぶつかったときは跳ね返らせない (静止時の細かい振動を防ぐ)
+const RESTITUTION_THRESHOLD: f32 = 1.0;
+
+// 接触している剛体の組
+#[derive(Clone, Debug)]
+pub struct Contact {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub manifold: Manifold,
+}
+
+// 接触点ごとの拘束 (ソルバーの作業用)
+struct ContactConstraint {
+    a: usize,
+    b: usize,
+    normal: Vector2,
+    tangent: Vector2,
+    friction: f32,
+    points: Vec<PointConstraint>,
+}
+
+struct PointConstraint {
+    ra: Vector2, // 剛体Aの中心から接触点へのベクトル
+    rb: Vector2,
+    penetration: f32,
+    normal_mass: f32,
+    tangent_mass: f32,
+    velocity_bias: f32, // 反発による目標の速度
+    normal_impulse: f32,
+    tangent_impulse: f32,
+}
+
+struct BodySlot {
+    generation: u32,
+    body: Option<RigidBody>,
+}
+
+// 2Dの剛体シミュレーション (GLを使わないのでヘッドレスで動かせる)
+pub struct PhysicsWorld {
+    pub gravity: Vector2,
+    pub velocity_iterations: u32,
+    pub position_correction: f32, // めり込みを1ステップで押し戻す割合
+    pub slop: f32,                // 許容するめり込みの深さ
+    slots: Vec<BodySlot>,
+    free: Vec<u32>,
+    pub(super) broadphase: SpatialHash,
+    pub(super) shapes: Vec<Option<WorldShape>>, // 直前のステップで計算したワールド座標の形状
+    pub(super) added: Vec<u32>, // 直前のステップの後に追加した剛体 (ブロードフェーズに入っていない)
+    contacts: Vec<Contact>,
+    sensor_overlaps: Vec<Overlap>,
+    tracker: EventTracker,
+    events: Vec<CollisionEvent>,
+}
+
+#[allow(dead_code)]
+impl PhysicsWorld {
+    pub fn new(gravity: Vector2) -> PhysicsWorld {
+        PhysicsWorld {
+            gravity,
+            velocity_iterations: 8,
+            position_correction: 0.4,
+            slop: 0.005,
+            slots: Vec::new(),
+            free: Vec::new(),
+            broadphase: SpatialHash::new(2.0),
+            shapes: Vec::new(),
+            added: Vec::new(),
+            contacts: Vec::new(),
+            sensor_overlaps: Vec::new(),
+            tracker: EventTracker::default(),
+            events: Vec::new(),
+        }
+    }
+
+    // ブロードフェーズのセルの大きさ (物体の平均的な大きさの2倍程度がよい)
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.broadphase = SpatialHash::new(cell_size);
+    }
+
+    pub fn add_body(&mut self, body: RigidBody) -> BodyHandle {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(BodySlot {
+                    generation: 0,
+                    body: None,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.body = Some(body);
+        self.added.push(index);
+        BodyHandle {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let body = slot.body.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.contacts
+            .retain(|contact| contact.a != handle && contact.b != handle);
+        self.sensor_overlaps
+            .retain(|overlap| overlap.a != handle && overlap.b != handle);
+        Some(body)
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.body.as_ref()
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.body.as_mut()
+    }
+
+    pub fn handle(&self, index: usize) -> Option<BodyHandle> {
+        let slot = self.slots.get(index)?;
+        slot.body.as_ref()?;
+        Some(BodyHandle {
+            index: index as u32,
+            generation: slot.generation,
+        })
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.body.as_ref().map(|body| {
+                (
+                    BodyHandle {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    body,
+                )
+            })
+        })
+    }
+
+    pub fn body_count(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.body.is_some()).count()
+    }
+
+    // 直前のステップで見つかった接触
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    // 直前のステップで起きた衝突のイベント
+    pub fn events(&self) -> &[CollisionEvent] {
+        &self.events
+    }
+
+    // 2つのボディの両方が動かないときは衝突を調べる必要がない
+    // センサーは動く床やキャラクターのような Kinematic なボディも検出する
+    fn should_collide(a: &RigidBody, b: &RigidBody) -> bool {
+        if a.collider().sensor || b.collider().sensor {
+            return a.body_type != BodyType::Static || b.body_type != BodyType::Static;
+        }
+        a.body_type == BodyType::Dynamic || b.body_type == BodyType::Dynamic
+    }
+
+    // 形状とブロードフェーズを現在の位置で作り直す
+    fn update_shapes(&mut self) {
+        self.broadphase.clear();
+        self.shapes.clear();
+        self.added.clear();
+        for (index, slot) in self.slots.iter().enumerate() {
+            let shape = slot.body.as_ref().map(|body| body.world_shape());
+            if let Some(shape) = &shape {
+                self.broadphase.insert(index as u32, shape.aabb());
+            }
+            self.shapes.push(shape);
+        }
+    }
+
+    fn find_contacts(&mut self) {
+        self.contacts.clear();
+        self.sensor_overlaps.clear();
+        for (a, b) in self.broadphase.pairs() {
+            let (mut a, mut b) = (a as usize, b as usize);
+            let body_a = self.slots[a].body.as_ref().unwrap();
+            let body_b = self.slots[b].body.as_ref().unwrap();
+            if !PhysicsWorld::should_collide(body_a, body_b)
+                || !body_a.collider().can_collide(body_b.collider())
+            {
+                continue;
+            }
+            let sensor = body_a.collider().sensor || body_b.collider().sensor;
+            // センサーのイベントでは a をセンサー側にそろえる
+            if sensor && !body_a.collider().sensor {
+                std::mem::swap(&mut a, &mut b);
+            }
+            let shape_a = self.shapes[a].as_ref().unwrap();
+            let shape_b = self.shapes[b].as_ref().unwrap();
+            let manifold = match collide(shape_a, shape_b) {
+                Some(manifold) => manifold,
+                None => continue,
+            };
+            if sensor {
+                self.sensor_overlaps.push(Overlap {
+                    a: self.handle(a).unwrap(),
+                    b: self.handle(b).unwrap(),
+                    entity_a: self.slots[a].body.as_ref().unwrap().entity,
+                    entity_b: self.slots[b].body.as_ref().unwrap().entity,
+                    sensor: true,
+                    normal: manifold.normal,
+                    point: manifold.center(),
+                });
+            } else {
+                self.contacts.push(Contact {
+                    a: self.handle(a).unwrap(),
+                    b: self.handle(b).unwrap(),
+                    manifold,
+                });
+            }
+        }
+    }
+
+    // 今回の接触とセンサーの重なりを前回と比べてイベントを作る
+    fn update_events(&mut self) {
+        let mut overlaps = self.sensor_overlaps.clone();
+        for contact in &self.contacts {
+            overlaps.push(Overlap {
+                a: contact.a,
+                b: contact.b,
+                entity_a: self.body(contact.a).and_then(|body| body.entity),
+                entity_b: self.body(contact.b).and_then(|body| body.entity),
+                sensor: false,
+                normal: contact.manifold.normal,
+                point: contact.manifold.center(),
+            });
+        }
+        self.tracker.update(overlaps, &mut self.events);
+    }
+
+    // 時間を delta_time 秒進める (固定の時間間隔で呼び出すこと)
+    pub fn step(&mut self, delta_time: f32) {
+        if delta_time <= 0.0 {
+            return;
+        }
+
+        // 力を速度に反映する
+        let gravity = self.gravity;
+        for body in self.slots.iter_mut().filter_map(|s| s.body.as_mut()) {
+            if body.body_type != BodyType::Dynamic {
+                continue;
+            }
+            body.velocity +=
+                (gravity * body.gravity_scale + body.force() * body.inv_mass()) * delta_time;
+            body.angular_velocity += body.torque() * body.inv_inertia() * delta_time;
+            body.velocity *= 1.0 / (1.0 + delta_time * body.linear_damping);
+            body.angular_velocity *= 1.0 / (1.0 + delta_time * body.angular_damping);
+        }
+
+        self.update_shapes();
+        self.find_contacts();
+        self.update_events();
+
+        let mut constraints = self.prepare_constraints();
+        for _ in 0..self.velocity_iterations {
+            self.solve_velocities(&mut constraints);
+        }
+
+        // 速度を位置に反映する
+        for body in self.slots.iter_mut().filter_map(|s| s.body.as_mut()) {
+            if body.body_type == BodyType::Static {
+                continue;
+            }
+            body.position += body.velocity * delta_time;
+            if !body.is_fixed_rotation() {
+                body.rotation += body.angular_velocity * delta_time;
+            }
+            body.clear_forces();
+        }
+
+        self.correct_positions(&constraints);
+
+        // ステップの間に行うクエリのために、移動後の位置で作り直しておく
+        self.update_shapes();
+    }
+
+    fn body_pair(&mut self, a: usize, b: usize) -> (&mut RigidBody, &mut RigidBody) {
+        assert_ne!(a, b);
+        let (first, second) = if a < b {
+            let (left, right) = self.slots.split_at_mut(b);
+            (&mut left[a], &mut right[0])
+        } else {
+            let (left, right) = self.slots.split_at_mut(a);
+            (&mut right[0], &mut left[b])
+        };
+        (first.body.as_mut().unwrap(), second.body.as_mut().unwrap())
+    }
+
+    fn prepare_constraints(&mut self) -> Vec<ContactConstraint> {
+        let mut constraints = Vec::with_capacity(self.contacts.len());
+        for contact in &self.contacts {
+            let a = self.slots[contact.a.index as usize].body.as_ref().unwrap();
+            let b = self.slots[contact.b.index as usize].body.as_ref().unwrap();
+            let normal = contact.manifold.normal;
+            let tangent = vec2(-normal.y, normal.x);
+            let restitution = a.collider().restitution.max(b.collider().restitution);
+            let friction = (a.collider().friction * b.collider().friction).sqrt();
+
+            let points = contact
+                .manifold
+                .points
+                .iter()
+                .map(|point| {
+                    let ra = point.position - a.position;
+                    let rb = point.position - b.position;
+                    let rn_a = cross(ra, normal);
+                    let rn_b = cross(rb, normal);
+                    let rt_a = cross(ra, tangent);
+                    let rt_b = cross(rb, tangent);
+                    let inv_mass_sum = a.inv_mass() + b.inv_mass();
+                    let normal_mass = inv_mass_sum
+                        + a.inv_inertia() * rn_a * rn_a
+                        + b.inv_inertia() * rn_b * rn_b;
+                    let tangent_mass = inv_mass_sum
+                        + a.inv_inertia() * rt_a * rt_a
+                        + b.inv_inertia() * rt_b * rt_b;
+
+                    let relative_velocity =
+                        b.velocity_at(point.position) - a.velocity_at(point.position);
+                    let normal_velocity = relative_velocity.dot(normal);
+                    let velocity_bias = if normal_velocity < -RESTITUTION_THRESHOLD {
+                        -restitution * normal_velocity
+                    } else {
+                        0.0
+                    };
+
+                    PointConstraint {
+                        ra,
+                        rb,
+                        penetration: point.penetration,
+                        normal_mass: if normal_mass > 0.0 {
+                            1.0 / normal_mass
+                        } else {
+                            0.0
+                        },
+                        tangent_mass: if tangent_mass > 0.0 {
+                            1.0 / tangent_mass
+                        } else {
+                            0.0
+                        },
+                        velocity_bias,
+                        normal_impulse: 0.0,
+                        tangent_impulse: 0.0,
+                    }
+                })
+                .collect();
+
+            constraints.push(ContactConstraint {
+                a: contact.a.index as usize,
+                b: contact.b.index as usize,
+                normal,
+                tangent,
+                friction,
+                points,
+            });
+        }
+        constraints
+    }
+
+    // 逐次インパルス法: 接触点ごとに力積を加え、累積した力積を制限内に収める
+    fn solve_velocities(&mut self, constraints: &mut [ContactConstraint]) {
+        for constraint in constraints.iter_mut() {
+            let (a, b) = self.body_pair(constraint.a, constraint.b);
+            for point in constraint.points.iter_mut() {
+                // 摩擦 (法線方向の力積に比例した範囲に制限する)
+                let relative_velocity = b.velocity + cross_scalar(b.angular_velocity, point.rb)
+                    - a.velocity
+                    - cross_scalar(a.angular_velocity, point.ra);
+                let tangent_velocity = relative_velocity.dot(constraint.tangent);
+                let max_friction = constraint.friction * point.normal_impulse;
+                let old_impulse = point.tangent_impulse;
+                point.tangent_impulse = (old_impulse - tangent_velocity * point.tangent_mass)
+                    .max(-max_friction)
+                    .min(max_friction);
+                let impulse = constraint.tangent * (point.tangent_impulse - old_impulse);
+                apply_impulse_pair(a, b, point.ra, point.rb, impulse);
+
+                // 法線方向 (めり込む向きの速度を打ち消す。引っぱる力積にはしない)
+                let relative_velocity = b.velocity + cross_scalar(b.angular_velocity, point.rb)
+                    - a.velocity
+                    - cross_scalar(a.angular_velocity, point.ra);
+                let normal_velocity = relative_velocity.dot(constraint.normal);
+                let old_impulse = point.normal_impulse;
+                point.normal_impulse = (old_impulse
+                    + (point.velocity_bias - normal_velocity) * point.normal_mass)
+                    .max(0.0);
+                let impulse = constraint.normal * (point.normal_impulse - old_impulse);
+                apply_impulse_pair(a, b, point.ra, point.rb, impulse);
+            }
+        }
+    }
+
+    // めり込みを直接位置をずらして解消する
+    fn correct_positions(&mut self, constraints: &[ContactConstraint]) {
+        let percent = self.position_correction;
+        let slop = self.slop;
+        for constraint in constraints {
+            let (a, b) = self.body_pair(constraint.a, constraint.b);
+            let inv_mass_sum = a.inv_mass() + b.inv_mass();
+            if inv_mass_sum <= 0.0 {
+                continue;
+            }
+            let penetration = constraint
+                .points
+                .iter()
+                .map(|p| p.penetration)
+                .fold(0.0, f32::max);
+            let correction =
+                constraint.normal * ((penetration - slop).max(0.0) / inv_mass_sum * percent);
+            a.position -= correction * a.inv_mass();
+            b.position += correction * b.inv_mass();
+        }
+    }
+}
+
+fn apply_impulse_pair(
+    a: &mut RigidBody,
+    b: &mut RigidBody,
+    ra: Vector2,
+    rb: Vector2,
+    impulse: Vector2,
+) {
+    a.velocity -= impulse * a.inv_mass();
+    a.angular_velocity -= cross(ra, impulse) * a.inv_inertia();
+    b.velocity += impulse * b.inv_mass();
+    b.angular_velocity += cross(rb, impulse) * b.inv_inertia();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::body::Collider;
+    use crate::physics::shape::{Aabb, Shape};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn floor(world: &mut PhysicsWorld, collider: impl Fn(Collider) -> Collider) -> BodyHandle {
+        world.add_body(RigidBody::fixed(
+            vec2(0.0, 0.0),
+            collider(Collider::new(Shape::Aabb {
+                half_extents: vec2(10.0, 0.5),
+            })),
+        ))
+    }
+
+    fn run(world: &mut PhysicsWorld, seconds: f32) {
+        for _ in 0..(seconds / DT).round() as u32 {
+            world.step(DT);
+        }
+    }
+
+    #[test]
+    fn box_settles_on_floor_without_sinking() {
+        let mut world = PhysicsWorld::new(vec2(0.0, -10.0));
+        floor(&mut world, |c| c);
+        let handle = world.add_body(RigidBody::dynamic(
+            vec2(0.0, 3.0),
+            Collider::new(Shape::Aabb {
+                half_extents: vec2(0.5, 0.5),
+            }),
+        ));
+        run(&mut world, 3.0);
+
+        // 床の上面 (y = 0.5) に箱の下面が乗る
+        let body = world.body(handle).unwrap();
+        let bottom = body.position.y - 0.5;
+        assert!((bottom - 0.5).abs() < 0.02, "bottom = {}", bottom);
+        assert!(body.velocity.magnitude() < 0.05, "{:?}", body.velocity);
+
+        // 乗ったまま時間がたっても沈まない
+        run(&mut world, 5.0);
+        let body = world.body(handle).unwrap();
+        assert!((body.position.y - 0.5 - 0.5).abs() < 0.02);
+        assert_eq!(world.contacts().len(), 1);
+    }
+
+    // 重力なしで床に向かって速度 10 でぶつけたあとの縦の速度
+    fn bounce_velocity(restitution: f32) -> f32 {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        floor(&mut world, |c| c.with_restitution(restitution));
+        let mut ball = RigidBody::dynamic(
+            vec2(0.0, 1.5),
+            Collider::new(Shape::Circle { radius: 0.5 }).with_restitution(restitution),
+        );
+        ball.velocity = vec2(0.0, -10.0);
+        let handle = world.add_body(ball);
+        run(&mut world, 0.5);
+        world.body(handle).unwrap().velocity.y
+    }
+
+    #[test]
+    fn restitution() {
+        let elastic = bounce_velocity(1.0);
+        assert!((elastic - 10.0).abs() < 0.5, "elastic = {}", elastic);
+        let half = bounce_velocity(0.5);
+        assert!((half - 5.0).abs() < 0.5, "half = {}", half);
+        let inelastic = bounce_velocity(0.0);
+        assert!(inelastic.abs() < 0.1, "inelastic = {}", inelastic);
+    }
+
+    // 床の上を横に速度 5 で滑らせ、1秒後の横の速度と進んだ距離
+    fn slide(friction: f32) -> (f32, f32) {
+        let mut world = PhysicsWorld::new(vec2(0.0, -10.0));
+        floor(&mut world, |c| c.with_friction(friction));
+        let mut block = RigidBody::dynamic(
+            vec2(0.0, 1.0),
+            Collider::new(Shape::Aabb {
+                half_extents: vec2(0.5, 0.5),
+            })
+            .with_friction(friction),
+        );
+        block.velocity = vec2(5.0, 0.0);
+        let handle = world.add_body(block);
+        run(&mut world, 1.0);
+        let body = world.body(handle).unwrap();
+        (body.velocity.x, body.position.x)
+    }
+
+    #[test]
+    fn friction() {
+        // 摩擦がなければ滑り続ける
+        let (velocity, distance) = slide(0.0);
+        assert!((velocity - 5.0).abs() < 0.01, "velocity = {}", velocity);
+        assert!((distance - 5.0).abs() < 0.1);
+
+        // 摩擦係数 0.5 (お互いの積の平方根) では 5 m/s² で減速し、1秒で止まる (2.5 m 進む)
+        let (velocity, distance) = slide(0.5);
+        assert!(velocity.abs() < 0.1, "velocity = {}", velocity);
+        assert!((distance - 2.5).abs() < 0.2, "distance = {}", distance);
+    }
+
+    #[test]
+    fn kinematic_bodies_ignore_impulses() {
+        let mut world = PhysicsWorld::new(vec2(0.0, -10.0));
+        let mut platform = RigidBody::kinematic(
+            vec2(0.0, 0.0),
+            Collider::new(Shape::Aabb {
+                half_extents: vec2(2.0, 0.5),
+            }),
+        );
+        platform.velocity = vec2(1.0, 0.0);
+        platform.apply_impulse(vec2(0.0, 100.0), vec2(0.5, 0.0));
+        assert_eq!(platform.velocity, vec2(1.0, 0.0));
+        assert_eq!(platform.angular_velocity, 0.0);
+        let platform = world.add_body(platform);
+
+        // 上に落ちてきた箱にも押し戻されず、重力も受けない
+        let crate_body = world.add_body(RigidBody::dynamic(
+            vec2(0.0, 2.0),
+            Collider::new(Shape::Aabb {
+                half_extents: vec2(0.5, 0.5),
+            }),
+        ));
+        run(&mut world, 1.0);
+        let body = world.body(platform).unwrap();
+        assert_eq!(body.velocity, vec2(1.0, 0.0));
+        assert!((body.position.x - 1.0).abs() < 1e-3);
+        assert_eq!(body.position.y, 0.0);
+        // 箱は床の上に乗っている
+        let resting = world.body(crate_body).unwrap();
+        assert!((resting.position.y - 1.0).abs() < 0.05);
+    }
+
+    // 再現できる疑似乱数 (0.0 ~ 1.0)
+    fn random(seed: &mut u32) -> f32 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 17;
+        *seed ^= *seed << 5;
+        (*seed >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    #[test]
+    fn spatial_hash_query_matches_brute_force() {
+        let mut world = PhysicsWorld::new(vec2(0.0, 0.0));
+        let mut seed = 12345;
+        for i in 0..300 {
+            let position = vec2(
+                random(&mut seed) * 60.0 - 30.0,
+                random(&mut seed) * 60.0 - 30.0,
+            );
+            // いくつかはセルに入りきらない大きな物体にする
+            let size = if i % 50 == 0 {
+                40.0
+            } else {
+                0.1 + random(&mut seed) * 3.0
+            };
+            let shape = if i % 3 == 0 {
+                Shape::Circle { radius: size * 0.5 }
+            } else {
+                Shape::Aabb {
+                    half_extents: vec2(size * 0.5, size * 0.25 + 0.05),
+                }
+            };
+            world.add_body(RigidBody::fixed(position, Collider::new(shape)));
+        }
+        world.step(DT);
+
+        for _ in 0..200 {
+            let min = vec2(
+                random(&mut seed) * 70.0 - 35.0,
+                random(&mut seed) * 70.0 - 35.0,
+            );
+            let size = vec2(random(&mut seed), random(&mut seed)) * 8.0;
+            let aabb = Aabb::new(min, min + size);
+            let expected: Vec<u32> = world
+                .bodies()
+                .filter(|(_, body)| body.world_shape().aabb().overlaps(&aabb))
+                .map(|(handle, _)| handle.index)
+                .collect();
+            assert_eq!(world.broadphase.query(&aabb), expected);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::assets::Handle;
+use crate::texture::Texture2D;
+
+// 画面の代わりに描画する先 (フレームバッファー)
+// 色はテクスチャに書き込むので、マテリアルのテクスチャとしてそのまま使える
+pub struct RenderTarget {
+    pub texture: Handle<Texture2D>,
+    fbo: u32,
+    depth_stencil: u32, // 深度とステンシルのレンダーバッファー (使わないときは0)
+}
+
+#[allow(dead_code)]
+impl RenderTarget {
+    // stencil が true のときは、深度とステンシルのバッファーも作る
+    pub fn new(name: &str, width: u32, height: u32, stencil: bool) -> Result<RenderTarget, String> {
+        let texture = Texture2D::empty(width, height);
+        let mut fbo = 0;
+        let mut depth_stencil = 0;
+        let status = unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture.id,
+                0,
+            );
+            if stencil {
+                gl::GenRenderbuffers(1, &mut depth_stencil);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, depth_stencil);
+                gl::RenderbufferStorage(
+                    gl::RENDERBUFFER,
+                    gl::DEPTH24_STENCIL8,
+                    width as i32,
+                    height as i32,
+                );
+                gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::DEPTH_STENCIL_ATTACHMENT,
+                    gl::RENDERBUFFER,
+                    depth_stencil,
+                );
+            }
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+
+        // 作れなかったときも、ここまでに作ったものは Drop で片づける
+        let target = RenderTarget {
+            texture: Handle::new(name, texture),
+            fbo,
+            depth_stencil,
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "failed to create render target: {}: status 0x{:x}",
+                name, status
+            ));
+        }
+        Ok(target)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height
+    }
+
+    // これ以降の描画をこのターゲットに向ける (ビューポートも大きさに合わせる)
+    pub unsafe fn bind(&self) {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        gl::Viewport(0, 0, self.width() as i32, self.height() as i32);
+    }
+
+    // 画面への描画に戻す (ビューポートは呼び出し側で戻す)
+    pub unsafe fn unbind() {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            if self.depth_stencil != 0 {
+                gl::DeleteRenderbuffers(1, &self.depth_stencil);
+            }
+            gl::DeleteFramebuffers(1, &self.fbo);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use c_str_macro::c_str;
+use cgmath::{vec4, SquareMatrix};
+
+use crate::lighting::Lighting;
+use crate::material::{MaterialId, MaterialLibrary, RenderState};
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Matrix4 = cgmath::Matrix4<f32>;
+#[allow(dead_code)]
+type Vector3 = cgmath::Vector3<f32>;
+#[allow(dead_code)]
+type Vector4 = cgmath::Vector4<f32>;
+
+// テクスチャユニットごとに現在バインドされているテクスチャを覚えておく数
+const MAX_TEXTURE_UNITS: usize = 16;
+
+pub struct DrawCommand {
+    pub vertex: Rc<Vertex>,
+    pub material: MaterialId,
+    pub model: Matrix4,
+    pub layer: i32, // 小さいレイヤーから順に描画する
+}
+
+// 1フレーム分の描画の統計
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub vertices: u32,
+    pub state_changes: u32, // RenderStateの切り替えとシェーダープログラムの切り替えの回数
+    pub texture_binds: u32,
+}
+
+struct QueuedCommand {
+    command: DrawCommand,
+    transparent: bool,
+    depth: f32, // カメラからの距離
+}
+
+pub struct Renderer {
+    pub materials: MaterialLibrary,
+    pub lighting: Lighting,
+    queue: Vec<QueuedCommand>,
+    view: Matrix4,
+    projection: Matrix4,
+    stats: RenderStats,
+}
+
+impl Renderer {
+    pub fn new(materials: MaterialLibrary) -> Renderer {
+        Renderer {
+            materials,
+            lighting: Lighting::new(),
+            queue: Vec::new(),
+            view: Matrix4::identity(),
+            projection: Matrix4::identity(),
+            stats: RenderStats::default(),
+        }
+    }
+
+    // フレームの最初に呼び出し、このフレームで使うカメラの行列を設定する
+    pub fn begin_frame(&mut self, view: &Matrix4, projection: &Matrix4) {
+        self.queue.clear();
+        self.view = *view;
+        self.projection = *projection;
+    }
+
+    pub fn view(&self) -> Matrix4 {
+        self.view
+    }
+
+    pub fn projection(&self) -> Matrix4 {
+        self.projection
+    }
+
+    // begin_frame()で設定したカメラの行列 (ワールド座標からクリップ座標への変換)
+    pub fn view_projection(&self) -> Matrix4 {
+        self.projection * self.view
+    }
+
+    // ビュー行列の逆行列の平行移動成分がカメラの位置
+    pub fn camera_position(&self) -> Vector3 {
+        self.view
+            .invert()
+            .map_or(Vector3::new(0.0, 0.0, 0.0), |inverse| inverse.w.truncate())
+    }
+
+    // モデル空間の箱 (min..max) が画面に映る可能性があるかどうか
+    // 8つの角をクリップ空間に移し、すべてが同じ面の外側にあるときだけ見えないとする
+    pub fn is_visible(&self, model: &Matrix4, min: Vector3, max: Vector3) -> bool {
+        let matrix = self.projection * self.view * model;
+        let corners: Vec<Vector4> = (0..8)
+            .map(|i| {
+                let x = if i & 1 == 0 { min.x } else { max.x };
+                let y = if i & 2 == 0 { min.y } else { max.y };
+                let z = if i & 4 == 0 { min.z } else { max.z };
+                matrix * vec4(x, y, z, 1.0)
+            })
+            .collect();
+        let outside = |plane: &dyn Fn(&Vector4) -> bool| corners.iter().all(plane);
+        !(outside(&|c| c.x < -c.w)
+            || outside(&|c| c.x > c.w)
+            || outside(&|c| c.y < -c.w)
+            || outside(&|c| c.y > c.w)
+            || outside(&|c| c.z < -c.w)
+            || outside(&|c| c.z > c.w))
+    }
+
+    // すぐには描画せず、flush()が呼ばれるまでキューに溜めておく
+    pub fn submit(&mut self, command: DrawCommand) {
+        let transparent = self.materials.get(command.material).is_transparent();
+        let position = self.view * command.model * vec4(0.0, 0.0, 0.0, 1.0);
+        self.queue.push(QueuedCommand {
+            command,
+            transparent,
+            depth: -position.z, // 右手系のビュー空間ではカメラの前方が -z
+        });
+    }
+
+    // レイヤー、不透明/半透明、マテリアル、深度の順に並べ替えてから描画する
+    // 不透明なものは手前から奥へ (深度テストで無駄な塗りを減らす)、
+    // 半透明なものは奥から手前へ (正しく重ねるため) 描画する
+    pub unsafe fn flush(&mut self) {
+        self.queue.sort_by(|a, b| {
+            a.command
+                .layer
+                .cmp(&b.command.layer)
+                .then(a.transparent.cmp(&b.transparent))
+                .then_with(|| {
+                    if a.transparent {
+                        compare_depth(b.depth, a.depth)
+                            .then(a.command.material.cmp(&b.command.material))
+                    } else {
+                        a.command
+                            .material
+                            .cmp(&b.command.material)
+                            .then(compare_depth(a.depth, b.depth))
+                    }
+                })
+        });
+
+        let mut stats = RenderStats::default();
+        let mut current_material: Option<MaterialId> = None;
+        let mut current_state: Option<RenderState> = None;
+        let mut current_program: Option<u32> = None;
+        let mut bound_textures = [0u32; MAX_TEXTURE_UNITS];
+
+        for queued in &self.queue {
+            let command = &queued.command;
+            let material = self.materials.get(command.material);
+
+            if current_material != Some(command.material) {
+                if current_state != Some(material.render_state) {
+                    material.render_state.apply();
+                    current_state = Some(material.render_state);
+                    stats.state_changes += 1;
+                }
+                if current_program != Some(material.shader.id) {
+                    material.shader.use_program();
+                    material.shader.set_mat4(c_str!("uView"), &self.view);
+                    material
+                        .shader
+                        .set_mat4(c_str!("uProjection"), &self.projection);
+                    if Lighting::is_lit(&material.shader) {
+                        self.lighting
+                            .apply(&material.shader, self.camera_position());
+                    }
+                    current_program = Some(material.shader.id);
+                    stats.state_changes += 1;
+                }
+                for (unit, (name, texture)) in material.textures.iter().enumerate() {
+                    material.shader.set_int(name, unit as i32);
+                    if unit < MAX_TEXTURE_UNITS && bound_textures[unit] == texture.id {
+                        continue; // 同じテクスチャがすでにバインドされている
+                    }
+                    texture.bind(unit as u32);
+                    if unit < MAX_TEXTURE_UNITS {
+                        bound_textures[unit] = texture.id;
+                    }
+                    stats.texture_binds += 1;
+                }
+                material.set_uniforms();
+                current_material = Some(command.material);
+            }
+
+            material.shader.set_mat4(c_str!("uModel"), &command.model);
+            command.vertex.draw();
+            stats.draw_calls += 1;
+            let vertex = &command.vertex;
+            stats.vertices += if vertex.is_instanced() {
+                (vertex.vertex_num() * vertex.instance_num()) as u32
+            } else {
+                vertex.vertex_num() as u32
+            };
+        }
+
+        self.queue.clear();
+        self.stats = stats;
+    }
+
+    // 直前にflush()したフレームの統計
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+}
+
+fn compare_depth(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
@@ -1,6 +1,5 @@
 use cgmath::Array;
 use cgmath::Matrix;
-use gl;
 use gl::types::*;
 
 use std::ffi::{CStr, CString};
@@ -9,9 +8,15 @@ use std::io::Read;
 use std::ptr;
 use std::str;
 
+use crate::assets::{Asset, Assets};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
 #[allow(dead_code)]
 type Vector3 = cgmath::Vector3<f32>;
 #[allow(dead_code)]
+type Vector4 = cgmath::Vector4<f32>;
+#[allow(dead_code)]
 type Matrix4 = cgmath::Matrix4<f32>;
 
 pub struct Shader {
@@ -22,8 +27,6 @@ pub struct Shader {
 impl Shader {
     #[rustfmt::skip]
     pub fn new(vertex_path: &str, fragment_path: &str)->Shader {
-        let mut shader = Shader {id: 0};
-
         //vertex
         let mut vertex_file = File::open(vertex_path)
             .unwrap_or_else(|_|panic!("failed to open file: {}", vertex_path));
@@ -42,6 +45,15 @@ impl Shader {
             .read_to_string(&mut fragment_code)
             .expect("failed to read fragment shader file");
 
+        Shader::from_source(&vertex_code, &fragment_code, None)
+    }
+
+    // ソースコードから作る (ジオメトリシェーダーは省略できる)
+    // コンパイルやリンクに失敗したときはログを表示する
+    #[rustfmt::skip]
+    pub fn from_source(vertex_code: &str, fragment_code: &str, geometry_code: Option<&str>) -> Shader {
+        let mut shader = Shader {id: 0};
+
         // create cstring (C言語と互換性のあるCString型のデータを用意する)
         let cstr_vertex_code = CString::new(
             vertex_code.as_bytes()
@@ -49,6 +61,9 @@ impl Shader {
         let cstr_fragment_code = CString::new(
             fragment_code.as_bytes()
         ).unwrap();
+        let cstr_geometry_code = geometry_code.map(|code| CString::new(
+            code.as_bytes()
+        ).unwrap());
 
         unsafe {
             // vertex shader
@@ -63,16 +78,31 @@ impl Shader {
             gl::CompileShader(fragment);
             shader.check_compile_errors(fragment, "FRAGMENT");
 
+            // geometry shader
+            let geometry = cstr_geometry_code.map(|code| {
+                let geometry = gl::CreateShader(gl::GEOMETRY_SHADER);
+                gl::ShaderSource(geometry, 1, &code.as_ptr(), ptr::null());
+                gl::CompileShader(geometry);
+                shader.check_compile_errors(geometry, "GEOMETRY");
+                geometry
+            });
+
             // shader program (シェーダープログラムの準備)
             let id = gl::CreateProgram(); // シェーダープログラムの生成
             gl::AttachShader(id, vertex); // シェーダーをアタッチ
             gl::AttachShader(id, fragment);
+            if let Some(geometry) = geometry {
+                gl::AttachShader(id, geometry);
+            }
             gl::LinkProgram(id); // それぞれのシェーダーを実行可能な形式に生成
             shader.check_compile_errors(id, "PROGRAM"); // 再びエラーの確認
 
             // delete (不要になったシェーダーを削除)
             gl::DeleteShader(vertex);
             gl::DeleteShader(fragment);
+            if let Some(geometry) = geometry {
+                gl::DeleteShader(geometry);
+            }
 
             shader.id = id;
         }
@@ -92,8 +122,12 @@ impl Shader {
         gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value);
     }
 
-    pub unsafe fn set_float(&self, name: &CStr, value: &Vector3) {
-        gl::Uniform3fv(
+    pub unsafe fn set_float(&self, name: &CStr, value: f32) {
+        gl::Uniform1f(gl::GetUniformLocation(self.id, name.as_ptr()), value);
+    }
+
+    pub unsafe fn set_vector2(&self, name: &CStr, value: &Vector2) {
+        gl::Uniform2fv(
             gl::GetUniformLocation(self.id, name.as_ptr()),
             1,
             value.as_ptr(),
@@ -108,6 +142,14 @@ impl Shader {
         );
     }
 
+    pub unsafe fn set_vector4(&self, name: &CStr, value: &Vector4) {
+        gl::Uniform4fv(
+            gl::GetUniformLocation(self.id, name.as_ptr()),
+            1,
+            value.as_ptr(),
+        );
+    }
+
     pub unsafe fn set_mat4(&self, name: &CStr, mat: &Matrix4) {
         gl::UniformMatrix4fv(
             gl::GetUniformLocation(self.id, name.as_ptr()),
@@ -119,8 +161,7 @@ impl Shader {
 
     unsafe fn check_compile_errors(&self, shader: u32, type_: &str) {
         let mut success = gl::FALSE as GLint;
-        let mut info_log = Vec::with_capacity(1024);
-        info_log.set_len(1024 - 1); // subtract 1 to skip the trailing null character
+        let mut info_log = vec![0u8; 1024];
 
         if type_ != "PROGRAM" {
             gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
@@ -167,7 +208,6 @@ impl Shader {
         fragment_path: &str,
         geometry_path: &str,
     ) -> Self {
-        let mut shader = Shader {id: 0};
         let mut vertex_file = File::open(vertex_path)
             .unwrap_or_else(|_|panic!("failed to open file: {}", vertex_path));
         let mut fragment_file = File::open(fragment_path)
@@ -187,50 +227,35 @@ impl Shader {
             .read_to_string(&mut geometry_code)
             .expect("falied to read geometry shader");
 
-        let cstr_vertex_code = CString::new(
-            vertex_code.as_bytes()
-        ).unwrap();
-        let cstr_fragment_code = CString::new(
-            fragment_code.as_bytes()
-        ).unwrap();
-        let cstr_geometry_code = CString::new(
-            geometry_code.as_bytes()
-        ).unwrap();
-
-        unsafe {
-            // vertex shader
-            let vertex = gl::CreateShader(gl::VERTEX_SHADER);
-            gl::ShaderSource(vertex, 1, &cstr_vertex_code.as_ptr(), ptr::null());
-            gl::CompileShader(vertex);
-            shader.check_compile_errors(vertex, "VERTEX");
-
-            // fragment shader
-            let fragment = gl::CreateShader(gl::FRAGMENT_SHADER);
-            gl::ShaderSource(fragment, 1, &cstr_fragment_code.as_ptr(), ptr::null());
-            gl::CompileShader(fragment);
-            shader.check_compile_errors(fragment, "FRAGMENT");
-
-            // geometry shader
-            let geometry = gl::CreateShader(gl::GEOMETRY_SHADER);
-            gl::ShaderSource(geometry, 1, &cstr_geometry_code.as_ptr(), ptr::null());
-            gl::CompileShader(geometry);
-            shader.check_compile_errors(geometry, "GEOMETRY");
-
-            // shader program
-            let id = gl::CreateProgram();
-            gl::AttachShader(id, vertex);
-            gl::AttachShader(id, fragment);
-            gl::AttachShader(id, geometry);
-            gl::LinkProgram(id);
-            shader.check_compile_errors(id, "PROGRAM");
+        Shader::from_source(&vertex_code, &fragment_code, Some(&geometry_code))
+    }
+}
 
-            // delete
-            gl::DeleteShader(vertex);
-            gl::DeleteShader(fragment);
-            gl::DeleteShader(geometry);
-            shader.id = id;
+// Assets::load_shader() で読み込む (path は "頂点|フラグメント" か "頂点|フラグメント|ジオメトリ")
+impl Asset for Shader {
+    fn load(assets: &Assets, path: &str) -> Result<Shader, String> {
+        let paths: Vec<&str> = path.split('|').collect();
+        if paths.len() < 2 || paths.len() > 3 {
+            return Err(format!("invalid shader path: {}", path));
         }
+        let vertex_code = assets.read_to_string(paths[0])?;
+        let fragment_code = assets.read_to_string(paths[1])?;
+        let geometry_code = match paths.get(2) {
+            Some(geometry) => Some(assets.read_to_string(geometry)?),
+            None => None,
+        };
+        Ok(Shader::from_source(
+            &vertex_code,
+            &fragment_code,
+            geometry_code.as_deref(),
+        ))
+    }
+}
 
-        shader
+impl Drop for Shader {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
     }
 }
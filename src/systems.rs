@@ -0,0 +1,154 @@
+use crate::character::CharacterController;
+use crate::components::{Pickup, Score, Sprite, Velocity};
+use crate::ecs::{Entity, Events, Read, Time, World, Write};
+use crate::physics::{CollisionEvent, EventPhase, PhysicsBody, PhysicsWorld};
+use crate::renderer::{DrawCommand, Renderer};
+use crate::transform::{self, Transform2D};
+
+// 速度に従って位置と回転を進める (FixedUpdate)
+pub fn movement_system(world: &mut World) {
+    let delta_time = world.resource::<Time>().fixed_delta_time;
+    world
+        .query::<(Write<Transform2D>, Read<Velocity>)>()
+        .for_each(|_, (transform, velocity)| {
+            if velocity.linear.x != 0.0 || velocity.linear.y != 0.0 {
+                transform.translate(velocity.linear * delta_time);
+            }
+            if velocity.angular.0 != 0.0 {
+                transform.rotate(velocity.angular * delta_time);
+            }
+        });
+}
+
+// キャラクターが入ったセンサーの Pickup を拾い、剛体とエンティティを (子も一緒に) 消す
+// (FixedUpdate、physics_step_system の後。イベントはステップごとに入れ直される)
+pub fn pickup_system(world: &mut World) {
+    let picked: Vec<Entity> = world
+        .resource::<Events<CollisionEvent>>()
+        .iter()
+        .filter_map(|event| match *event {
+            CollisionEvent::Trigger {
+                phase: EventPhase::Enter,
+                sensor_entity: Some(sensor),
+                other_entity: Some(other),
+                ..
+            } if world.has::<Pickup>(sensor) && world.has::<CharacterController>(other) => {
+                Some(sensor)
+            }
+            _ => None,
+        })
+        .collect();
+    for entity in picked {
+        // 同じステップで2人が触れても1回だけ拾う
+        let pickup = match world.remove::<Pickup>(entity) {
+            Some(pickup) => pickup,
+            None => continue,
+        };
+        if let Some(body) = world.remove::<PhysicsBody>(entity) {
+            world.resource_mut::<PhysicsWorld>().remove_body(body.0);
+        }
+        if world.has_resource::<Score>() {
+            world.resource_mut::<Score>().0 += pickup.value;
+        }
+        transform::despawn_recursive(world, entity);
+    }
+}
+
+// 見えているスプライトの描画コマンドをレンダラーのキューに積む (Render)
+pub fn sprite_render_system(world: &mut World) {
+    let mut renderer = world.resource_mut::<Renderer>();
+    world
+        .query::<(Read<Transform2D>, Read<Sprite>)>()
+        .for_each(|_, (transform, sprite)| {
+            if !sprite.visible {
+                return;
+            }
+            renderer.submit(DrawCommand {
+                vertex: sprite.vertex.clone(),
+                material: sprite.material,
+                model: transform.world_matrix(),
+                layer: sprite.layer,
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec2;
+
+    use super::*;
+    use crate::character::CharacterConfig;
+    use crate::physics::{physics_step_system, Collider, RigidBody, Shape};
+
+    fn spawn_body(world: &mut World, body: RigidBody) -> Entity {
+        let entity = world.spawn();
+        let handle = world
+            .resource_mut::<PhysicsWorld>()
+            .add_body(body.with_entity(entity));
+        world.insert(entity, Transform2D::new(vec2(0.0, 0.0)));
+        world.insert(entity, PhysicsBody(handle));
+        entity
+    }
+
+    #[test]
+    fn characters_pick_up_sensors() {
+        let mut world = World::new();
+        world.insert_resource(Time {
+            delta_time: 1.0 / 60.0,
+            fixed_delta_time: 1.0 / 60.0,
+            elapsed: 0.0,
+            alpha: 0.0,
+            frame: 0,
+        });
+        world.insert_resource(PhysicsWorld::new(vec2(0.0, 0.0)));
+        world.insert_resource(Events::<CollisionEvent>::new());
+        world.insert_resource(Score::default());
+
+        let coin_collider = Collider::new(Shape::Circle { radius: 0.2 }).with_sensor(true);
+        let coin = spawn_body(
+            &mut world,
+            RigidBody::fixed(vec2(0.0, 0.0), coin_collider.clone()),
+        );
+        world.insert(coin, Pickup { value: 3 });
+        let child = world.spawn();
+        world.insert(child, Transform2D::new(vec2(0.0, 0.0)));
+        transform::set_parent(&mut world, child, Some(coin));
+        // Pickup のないセンサーと、キャラクターでない剛体は拾わない
+        let zone = spawn_body(&mut world, RigidBody::fixed(vec2(0.0, 0.0), coin_collider));
+        let crate_box = spawn_body(
+            &mut world,
+            RigidBody::kinematic(vec2(0.0, 0.0), Collider::new(Shape::rect(vec2(0.5, 0.5)))),
+        );
+        world.insert(crate_box, Pickup { value: 1 });
+
+        let player = spawn_body(
+            &mut world,
+            RigidBody::kinematic(vec2(3.0, 0.0), Collider::new(Shape::rect(vec2(0.25, 0.5)))),
+        );
+        world.insert(player, CharacterController::new(CharacterConfig::default()));
+
+        physics_step_system(&mut world);
+        pickup_system(&mut world);
+        assert_eq!(world.resource::<Score>().0, 0);
+        assert!(world.is_alive(coin));
+
+        let handle = world.get::<PhysicsBody>(player).unwrap().0;
+        world
+            .resource_mut::<PhysicsWorld>()
+            .body_mut(handle)
+            .unwrap()
+            .position = vec2(0.1, 0.0);
+        physics_step_system(&mut world);
+        pickup_system(&mut world);
+        assert_eq!(world.resource::<Score>().0, 3);
+        assert!(!world.is_alive(coin));
+        assert!(!world.is_alive(child));
+        assert!(world.is_alive(zone));
+        assert!(world.is_alive(crate_box));
+        // 剛体も消えているので、次のステップで拾い直すことはない
+        assert_eq!(world.resource::<PhysicsWorld>().body_count(), 3);
+        physics_step_system(&mut world);
+        pickup_system(&mut world);
+        assert_eq!(world.resource::<Score>().0, 3);
+    }
+}
@@ -0,0 +1,190 @@
+use std::cell::Cell;
+use std::os::raw::c_void;
+
+use gl::types::{GLenum, GLint};
+
+use image::RgbaImage;
+
+use crate::assets::{Asset, Assets, AsyncAsset};
+
+#[allow(dead_code)]
+pub struct Texture2D {
+    pub id: u32,
+    pub width: u32,
+    pub height: u32,
+    filter: Cell<(GLenum, GLenum)>, // (縮小, 拡大) 読み込み直したときに引き継ぐ
+}
+
+#[allow(dead_code)]
+impl Texture2D {
+    pub fn from_file(path: &str) -> Result<Texture2D, String> {
+        // 画像ファイルを読み込み、RGBA8形式に変換してからGPUへ転送する
+        let image = image::open(path)
+            .map_err(|e| format!("failed to load texture: {}: {}", path, e))?
+            .to_rgba8();
+        let (width, height) = image.dimensions();
+        Ok(Texture2D::from_rgba(width, height, image.as_raw()))
+    }
+
+    // 画像ファイルの中身 (PNGやJPEG) から作る
+    pub fn from_bytes(bytes: &[u8], name: &str) -> Result<Texture2D, String> {
+        let image = decode_image(bytes, name)?;
+        let (width, height) = image.dimensions();
+        Ok(Texture2D::from_rgba(width, height, image.as_raw()))
+    }
+
+    pub fn from_rgba(width: u32, height: u32, pixels: &[u8]) -> Texture2D {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id); // GPU上にテクスチャ用のメモリを1つ確保
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,                 // ミップマップのレベル
+                gl::RGBA as GLint, // GPU上での画素の形式
+                width as i32,      // 幅
+                height as i32,     // 高さ
+                0,                 // 常に0
+                gl::RGBA,          // 転送元の画素の形式
+                gl::UNSIGNED_BYTE, // 転送元の画素のデータ型
+                pixels.as_ptr() as *const c_void,
+            );
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR_MIPMAP_LINEAR as GLint,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Texture2D {
+            id,
+            width,
+            height,
+            filter: Cell::new((gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR)),
+        }
+    }
+
+    // 中身のないテクスチャ (描画先にする)
+    // ミップマップを作らず、端の外側を読んでも繰り返さない
+    pub fn empty(width: u32, height: u32) -> Texture2D {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as GLint,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Texture2D {
+            id,
+            width,
+            height,
+            filter: Cell::new((gl::LINEAR, gl::LINEAR)),
+        }
+    }
+
+    // 同じ大きさの画像で中身を置き換える (フォントのアトラスのように後から書き足すもの向け)
+    pub fn update_rgba(&self, pixels: &[u8]) {
+        assert_eq!(pixels.len(), (self.width * self.height * 4) as usize);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const c_void,
+            );
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    // ドット絵のように拡大時にぼかしたくない場合は NEAREST を指定する
+    pub fn set_filter(&self, min_filter: GLenum, mag_filter: GLenum) {
+        self.filter.set((min_filter, mag_filter));
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    pub unsafe fn bind(&self, unit: u32) {
+        gl::ActiveTexture(gl::TEXTURE0 + unit); // 使用するテクスチャユニットを選択
+        gl::BindTexture(gl::TEXTURE_2D, self.id);
+    }
+}
+
+impl Asset for Texture2D {
+    fn load(assets: &Assets, path: &str) -> Result<Texture2D, String> {
+        Texture2D::from_bytes(&assets.read(path)?, path)
+    }
+
+    fn on_reload(&self, previous: &Texture2D) {
+        let (min_filter, mag_filter) = previous.filter.get();
+        self.set_filter(min_filter, mag_filter);
+    }
+}
+
+// 画像のデコードはバックグラウンドで行い、GPUへの転送だけメインスレッドで行う
+impl AsyncAsset for Texture2D {
+    type Data = RgbaImage;
+
+    fn decode(bytes: Vec<u8>, path: &str) -> Result<RgbaImage, String> {
+        decode_image(&bytes, path)
+    }
+
+    fn create(image: RgbaImage, _path: &str) -> Result<Texture2D, String> {
+        let (width, height) = image.dimensions();
+        Ok(Texture2D::from_rgba(width, height, image.as_raw()))
+    }
+}
+
+fn decode_image(bytes: &[u8], name: &str) -> Result<RgbaImage, String> {
+    Ok(image::load_from_memory(bytes)
+        .map_err(|e| format!("failed to load texture: {}: {}", name, e))?
+        .to_rgba8())
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
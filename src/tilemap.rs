@@ -0,0 +1,15 @@
+mod collider;
+mod json;
+mod map;
+mod render;
+mod tmx;
+
+#[allow(unused_imports)]
+pub use collider::TileRect;
+#[allow(unused_imports)]
+pub use map::{
+    Layer, MapObject, ObjectLayer, ObjectShape, Properties, PropertyValue, Tile, TileInfo,
+    TileLayer, TiledMap, Tileset, FLIPPED_DIAGONALLY, FLIPPED_HORIZONTALLY, FLIPPED_VERTICALLY,
+};
+#[allow(unused_imports)]
+pub use render::{build_chunk_geometry, tilemap_render_system, ChunkGeometry, Tilemap, CHUNK_SIZE};
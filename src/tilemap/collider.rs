@@ -0,0 +1,249 @@
+use cgmath::vec2;
+
+use super::map::{Layer, TiledMap};
+use crate::physics::{BodyHandle, Collider, PhysicsWorld, RigidBody, Shape};
+use crate::transform::Transform2D;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// マップ上の矩形 (タイル単位、Tiledと同じく左上が原点)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[allow(dead_code)]
+impl TiledMap {
+    // property が true のタイルがあるセル
+    // タイルセットのタイルのプロパティか、レイヤーのプロパティで指定する
+    // 見えないレイヤーも対象にするので、当たり判定だけのレイヤーを作れる
+    pub fn flagged_cells(&self, property: &str) -> Vec<bool> {
+        let mut cells = vec![false; (self.width * self.height) as usize];
+        for layer in &self.layers {
+            let layer = match layer {
+                Layer::Tiles(layer) => layer,
+                _ => continue,
+            };
+            let whole_layer = layer
+                .properties
+                .get(property)
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            for y in 0..layer.height.min(self.height) {
+                for x in 0..layer.width.min(self.width) {
+                    let tile = layer.get(x, y);
+                    if tile.is_empty() {
+                        continue;
+                    }
+                    let flagged = whole_layer
+                        || self
+                            .tile_property(tile, property)
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                    if flagged {
+                        cells[(y * self.width + x) as usize] = true;
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    // 印の付いたセルを、なるべく少ない数の矩形にまとめる
+    // 横に並んだセルをつなげてから、同じ幅のものを下へ伸ばしていく
+    pub fn merge_cells(&self, cells: &[bool]) -> Vec<TileRect> {
+        let (width, height) = (self.width, self.height);
+        let mut used = vec![false; cells.len()];
+        let free = |used: &[bool], x: u32, y: u32| {
+            let index = (y * width + x) as usize;
+            cells[index] && !used[index]
+        };
+        let mut rects = Vec::new();
+        for y in 0..height {
+            let mut x = 0;
+            while x < width {
+                if !free(&used, x, y) {
+                    x += 1;
+                    continue;
+                }
+                let mut rect_width = 1;
+                while x + rect_width < width && free(&used, x + rect_width, y) {
+                    rect_width += 1;
+                }
+                let mut rect_height = 1;
+                while y + rect_height < height
+                    && (x..x + rect_width).all(|cx| free(&used, cx, y + rect_height))
+                {
+                    rect_height += 1;
+                }
+                for cy in y..y + rect_height {
+                    for cx in x..x + rect_width {
+                        used[(cy * width + cx) as usize] = true;
+                    }
+                }
+                rects.push(TileRect {
+                    x,
+                    y,
+                    width: rect_width,
+                    height: rect_height,
+                });
+                x += rect_width;
+            }
+        }
+        rects
+    }
+
+    // property が true のタイルから、動かない剛体を作る
+    // template の形状以外の設定 (摩擦やレイヤーなど) をそのまま使う
+    // マップの位置と拡大率は transform から求める (回転は考慮しない)
+    pub fn build_colliders(
+        &self,
+        physics: &mut PhysicsWorld,
+        transform: &Transform2D,
+        property: &str,
+        template: &Collider,
+    ) -> Vec<BodyHandle> {
+        let scale = transform.scale();
+        let to_world = |p: Vector2| {
+            let local = p - transform.pivot();
+            transform.position() + vec2(local.x * scale.x, local.y * scale.y)
+        };
+        let cells = self.flagged_cells(property);
+        self.merge_cells(&cells)
+            .into_iter()
+            .map(|rect| {
+                // y軸を上向きに直す
+                let min = to_world(vec2(
+                    rect.x as f32,
+                    (self.height - rect.y - rect.height) as f32,
+                ));
+                let max = to_world(vec2(
+                    (rect.x + rect.width) as f32,
+                    (self.height - rect.y) as f32,
+                ));
+                let mut collider = template.clone();
+                collider.shape = Shape::Aabb {
+                    half_extents: (max - min) * 0.5,
+                };
+                physics.add_body(RigidBody::fixed((min + max) * 0.5, collider))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map orientation="orthogonal" width="5" height="4" tilewidth="16" tileheight="16">
+ <tileset firstgid="1" name="tiles" tilewidth="16" tileheight="16" tilecount="3" columns="3">
+  <tile id="0">
+   <properties>
+    <property name="solid" type="bool" value="true"/>
+   </properties>
+  </tile>
+  <tile id="1">
+   <properties>
+    <property name="solid" type="bool" value="false"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer name="ground" width="5" height="4">
+  <data encoding="csv">
+1,1,2,3,0,
+0,0,0,0,0,
+0,0,0,0,0,
+2147483649,1,1,1,1
+</data>
+ </layer>
+ <layer name="walls" width="5" height="4" visible="0">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+  <data encoding="csv">
+0,0,0,0,3,
+0,0,0,0,2,
+0,0,0,0,0,
+0,0,0,0,0
+</data>
+ </layer>
+</map>
+"#;
+
+    fn map() -> TiledMap {
+        TiledMap::load_with("map.tmx", &|_| Ok(MAP.to_string())).unwrap()
+    }
+
+    // 文字列の図からセルの並びを作る ('#' が印の付いたセル)
+    fn cells(rows: &[&str]) -> Vec<bool> {
+        rows.iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect()
+    }
+
+    #[test]
+    fn flagged_cells_use_tile_and_layer_properties() {
+        let map = map();
+        // タイルのプロパティ (反転していても同じタイル) と、見えないレイヤー全体のプロパティ
+        assert_eq!(
+            map.flagged_cells("solid"),
+            cells(&["##..#", "....#", ".....", "#####"])
+        );
+        assert_eq!(map.flagged_cells("one_way"), vec![false; 20]);
+    }
+
+    #[test]
+    fn merge_cells_into_rects() {
+        let map = map();
+        let cells = cells(&["###.#", "###.#", ".##..", "#####"]);
+        let rects = map.merge_cells(&cells);
+        let rect = |x, y, width, height| TileRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(
+            rects,
+            vec![
+                rect(0, 0, 3, 2),
+                rect(4, 0, 1, 2),
+                rect(1, 2, 2, 2),
+                rect(0, 3, 1, 1),
+                rect(3, 3, 2, 1),
+            ]
+        );
+
+        // 印の付いたセルがちょうど1回ずつ覆われる
+        let mut covered = vec![0; cells.len()];
+        for rect in &rects {
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    covered[(y * map.width + x) as usize] += 1;
+                }
+            }
+        }
+        let expected: Vec<_> = cells.iter().map(|&c| c as i32).collect();
+        assert_eq!(covered, expected);
+    }
+
+    #[test]
+    fn merge_empty_and_full_cells() {
+        let map = map();
+        assert!(map.merge_cells(&[false; 20]).is_empty());
+        assert_eq!(
+            map.merge_cells(&[true; 20]),
+            vec![TileRect {
+                x: 0,
+                y: 0,
+                width: 5,
+                height: 4,
+            }]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cgmath::vec2;
+use serde_json::Value;
+
+use super::map::{
+    check_tile_count, decode_base64_tiles, Layer, MapObject, ObjectLayer, ObjectShape, Properties,
+    PropertyValue, Tile, TileInfo, TileLayer, TiledMap, Tileset,
+};
+use super::tmx::resolve;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// TiledのJSON形式 (.json / .tmj) のマップを読み込む
+pub fn load_map(
+    path: &str,
+    read: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<TiledMap, String> {
+    let root = read_json(path, read)?;
+    parse_map(&root, base_dir(path), read).map_err(|e| format!("{}: {}", path, e))
+}
+
+// 外部タイルセット (.json / .tsj)
+pub fn load_tileset(
+    path: &str,
+    first_gid: u32,
+    read: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<Tileset, String> {
+    let root = read_json(path, read)?;
+    let mut tileset =
+        parse_tileset(&root, first_gid, base_dir(path)).map_err(|e| format!("{}: {}", path, e))?;
+    tileset.source = Some(path.to_string());
+    Ok(tileset)
+}
+
+fn read_json(path: &str, read: &dyn Fn(&str) -> Result<String, String>) -> Result<Value, String> {
+    let source = read(path)?;
+    serde_json::from_str(&source).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn base_dir(path: &str) -> &Path {
+    Path::new(path).parent().unwrap_or_else(|| Path::new(""))
+}
+
+fn string(value: &Value, key: &str) -> String {
+    value[key].as_str().unwrap_or("").to_string()
+}
+
+fn uint(value: &Value, key: &str) -> Result<u32, String> {
+    value[key]
+        .as_u64()
+        .map(|v| v as u32)
+        .ok_or_else(|| format!("missing or invalid field: {}", key))
+}
+
+fn float(value: &Value, key: &str, default: f32) -> f32 {
+    value[key].as_f64().map(|v| v as f32).unwrap_or(default)
+}
+
+fn parse_properties(value: &Value) -> Result<Properties, String> {
+    let mut properties = Properties::new();
+    for property in value["properties"].as_array().into_iter().flatten() {
+        let property_type = property["type"].as_str().unwrap_or("string");
+        if property_type == "class" {
+            continue; // 入れ子のプロパティは扱わない
+        }
+        // 値は型に応じたJSONの値で書かれているので、いったん文字列にしてTMXと同じ処理を通す
+        let value = match &property["value"] {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        properties.insert(
+            string(property, "name"),
+            PropertyValue::parse(property_type, &value)?,
+        );
+    }
+    Ok(properties)
+}
+
+fn parse_map(
+    root: &Value,
+    dir: &Path,
+    read: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<TiledMap, String> {
+    if let Some(orientation) = root["orientation"].as_str() {
+        if orientation != "orthogonal" {
+            return Err(format!("unsupported orientation: {}", orientation));
+        }
+    }
+    if root["infinite"].as_bool() == Some(true) {
+        return Err("infinite maps are not supported".to_string());
+    }
+
+    let mut tilesets = Vec::new();
+    for value in root["tilesets"].as_array().into_iter().flatten() {
+        let first_gid = uint(value, "firstgid")?;
+        let tileset = match value["source"].as_str() {
+            Some(source) => {
+                let path = resolve(dir, source);
+                if path.ends_with(".tsx") {
+                    super::tmx::load_tileset(&path, first_gid, read)?
+                } else {
+                    load_tileset(&path, first_gid, read)?
+                }
+            }
+            None => parse_tileset(value, first_gid, dir)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    parse_layers(&root["layers"], &mut layers)?;
+
+    Ok(TiledMap {
+        width: uint(root, "width")?,
+        height: uint(root, "height")?,
+        tile_width: uint(root, "tilewidth")?,
+        tile_height: uint(root, "tileheight")?,
+        properties: parse_properties(root)?,
+        tilesets,
+        layers,
+    })
+}
+
+fn parse_tileset(value: &Value, first_gid: u32, dir: &Path) -> Result<Tileset, String> {
+    let mut tiles = HashMap::new();
+    for tile in value["tiles"].as_array().into_iter().flatten() {
+        let class = tile["class"]
+            .as_str()
+            .or_else(|| tile["type"].as_str())
+            .unwrap_or("")
+            .to_string();
+        tiles.insert(
+            uint(tile, "id")?,
+            TileInfo {
+                class,
+                properties: parse_properties(tile)?,
+            },
+        );
+    }
+    Ok(Tileset {
+        name: string(value, "name"),
+        first_gid,
+        tile_width: uint(value, "tilewidth")?,
+        tile_height: uint(value, "tileheight")?,
+        tile_count: uint(value, "tilecount").unwrap_or(0),
+        columns: uint(value, "columns").unwrap_or(0),
+        spacing: uint(value, "spacing").unwrap_or(0),
+        margin: uint(value, "margin").unwrap_or(0),
+        image: value["image"].as_str().map(|image| resolve(dir, image)),
+        image_width: uint(value, "imagewidth").unwrap_or(0),
+        image_height: uint(value, "imageheight").unwrap_or(0),
+        properties: parse_properties(value)?,
+        tiles,
+        source: None,
+    })
+}
+
+// グループレイヤーの中身は同じ並びに展開する
+fn parse_layers(values: &Value, layers: &mut Vec<Layer>) -> Result<(), String> {
+    for value in values.as_array().into_iter().flatten() {
+        match value["type"].as_str() {
+            Some("tilelayer") => layers.push(Layer::Tiles(parse_tile_layer(value)?)),
+            Some("objectgroup") => layers.push(Layer::Objects(parse_object_layer(value)?)),
+            Some("group") => parse_layers(&value["layers"], layers)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_tile_layer(value: &Value) -> Result<TileLayer, String> {
+    let name = string(value, "name");
+    let width = uint(value, "width")?;
+    let height = uint(value, "height")?;
+    let tiles = match &value["data"] {
+        Value::Array(data) => data
+            .iter()
+            .map(|gid| {
+                gid.as_u64()
+                    .map(|gid| Tile(gid as u32))
+                    .ok_or_else(|| format!("invalid tile data: {}", gid))
+            })
+            .collect::<Result<_, _>>()?,
+        Value::String(data) => decode_base64_tiles(data, value["compression"].as_str())?,
+        _ => return Err(format!("layer {} has no data", name)),
+    };
+    check_tile_count(&name, &tiles, width, height)?;
+    Ok(TileLayer {
+        width,
+        height,
+        visible: value["visible"].as_bool().unwrap_or(true),
+        opacity: float(value, "opacity", 1.0),
+        offset: vec2(float(value, "offsetx", 0.0), float(value, "offsety", 0.0)),
+        properties: parse_properties(value)?,
+        tiles,
+        name,
+    })
+}
+
+fn parse_object_layer(value: &Value) -> Result<ObjectLayer, String> {
+    let objects = value["objects"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(parse_object)
+        .collect::<Result<_, _>>()?;
+    Ok(ObjectLayer {
+        name: string(value, "name"),
+        visible: value["visible"].as_bool().unwrap_or(true),
+        properties: parse_properties(value)?,
+        objects,
+    })
+}
+
+fn parse_points(value: &Value) -> Vec<Vector2> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|point| vec2(float(point, "x", 0.0), float(point, "y", 0.0)))
+        .collect()
+}
+
+fn parse_object(value: &Value) -> Result<MapObject, String> {
+    let width = float(value, "width", 0.0);
+    let height = float(value, "height", 0.0);
+    let shape = if let Some(gid) = value["gid"].as_u64() {
+        ObjectShape::Tile {
+            tile: Tile(gid as u32),
+            width,
+            height,
+        }
+    } else if value["ellipse"].as_bool() == Some(true) {
+        ObjectShape::Ellipse { width, height }
+    } else if value["point"].as_bool() == Some(true) {
+        ObjectShape::Point
+    } else if value["polygon"].is_array() {
+        ObjectShape::Polygon(parse_points(&value["polygon"]))
+    } else if value["polyline"].is_array() {
+        ObjectShape::Polyline(parse_points(&value["polyline"]))
+    } else {
+        ObjectShape::Rect { width, height }
+    };
+    Ok(MapObject {
+        id: uint(value, "id").unwrap_or(0),
+        name: string(value, "name"),
+        class: value["class"]
+            .as_str()
+            .or_else(|| value["type"].as_str())
+            .unwrap_or("")
+            .to_string(),
+        position: vec2(float(value, "x", 0.0), float(value, "y", 0.0)),
+        rotation: float(value, "rotation", 0.0),
+        visible: value["visible"].as_bool().unwrap_or(true),
+        shape,
+        properties: parse_properties(value)?,
+    })
+}
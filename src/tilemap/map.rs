@@ -0,0 +1,694 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use cgmath::vec2;
+
+use super::{json, tmx};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// GIDの上位ビットはタイルの反転を表す
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const ROTATED_HEXAGONAL_120: u32 = 0x1000_0000;
+const FLAG_MASK: u32 =
+    FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL_120;
+
+// Tiledのカスタムプロパティの値
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Color([u8; 4]), // RGBA
+    File(String),
+    Object(u32), // オブジェクトのID
+}
+
+pub type Properties = BTreeMap<String, PropertyValue>;
+
+#[allow(dead_code)]
+impl PropertyValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            PropertyValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            PropertyValue::Int(v) => Some(v),
+            PropertyValue::Object(v) => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match *self {
+            PropertyValue::Float(v) => Some(v),
+            PropertyValue::Int(v) => Some(v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropertyValue::String(v) | PropertyValue::File(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // 型の名前と文字列の値から作る (TMXとJSONで共通)
+    pub(super) fn parse(property_type: &str, value: &str) -> Result<PropertyValue, String> {
+        let error = || format!("invalid {} property value: {}", property_type, value);
+        Ok(match property_type {
+            "bool" => PropertyValue::Bool(value == "true"),
+            "int" => PropertyValue::Int(value.parse().map_err(|_| error())?),
+            "float" => PropertyValue::Float(value.parse().map_err(|_| error())?),
+            "color" => PropertyValue::Color(parse_color(value).ok_or_else(error)?),
+            "file" => PropertyValue::File(value.to_string()),
+            "object" => PropertyValue::Object(value.parse().map_err(|_| error())?),
+            _ => PropertyValue::String(value.to_string()),
+        })
+    }
+}
+
+// "#AARRGGBB" または "#RRGGBB"
+fn parse_color(value: &str) -> Option<[u8; 4]> {
+    let hex = value.trim_start_matches('#');
+    let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    match hex.len() {
+        6 => Some([byte(0)?, byte(2)?, byte(4)?, 255]),
+        8 => Some([byte(2)?, byte(4)?, byte(6)?, byte(0)?]),
+        _ => None,
+    }
+}
+
+// レイヤーに置かれたタイル (0は何も置かれていない)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tile(pub u32);
+
+#[allow(dead_code)]
+impl Tile {
+    pub const EMPTY: Tile = Tile(0);
+
+    // 反転のフラグを除いたGID
+    pub fn gid(&self) -> u32 {
+        self.0 & !FLAG_MASK
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gid() == 0
+    }
+
+    pub fn flipped_horizontally(&self) -> bool {
+        self.0 & FLIPPED_HORIZONTALLY != 0
+    }
+
+    pub fn flipped_vertically(&self) -> bool {
+        self.0 & FLIPPED_VERTICALLY != 0
+    }
+
+    pub fn flipped_diagonally(&self) -> bool {
+        self.0 & FLIPPED_DIAGONALLY != 0
+    }
+}
+
+// タイルセットの中の1枚のタイルの情報 (プロパティが設定されたものだけ)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TileInfo {
+    pub class: String,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tileset {
+    pub name: String,
+    pub first_gid: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    pub image: Option<String>, // 実行時のカレントディレクトリからのパス
+    pub image_width: u32,
+    pub image_height: u32,
+    pub properties: Properties,
+    pub tiles: HashMap<u32, TileInfo>, // タイルセット内のID -> 情報
+    pub source: Option<String>, // 外部タイルセットのファイル (マップに埋め込まれていれば None)
+}
+
+#[allow(dead_code)]
+impl Tileset {
+    pub fn contains(&self, gid: u32) -> bool {
+        gid >= self.first_gid && gid < self.first_gid + self.tile_count
+    }
+
+    pub fn tile(&self, local_id: u32) -> Option<&TileInfo> {
+        self.tiles.get(&local_id)
+    }
+
+    // 画像の中のタイルの範囲をテクスチャ座標で表す ((左, 上), (右, 下))
+    pub fn uv(&self, local_id: u32) -> (Vector2, Vector2) {
+        let columns = self.columns.max(1);
+        let x = self.margin + (local_id % columns) * (self.tile_width + self.spacing);
+        let y = self.margin + (local_id / columns) * (self.tile_height + self.spacing);
+        let size = vec2(
+            self.image_width.max(1) as f32,
+            self.image_height.max(1) as f32,
+        );
+        (
+            vec2(x as f32 / size.x, y as f32 / size.y),
+            vec2(
+                (x + self.tile_width) as f32 / size.x,
+                (y + self.tile_height) as f32 / size.y,
+            ),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub visible: bool,
+    pub opacity: f32,
+    pub offset: Vector2, // ピクセル単位 (Tiledの座標系)
+    pub properties: Properties,
+    pub tiles: Vec<Tile>, // 左上から行ごとに並ぶ
+}
+
+#[allow(dead_code)]
+impl TileLayer {
+    pub fn get(&self, x: u32, y: u32) -> Tile {
+        if x >= self.width || y >= self.height {
+            return Tile::EMPTY;
+        }
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, tile: Tile) {
+        if x < self.width && y < self.height {
+            self.tiles[(y * self.width + x) as usize] = tile;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectShape {
+    Rect { width: f32, height: f32 },
+    Ellipse { width: f32, height: f32 },
+    Point,
+    Polygon(Vec<Vector2>),  // オブジェクトの位置からの相対座標
+    Polyline(Vec<Vector2>), // 同上
+    Tile { tile: Tile, width: f32, height: f32 },
+}
+
+// オブジェクトレイヤーに置かれたもの (座標はTiledと同じピクセル単位でy軸が下向き)
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub position: Vector2,
+    pub rotation: f32, // 度 (時計回り)
+    pub visible: bool,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub visible: bool,
+    pub properties: Properties,
+    pub objects: Vec<MapObject>,
+}
+
+#[allow(dead_code)]
+impl ObjectLayer {
+    pub fn object(&self, name: &str) -> Option<&MapObject> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+}
+
+// グループレイヤーは読み込むときに展開するので、奥から手前へ描画する順に並ぶ
+#[derive(Clone, Debug, PartialEq)]
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+// Tiledで作った正方向 (orthogonal) のマップ
+// ワールド座標ではタイル1枚を1単位とし、y軸を上向きにしてマップの左下を原点とする
+#[derive(Clone, Debug, PartialEq)]
+pub struct TiledMap {
+    pub width: u32, // タイルの数
+    pub height: u32,
+    pub tile_width: u32, // ピクセル
+    pub tile_height: u32,
+    pub properties: Properties,
+    pub tilesets: Vec<Tileset>,
+    pub layers: Vec<Layer>,
+}
+
+#[allow(dead_code)]
+impl TiledMap {
+    // 拡張子で形式を判断して読み込む (.tmx または .json / .tmj)
+    pub fn load(path: &str) -> Result<TiledMap, String> {
+        TiledMap::load_with(path, &|path| {
+            fs::read_to_string(path).map_err(|e| format!("failed to open file: {}: {}", path, e))
+        })
+    }
+
+    // マップと外部タイルセットのファイルを read で読む (アーカイブから読み込むときなど)
+    pub fn load_with(
+        path: &str,
+        read: &dyn Fn(&str) -> Result<String, String>,
+    ) -> Result<TiledMap, String> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("tmx") => tmx::load_map(path, read),
+            Some("json") | Some("tmj") => json::load_map(path, read),
+            _ => Err(format!("unsupported map format: {}", path)),
+        }
+    }
+
+    // GIDが属するタイルセットとタイルセット内のID
+    pub fn tileset_for(&self, gid: u32) -> Option<(usize, u32)> {
+        // first_gid の大きい順に探す (tile_count が0の古いファイルにも対応するため)
+        self.tilesets
+            .iter()
+            .enumerate()
+            .filter(|(_, tileset)| tileset.first_gid <= gid)
+            .max_by_key(|(_, tileset)| tileset.first_gid)
+            .map(|(index, tileset)| (index, gid - tileset.first_gid))
+    }
+
+    pub fn tile_info(&self, tile: Tile) -> Option<&TileInfo> {
+        let (tileset, local_id) = self.tileset_for(tile.gid())?;
+        self.tilesets[tileset].tile(local_id)
+    }
+
+    pub fn tile_property(&self, tile: Tile, name: &str) -> Option<&PropertyValue> {
+        self.tile_info(tile)?.properties.get(name)
+    }
+
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TileLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Tiles(layer) => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Objects(layer) => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn tile_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.tile_layers().find(|layer| layer.name == name)
+    }
+
+    pub fn object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.object_layers().find(|layer| layer.name == name)
+    }
+
+    // Tiledのピクセル座標 (左上が原点、y軸が下向き) をワールド座標に変換する
+    pub fn to_world(&self, pixel: Vector2) -> Vector2 {
+        vec2(
+            pixel.x / self.tile_width as f32,
+            self.height as f32 - pixel.y / self.tile_height as f32,
+        )
+    }
+}
+
+// base64 (と圧縮) で格納されたタイルの並びを読む (TMXとJSONで共通)
+pub(super) fn decode_base64_tiles(
+    data: &str,
+    compression: Option<&str>,
+) -> Result<Vec<Tile>, String> {
+    use base64::Engine;
+    use std::io::Read;
+
+    let text: String = data.split_whitespace().collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|e| format!("invalid base64 tile data: {}", e))?;
+    let bytes = match compression {
+        None | Some("") => bytes,
+        Some("zlib") => {
+            let mut out = Vec::new();
+            flate2::read::ZlibDecoder::new(&bytes[..])
+                .read_to_end(&mut out)
+                .map_err(|e| format!("failed to decompress tile data: {}", e))?;
+            out
+        }
+        Some("gzip") => {
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(&bytes[..])
+                .read_to_end(&mut out)
+                .map_err(|e| format!("failed to decompress tile data: {}", e))?;
+            out
+        }
+        Some(other) => return Err(format!("unsupported tile data compression: {}", other)),
+    };
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| Tile(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+        .collect())
+}
+
+// CSVで格納されたタイルの並びを読む
+pub(super) fn decode_csv_tiles(data: &str) -> Result<Vec<Tile>, String> {
+    data.split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse()
+                .map(Tile)
+                .map_err(|_| format!("invalid csv tile data: {}", v))
+        })
+        .collect()
+}
+
+// タイルの数がレイヤーの大きさと一致しているか確かめる
+pub(super) fn check_tile_count(
+    name: &str,
+    tiles: &[Tile],
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    if tiles.len() != (width * height) as usize {
+        return Err(format!(
+            "layer {}: expected {} tiles, found {}",
+            name,
+            width * height,
+            tiles.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use base64::Engine;
+
+    fn to_bytes(gids: &[u32]) -> Vec<u8> {
+        gids.iter().flat_map(|gid| gid.to_le_bytes()).collect()
+    }
+
+    fn encode(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    fn zlib(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn tiles(gids: &[u32]) -> Vec<Tile> {
+        gids.iter().copied().map(Tile).collect()
+    }
+
+    const GIDS: [u32; 6] = [0, 1, 2, FLIPPED_HORIZONTALLY | 3, 0, 17];
+
+    #[test]
+    fn decode_uncompressed_base64() {
+        let data = encode(&to_bytes(&GIDS));
+        assert_eq!(decode_base64_tiles(&data, None).unwrap(), tiles(&GIDS));
+        assert_eq!(decode_base64_tiles(&data, Some("")).unwrap(), tiles(&GIDS));
+    }
+
+    #[test]
+    fn decode_zlib_and_gzip() {
+        let bytes = to_bytes(&GIDS);
+        // TMXではデータの前後や途中に改行と字下げが入る
+        let data = format!("\n   {}\n  ", encode(&zlib(&bytes)));
+        assert_eq!(
+            decode_base64_tiles(&data, Some("zlib")).unwrap(),
+            tiles(&GIDS)
+        );
+        let data = encode(&gzip(&bytes));
+        assert_eq!(
+            decode_base64_tiles(&data, Some("gzip")).unwrap(),
+            tiles(&GIDS)
+        );
+    }
+
+    #[test]
+    fn decode_base64_errors() {
+        let data = encode(&to_bytes(&GIDS));
+        assert_eq!(
+            decode_base64_tiles(&data, Some("zstd")).unwrap_err(),
+            "unsupported tile data compression: zstd"
+        );
+        assert!(decode_base64_tiles("not base64!", None)
+            .unwrap_err()
+            .starts_with("invalid base64 tile data"));
+        assert!(decode_base64_tiles(&data, Some("zlib"))
+            .unwrap_err()
+            .starts_with("failed to decompress tile data"));
+    }
+
+    #[test]
+    fn decode_csv() {
+        let data = "\n0,1,2,\n2147483651,0,17\n";
+        assert_eq!(decode_csv_tiles(data).unwrap(), tiles(&GIDS));
+        assert_eq!(decode_csv_tiles(" 4 , 5 ").unwrap(), tiles(&[4, 5]));
+        assert_eq!(
+            decode_csv_tiles("1,x,3").unwrap_err(),
+            "invalid csv tile data: x"
+        );
+    }
+
+    #[test]
+    fn tile_flags_are_masked_from_the_gid() {
+        let tile = Tile(FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY | 5);
+        assert_eq!(tile.gid(), 5);
+        assert!(tile.flipped_horizontally());
+        assert!(!tile.flipped_vertically());
+        assert!(tile.flipped_diagonally());
+
+        let tile = Tile(FLIPPED_VERTICALLY | ROTATED_HEXAGONAL_120 | 7);
+        assert_eq!(tile.gid(), 7);
+        assert!(!tile.flipped_horizontally());
+        assert!(tile.flipped_vertically());
+
+        // フラグだけのタイルは何も置かれていない
+        assert!(Tile(FLIPPED_HORIZONTALLY).is_empty());
+        assert!(Tile::EMPTY.is_empty());
+        assert!(!Tile(1).is_empty());
+    }
+
+    const TILESET_TSX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="tiles" tilewidth="16" tileheight="16" tilecount="4" columns="4">
+ <image source="../texture/tiles.png" width="64" height="16"/>
+ <tile id="0" type="grass">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+ </tile>
+</tileset>
+"#;
+
+    const MAP_TMX: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="gravity" type="float" value="-9.5"/>
+  <property name="music" type="file" value="../sound/bgm.ogg"/>
+  <property name="title" value="テスト"/>
+ </properties>
+ <tileset firstgid="1" source="tiles.tsx"/>
+ <tileset firstgid="5" name="extra" tilewidth="16" tileheight="16" tilecount="2" columns="2" spacing="1" margin="2">
+  <image source="extra.png" width="36" height="20"/>
+  <properties>
+   <property name="tint" type="color" value="#80ff0000"/>
+  </properties>
+  <tile id="1" class="spike">
+   <properties>
+    <property name="damage" type="int" value="3"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+  <data encoding="csv">
+0,0,6,
+1,2147483650,1
+</data>
+ </layer>
+ <group id="2" name="front">
+  <layer id="3" name="decoration" width="3" height="2" visible="0" opacity="0.5" offsetx="4" offsety="-2">
+   <data encoding="base64" compression="zlib">
+    ZLIB_DATA
+   </data>
+  </layer>
+  <objectgroup id="4" name="objects">
+   <object id="1" name="player_spawn" x="8" y="24">
+    <point/>
+   </object>
+   <object id="2" name="door" type="trigger" x="32" y="0" width="16" height="32">
+    <properties>
+     <property name="target" type="object" value="1"/>
+    </properties>
+   </object>
+   <object id="3" x="0" y="0" width="8" height="8" rotation="45">
+    <ellipse/>
+   </object>
+   <object id="4" x="16" y="16">
+    <polygon points="0,0 16,0 8,-8"/>
+   </object>
+   <object id="5" x="0" y="32" visible="0">
+    <polyline points="0,0 48,0"/>
+   </object>
+   <object id="6" gid="1073741830" x="16" y="32" width="16" height="16"/>
+  </objectgroup>
+ </group>
+</map>
+"##;
+
+    const MAP_JSON: &str = r##"{
+ "type": "map", "version": "1.10", "orientation": "orthogonal", "infinite": false,
+ "width": 3, "height": 2, "tilewidth": 16, "tileheight": 16,
+ "properties": [
+  {"name": "gravity", "type": "float", "value": -9.5},
+  {"name": "music", "type": "file", "value": "../sound/bgm.ogg"},
+  {"name": "title", "type": "string", "value": "テスト"}
+ ],
+ "tilesets": [
+  {"firstgid": 1, "source": "tiles.tsx"},
+  {"firstgid": 5, "name": "extra", "tilewidth": 16, "tileheight": 16, "tilecount": 2,
+   "columns": 2, "spacing": 1, "margin": 2,
+   "image": "extra.png", "imagewidth": 36, "imageheight": 20,
+   "properties": [{"name": "tint", "type": "color", "value": "#80ff0000"}],
+   "tiles": [
+    {"id": 1, "type": "spike", "properties": [{"name": "damage", "type": "int", "value": 3}]}
+   ]}
+ ],
+ "layers": [
+  {"id": 1, "type": "tilelayer", "name": "ground", "width": 3, "height": 2,
+   "opacity": 1, "visible": true, "x": 0, "y": 0,
+   "properties": [{"name": "solid", "type": "bool", "value": true}],
+   "data": [0, 0, 6, 1, 2147483650, 1]},
+  {"id": 2, "type": "group", "name": "front", "layers": [
+   {"id": 3, "type": "tilelayer", "name": "decoration", "width": 3, "height": 2,
+    "opacity": 0.5, "visible": false, "offsetx": 4, "offsety": -2,
+    "encoding": "base64", "compression": "gzip", "data": "GZIP_DATA"},
+   {"id": 4, "type": "objectgroup", "name": "objects", "visible": true, "objects": [
+    {"id": 1, "name": "player_spawn", "type": "", "x": 8, "y": 24,
+     "width": 0, "height": 0, "rotation": 0, "visible": true, "point": true},
+    {"id": 2, "name": "door", "type": "trigger", "x": 32, "y": 0,
+     "width": 16, "height": 32, "rotation": 0, "visible": true,
+     "properties": [{"name": "target", "type": "object", "value": 1}]},
+    {"id": 3, "name": "", "x": 0, "y": 0, "width": 8, "height": 8,
+     "rotation": 45, "visible": true, "ellipse": true},
+    {"id": 4, "name": "", "x": 16, "y": 16, "width": 0, "height": 0, "rotation": 0,
+     "visible": true, "polygon": [{"x": 0, "y": 0}, {"x": 16, "y": 0}, {"x": 8, "y": -8}]},
+    {"id": 5, "name": "", "x": 0, "y": 32, "width": 0, "height": 0, "rotation": 0,
+     "visible": false, "polyline": [{"x": 0, "y": 0}, {"x": 48, "y": 0}]},
+    {"id": 6, "name": "", "gid": 1073741830, "x": 16, "y": 32,
+     "width": 16, "height": 16, "rotation": 0, "visible": true}
+   ]}
+  ]}
+ ]
+}"##;
+
+    const DECORATION: [u32; 6] = [5, 0, 0, 0, FLIPPED_DIAGONALLY | 3, 4];
+
+    // 同じ内容のマップをTMXとJSONで用意して、ファイルの代わりに返す
+    fn read(path: &str) -> Result<String, String> {
+        let bytes = to_bytes(&DECORATION);
+        match path {
+            "rsc/map/map.tmx" => Ok(MAP_TMX.replace("ZLIB_DATA", &encode(&zlib(&bytes)))),
+            "rsc/map/map.json" => Ok(MAP_JSON.replace("GZIP_DATA", &encode(&gzip(&bytes)))),
+            "rsc/map/tiles.tsx" => Ok(TILESET_TSX.to_string()),
+            _ => Err(format!("not found: {}", path)),
+        }
+    }
+
+    #[test]
+    fn tmx_and_json_load_the_same_map() {
+        let tmx = TiledMap::load_with("rsc/map/map.tmx", &read).unwrap();
+        let json = TiledMap::load_with("rsc/map/map.json", &read).unwrap();
+        assert_eq!(tmx, json);
+
+        assert_eq!((tmx.width, tmx.height), (3, 2));
+        assert_eq!(tmx.properties["gravity"], PropertyValue::Float(-9.5),);
+        assert_eq!(
+            tmx.properties["title"],
+            PropertyValue::String("テスト".to_string())
+        );
+
+        // 外部タイルセットの画像はマップからではなくタイルセットのファイルからの相対パス
+        let tileset = &tmx.tilesets[0];
+        assert_eq!(tileset.source.as_deref(), Some("rsc/map/tiles.tsx"));
+        assert_eq!(tileset.image.as_deref(), Some("rsc/texture/tiles.png"));
+        let extra = &tmx.tilesets[1];
+        assert_eq!(extra.source, None);
+        assert_eq!(extra.image.as_deref(), Some("rsc/map/extra.png"));
+        assert_eq!(
+            extra.properties["tint"],
+            PropertyValue::Color([255, 0, 0, 128])
+        );
+
+        assert_eq!(tmx.tile_info(Tile(1)).unwrap().class, "grass");
+        assert_eq!(
+            tmx.tile_property(Tile(FLIPPED_HORIZONTALLY | 6), "damage"),
+            Some(&PropertyValue::Int(3))
+        );
+
+        // グループの中のレイヤーは展開される
+        assert_eq!(tmx.layers.len(), 3);
+        let decoration = tmx.tile_layer("decoration").unwrap();
+        assert_eq!(decoration.tiles, tiles(&DECORATION));
+        assert!(!decoration.visible);
+        assert_eq!(decoration.offset, vec2(4.0, -2.0));
+
+        let objects = tmx.object_layer("objects").unwrap();
+        let spawn = objects.object("player_spawn").unwrap();
+        assert_eq!(spawn.shape, ObjectShape::Point);
+        assert_eq!(tmx.to_world(spawn.position), vec2(0.5, 0.5));
+        assert_eq!(
+            objects.objects[5].shape,
+            ObjectShape::Tile {
+                tile: Tile(FLIPPED_VERTICALLY | 6),
+                width: 16.0,
+                height: 16.0,
+            }
+        );
+    }
+
+    #[test]
+    fn load_errors() {
+        assert_eq!(
+            TiledMap::load_with("rsc/map/map.txt", &read).unwrap_err(),
+            "unsupported map format: rsc/map/map.txt"
+        );
+        assert_eq!(
+            TiledMap::load_with("rsc/map/missing.tmx", &read).unwrap_err(),
+            "not found: rsc/map/missing.tmx"
+        );
+    }
+}
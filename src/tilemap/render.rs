@@ -0,0 +1,389 @@
+use std::mem;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use cgmath::{vec2, vec3};
+use gl::types::{GLfloat, GLsizei, GLsizeiptr};
+
+use super::map::{Layer, Tile, TileLayer, TiledMap};
+use crate::ecs::{Read, World, Write};
+use crate::material::{Material, MaterialId, MaterialLibrary, RenderState};
+use crate::renderer::{DrawCommand, Renderer};
+use crate::transform::Transform2D;
+use crate::vertex::Vertex;
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// チャンク1つに入るタイルの数 (縦横)
+pub const CHUNK_SIZE: u32 = 16;
+// 1頂点あたりの要素数 (位置xy、テクスチャ座標uv、不透明度)
+const FLOATS_PER_VERTEX: usize = 5;
+
+const TILE_VERTEX_SHADER: &str = "rsc/shader/tile.vs";
+const TILE_FRAGMENT_SHADER: &str = "rsc/shader/tile.fs";
+
+// チャンク内の同じタイルセットを使うタイルをまとめた頂点データ (GLを使わないので単体で確かめられる)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkGeometry {
+    pub tileset: usize,
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+struct ChunkMesh {
+    tileset: usize,
+    vertex: Rc<Vertex>,
+}
+
+struct Chunk {
+    layer: usize, // map.layers の番号
+    x: u32,       // チャンク単位の位置
+    y: u32,
+    min: Vector2, // マップ上の範囲 (タイル単位、y軸が上向き)
+    max: Vector2,
+    meshes: Vec<ChunkMesh>,
+    dirty: bool,
+}
+
+// Tiledのマップを描画するコンポーネント
+// タイルレイヤーを CHUNK_SIZE ごとのチャンクに分け、画面に映るチャンクだけを描画する
+// タイルを書き換えると、そのチャンクの頂点データだけを次の描画の前に作り直す
+pub struct Tilemap {
+    pub map: TiledMap,
+    pub layer: i32, // 最初のレイヤーを描画するレンダラーのレイヤー (以降のレイヤーは1ずつ増える)
+    materials: Vec<Option<MaterialId>>, // タイルセットごと (画像がないものは None)
+    chunks: Vec<Chunk>,
+    source: Option<String>, // 読み込んだマップファイル (ホットリロード用)
+}
+
+#[allow(dead_code)]
+impl Tilemap {
+    pub fn load(path: &str, materials: &mut MaterialLibrary) -> Result<Tilemap, String> {
+        // タイルセットの画像もマップファイルからの相対パスで探すので、先にルートからのパスにする
+        let path = materials.assets().path(path);
+        // マップと外部タイルセットのファイルは Assets を通して読むので、アーカイブにあっても読める
+        // 読んだファイルが変わったら hot_reload() で読み込み直す
+        let assets = materials.assets().clone();
+        let map = TiledMap::load_with(&path, &|path| assets.read_to_string(path))?;
+        let mut tilemap = Tilemap::new(map, materials)?;
+        tilemap.source = Some(path);
+        Ok(tilemap)
+    }
+
+    // マップか外部タイルセットのファイルが変わっていたら読み込み直す (読み込み直したら true)
+    // タイルセットの画像は Assets が読み込み直し、マテリアルが持ち替える
+    // set_tile() で書き換えたタイルは元に戻る
+    pub fn hot_reload(&mut self, materials: &mut MaterialLibrary) -> Result<bool, String> {
+        let source = match &self.source {
+            Some(source) => source.clone(),
+            None => return Ok(false),
+        };
+        let assets = materials.assets();
+        let changed = assets.is_changed(&source)
+            || self
+                .map
+                .tilesets
+                .iter()
+                .filter_map(|t| t.source.as_ref())
+                .any(|path| assets.is_changed(path));
+        if !changed {
+            return Ok(false);
+        }
+        let mut reloaded = Tilemap::load(&source, materials)?;
+        reloaded.layer = self.layer;
+        *self = reloaded;
+        Ok(true)
+    }
+
+    // タイルセットの画像ごとにマテリアルを作る
+    pub fn new(map: TiledMap, library: &mut MaterialLibrary) -> Result<Tilemap, String> {
+        let mut materials = Vec::new();
+        for tileset in &map.tilesets {
+            let image = match &tileset.image {
+                Some(image) => image,
+                None => {
+                    materials.push(None);
+                    continue;
+                }
+            };
+            let name = format!("tilemap:{}", image);
+            if let Some(id) = library.id(&name) {
+                materials.push(Some(id));
+                continue;
+            }
+            let texture = library.load_texture(image)?;
+            texture.set_filter(gl::NEAREST, gl::NEAREST); // ドット絵がぼやけないようにする
+            let shader = library.load_shader(TILE_VERTEX_SHADER, TILE_FRAGMENT_SHADER);
+            let mut material = Material::new(&name, shader);
+            material.set_texture("uTexture", texture);
+            material.render_state = RenderState {
+                depth_test: true,
+                depth_write: false,
+                blend: true,
+                culling: false,
+                ..RenderState::default()
+            };
+            materials.push(Some(library.insert(material)));
+        }
+
+        let mut chunks = Vec::new();
+        for (index, layer) in map.layers.iter().enumerate() {
+            let layer = match layer {
+                Layer::Tiles(layer) => layer,
+                _ => continue,
+            };
+            for y in 0..layer.height.div_ceil(CHUNK_SIZE) {
+                for x in 0..layer.width.div_ceil(CHUNK_SIZE) {
+                    chunks.push(Chunk {
+                        layer: index,
+                        x,
+                        y,
+                        min: vec2(0.0, 0.0),
+                        max: vec2(0.0, 0.0),
+                        meshes: Vec::new(),
+                        dirty: true,
+                    });
+                }
+            }
+        }
+
+        Ok(Tilemap {
+            map,
+            layer: 0,
+            materials,
+            chunks,
+            source: None,
+        })
+    }
+
+    pub fn tile_layer_index(&self, name: &str) -> Option<usize> {
+        self.map
+            .layers
+            .iter()
+            .position(|layer| matches!(layer, Layer::Tiles(layer) if layer.name == name))
+    }
+
+    pub fn tile(&self, layer: usize, x: u32, y: u32) -> Tile {
+        match self.map.layers.get(layer) {
+            Some(Layer::Tiles(layer)) => layer.get(x, y),
+            _ => Tile::EMPTY,
+        }
+    }
+
+    // タイルを書き換え、そのタイルを含むチャンクを作り直す対象にする
+    pub fn set_tile(&mut self, layer: usize, x: u32, y: u32, tile: Tile) {
+        if let Some(Layer::Tiles(tile_layer)) = self.map.layers.get_mut(layer) {
+            tile_layer.set(x, y, tile);
+            let (cx, cy) = (x / CHUNK_SIZE, y / CHUNK_SIZE);
+            for chunk in &mut self.chunks {
+                if chunk.layer == layer && chunk.x == cx && chunk.y == cy {
+                    chunk.dirty = true;
+                }
+            }
+        }
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // 書き換えられたチャンクの頂点データを作り直す (GLコンテキストが必要)
+    pub fn rebuild_dirty_chunks(&mut self) {
+        for chunk in self.chunks.iter_mut().filter(|chunk| chunk.dirty) {
+            chunk.dirty = false;
+            let layer = match &self.map.layers[chunk.layer] {
+                Layer::Tiles(layer) => layer,
+                _ => continue,
+            };
+            let geometries = build_chunk_geometry(&self.map, layer, chunk.x, chunk.y);
+
+            // 範囲 (タイルセットの画像がマップのタイルより大きいときははみ出す)
+            let positions = geometries.iter().flat_map(|geometry| {
+                geometry
+                    .vertices
+                    .chunks(FLOATS_PER_VERTEX)
+                    .map(|v| vec2(v[0], v[1]))
+            });
+            let (min, max) = positions.fold(
+                (vec2(f32::MAX, f32::MAX), vec2(f32::MIN, f32::MIN)),
+                |(min, max), p| {
+                    (
+                        vec2(min.x.min(p.x), min.y.min(p.y)),
+                        vec2(max.x.max(p.x), max.y.max(p.y)),
+                    )
+                },
+            );
+            chunk.min = min;
+            chunk.max = max;
+
+            let mut old_meshes = mem::take(&mut chunk.meshes);
+            for geometry in geometries {
+                // 同じタイルセットの頂点バッファーがあれば、作り直さずに中身だけ書き換える
+                let reused = old_meshes
+                    .iter()
+                    .position(|mesh| mesh.tileset == geometry.tileset)
+                    .map(|index| old_meshes.swap_remove(index))
+                    .and_then(|mut mesh| {
+                        let vertex = Rc::get_mut(&mut mesh.vertex)?;
+                        vertex.update(
+                            (geometry.vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                            geometry.vertices.as_ptr() as *const c_void,
+                            (geometry.vertices.len() / FLOATS_PER_VERTEX) as i32,
+                        );
+                        vertex.update_indices(&geometry.indices);
+                        Some(mesh)
+                    });
+                let mesh = match reused {
+                    Some(mesh) => mesh,
+                    None => ChunkMesh {
+                        tileset: geometry.tileset,
+                        vertex: Rc::new(create_vertex(&geometry)),
+                    },
+                };
+                chunk.meshes.push(mesh);
+            }
+        }
+    }
+}
+
+fn create_vertex(geometry: &ChunkGeometry) -> Vertex {
+    Vertex::new(
+        (geometry.vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+        geometry.vertices.as_ptr() as *const c_void,
+        gl::DYNAMIC_DRAW, // タイルの書き換えで中身を更新する
+        vec![gl::FLOAT, gl::FLOAT, gl::FLOAT],
+        vec![2, 2, 1],
+        (FLOATS_PER_VERTEX * mem::size_of::<GLfloat>()) as GLsizei,
+        (geometry.vertices.len() / FLOATS_PER_VERTEX) as i32,
+    )
+    .with_indices(&geometry.indices)
+}
+
+// チャンク内のタイルを四角形に並べた頂点データを、タイルセットごとに作る
+// マップの左下を原点としてタイル1枚を1単位とする
+pub fn build_chunk_geometry(
+    map: &TiledMap,
+    layer: &TileLayer,
+    chunk_x: u32,
+    chunk_y: u32,
+) -> Vec<ChunkGeometry> {
+    let mut geometries: Vec<ChunkGeometry> = Vec::new();
+    let offset = vec2(
+        layer.offset.x / map.tile_width as f32,
+        -layer.offset.y / map.tile_height as f32,
+    );
+    let x_range = chunk_x * CHUNK_SIZE..((chunk_x + 1) * CHUNK_SIZE).min(layer.width);
+    let y_range = chunk_y * CHUNK_SIZE..((chunk_y + 1) * CHUNK_SIZE).min(layer.height);
+
+    for y in y_range {
+        for x in x_range.clone() {
+            let tile = layer.get(x, y);
+            if tile.is_empty() {
+                continue;
+            }
+            let (tileset_index, local_id) = match map.tileset_for(tile.gid()) {
+                Some(found) => found,
+                None => continue,
+            };
+            let tileset = &map.tilesets[tileset_index];
+
+            // 大きなタイルはセルの左下を基準に右上へはみ出す
+            let size = vec2(
+                tileset.tile_width as f32 / map.tile_width as f32,
+                tileset.tile_height as f32 / map.tile_height as f32,
+            );
+            let origin = vec2(x as f32, (map.height - 1 - y) as f32) + offset;
+            let (uv_min, uv_max) = tileset.uv(local_id);
+
+            let geometry = match geometries.iter().position(|g| g.tileset == tileset_index) {
+                Some(index) => &mut geometries[index],
+                None => {
+                    geometries.push(ChunkGeometry {
+                        tileset: tileset_index,
+                        ..ChunkGeometry::default()
+                    });
+                    geometries.last_mut().unwrap()
+                }
+            };
+            let base = (geometry.vertices.len() / FLOATS_PER_VERTEX) as u32;
+            // 左下、右下、右上、左上 (画像の座標系では y が下向きなので、下の角が t = 1)
+            for &(sx, sy) in &[(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)] {
+                let (s, t) = flip(tile, sx, sy);
+                geometry.vertices.extend_from_slice(&[
+                    origin.x + sx * size.x,
+                    origin.y + (1.0 - sy) * size.y,
+                    uv_min.x + (uv_max.x - uv_min.x) * s,
+                    uv_min.y + (uv_max.y - uv_min.y) * t,
+                    layer.opacity,
+                ]);
+            }
+            geometry.indices.extend_from_slice(&[
+                base,
+                base + 1,
+                base + 2,
+                base,
+                base + 2,
+                base + 3,
+            ]);
+        }
+    }
+    geometries
+}
+
+// 表示する位置 (sx, sy) に対応する画像上の位置
+// Tiledでは対角線で反転してから、左右、上下の順に反転する
+fn flip(tile: Tile, sx: f32, sy: f32) -> (f32, f32) {
+    let sy = if tile.flipped_vertically() {
+        1.0 - sy
+    } else {
+        sy
+    };
+    let sx = if tile.flipped_horizontally() {
+        1.0 - sx
+    } else {
+        sx
+    };
+    if tile.flipped_diagonally() {
+        (sy, sx)
+    } else {
+        (sx, sy)
+    }
+}
+
+// 画面に映るチャンクの描画コマンドをレンダラーのキューに積む (Render)
+pub fn tilemap_render_system(world: &mut World) {
+    let mut renderer = world.resource_mut::<Renderer>();
+    world
+        .query::<(Write<Tilemap>, Read<Transform2D>)>()
+        .for_each(|_, (tilemap, transform)| {
+            tilemap.rebuild_dirty_chunks();
+            let model = transform.world_matrix();
+            for chunk in &tilemap.chunks {
+                let visible = match &tilemap.map.layers[chunk.layer] {
+                    Layer::Tiles(layer) => layer.visible,
+                    _ => false,
+                };
+                if !visible
+                    || chunk.meshes.is_empty()
+                    || !renderer.is_visible(
+                        &model,
+                        vec3(chunk.min.x, chunk.min.y, 0.0),
+                        vec3(chunk.max.x, chunk.max.y, 0.0),
+                    )
+                {
+                    continue;
+                }
+                for mesh in &chunk.meshes {
+                    if let Some(material) = tilemap.materials[mesh.tileset] {
+                        renderer.submit(DrawCommand {
+                            vertex: mesh.vertex.clone(),
+                            material,
+                            model,
+                            layer: tilemap.layer + chunk.layer as i32,
+                        });
+                    }
+                }
+            }
+        });
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use cgmath::vec2;
+use roxmltree::{Document, Node};
+
+use super::map::{
+    check_tile_count, decode_base64_tiles, decode_csv_tiles, Layer, MapObject, ObjectLayer,
+    ObjectShape, Properties, PropertyValue, Tile, TileInfo, TileLayer, TiledMap, Tileset,
+};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+
+// TiledのXML形式 (.tmx) のマップを読み込む
+pub fn load_map(
+    path: &str,
+    read: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<TiledMap, String> {
+    let source = read(path)?;
+    let document = Document::parse(&source).map_err(|e| format!("{}: {}", path, e))?;
+    let root = document.root_element();
+    if !root.has_tag_name("map") {
+        return Err(format!("{}: root element is not <map>", path));
+    }
+    parse_map(root, base_dir(path), read).map_err(|e| format!("{}: {}", path, e))
+}
+
+// 外部タイルセット (.tsx)
+pub fn load_tileset(
+    path: &str,
+    first_gid: u32,
+    read: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<Tileset, String> {
+    let source = read(path)?;
+    let document = Document::parse(&source).map_err(|e| format!("{}: {}", path, e))?;
+    let mut tileset = parse_tileset(document.root_element(), first_gid, base_dir(path))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    tileset.source = Some(path.to_string());
+    Ok(tileset)
+}
+
+fn base_dir(path: &str) -> &Path {
+    Path::new(path).parent().unwrap_or_else(|| Path::new(""))
+}
+
+// ファイルからの相対パスを、実行時のカレントディレクトリからのパスにする
+// テクスチャのキャッシュで同じファイルと分かるように ".." を取り除く
+pub(super) fn resolve(dir: &Path, path: &str) -> String {
+    let mut resolved = PathBuf::new();
+    for component in dir.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    resolved.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved.to_string_lossy().replace('\\', "/")
+}
+
+fn attribute<T: FromStr>(node: Node, name: &str) -> Result<Option<T>, String> {
+    match node.attribute(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid attribute {}=\"{}\"", name, value)),
+        None => Ok(None),
+    }
+}
+
+fn required<T: FromStr>(node: Node, name: &str) -> Result<T, String> {
+    attribute(node, name)?
+        .ok_or_else(|| format!("<{}> has no attribute {}", node.tag_name().name(), name))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn parse_properties(node: Node) -> Result<Properties, String> {
+    let mut properties = Properties::new();
+    let list = match child(node, "properties") {
+        Some(list) => list,
+        None => return Ok(properties),
+    };
+    for property in list.children().filter(|n| n.has_tag_name("property")) {
+        let name: String = required(property, "name")?;
+        let property_type = property.attribute("type").unwrap_or("string");
+        if property_type == "class" {
+            continue; // 入れ子のプロパティは扱わない
+        }
+        // 複数行の文字列は value 属性ではなく中身に書かれる
+        let value = property
+            .attribute("value")
+            .or_else(|| property.text())
+            .unwrap_or("");
+        properties.insert(name, PropertyValue::parse(property_type, value)?);
+    }
+    Ok(properties)
+}
+
+fn parse_map(
+    root: Node,
+    dir: &Path,
+    read: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<TiledMap, String> {
+    if let Some(orientation) = root.attribute("orientation") {
+        if orientation != "orthogonal" {
+            return Err(format!("unsupported orientation: {}", orientation));
+        }
+    }
+    if root.attribute("infinite") == Some("1") {
+        return Err("infinite maps are not supported".to_string());
+    }
+
+    let mut tilesets = Vec::new();
+    for node in root.children().filter(|n| n.has_tag_name("tileset")) {
+        let first_gid = required(node, "firstgid")?;
+        let tileset = match node.attribute("source") {
+            Some(source) => {
+                let path = resolve(dir, source);
+                if path.ends_with(".tsx") {
+                    load_tileset(&path, first_gid, read)?
+                } else {
+                    super::json::load_tileset(&path, first_gid, read)?
+                }
+            }
+            None => parse_tileset(node, first_gid, dir)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    parse_layers(root, &mut layers)?;
+
+    Ok(TiledMap {
+        width: required(root, "width")?,
+        height: required(root, "height")?,
+        tile_width: required(root, "tilewidth")?,
+        tile_height: required(root, "tileheight")?,
+        properties: parse_properties(root)?,
+        tilesets,
+        layers,
+    })
+}
+
+fn parse_tileset(node: Node, first_gid: u32, dir: &Path) -> Result<Tileset, String> {
+    let image = child(node, "image");
+    let mut tiles = HashMap::new();
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        let id = required(tile, "id")?;
+        let class = tile
+            .attribute("class")
+            .or_else(|| tile.attribute("type"))
+            .unwrap_or("")
+            .to_string();
+        tiles.insert(
+            id,
+            TileInfo {
+                class,
+                properties: parse_properties(tile)?,
+            },
+        );
+    }
+    Ok(Tileset {
+        name: node.attribute("name").unwrap_or("").to_string(),
+        first_gid,
+        tile_width: required(node, "tilewidth")?,
+        tile_height: required(node, "tileheight")?,
+        tile_count: attribute(node, "tilecount")?.unwrap_or(0),
+        columns: attribute(node, "columns")?.unwrap_or(0),
+        spacing: attribute(node, "spacing")?.unwrap_or(0),
+        margin: attribute(node, "margin")?.unwrap_or(0),
+        image: image
+            .and_then(|image| image.attribute("source"))
+            .map(|source| resolve(dir, source)),
+        image_width: match image {
+            Some(image) => attribute(image, "width")?.unwrap_or(0),
+            None => 0,
+        },
+        image_height: match image {
+            Some(image) => attribute(image, "height")?.unwrap_or(0),
+            None => 0,
+        },
+        properties: parse_properties(node)?,
+        tiles,
+        source: None,
+    })
+}
+
+// グループレイヤーの中身は同じ並びに展開する
+fn parse_layers(parent: Node, layers: &mut Vec<Layer>) -> Result<(), String> {
+    for node in parent.children().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "layer" => layers.push(Layer::Tiles(parse_tile_layer(node)?)),
+            "objectgroup" => layers.push(Layer::Objects(parse_object_layer(node)?)),
+            "group" => parse_layers(node, layers)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_tile_layer(node: Node) -> Result<TileLayer, String> {
+    let name: String = node.attribute("name").unwrap_or("").to_string();
+    let width = required(node, "width")?;
+    let height = required(node, "height")?;
+    let data = child(node, "data").ok_or_else(|| format!("layer {} has no <data>", name))?;
+    let tiles = match data.attribute("encoding") {
+        Some("csv") => decode_csv_tiles(data.text().unwrap_or(""))?,
+        Some("base64") => {
+            decode_base64_tiles(data.text().unwrap_or(""), data.attribute("compression"))?
+        }
+        Some(other) => return Err(format!("unsupported layer encoding: {}", other)),
+        None => data
+            .children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|tile| attribute(tile, "gid").map(|gid| Tile(gid.unwrap_or(0))))
+            .collect::<Result<_, _>>()?,
+    };
+    check_tile_count(&name, &tiles, width, height)?;
+    Ok(TileLayer {
+        width,
+        height,
+        visible: node.attribute("visible") != Some("0"),
+        opacity: attribute(node, "opacity")?.unwrap_or(1.0),
+        offset: vec2(
+            attribute(node, "offsetx")?.unwrap_or(0.0),
+            attribute(node, "offsety")?.unwrap_or(0.0),
+        ),
+        properties: parse_properties(node)?,
+        tiles,
+        name,
+    })
+}
+
+fn parse_object_layer(node: Node) -> Result<ObjectLayer, String> {
+    let objects = node
+        .children()
+        .filter(|n| n.has_tag_name("object"))
+        .map(parse_object)
+        .collect::<Result<_, _>>()?;
+    Ok(ObjectLayer {
+        name: node.attribute("name").unwrap_or("").to_string(),
+        visible: node.attribute("visible") != Some("0"),
+        properties: parse_properties(node)?,
+        objects,
+    })
+}
+
+fn parse_points(node: Node) -> Result<Vec<Vector2>, String> {
+    let points = node.attribute("points").unwrap_or("");
+    points
+        .split_whitespace()
+        .map(|point| {
+            let mut xy = point.split(',').map(|v| v.parse::<f32>());
+            match (xy.next(), xy.next()) {
+                (Some(Ok(x)), Some(Ok(y))) => Ok(vec2(x, y)),
+                _ => Err(format!("invalid point: {}", point)),
+            }
+        })
+        .collect()
+}
+
+fn parse_object(node: Node) -> Result<MapObject, String> {
+    let width = attribute(node, "width")?.unwrap_or(0.0);
+    let height = attribute(node, "height")?.unwrap_or(0.0);
+    let shape = if let Some(gid) = attribute(node, "gid")? {
+        ObjectShape::Tile {
+            tile: Tile(gid),
+            width,
+            height,
+        }
+    } else if child(node, "ellipse").is_some() {
+        ObjectShape::Ellipse { width, height }
+    } else if child(node, "point").is_some() {
+        ObjectShape::Point
+    } else if let Some(polygon) = child(node, "polygon") {
+        ObjectShape::Polygon(parse_points(polygon)?)
+    } else if let Some(polyline) = child(node, "polyline") {
+        ObjectShape::Polyline(parse_points(polyline)?)
+    } else {
+        ObjectShape::Rect { width, height }
+    };
+    Ok(MapObject {
+        id: attribute(node, "id")?.unwrap_or(0),
+        name: node.attribute("name").unwrap_or("").to_string(),
+        class: node
+            .attribute("class")
+            .or_else(|| node.attribute("type"))
+            .unwrap_or("")
+            .to_string(),
+        position: vec2(
+            attribute(node, "x")?.unwrap_or(0.0),
+            attribute(node, "y")?.unwrap_or(0.0),
+        ),
+        rotation: attribute(node, "rotation")?.unwrap_or(0.0),
+        visible: node.attribute("visible") != Some("0"),
+        shape,
+        properties: parse_properties(node)?,
+    })
+}
@@ -0,0 +1,353 @@
+use cgmath::{vec2, vec3, vec4, Rad, SquareMatrix};
+
+use crate::ecs::{Entity, World};
+
+#[allow(dead_code)]
+type Vector2 = cgmath::Vector2<f32>;
+#[allow(dead_code)]
+type Matrix4 = cgmath::Matrix4<f32>;
+
+// 親子関係をもつ2Dの変換 (位置・回転・拡大率)
+// 回転と拡大は pivot を中心に行い、親がいるときは親のローカル座標系で表す
+// 値を書き換えると dirty になり、transform_propagate_system で行列が計算し直される
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform2D {
+    position: Vector2,
+    rotation: Rad<f32>,
+    scale: Vector2,
+    pivot: Vector2,
+    parent: Option<Entity>,
+    local: Matrix4,
+    world: Matrix4,
+    dirty: bool,
+}
+
+#[allow(dead_code)]
+impl Transform2D {
+    pub fn new(position: Vector2) -> Transform2D {
+        Transform2D {
+            position,
+            rotation: Rad(0.0),
+            scale: vec2(1.0, 1.0),
+            pivot: vec2(0.0, 0.0),
+            parent: None,
+            local: Matrix4::identity(),
+            world: Matrix4::identity(),
+            dirty: true,
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: Rad<f32>) -> Transform2D {
+        self.set_rotation(rotation);
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector2) -> Transform2D {
+        self.set_scale(scale);
+        self
+    }
+
+    pub fn with_pivot(mut self, pivot: Vector2) -> Transform2D {
+        self.set_pivot(pivot);
+        self
+    }
+
+    pub fn position(&self) -> Vector2 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vector2) {
+        self.position = position;
+        self.dirty = true;
+    }
+
+    pub fn translate(&mut self, delta: Vector2) {
+        self.set_position(self.position + delta);
+    }
+
+    pub fn rotation(&self) -> Rad<f32> {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rad<f32>) {
+        self.rotation = rotation;
+        self.dirty = true;
+    }
+
+    pub fn rotate(&mut self, delta: Rad<f32>) {
+        self.set_rotation(self.rotation + delta);
+    }
+
+    pub fn scale(&self) -> Vector2 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: Vector2) {
+        self.scale = scale;
+        self.dirty = true;
+    }
+
+    pub fn pivot(&self) -> Vector2 {
+        self.pivot
+    }
+
+    pub fn set_pivot(&mut self, pivot: Vector2) {
+        self.pivot = pivot;
+        self.dirty = true;
+    }
+
+    pub fn parent(&self) -> Option<Entity> {
+        self.parent
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    // 親を考慮しない変換行列: translate * rotate_z * scale * translate(-pivot)
+    pub fn compute_local_matrix(&self) -> Matrix4 {
+        Matrix4::from_translation(vec3(self.position.x, self.position.y, 0.0))
+            * Matrix4::from_angle_z(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, 1.0)
+            * Matrix4::from_translation(vec3(-self.pivot.x, -self.pivot.y, 0.0))
+    }
+
+    // 最後に計算されたワールド座標への変換行列 (シェーダーの uModel に渡す)
+    pub fn world_matrix(&self) -> Matrix4 {
+        self.world
+    }
+
+    // pivot のワールド座標
+    pub fn world_position(&self) -> Vector2 {
+        let p = self.world * vec4(self.pivot.x, self.pivot.y, 0.0, 1.0);
+        vec2(p.x, p.y)
+    }
+}
+
+// 子エンティティの一覧 (set_parent が管理する)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Children(pub Vec<Entity>);
+
+// 親を付け替える (Noneなら親から外す)
+// 自分の子孫を親にしようとしたときは何もせず false を返す
+#[allow(dead_code)]
+pub fn set_parent(world: &mut World, child: Entity, parent: Option<Entity>) -> bool {
+    let mut ancestor = parent;
+    while let Some(entity) = ancestor {
+        if entity == child {
+            return false;
+        }
+        ancestor = world.get::<Transform2D>(entity).and_then(|t| t.parent);
+    }
+
+    let old_parent = {
+        let mut transform = world
+            .get_mut::<Transform2D>(child)
+            .expect("entity has no Transform2D");
+        let old_parent = transform.parent;
+        transform.parent = parent;
+        transform.dirty = true;
+        old_parent
+    };
+    if let Some(old_parent) = old_parent {
+        if let Some(mut children) = world.get_mut::<Children>(old_parent) {
+            children.0.retain(|&e| e != child);
+        }
+    }
+    if let Some(parent) = parent {
+        if !world.has::<Children>(parent) {
+            world.insert(parent, Children::default());
+        }
+        world.get_mut::<Children>(parent).unwrap().0.push(child);
+    }
+    true
+}
+
+// 子孫もまとめて削除する
+#[allow(dead_code)]
+pub fn despawn_recursive(world: &mut World, entity: Entity) {
+    if let Some(parent) = world.get::<Transform2D>(entity).and_then(|t| t.parent) {
+        if let Some(mut children) = world.get_mut::<Children>(parent) {
+            children.0.retain(|&e| e != entity);
+        }
+    }
+    let mut stack = vec![entity];
+    while let Some(entity) = stack.pop() {
+        if let Some(children) = world.remove::<Children>(entity) {
+            stack.extend(children.0);
+        }
+        world.despawn(entity);
+    }
+}
+
+// 親から子へ順にワールド行列を計算する (PostUpdate)
+// 自分も親も変更されていなければ、前回の行列をそのまま使う
+pub fn transform_propagate_system(world: &mut World) {
+    let mut transforms = match world.try_write::<Transform2D>() {
+        Some(transforms) => transforms,
+        None => return,
+    };
+    let children = world.try_read::<Children>();
+
+    // 親がいない (または親が削除された) ものを根とする
+    // 親が削除されたものは親から外し、親の行列を含んだままにならないように計算し直す
+    let roots: Vec<(Entity, bool)> = transforms
+        .iter()
+        .filter_map(|(entity, t)| match t.parent {
+            None => Some((entity, false)),
+            Some(parent) if !transforms.contains(parent) => Some((entity, true)),
+            Some(_) => None,
+        })
+        .collect();
+    let mut stack: Vec<(Entity, Matrix4, bool)> = Vec::with_capacity(roots.len());
+    for (entity, orphaned) in roots {
+        if orphaned {
+            transforms.get_mut(entity).unwrap().parent = None;
+        }
+        stack.push((entity, Matrix4::identity(), orphaned));
+    }
+
+    while let Some((entity, parent_world, parent_changed)) = stack.pop() {
+        let transform = match transforms.get_mut(entity) {
+            Some(transform) => transform,
+            None => continue,
+        };
+        let changed = transform.dirty || parent_changed;
+        if transform.dirty {
+            transform.local = transform.compute_local_matrix();
+            transform.dirty = false;
+        }
+        if changed {
+            transform.world = parent_world * transform.local;
+        }
+
+        let world_matrix = transform.world;
+        if let Some(children) = children.as_ref().and_then(|c| c.get(entity)) {
+            for &child in &children.0 {
+                stack.push((child, world_matrix, changed));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use cgmath::InnerSpace;
+
+    use super::*;
+
+    fn assert_close(actual: Vector2, expected: Vector2) {
+        assert!(
+            (actual - expected).magnitude() < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn spawn(world: &mut World, transform: Transform2D, parent: Option<Entity>) -> Entity {
+        let entity = world.spawn();
+        world.insert(entity, transform);
+        if parent.is_some() {
+            set_parent(world, entity, parent);
+        }
+        entity
+    }
+
+    fn position(world: &World, entity: Entity) -> Vector2 {
+        world.get::<Transform2D>(entity).unwrap().world_position()
+    }
+
+    #[test]
+    fn parent_to_child_propagation() {
+        let mut world = World::new();
+        let parent = spawn(
+            &mut world,
+            Transform2D::new(vec2(10.0, 0.0))
+                .with_rotation(Rad(FRAC_PI_2))
+                .with_scale(vec2(2.0, 2.0)),
+            None,
+        );
+        let child = spawn(&mut world, Transform2D::new(vec2(1.0, 0.0)), Some(parent));
+        let grandchild = spawn(&mut world, Transform2D::new(vec2(0.0, 1.0)), Some(child));
+        transform_propagate_system(&mut world);
+        // 親の回転と拡大は子の位置にも効く
+        assert_close(position(&world, child), vec2(10.0, 2.0));
+        assert_close(position(&world, grandchild), vec2(8.0, 2.0));
+        assert!(!world.get::<Transform2D>(child).unwrap().is_dirty());
+
+        // 親だけを動かしても子孫は計算し直す
+        world
+            .get_mut::<Transform2D>(parent)
+            .unwrap()
+            .set_position(vec2(0.0, 5.0));
+        transform_propagate_system(&mut world);
+        assert_close(position(&world, child), vec2(0.0, 7.0));
+        assert_close(position(&world, grandchild), vec2(-2.0, 7.0));
+
+        // 親から外すと自分の座標だけになる
+        set_parent(&mut world, child, None);
+        transform_propagate_system(&mut world);
+        assert_close(position(&world, child), vec2(1.0, 0.0));
+        assert_close(position(&world, grandchild), vec2(1.0, 1.0));
+    }
+
+    #[test]
+    fn pivot_is_the_center_of_rotation() {
+        let mut world = World::new();
+        let entity = spawn(
+            &mut world,
+            Transform2D::new(vec2(3.0, 3.0))
+                .with_pivot(vec2(1.0, 1.0))
+                .with_rotation(Rad(FRAC_PI_2)),
+            None,
+        );
+        transform_propagate_system(&mut world);
+        assert_close(position(&world, entity), vec2(3.0, 3.0));
+        let corner =
+            world.get::<Transform2D>(entity).unwrap().world_matrix() * vec4(2.0, 1.0, 0.0, 1.0);
+        assert_close(vec2(corner.x, corner.y), vec2(3.0, 4.0));
+    }
+
+    #[test]
+    fn orphaned_children_become_roots() {
+        let mut world = World::new();
+        let parent = spawn(&mut world, Transform2D::new(vec2(10.0, 10.0)), None);
+        let child = spawn(&mut world, Transform2D::new(vec2(1.0, 0.0)), Some(parent));
+        let grandchild = spawn(&mut world, Transform2D::new(vec2(0.0, 1.0)), Some(child));
+        transform_propagate_system(&mut world);
+        assert_close(position(&world, grandchild), vec2(11.0, 11.0));
+
+        // 子を残したまま親だけを削除すると、子はワールド座標の原点からの位置になる
+        world.despawn(parent);
+        transform_propagate_system(&mut world);
+        assert_eq!(world.get::<Transform2D>(child).unwrap().parent(), None);
+        assert_close(position(&world, child), vec2(1.0, 0.0));
+        assert_close(position(&world, grandchild), vec2(1.0, 1.0));
+    }
+
+    #[test]
+    fn despawn_recursive_removes_descendants() {
+        let mut world = World::new();
+        let root = spawn(&mut world, Transform2D::new(vec2(0.0, 0.0)), None);
+        let parent = spawn(&mut world, Transform2D::new(vec2(0.0, 0.0)), Some(root));
+        let child = spawn(&mut world, Transform2D::new(vec2(0.0, 0.0)), Some(parent));
+        despawn_recursive(&mut world, parent);
+        assert!(!world.is_alive(parent));
+        assert!(!world.is_alive(child));
+        assert!(world.get::<Children>(root).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut world = World::new();
+        let parent = spawn(&mut world, Transform2D::new(vec2(0.0, 0.0)), None);
+        let child = spawn(&mut world, Transform2D::new(vec2(0.0, 0.0)), Some(parent));
+        assert!(!set_parent(&mut world, parent, Some(child)));
+        assert!(!set_parent(&mut world, parent, Some(parent)));
+        assert_eq!(world.get::<Transform2D>(parent).unwrap().parent(), None);
+        assert_eq!(world.get::<Children>(parent).unwrap().0, vec![child]);
+    }
+}
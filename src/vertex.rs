@@ -1,14 +1,32 @@
 use std::mem;
 use std::os::raw::c_void;
+use std::ptr;
 
 use gl::types::{GLenum, GLfloat, GLint, GLsizei, GLsizeiptr};
 
+// インスタンスごとに1つずつ進む頂点属性のバッファー
+struct InstanceBuffer {
+    vbo: u32,
+    usage: GLenum,
+    size: GLsizeiptr, // 確保したサイズ (バイト)
+}
+
 pub struct Vertex {
     vao: u32,
-    _vbo: u32,
+    vbo: u32,
+    ebo: u32, // インデックスバッファー (使わないときは0)
+    usage: GLenum,
+    size: GLsizeiptr,      // VBOに確保したサイズ (バイト)
+    index_capacity: usize, // EBOに確保したインデックスの数
     vertex_num: i32,
+    index_num: i32,
+    mode: GLenum,       // 描画するプリミティブの種類
+    attribute_num: u32, // 有効にした頂点属性の数 (インスタンスの属性はこの続きの番号になる)
+    instances: Vec<InstanceBuffer>,
+    instance_num: i32,
 }
 
+#[allow(dead_code)]
 impl Vertex {
     pub fn new(
         size: GLsizeiptr,
@@ -35,20 +53,7 @@ impl Vertex {
             gl::BufferData(gl::ARRAY_BUFFER, size, data, usage); // VBOへのはじめてのデータ転送
                                                                  // (バッファーの種類、バッファーのサイズ、転送元のデータ、アクセス頻度を指定)
 
-            let mut offset = 0;
-            for i in 0..attribute_type_vec.len() {
-                gl::EnableVertexAttribArray(i as u32); // i番目の頂点属性の配列を有効にする
-                gl::VertexAttribPointer(
-                    // GPUへ送る頂点属性のデータがどのようなまとまりになっているかを設定する
-                    i as u32,              // 頂点属性の順番(0から始まる)
-                    attribute_size_vec[i], // 頂点属性あたりの要素数
-                    attribute_type_vec[i], // データ型
-                    gl::FALSE,             // 整数を浮動小数点型に正規化するかどうか
-                    stride,                // 各頂点データの始まりが何個おきに並んでいるのか
-                    (offset * mem::size_of::<GLfloat>()) as *const c_void, // 頂点データの開始地点のオフセット
-                );
-                offset += attribute_size_vec[i] as usize;
-            }
+            set_attributes(0, &attribute_type_vec, &attribute_size_vec, stride, 0);
 
             // unbind (VAOとVBOを準備した後の片づけ: 空のIDをバインドしてVAOとVBOの紐づけを解除)
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
@@ -56,17 +61,271 @@ impl Vertex {
         }
 
         Vertex {
-            vao: vao,
-            _vbo: vbo,
-            vertex_num: vertex_num,
+            vao,
+            vbo,
+            ebo: 0,
+            usage,
+            size,
+            index_capacity: 0,
+            vertex_num,
+            index_num: 0,
+            mode: gl::TRIANGLES,
+            attribute_num: attribute_type_vec.len() as u32,
+            instances: Vec::new(),
+            instance_num: 0,
+        }
+    }
+
+    // インスタンスごとの頂点属性のバッファーを付ける (glVertexAttribDivisor)
+    // 属性の番号は、頂点の属性とそれまでに付けたインスタンスの属性の続きになる
+    // インスタンスの数は size / stride で、インスタンスのバッファーを持つと draw() はその数だけ描画する
+    pub fn with_instances(
+        mut self,
+        size: GLsizeiptr,
+        data: *const c_void,
+        usage: GLenum,
+        attribute_type_vec: std::vec::Vec<GLenum>,
+        attribute_size_vec: std::vec::Vec<GLint>,
+        stride: GLsizei,
+    ) -> Vertex {
+        self.add_instances(
+            size,
+            data,
+            usage,
+            attribute_type_vec,
+            attribute_size_vec,
+            stride,
+        );
+        self
+    }
+
+    // 付けたバッファーの番号 (update_instances に渡す) を返す
+    pub fn add_instances(
+        &mut self,
+        size: GLsizeiptr,
+        data: *const c_void,
+        usage: GLenum,
+        attribute_type_vec: std::vec::Vec<GLenum>,
+        attribute_size_vec: std::vec::Vec<GLint>,
+        stride: GLsizei,
+    ) -> usize {
+        let mut vbo = 0;
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, size, data, usage);
+            set_attributes(
+                self.attribute_num,
+                &attribute_type_vec,
+                &attribute_size_vec,
+                stride,
+                1, // 1インスタンスごとに次の値へ進む
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+        self.attribute_num += attribute_type_vec.len() as u32;
+        self.instances.push(InstanceBuffer { vbo, usage, size });
+        self.instance_num = (size / stride.max(1) as GLsizeiptr) as i32;
+        self.instances.len() - 1
+    }
+
+    // インデックスバッファーを付けて、頂点を使い回して描画する
+    pub fn with_indices(mut self, indices: &[u32]) -> Vertex {
+        self.update_indices(indices);
+        self
+    }
+
+    // gl::LINES などで描画する
+    pub fn with_mode(mut self, mode: GLenum) -> Vertex {
+        self.mode = mode;
+        self
+    }
+
+    pub fn set_mode(&mut self, mode: GLenum) {
+        self.mode = mode;
+    }
+
+    // 頂点データを書き換える (確保済みのサイズに収まるときはバッファーを作り直さない)
+    pub fn update(&mut self, size: GLsizeiptr, data: *const c_void, vertex_num: i32) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            if size <= self.size {
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, size, data);
+            } else {
+                gl::BufferData(gl::ARRAY_BUFFER, size, data, self.usage);
+                self.size = size;
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.vertex_num = vertex_num;
+    }
+
+    // index 番目のインスタンスのバッファーを書き換え、描画するインスタンスの数を変える
+    pub fn update_instances(
+        &mut self,
+        index: usize,
+        size: GLsizeiptr,
+        data: *const c_void,
+        instance_num: i32,
+    ) {
+        let buffer = &mut self.instances[index];
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer.vbo);
+            if size <= buffer.size {
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, size, data);
+            } else {
+                gl::BufferData(gl::ARRAY_BUFFER, size, data, buffer.usage);
+                buffer.size = size;
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.instance_num = instance_num;
+    }
+
+    pub fn update_indices(&mut self, indices: &[u32]) {
+        let size = mem::size_of_val(indices) as GLsizeiptr;
+        unsafe {
+            // EBOの紐づけはVAOに記録されるので、VAOをバインドしてから行う
+            gl::BindVertexArray(self.vao);
+            if self.ebo == 0 {
+                gl::GenBuffers(1, &mut self.ebo);
+            }
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            if indices.len() <= self.index_capacity {
+                gl::BufferSubData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    0,
+                    size,
+                    indices.as_ptr() as *const c_void,
+                );
+            } else {
+                gl::BufferData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    size,
+                    indices.as_ptr() as *const c_void,
+                    self.usage,
+                );
+                self.index_capacity = indices.len();
+            }
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         }
+        self.index_num = indices.len() as i32;
+    }
+
+    pub fn vertex_num(&self) -> i32 {
+        self.vertex_num
+    }
+
+    pub fn index_num(&self) -> i32 {
+        self.index_num
+    }
+
+    // インスタンスのバッファーを持たないときは0
+    pub fn instance_num(&self) -> i32 {
+        self.instance_num
+    }
+
+    pub fn is_instanced(&self) -> bool {
+        !self.instances.is_empty()
     }
 
     pub fn draw(&self) {
+        if self.is_instanced() {
+            self.draw_instanced(self.instance_num);
+            return;
+        }
         unsafe {
             gl::BindVertexArray(self.vao); // 再びVAOを紐づける
-            gl::DrawArrays(gl::TRIANGLES, 0, self.vertex_num); // 描画するプリミティブの種類、頂点データの開始インデックス、描画する頂点の数
+            if self.ebo != 0 {
+                // インデックスの並びに従って描画する
+                gl::DrawElements(self.mode, self.index_num, gl::UNSIGNED_INT, ptr::null());
+            } else {
+                gl::DrawArrays(self.mode, 0, self.vertex_num); // 描画するプリミティブの種類、頂点データの開始インデックス、描画する頂点の数
+            }
             gl::BindVertexArray(0); // VAOの紐づけを解除
         }
     }
+
+    // 同じ頂点を count 回描画する (シェーダーでは gl_InstanceID とインスタンスの属性で描き分ける)
+    pub fn draw_instanced(&self, count: i32) {
+        if count <= 0 {
+            return;
+        }
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            if self.ebo != 0 {
+                gl::DrawElementsInstanced(
+                    self.mode,
+                    self.index_num,
+                    gl::UNSIGNED_INT,
+                    ptr::null(),
+                    count,
+                );
+            } else {
+                gl::DrawArraysInstanced(self.mode, 0, self.vertex_num, count);
+            }
+            gl::BindVertexArray(0);
+        }
+    }
+
+    // first 番目から count 個の頂点だけを描画する (インデックスは使わない)
+    // 1つのバッファーにまとめた、いくつかの図形を別々に描くときに使う
+    pub fn draw_range(&self, first: i32, count: i32) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(self.mode, first, count);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for Vertex {
+    fn drop(&mut self) {
+        unsafe {
+            if self.ebo != 0 {
+                gl::DeleteBuffers(1, &self.ebo);
+            }
+            for buffer in &self.instances {
+                gl::DeleteBuffers(1, &buffer.vbo);
+            }
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+// バインド中のVAOとVBOに、first 番目からの頂点属性を設定する
+// divisor が0なら頂点ごと、1ならインスタンスごとに次の値へ進む (OpenGL 3.3)
+unsafe fn set_attributes(
+    first: u32,
+    attribute_type_vec: &[GLenum],
+    attribute_size_vec: &[GLint],
+    stride: GLsizei,
+    divisor: u32,
+) {
+    let mut offset = 0;
+    for (i, (&attribute_type, &attribute_size)) in attribute_type_vec
+        .iter()
+        .zip(attribute_size_vec)
+        .enumerate()
+    {
+        let location = first + i as u32;
+        gl::EnableVertexAttribArray(location); // location番目の頂点属性の配列を有効にする
+        gl::VertexAttribPointer(
+            // GPUへ送る頂点属性のデータがどのようなまとまりになっているかを設定する
+            location,       // 頂点属性の順番(0から始まる)
+            attribute_size, // 頂点属性あたりの要素数
+            attribute_type, // データ型
+            gl::FALSE,      // 整数を浮動小数点型に正規化するかどうか
+            stride,         // 各頂点データの始まりが何個おきに並んでいるのか
+            (offset * mem::size_of::<GLfloat>()) as *const c_void, // 頂点データの開始地点のオフセット
+        );
+        if divisor != 0 {
+            gl::VertexAttribDivisor(location, divisor);
+        }
+        offset += attribute_size as usize;
+    }
 }